@@ -13,7 +13,7 @@ use smithay::{
         SwapBuffersError,
     },
     reexports::wayland_server::protocol::{wl_buffer, wl_surface},
-    utils::{Logical, Point, Rectangle},
+    utils::{Logical, Physical, Point, Rectangle},
     wayland::{
         compositor::{
             get_role, with_states, with_surface_tree_upward, Damage, SubsurfaceCachedState,
@@ -39,6 +39,11 @@ impl<T> Drop for BufferTextures<T> {
     }
 }
 
+/// Damage covering the whole frame, as anvil redraws everything every frame
+pub fn full_damage() -> [Rectangle<i32, Physical>; 1] {
+    [Rectangle::from_loc_and_size((0, 0), (i32::MAX, i32::MAX))]
+}
+
 pub fn draw_cursor<R, E, F, T>(
     renderer: &mut R,
     frame: &mut F,
@@ -178,6 +183,7 @@ where
                         buffer_scale,
                         output_scale as f64,
                         Transform::Normal, /* TODO */
+                        &full_damage(),
                         1.0,
                     ) {
                         result = Err(err.into());
@@ -356,6 +362,7 @@ where
                 },
                 Rectangle::from_loc_and_size((offset_x, 0.0), (22.0 * output_scale, 35.0 * output_scale)),
                 Transform::Normal,
+                &full_damage(),
                 1.0,
             )
             .map_err(Into::into)?;
@@ -807,6 +807,7 @@ fn render_surface(
                                 1,
                                 output_scale as f64,
                                 Transform::Normal,
+                                &full_damage(),
                                 1.0,
                             )?;
                         }
@@ -835,7 +836,7 @@ fn render_surface(
     {
         Ok(()) => surface
             .surface
-            .queue_buffer()
+            .queue_buffer(false)
             .map_err(Into::<SwapBuffersError>::into),
         Err(err) => Err(err),
     }
@@ -878,6 +879,6 @@ fn initial_render(surface: &mut RenderSurface, renderer: &mut Gles2Renderer) ->
         })
         .map_err(Into::<SwapBuffersError>::into)
         .and_then(|x| x.map_err(Into::<SwapBuffersError>::into))?;
-    surface.queue_buffer()?;
+    surface.queue_buffer(false)?;
     Ok(())
 }
@@ -60,6 +60,35 @@ fn gl_generate() {
     }
 }
 
+#[cfg(feature = "wayland_frontend")]
+fn protocols_generate() {
+    use std::{env, path::PathBuf};
+    use wayland_scanner::{generate_code, Side};
+
+    // Protocols not (yet) shipped by the `wayland-protocols` crate, see `src/wayland/protocols.rs`
+    let protocols: &[(&str, &str)] = &[
+        ("staging", "tearing-control-v1"),
+        ("staging", "linux-drm-syncobj-v1"),
+        ("staging", "security-context-v1"),
+    ];
+
+    let dest = PathBuf::from(&env::var("OUT_DIR").unwrap());
+
+    for (kind, name) in protocols {
+        // protocol files are stored in a directory named after the protocol without its version
+        let family = match name.rsplit_once('-') {
+            Some((family, _version)) => family,
+            None => name,
+        };
+        let file = PathBuf::from("protocols")
+            .join(kind)
+            .join(family)
+            .join(format!("{}.xml", name));
+        println!("cargo:rerun-if-changed={}", file.display());
+        generate_code(&file, dest.join(format!("{}_server_api.rs", name)), Side::Server);
+    }
+}
+
 #[cfg(feature = "backend_session_logind")]
 fn find_logind() {
     // We should allow only dynamic linkage due to libsystemd and libelogind LICENSE.
@@ -96,6 +125,9 @@ fn main() {
     #[cfg(any(feature = "backend_egl", feature = "renderer_gl"))]
     gl_generate();
 
+    #[cfg(feature = "wayland_frontend")]
+    protocols_generate();
+
     #[cfg(feature = "backend_session_logind")]
     find_logind();
 }
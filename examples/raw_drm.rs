@@ -167,7 +167,7 @@ impl VBlankHandler {
 
         let fb = *self.current.userdata().get::<framebuffer::Handle>().unwrap();
         self.surface
-            .page_flip([(fb, self.surface.plane())].iter(), true)
+            .page_flip([(fb, self.surface.plane())].iter(), true, false)
             .unwrap();
     }
 }
@@ -6,7 +6,7 @@ use std::sync::{atomic::AtomicBool, Arc};
 
 use calloop::{EventSource, Interest, Poll, PostAction, Readiness, Token, TokenFactory};
 use drm::control::{connector, crtc, Device as ControlDevice, Event, Mode, ResourceHandles};
-use drm::{ClientCapability, Device as BasicDevice};
+use drm::{ClientCapability, Device as BasicDevice, DriverCapability};
 use nix::libc::dev_t;
 use nix::sys::stat::fstat;
 
@@ -19,6 +19,9 @@ use legacy::LegacyDrmDevice;
 
 use slog::{error, info, o, trace, warn};
 
+// Not yet part of `drm-ffi`, see `include/uapi/drm/drm.h` of the linux kernel
+const DRM_CAP_ATOMIC_ASYNC_PAGE_FLIP: u64 = 0x15;
+
 /// An open drm device
 #[derive(Debug)]
 pub struct DrmDevice<A: AsRawFd + 'static> {
@@ -27,6 +30,7 @@ pub struct DrmDevice<A: AsRawFd + 'static> {
     #[cfg(feature = "backend_session")]
     pub(super) links: RefCell<Vec<crate::utils::signaling::SignalToken>>,
     has_universal_planes: bool,
+    has_async_page_flip: bool,
     resources: ResourceHandles,
     pub(super) logger: ::slog::Logger,
     token: Token,
@@ -147,6 +151,20 @@ impl<A: AsRawFd + 'static> DrmDevice<A> {
             disable_connectors,
             log.clone(),
         )?);
+        let has_async_page_flip = match &*internal {
+            // the atomic api has its own capability, async flips via the legacy api are not sufficient
+            DrmDeviceInternal::Atomic(dev) => {
+                drm_ffi::get_capability(dev.fd.as_raw_fd(), DRM_CAP_ATOMIC_ASYNC_PAGE_FLIP)
+                    .map(|cap| cap.value == 1)
+                    .unwrap_or(false)
+            }
+            DrmDeviceInternal::Legacy(dev) => dev
+                .fd
+                .get_driver_capability(DriverCapability::ASyncPageFlip)
+                .map(|value| value == 1)
+                .unwrap_or(false),
+        };
+        info!(log, "Asynchronous page flips supported: {}", has_async_page_flip);
 
         Ok(DrmDevice {
             dev_id,
@@ -154,6 +172,7 @@ impl<A: AsRawFd + 'static> DrmDevice<A> {
             #[cfg(feature = "backend_session")]
             links: RefCell::new(Vec::new()),
             has_universal_planes,
+            has_async_page_flip,
             resources,
             logger: log,
             token: Token::invalid(),
@@ -194,6 +213,18 @@ impl<A: AsRawFd + 'static> DrmDevice<A> {
         }
     }
 
+    /// Returns if asynchronous page flips are supported by this device
+    ///
+    /// Asynchronous page flips do not wait for the next vertical blank and may therefor cause
+    /// visible tearing, but reduce latency. Compositors may use this to decide per-frame whether
+    /// to use [`DrmSurface::page_flip`] with `flip_async` set, e.g. for fullscreen surfaces
+    /// requesting it via the [`tearing_control`](crate::wayland::tearing_control) protocol.
+    ///
+    /// For atomic devices this requires kernel support for asynchronous atomic commits.
+    pub fn supports_async_page_flip(&self) -> bool {
+        self.has_async_page_flip
+    }
+
     /// Returns a list of crtcs for this device
     pub fn crtcs(&self) -> &[crtc::Handle] {
         self.resources.crtcs()
@@ -277,6 +308,7 @@ impl<A: AsRawFd + 'static> DrmDevice<A> {
             primary: plane,
             internal: Arc::new(internal),
             has_universal_planes: self.has_universal_planes,
+            has_async_page_flip: self.has_async_page_flip,
             #[cfg(feature = "backend_session")]
             links: RefCell::new(Vec::new()),
         })
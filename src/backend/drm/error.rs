@@ -56,6 +56,9 @@ pub enum Error {
         /// Property name
         name: &'static str,
     },
+    /// Asynchronous page flips are not supported by the device
+    #[error("Asynchronous page flips are not supported on crtc ({0:?})")]
+    AsyncPageFlipUnsupported(crtc::Handle),
     /// Atomic Test failed for new properties
     #[error("Atomic Test failed for new properties on crtc ({0:?})")]
     TestFailed(crtc::Handle),
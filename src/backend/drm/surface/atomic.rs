@@ -603,6 +603,50 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
         Ok(())
     }
 
+    pub fn page_flip_async<'a>(
+        &self,
+        framebuffers: impl Iterator<Item = &'a (framebuffer::Handle, plane::Handle)>,
+        event: bool,
+    ) -> Result<(), Error> {
+        if !self.active.load(Ordering::SeqCst) {
+            return Err(Error::DeviceInactive);
+        }
+
+        // Asynchronous atomic commits may only change the `FB_ID` of planes,
+        // so we cannot use `build_request`, which always sets the full plane state.
+        // `AtomicCommitFlags` is also missing the async flag, so we construct the request by hand.
+        let mut objects = Vec::new();
+        let mut prop_counts = Vec::new();
+        let mut props = Vec::new();
+        let mut values = Vec::new();
+        for (fb, plane) in framebuffers {
+            objects.push(Into::<u32>::into(*plane));
+            prop_counts.push(1);
+            props.push(Into::<u32>::into(self.plane_prop_handle(*plane, "FB_ID")?));
+            values.push(Into::<u32>::into(*fb) as u64);
+        }
+
+        let mut flags = drm_ffi::DRM_MODE_PAGE_FLIP_ASYNC | drm_ffi::DRM_MODE_ATOMIC_NONBLOCK;
+        if event {
+            flags |= drm_ffi::DRM_MODE_PAGE_FLIP_EVENT;
+        }
+
+        trace!(self.logger, "Queueing async page flip: {:?}", objects);
+        drm_ffi::mode::atomic_commit(
+            self.fd.as_raw_fd(),
+            flags,
+            &mut objects,
+            &mut prop_counts,
+            &mut props,
+            &mut values,
+        )
+        .map_err(|source| Error::Access {
+            errmsg: "Async page flip commit failed",
+            dev: self.fd.dev_path(),
+            source,
+        })
+    }
+
     pub fn test_buffer(&self, fb: framebuffer::Handle, mode: &Mode) -> Result<bool, Error> {
         if !self.active.load(Ordering::SeqCst) {
             return Err(Error::DeviceInactive);
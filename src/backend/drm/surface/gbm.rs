@@ -22,6 +22,7 @@ pub struct GbmBufferedSurface<D: AsRawFd + 'static> {
     current_fb: Slot<BufferObject<()>>,
     pending_fb: Option<Slot<BufferObject<()>>>,
     queued_fb: Option<Slot<BufferObject<()>>>,
+    queued_async: bool,
     next_fb: Option<Slot<BufferObject<()>>>,
     swapchain: Swapchain<GbmDevice<D>, BufferObject<()>>,
     drm: Arc<DrmSurface<D>>,
@@ -152,6 +153,7 @@ where
                     current_fb: buffer,
                     pending_fb: None,
                     queued_fb: None,
+                    queued_async: false,
                     next_fb: None,
                     swapchain,
                     drm,
@@ -194,11 +196,16 @@ where
 
     /// Queues the current buffer for rendering.
     ///
+    /// If `flip_async` is set and the underlying device supports it, the buffer will be
+    /// presented with an asynchronous page flip, not waiting for the next vertical blank.
+    /// Otherwise it silently falls back to a regular page flip.
+    ///
     /// *Note*: This function needs to be followed up with [`GbmBufferedSurface::frame_submitted`]
     /// when a vblank event is received, that denotes successful scanout of the buffer.
     /// Otherwise the underlying swapchain will eventually run out of buffers.
-    pub fn queue_buffer(&mut self) -> Result<(), Error> {
+    pub fn queue_buffer(&mut self, flip_async: bool) -> Result<(), Error> {
         self.queued_fb = self.next_fb.take();
+        self.queued_async = flip_async && self.drm.supports_async_page_flip();
         if self.pending_fb.is_none() && self.queued_fb.is_some() {
             self.submit()?;
         }
@@ -230,7 +237,8 @@ where
         let flip = if self.drm.commit_pending() {
             self.drm.commit([(fb, self.drm.plane())].iter(), true)
         } else {
-            self.drm.page_flip([(fb, self.drm.plane())].iter(), true)
+            self.drm
+                .page_flip([(fb, self.drm.plane())].iter(), true, self.queued_async)
         };
         if flip.is_ok() {
             self.pending_fb = Some(slot);
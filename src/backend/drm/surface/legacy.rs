@@ -306,28 +306,32 @@ impl<A: AsRawFd + 'static> LegacyDrmSurface<A> {
         Ok(())
     }
 
-    pub fn page_flip(&self, framebuffer: framebuffer::Handle, event: bool) -> Result<(), Error> {
+    pub fn page_flip(
+        &self,
+        framebuffer: framebuffer::Handle,
+        event: bool,
+        flip_async: bool,
+    ) -> Result<(), Error> {
         trace!(self.logger, "Queueing Page flip");
 
         if !self.active.load(Ordering::SeqCst) {
             return Err(Error::DeviceInactive);
         }
 
-        ControlDevice::page_flip(
-            &*self.fd,
-            self.crtc,
-            framebuffer,
-            if event {
-                &[PageFlipFlags::PageFlipEvent]
-            } else {
-                &[]
-            },
-            None,
-        )
-        .map_err(|source| Error::Access {
-            errmsg: "Failed to page flip",
-            dev: self.fd.dev_path(),
-            source,
+        let mut flags = Vec::with_capacity(2);
+        if event {
+            flags.push(PageFlipFlags::PageFlipEvent);
+        }
+        if flip_async {
+            flags.push(PageFlipFlags::PageFlipAsync);
+        }
+
+        ControlDevice::page_flip(&*self.fd, self.crtc, framebuffer, &flags, None).map_err(|source| {
+            Error::Access {
+                errmsg: "Failed to page flip",
+                dev: self.fd.dev_path(),
+                source,
+            }
         })
     }
 
@@ -31,6 +31,7 @@ pub struct DrmSurface<A: AsRawFd + 'static> {
     pub(super) primary: plane::Handle,
     pub(super) internal: Arc<DrmSurfaceInternal<A>>,
     pub(super) has_universal_planes: bool,
+    pub(super) has_async_page_flip: bool,
     #[cfg(feature = "backend_session")]
     pub(super) links: RefCell<Vec<crate::utils::signaling::SignalToken>>,
 }
@@ -237,19 +238,31 @@ impl<A: AsRawFd + 'static> DrmSurface<A> {
     ///
     /// This operation is not blocking and will produce a `vblank` event once swapping is done.
     /// Make sure to have the device registered in your event loop to not miss the event.
+    ///
+    /// If `flip_async` is set, the flip is carried out as soon as possible instead of waiting
+    /// for the next vertical blank, which may result in visible tearing.
+    /// This fails with [`Error::AsyncPageFlipUnsupported`], if the device does not support it
+    /// (see [`DrmSurface::supports_async_page_flip`]). Asynchronous flips may only change the
+    /// framebuffers of already configured planes, many drivers only accept the primary plane.
     pub fn page_flip<'a>(
         &self,
         mut framebuffers: impl Iterator<Item = &'a (framebuffer::Handle, plane::Handle)>,
         event: bool,
+        flip_async: bool,
     ) -> Result<(), Error> {
+        if flip_async && !self.has_async_page_flip {
+            return Err(Error::AsyncPageFlipUnsupported(self.crtc));
+        }
+
         match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) if flip_async => surf.page_flip_async(framebuffers, event),
             DrmSurfaceInternal::Atomic(surf) => surf.page_flip(framebuffers, event),
             DrmSurfaceInternal::Legacy(surf) => {
                 if let Some((fb, plane)) = framebuffers.next() {
                     if plane_type(self, *plane)? != PlaneType::Primary {
                         return Err(Error::NonPrimaryPlane(*plane));
                     }
-                    surf.page_flip(*fb, event)
+                    surf.page_flip(*fb, event, flip_async)
                 } else {
                     Ok(())
                 }
@@ -257,6 +270,13 @@ impl<A: AsRawFd + 'static> DrmSurface<A> {
         }
     }
 
+    /// Returns if asynchronous (tearing) page flips are supported by the underlying device
+    ///
+    /// See [`DrmDevice::supports_async_page_flip`](crate::backend::drm::DrmDevice::supports_async_page_flip).
+    pub fn supports_async_page_flip(&self) -> bool {
+        self.has_async_page_flip
+    }
+
     /// Returns a set of supported pixel formats for attached buffers
     pub fn supported_formats(&self, plane: plane::Handle) -> Result<HashSet<Format>, Error> {
         // get plane formats
@@ -0,0 +1,301 @@
+//! Module for DRM synchronization objects
+//!
+//! DRM synchronization objects (syncobjs) are kernel containers for `dma_fence`s. Timeline
+//! syncobjs additionally carry a monotonically increasing 64-bit counter, where each point of
+//! the timeline is signaled once its associated fence is.
+//!
+//! Timelines are shared between processes as file descriptors and need to be imported on a
+//! [`DrmNode`], usually the render node the compositor is rendering with. See
+//! [`wayland::drm_syncobj`](crate::wayland::drm_syncobj) for the main user of this module.
+
+use std::{
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    sync::Arc,
+};
+
+use drm_ffi::{
+    drm_syncobj_create, drm_syncobj_destroy, drm_syncobj_handle, drm_syncobj_timeline_array,
+    drm_syncobj_transfer, DRM_CAP_SYNCOBJ_TIMELINE, DRM_IOCTL_BASE,
+    DRM_SYNCOBJ_FD_TO_HANDLE_FLAGS_IMPORT_SYNC_FILE, DRM_SYNCOBJ_HANDLE_TO_FD_FLAGS_EXPORT_SYNC_FILE,
+    DRM_SYNCOBJ_WAIT_FLAGS_WAIT_AVAILABLE,
+};
+use nix::sys::eventfd::{eventfd, EfdFlags};
+
+use super::DrmNode;
+
+// Not yet part of `drm-sys`, mirrors `struct drm_syncobj_eventfd` of the kernel uapi
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+struct drm_syncobj_eventfd {
+    handle: u32,
+    flags: u32,
+    point: u64,
+    fd: i32,
+    pad: u32,
+}
+
+mod ioctl {
+    use super::*;
+
+    nix::ioctl_readwrite!(syncobj_create, DRM_IOCTL_BASE, 0xBF, drm_syncobj_create);
+    nix::ioctl_readwrite!(syncobj_destroy, DRM_IOCTL_BASE, 0xC0, drm_syncobj_destroy);
+    nix::ioctl_readwrite!(syncobj_handle_to_fd, DRM_IOCTL_BASE, 0xC1, drm_syncobj_handle);
+    nix::ioctl_readwrite!(syncobj_fd_to_handle, DRM_IOCTL_BASE, 0xC2, drm_syncobj_handle);
+    nix::ioctl_readwrite!(syncobj_query, DRM_IOCTL_BASE, 0xCB, drm_syncobj_timeline_array);
+    nix::ioctl_readwrite!(syncobj_transfer, DRM_IOCTL_BASE, 0xCC, drm_syncobj_transfer);
+    nix::ioctl_readwrite!(
+        syncobj_timeline_signal,
+        DRM_IOCTL_BASE,
+        0xCD,
+        drm_syncobj_timeline_array
+    );
+    nix::ioctl_readwrite!(syncobj_eventfd, DRM_IOCTL_BASE, 0xCF, drm_syncobj_eventfd);
+}
+
+/// Returns whether the given device supports timeline syncobjs and waiting on them using an eventfd
+///
+/// Both are required by [`DrmTimeline`] to integrate with the event loop.
+pub fn supports_syncobj_eventfd<D: AsRawFd>(device: &D) -> bool {
+    let fd = device.as_raw_fd();
+    let timeline = matches!(
+        drm_ffi::get_capability(fd, DRM_CAP_SYNCOBJ_TIMELINE as u64),
+        Ok(cap) if cap.value != 0
+    );
+    if !timeline {
+        return false;
+    }
+
+    // there is no capability for the eventfd ioctl, so check if it is known to the kernel.
+    // An invalid handle is rejected with `ENOENT`, while older kernels return `EINVAL`.
+    let mut args = drm_syncobj_eventfd {
+        handle: 0,
+        flags: 0,
+        point: 0,
+        fd: -1,
+        pad: 0,
+    };
+    matches!(
+        unsafe { ioctl::syncobj_eventfd(fd, &mut args) },
+        Err(nix::errno::Errno::ENOENT)
+    )
+}
+
+#[derive(Debug)]
+struct TimelineInner {
+    device: Arc<DrmNode>,
+    handle: u32,
+}
+
+impl Drop for TimelineInner {
+    fn drop(&mut self) {
+        let mut args = drm_syncobj_destroy {
+            handle: self.handle,
+            pad: 0,
+        };
+        let _ = unsafe { ioctl::syncobj_destroy(self.device.as_raw_fd(), &mut args) };
+    }
+}
+
+/// A timeline syncobj imported on a [`DrmNode`]
+///
+/// The syncobj is destroyed once the last clone of this handle is dropped.
+#[derive(Debug, Clone)]
+pub struct DrmTimeline(Arc<TimelineInner>);
+
+impl PartialEq for DrmTimeline {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for DrmTimeline {}
+
+impl DrmTimeline {
+    /// Import a timeline from a syncobj file descriptor
+    ///
+    /// The file descriptor is not consumed and can be closed afterwards.
+    pub fn import(device: Arc<DrmNode>, fd: RawFd) -> io::Result<DrmTimeline> {
+        let mut args = drm_syncobj_handle {
+            handle: 0,
+            flags: 0,
+            fd,
+            pad: 0,
+        };
+        unsafe { ioctl::syncobj_fd_to_handle(device.as_raw_fd(), &mut args) }
+            .map_err(Into::<io::Error>::into)?;
+        Ok(DrmTimeline(Arc::new(TimelineInner {
+            device,
+            handle: args.handle,
+        })))
+    }
+
+    /// The device this timeline was imported on
+    pub fn device(&self) -> &Arc<DrmNode> {
+        &self.0.device
+    }
+
+    /// Query the last signaled point of this timeline
+    pub fn query_signaled_point(&self) -> io::Result<u64> {
+        let mut point = 0u64;
+        let mut args = drm_syncobj_timeline_array {
+            handles: &self.0.handle as *const u32 as u64,
+            points: &mut point as *mut u64 as u64,
+            count_handles: 1,
+            flags: 0,
+        };
+        unsafe { ioctl::syncobj_query(self.0.device.as_raw_fd(), &mut args) }
+            .map_err(Into::<io::Error>::into)?;
+        Ok(point)
+    }
+
+    /// Signal the given point of this timeline from the cpu
+    pub fn signal(&self, point: u64) -> io::Result<()> {
+        let mut args = drm_syncobj_timeline_array {
+            handles: &self.0.handle as *const u32 as u64,
+            points: &point as *const u64 as u64,
+            count_handles: 1,
+            flags: 0,
+        };
+        unsafe { ioctl::syncobj_timeline_signal(self.0.device.as_raw_fd(), &mut args) }
+            .map_err(Into::<io::Error>::into)?;
+        Ok(())
+    }
+
+    /// Create an eventfd, that becomes readable once the given point is signaled
+    ///
+    /// If `wait_available` is set, the eventfd instead becomes readable once a fence has been
+    /// attached to the point, which is not necessarily signaled yet.
+    /// The returned file descriptor is owned by the caller.
+    pub fn eventfd(&self, point: u64, wait_available: bool) -> io::Result<RawFd> {
+        let fd =
+            eventfd(0, EfdFlags::EFD_CLOEXEC | EfdFlags::EFD_NONBLOCK).map_err(Into::<io::Error>::into)?;
+        let mut args = drm_syncobj_eventfd {
+            handle: self.0.handle,
+            flags: if wait_available {
+                DRM_SYNCOBJ_WAIT_FLAGS_WAIT_AVAILABLE
+            } else {
+                0
+            },
+            point,
+            fd,
+            pad: 0,
+        };
+        if let Err(err) = unsafe { ioctl::syncobj_eventfd(self.0.device.as_raw_fd(), &mut args) } {
+            let _ = nix::unistd::close(fd);
+            return Err(err.into());
+        }
+        Ok(fd)
+    }
+
+    /// Export the fence of the given point as a `sync_file`
+    ///
+    /// The point needs to have a fence attached already (see [`DrmTimeline::eventfd`]).
+    /// The returned file descriptor is owned by the caller.
+    pub fn export_sync_file(&self, point: u64) -> io::Result<RawFd> {
+        let temp = self.transfer_to_binary(point)?;
+        let mut args = drm_syncobj_handle {
+            handle: temp.handle,
+            flags: DRM_SYNCOBJ_HANDLE_TO_FD_FLAGS_EXPORT_SYNC_FILE,
+            fd: -1,
+            pad: 0,
+        };
+        unsafe { ioctl::syncobj_handle_to_fd(self.0.device.as_raw_fd(), &mut args) }
+            .map_err(Into::<io::Error>::into)?;
+        Ok(args.fd)
+    }
+
+    /// Attach the fence of a `sync_file` to the given point
+    ///
+    /// The file descriptor is not consumed and can be closed afterwards.
+    pub fn import_sync_file(&self, point: u64, sync_file: RawFd) -> io::Result<()> {
+        let temp = self.create_binary()?;
+        let mut args = drm_syncobj_handle {
+            handle: temp.handle,
+            flags: DRM_SYNCOBJ_FD_TO_HANDLE_FLAGS_IMPORT_SYNC_FILE,
+            fd: sync_file,
+            pad: 0,
+        };
+        unsafe { ioctl::syncobj_fd_to_handle(self.0.device.as_raw_fd(), &mut args) }
+            .map_err(Into::<io::Error>::into)?;
+        let mut args = drm_syncobj_transfer {
+            src_handle: temp.handle,
+            dst_handle: self.0.handle,
+            src_point: 0,
+            dst_point: point,
+            flags: 0,
+            pad: 0,
+        };
+        unsafe { ioctl::syncobj_transfer(self.0.device.as_raw_fd(), &mut args) }
+            .map_err(Into::<io::Error>::into)?;
+        Ok(())
+    }
+
+    fn create_binary(&self) -> io::Result<TimelineInner> {
+        let mut args = drm_syncobj_create { handle: 0, flags: 0 };
+        unsafe { ioctl::syncobj_create(self.0.device.as_raw_fd(), &mut args) }
+            .map_err(Into::<io::Error>::into)?;
+        Ok(TimelineInner {
+            device: self.0.device.clone(),
+            handle: args.handle,
+        })
+    }
+
+    fn transfer_to_binary(&self, point: u64) -> io::Result<TimelineInner> {
+        let temp = self.create_binary()?;
+        let mut args = drm_syncobj_transfer {
+            src_handle: self.0.handle,
+            dst_handle: temp.handle,
+            src_point: point,
+            dst_point: 0,
+            flags: 0,
+            pad: 0,
+        };
+        unsafe { ioctl::syncobj_transfer(self.0.device.as_raw_fd(), &mut args) }
+            .map_err(Into::<io::Error>::into)?;
+        Ok(temp)
+    }
+}
+
+/// A point on a [`DrmTimeline`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrmSyncPoint {
+    /// The timeline of this point
+    pub timeline: DrmTimeline,
+    /// The value of this point
+    pub point: u64,
+}
+
+impl DrmSyncPoint {
+    /// Returns whether this point is already signaled
+    pub fn is_signaled(&self) -> io::Result<bool> {
+        Ok(self.timeline.query_signaled_point()? >= self.point)
+    }
+
+    /// Signal this point from the cpu
+    pub fn signal(&self) -> io::Result<()> {
+        self.timeline.signal(self.point)
+    }
+
+    /// Create an eventfd, that becomes readable once this point is signaled
+    ///
+    /// See [`DrmTimeline::eventfd`].
+    pub fn eventfd(&self) -> io::Result<RawFd> {
+        self.timeline.eventfd(self.point, false)
+    }
+
+    /// Export the fence of this point as a `sync_file`
+    ///
+    /// See [`DrmTimeline::export_sync_file`].
+    pub fn export_sync_file(&self) -> io::Result<RawFd> {
+        self.timeline.export_sync_file(self.point)
+    }
+
+    /// Signal this point once the fence of the given `sync_file` is signaled
+    ///
+    /// This is useful to release a buffer once the rendering operations using it finish,
+    /// without stalling the cpu. See [`DrmTimeline::import_sync_file`].
+    pub fn signal_with_sync_file(&self, sync_file: RawFd) -> io::Result<()> {
+        self.timeline.import_sync_file(self.point, sync_file)
+    }
+}
@@ -210,7 +210,7 @@ pub mod egl {
     }
 
     mod wayland_storage {
-        use super::{FnPtr, __gl_imports::raw};
+        use super::{__gl_imports::raw, FnPtr};
         pub static mut BindWaylandDisplayWL: FnPtr = FnPtr {
             f: super::missing_fn_panic as *const raw::c_void,
             is_loaded: false,
@@ -231,8 +231,8 @@ pub mod egl {
 
     #[allow(non_snake_case)]
     pub mod DebugMessageControlKHR {
-        use super::FnPtr;
         use super::__gl_imports::raw;
+        use super::FnPtr;
         use super::{metaloadfn, wayland_storage};
 
         #[inline]
@@ -255,7 +255,7 @@ pub mod egl {
 
     #[allow(non_snake_case)]
     pub mod BindWaylandDisplayWL {
-        use super::{FnPtr, __gl_imports::raw, metaloadfn, wayland_storage};
+        use super::{__gl_imports::raw, metaloadfn, wayland_storage, FnPtr};
 
         #[inline]
         #[allow(dead_code)]
@@ -277,7 +277,7 @@ pub mod egl {
 
     #[allow(non_snake_case)]
     pub mod UnbindWaylandDisplayWL {
-        use super::{FnPtr, __gl_imports::raw, metaloadfn, wayland_storage};
+        use super::{__gl_imports::raw, metaloadfn, wayland_storage, FnPtr};
 
         #[inline]
         #[allow(dead_code)]
@@ -299,7 +299,7 @@ pub mod egl {
 
     #[allow(non_snake_case)]
     pub mod QueryWaylandBufferWL {
-        use super::{FnPtr, __gl_imports::raw, metaloadfn, wayland_storage};
+        use super::{__gl_imports::raw, metaloadfn, wayland_storage, FnPtr};
 
         #[inline]
         #[allow(dead_code)]
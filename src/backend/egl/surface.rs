@@ -13,6 +13,7 @@ use crate::backend::egl::{
     EGLError, SwapBuffersError,
 };
 
+use crate::utils::{Physical, Size};
 use slog::{debug, o};
 
 /// EGL surface of a given EGL context for rendering
@@ -141,6 +142,32 @@ impl EGLSurface {
         self.pixel_format
     }
 
+    /// Returns the current size of the surface in pixels, if it can be queried.
+    pub fn get_size(&self) -> Option<Size<i32, Physical>> {
+        let surface = self.surface.load(Ordering::SeqCst);
+        let mut width = 0;
+        let mut height = 0;
+        let ok = unsafe {
+            ffi::egl::QuerySurface(
+                **self.display,
+                surface as *const _,
+                ffi::egl::WIDTH as i32,
+                &mut width,
+            ) == ffi::egl::TRUE as ffi::egl::types::EGLBoolean
+                && ffi::egl::QuerySurface(
+                    **self.display,
+                    surface as *const _,
+                    ffi::egl::HEIGHT as i32,
+                    &mut height,
+                ) == ffi::egl::TRUE as ffi::egl::types::EGLBoolean
+        };
+        if ok {
+            Some(Size::from((width, height)))
+        } else {
+            None
+        }
+    }
+
     /// Tries to resize the underlying native surface.
     ///
     /// The two first arguments (width, height) are the new size of the surface,
@@ -0,0 +1,547 @@
+//! Implementation of a backend without any display hardware
+//!
+//! The headless backend provides virtual outputs, that are rendered into memory-backed buffers by any
+//! [`Renderer`](crate::backend::renderer::Renderer) supporting [`Offscreen`] rendering, e.g. the
+//! [`SoftwareRenderer`](crate::backend::renderer::software::SoftwareRenderer) or a
+//! [`Gles2Renderer`](crate::backend::renderer::gles2::Gles2Renderer) on a surfaceless EGL context.
+//! This makes it possible to run a compositor in containers, in CI or for sessions only accessed remotely.
+//!
+//! The backend is initialized using [`HeadlessBackend::new`]. The [`HeadlessBackend`] is a calloop
+//! event source emitting [`HeadlessEvent::VBlank`] events, simulating the frame clock of the outputs.
+//! Outputs are created through the [`HeadlessHandle`] of the backend:
+//!
+//! ```rust,no_run
+//! # use smithay::backend::headless::{HeadlessBackend, HeadlessEvent, Mode};
+//! # use smithay::backend::renderer::{software::{SoftwareImage, SoftwareRenderer}, Frame, Renderer, Transform};
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! # let log = slog::Logger::root(slog::Discard, slog::o!());
+//! let mut event_loop = calloop::EventLoop::<()>::try_new()?;
+//! let backend = HeadlessBackend::new(log.clone())?;
+//! let mut output = backend.handle().create_output::<SoftwareImage>(
+//!     "HEADLESS-1",
+//!     Mode { size: (1920, 1080).into(), refresh: 60_000 },
+//! );
+//! event_loop.handle().insert_source(backend, |event, _, _| match event {
+//!     HeadlessEvent::VBlank { output, .. } => {
+//!         // the last submitted frame of `output` is now "on screen", render the next one
+//!     }
+//! })?;
+//!
+//! let mut renderer = SoftwareRenderer::new(log);
+//! // bind one of the buffers of the output and render into it
+//! let _age = output.bind(&mut renderer)?;
+//! renderer.render(output.mode().size, Transform::Normal, |_, frame| {
+//!     frame.clear([0.1, 0.1, 0.1, 1.0])
+//! })??;
+//! // and queue it for display
+//! output.submit()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Frame clock
+//!
+//! Like real displays, a virtual output emits a [`HeadlessEvent::VBlank`] once a submitted frame was
+//! "displayed". Frames are displayed at the refresh rate of the [`Mode`] of the output, but never before
+//! they were submitted: an idle output does not generate any events.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io,
+    rc::{Rc, Weak},
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    timer::{Timer, TimerHandle},
+    EventSource, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use slog::{debug, o, trace};
+
+use crate::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{Offscreen, Renderer},
+    },
+    utils::{Physical, Size},
+};
+
+// refresh rate used for modes without a valid refresh rate
+const DEFAULT_REFRESH: i32 = 60_000;
+// formats tried for the buffers of an output, if none was set explicitly
+const DEFAULT_FORMATS: [Fourcc; 2] = [Fourcc::Argb8888, Fourcc::Abgr8888];
+
+/// A mode of a virtual output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mode {
+    /// Size of the output, in pixels
+    pub size: Size<i32, Physical>,
+    /// Refresh rate in millihertz
+    ///
+    /// Values `<= 0` fall back to 60 Hz.
+    pub refresh: i32,
+}
+
+impl Mode {
+    /// Duration of a frame at the refresh rate of this mode
+    pub fn frame_duration(&self) -> Duration {
+        let refresh = if self.refresh > 0 {
+            self.refresh
+        } else {
+            DEFAULT_REFRESH
+        };
+        Duration::from_nanos(1_000_000_000_000 / refresh as u64)
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl From<Mode> for crate::wayland::output::Mode {
+    fn from(mode: Mode) -> Self {
+        crate::wayland::output::Mode {
+            size: mode.size,
+            refresh: mode.refresh,
+        }
+    }
+}
+
+/// Identifier of a virtual output of a [`HeadlessBackend`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OutputId(u32);
+
+/// Events emitted by the [`HeadlessBackend`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadlessEvent {
+    /// The last frame submitted to an output was displayed
+    VBlank {
+        /// Output the frame was submitted to
+        output: OutputId,
+        /// Time the frame was displayed at
+        time: Instant,
+    },
+}
+
+/// Error returned by a [`HeadlessOutput`]
+#[derive(Debug, thiserror::Error)]
+pub enum HeadlessError {
+    /// A frame was already submitted and not yet displayed
+    #[error("A frame is already pending for display")]
+    FramePending,
+    /// The backend of the output was dropped
+    #[error("The backend of the output was dropped")]
+    BackendDropped,
+}
+
+#[derive(Debug)]
+struct OutputState {
+    mode: Mode,
+    pending: bool,
+    last_vblank: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct HeadlessInner {
+    log: slog::Logger,
+    timer: TimerHandle<OutputId>,
+    next_id: u32,
+    outputs: HashMap<OutputId, Weak<RefCell<OutputState>>>,
+}
+
+/// A backend without any display hardware
+///
+/// It needs to be inserted into an [`EventLoop`](calloop::EventLoop) to receive the
+/// [`HeadlessEvent`]s of its outputs.
+#[derive(Debug)]
+pub struct HeadlessBackend {
+    inner: Rc<RefCell<HeadlessInner>>,
+    timer: Timer<OutputId>,
+}
+
+impl HeadlessBackend {
+    /// Initialize a new headless backend
+    pub fn new<L>(logger: L) -> io::Result<HeadlessBackend>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_headless"));
+        let timer = Timer::new()?;
+        debug!(log, "Initialized headless backend");
+        Ok(HeadlessBackend {
+            inner: Rc::new(RefCell::new(HeadlessInner {
+                log,
+                timer: timer.handle(),
+                next_id: 0,
+                outputs: HashMap::new(),
+            })),
+            timer,
+        })
+    }
+
+    /// Returns a handle to create new outputs
+    pub fn handle(&self) -> HeadlessHandle {
+        HeadlessHandle {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl EventSource for HeadlessBackend {
+    type Event = HeadlessEvent;
+    type Metadata = ();
+    type Ret = ();
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<PostAction>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let inner = self.inner.clone();
+        self.timer.process_events(readiness, token, |output, _| {
+            let state = inner.borrow_mut().outputs.get(&output).and_then(Weak::upgrade);
+            let state = match state {
+                Some(state) => state,
+                None => {
+                    // the output was dropped in the meantime
+                    inner.borrow_mut().outputs.remove(&output);
+                    return;
+                }
+            };
+            let time = Instant::now();
+            {
+                let mut state = state.borrow_mut();
+                state.pending = false;
+                state.last_vblank = Some(time);
+            }
+            trace!(inner.borrow().log, "VBlank on {:?}", output);
+            callback(HeadlessEvent::VBlank { output, time }, &mut ());
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.timer.register(poll, token_factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.timer.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+        self.timer.unregister(poll)
+    }
+}
+
+/// Handle to a [`HeadlessBackend`], used to create virtual outputs
+#[derive(Debug, Clone)]
+pub struct HeadlessHandle {
+    inner: Rc<RefCell<HeadlessInner>>,
+}
+
+impl HeadlessHandle {
+    /// Create a new virtual output with the given mode
+    ///
+    /// `T` is the type of buffers the output is rendered into, e.g.
+    /// [`SoftwareImage`](crate::backend::renderer::software::SoftwareImage) or
+    /// [`Gles2Texture`](crate::backend::renderer::gles2::Gles2Texture).
+    pub fn create_output<T>(&self, name: impl Into<String>, mode: Mode) -> HeadlessOutput<T> {
+        let mut inner = self.inner.borrow_mut();
+        let id = OutputId(inner.next_id);
+        inner.next_id += 1;
+        let name = name.into();
+        let state = Rc::new(RefCell::new(OutputState {
+            mode,
+            pending: false,
+            last_vblank: None,
+        }));
+        inner.outputs.insert(id, Rc::downgrade(&state));
+        let log = inner.log.new(o!("output" => name.clone()));
+        debug!(log, "Created output with mode {:?}", mode);
+
+        HeadlessOutput {
+            id,
+            name,
+            state,
+            backend: Rc::downgrade(&self.inner),
+            format: None,
+            buffers: Vec::new(),
+            current: None,
+            front: None,
+            log,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct HeadlessBuffer<T> {
+    buffer: T,
+    age: u8,
+}
+
+/// A virtual output of a [`HeadlessBackend`]
+///
+/// Frames are rendered into the buffers bound by [`HeadlessOutput::bind`] and queued for display
+/// by [`HeadlessOutput::submit`].
+#[derive(Debug)]
+pub struct HeadlessOutput<T> {
+    id: OutputId,
+    name: String,
+    state: Rc<RefCell<OutputState>>,
+    backend: Weak<RefCell<HeadlessInner>>,
+    // format of the buffers, chosen on the first bind unless set explicitly
+    format: Option<Fourcc>,
+    // double-buffered, created on demand
+    buffers: Vec<HeadlessBuffer<T>>,
+    // buffer bound for rendering
+    current: Option<usize>,
+    // buffer last submitted for display
+    front: Option<usize>,
+    log: slog::Logger,
+}
+
+impl<T> HeadlessOutput<T> {
+    /// Identifier of this output, as used by the [`HeadlessEvent`]s
+    pub fn id(&self) -> OutputId {
+        self.id
+    }
+
+    /// Name of this output
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Current mode of this output
+    pub fn mode(&self) -> Mode {
+        self.state.borrow().mode
+    }
+
+    /// Change the mode of this output
+    ///
+    /// If the size changes, the buffers of the output are recreated on the next call to
+    /// [`HeadlessOutput::bind`].
+    pub fn set_mode(&mut self, mode: Mode) {
+        let old = std::mem::replace(&mut self.state.borrow_mut().mode, mode);
+        if old.size != mode.size {
+            self.buffers.clear();
+            self.current = None;
+            self.front = None;
+        }
+        debug!(self.log, "Changed mode to {:?}", mode);
+    }
+
+    /// Format of the buffers of this output
+    ///
+    /// Returns `None` if no format was set and no buffer was created yet.
+    pub fn format(&self) -> Option<Fourcc> {
+        self.format
+    }
+
+    /// Set the format of the buffers of this output
+    ///
+    /// By default [`Fourcc::Argb8888`] is used, falling back to [`Fourcc::Abgr8888`] if the renderer
+    /// cannot render into it. An explicitly set format is used without any fallback. The buffers of the
+    /// output are recreated on the next call to [`HeadlessOutput::bind`].
+    pub fn set_format(&mut self, format: Fourcc) {
+        self.format = Some(format);
+        self.buffers.clear();
+        self.current = None;
+        self.front = None;
+    }
+
+    /// Returns `true` if a submitted frame was not yet displayed
+    pub fn frame_pending(&self) -> bool {
+        self.state.borrow().pending
+    }
+
+    /// The buffer last submitted for display, if any
+    ///
+    /// This can be used to read back the contents of the output, e.g. to stream them to a remote client.
+    pub fn front_buffer(&self) -> Option<&T> {
+        self.front.map(|idx| &self.buffers[idx].buffer)
+    }
+
+    /// Bind a buffer of this output to the given renderer
+    ///
+    /// Returns the age of the buffer, the number of frames submitted since its contents were last
+    /// rendered, or `0` if its contents are undefined.
+    pub fn bind<R>(&mut self, renderer: &mut R) -> Result<u8, <R as Renderer>::Error>
+    where
+        R: Offscreen<T>,
+        T: Clone,
+    {
+        let idx = match self.current {
+            Some(idx) => idx,
+            None => {
+                let idx = match (0..self.buffers.len()).find(|idx| Some(*idx) != self.front) {
+                    Some(idx) => idx,
+                    None => {
+                        let buffer = self.create_buffer(renderer)?;
+                        self.buffers.push(HeadlessBuffer { buffer, age: 0 });
+                        self.buffers.len() - 1
+                    }
+                };
+                self.current = Some(idx);
+                idx
+            }
+        };
+        renderer.bind(self.buffers[idx].buffer.clone())?;
+        Ok(self.buffers[idx].age)
+    }
+
+    fn create_buffer<R>(&mut self, renderer: &mut R) -> Result<T, <R as Renderer>::Error>
+    where
+        R: Offscreen<T>,
+    {
+        let size = self.mode().size;
+        let size = (size.w, size.h).into();
+        if let Some(format) = self.format {
+            return renderer.create_buffer(format, size);
+        }
+
+        let (last, formats) = DEFAULT_FORMATS.split_last().unwrap();
+        for format in formats.iter().copied() {
+            match renderer.create_buffer(format, size) {
+                Ok(buffer) => {
+                    self.format = Some(format);
+                    return Ok(buffer);
+                }
+                Err(err) => debug!(
+                    self.log,
+                    "Failed to create buffer with format {:?}: {}", format, err
+                ),
+            }
+        }
+        let buffer = renderer.create_buffer(*last, size)?;
+        self.format = Some(*last);
+        Ok(buffer)
+    }
+
+    /// Submit the frame rendered into the bound buffer for display
+    ///
+    /// A [`HeadlessEvent::VBlank`] is emitted once the frame is displayed, at the earliest one frame
+    /// duration after the previous one. If no buffer was bound, the last frame is displayed again.
+    pub fn submit(&mut self) -> Result<(), HeadlessError> {
+        let backend = self.backend.upgrade().ok_or(HeadlessError::BackendDropped)?;
+        let mut state = self.state.borrow_mut();
+        if state.pending {
+            return Err(HeadlessError::FramePending);
+        }
+
+        if let Some(idx) = self.current.take() {
+            for (i, buffer) in self.buffers.iter_mut().enumerate() {
+                if i == idx {
+                    buffer.age = 1;
+                } else if buffer.age > 0 {
+                    buffer.age = buffer.age.saturating_add(1);
+                }
+            }
+            self.front = Some(idx);
+        }
+
+        let now = Instant::now();
+        let deadline = state
+            .last_vblank
+            .map(|last| last + state.mode.frame_duration())
+            .filter(|deadline| *deadline > now)
+            .unwrap_or(now);
+        state.pending = true;
+        backend.borrow().timer.add_timeout(deadline - now, self.id);
+        trace!(self.log, "Submitted frame, displayed in {:?}", deadline - now);
+        Ok(())
+    }
+}
+
+impl<T> Drop for HeadlessOutput<T> {
+    fn drop(&mut self) {
+        if let Some(backend) = self.backend.upgrade() {
+            backend.borrow_mut().outputs.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "renderer_software"))]
+mod tests {
+    use super::*;
+    use crate::backend::renderer::{
+        software::{SoftwareImage, SoftwareRenderer},
+        Frame, Transform,
+    };
+
+    #[test]
+    fn vblank() {
+        let mut event_loop = calloop::EventLoop::<Vec<OutputId>>::try_new().unwrap();
+        let backend = HeadlessBackend::new(None).unwrap();
+        let mode = Mode {
+            size: (4, 4).into(),
+            refresh: 1_000_000,
+        };
+        let mut output = backend
+            .handle()
+            .create_output::<SoftwareImage>("HEADLESS-1", mode);
+        event_loop
+            .handle()
+            .insert_source(backend, |event, _, vblanks| match event {
+                HeadlessEvent::VBlank { output, .. } => vblanks.push(output),
+            })
+            .unwrap();
+        let mut renderer = SoftwareRenderer::new(None);
+
+        let mut vblanks = Vec::new();
+        for (frame, color) in [0xff00_00ffu32, 0xff00_ff00, 0xffff_0000]
+            .iter()
+            .copied()
+            .enumerate()
+        {
+            let age = output.bind(&mut renderer).unwrap();
+            assert_eq!(age, if frame < 2 { 0 } else { 2 });
+            let rgba = [
+                ((color >> 16) & 0xff) as f32 / 255.0,
+                ((color >> 8) & 0xff) as f32 / 255.0,
+                (color & 0xff) as f32 / 255.0,
+                1.0,
+            ];
+            renderer
+                .render(mode.size, Transform::Normal, |_, frame| frame.clear(rgba))
+                .unwrap()
+                .unwrap();
+            output.submit().unwrap();
+            assert!(matches!(output.submit(), Err(HeadlessError::FramePending)));
+            assert_eq!(output.front_buffer().unwrap().pixel(1, 1), Some(color));
+
+            while output.frame_pending() {
+                event_loop
+                    .dispatch(Some(Duration::from_millis(100)), &mut vblanks)
+                    .unwrap();
+            }
+        }
+        assert_eq!(vblanks, vec![output.id(); 3]);
+    }
+
+    #[test]
+    fn format() {
+        let backend = HeadlessBackend::new(None).unwrap();
+        let mode = Mode {
+            size: (4, 4).into(),
+            refresh: 60_000,
+        };
+        let mut output = backend
+            .handle()
+            .create_output::<SoftwareImage>("HEADLESS-1", mode);
+        let mut renderer = SoftwareRenderer::new(None);
+
+        assert_eq!(output.format(), None);
+        output.bind(&mut renderer).unwrap();
+        assert_eq!(output.format(), Some(Fourcc::Argb8888));
+
+        // explicitly set formats are not replaced by a fallback
+        output.set_format(Fourcc::Abgr8888);
+        assert!(output.bind(&mut renderer).is_err());
+        assert_eq!(output.format(), Some(Fourcc::Abgr8888));
+
+        output.set_format(Fourcc::Xrgb8888);
+        output.bind(&mut renderer).unwrap();
+        assert_eq!(output.format(), Some(Fourcc::Xrgb8888));
+    }
+}
@@ -2,6 +2,8 @@
 
 use std::path::PathBuf;
 
+pub mod recording;
+pub mod synthetic;
 mod tablet;
 
 pub use tablet::{
@@ -44,6 +46,32 @@ pub enum DeviceCapability {
     Switch,
 }
 
+bitflags::bitflags! {
+    /// LEDs of a keyboard
+    pub struct Led: u32 {
+        /// The "Num lock" LED
+        const NUMLOCK = 1;
+        /// The "Caps lock" LED
+        const CAPSLOCK = 2;
+        /// The "Scroll lock" LED
+        const SCROLLLOCK = 4;
+    }
+}
+
+/// A handle to the LEDs of the keyboard devices of an input backend
+///
+/// Backends driving physical devices provide a cloneable implementation of this trait, which keeps
+/// working after the backend was inserted into the event loop. Register it with
+/// [`KeyboardHandle::add_led_handle`](crate::wayland::seat::KeyboardHandle::add_led_handle) to keep
+/// the LEDs in sync with the keyboard of a seat. Nested backends do not provide one, as the LEDs
+/// are managed by the parent system.
+pub trait LedHandle: std::fmt::Debug {
+    /// Update the LEDs of all keyboard devices
+    ///
+    /// LEDs not contained in `leds` are turned off.
+    fn update_leds(&self, leds: Led);
+}
+
 /// Trait for generic functions every input event does provide
 pub trait Event<B: InputBackend> {
     /// Returns an upward counting variable useful for event ordering.
@@ -341,7 +369,6 @@ pub struct TouchSlot {
     id: u64,
 }
 
-#[cfg(any(feature = "backend_winit", feature = "backend_libinput"))]
 impl TouchSlot {
     pub(crate) fn new(id: u64) -> Self {
         TouchSlot { id }
@@ -0,0 +1,1007 @@
+//! Recording and replaying of input events
+//!
+//! The [`InputRecorder`] serializes the [`InputEvent`]s of any [`InputBackend`] into a line-based text
+//! format, including the devices emitting them and the time they were received at. Call
+//! [`InputRecorder::record`] with every event you receive from your backend, before handling it:
+//!
+//! ```no_run
+//! # use smithay::backend::input::{InputBackend, InputEvent};
+//! use smithay::backend::input::recording::InputRecorder;
+//!
+//! # fn handle_event<B: InputBackend>(event: InputEvent<B>) {}
+//! let mut recorder = InputRecorder::create("input.rec").expect("Failed to create the recording");
+//! # let events: Vec<InputEvent<smithay::backend::input::synthetic::SyntheticInputBackend>> = Vec::new();
+//! for event in events {
+//!     recorder.record(&event).expect("Failed to record the event");
+//!     handle_event(event);
+//! }
+//! ```
+//!
+//! The [`InputReplay`] reads a recording and emits the recorded events again through calloop, as
+//! [`SyntheticInputBackend`] events, with their original timing or sped up by a given factor.
+//! Backend specific [`InputEvent::Special`] events are not recorded.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{self, Write as _},
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use calloop::{timer::Timer, EventSource, Poll, PostAction, Readiness, Token, TokenFactory};
+
+use super::{
+    synthetic::{
+        SyntheticDevice, SyntheticInputBackend, SyntheticKeyboardKeyEvent, SyntheticPointerAxisEvent,
+        SyntheticPointerButtonEvent, SyntheticPointerMotionAbsoluteEvent, SyntheticPointerMotionEvent,
+        SyntheticTabletToolAxes, SyntheticTabletToolAxisEvent, SyntheticTabletToolButtonEvent,
+        SyntheticTabletToolProximityEvent, SyntheticTabletToolTipEvent, SyntheticTouchCancelEvent,
+        SyntheticTouchDownEvent, SyntheticTouchFrameEvent, SyntheticTouchMotionEvent, SyntheticTouchUpEvent,
+    },
+    Axis, AxisSource, ButtonState, Device, DeviceCapability, Event, InputBackend, InputEvent, KeyState,
+    KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, PointerMotionEvent,
+    ProximityState, TabletToolButtonEvent, TabletToolCapabilitys, TabletToolDescriptor, TabletToolEvent,
+    TabletToolProximityEvent, TabletToolTipEvent, TabletToolTipState, TabletToolType, TouchCancelEvent,
+    TouchDownEvent, TouchMotionEvent, TouchUpEvent,
+};
+
+// first line of every recording
+const HEADER: &str = "smithay-input-recording 1";
+// longest timeout of the replay timer, later events are waited for in several steps
+const MAX_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+const CAPABILITIES: [(DeviceCapability, &str); 7] = [
+    (DeviceCapability::Keyboard, "keyboard"),
+    (DeviceCapability::Pointer, "pointer"),
+    (DeviceCapability::Touch, "touch"),
+    (DeviceCapability::TabletTool, "tablet-tool"),
+    (DeviceCapability::TabletPad, "tablet-pad"),
+    (DeviceCapability::Gesture, "gesture"),
+    (DeviceCapability::Switch, "switch"),
+];
+
+const TOOL_TYPES: [(TabletToolType, &str); 9] = [
+    (TabletToolType::Pen, "pen"),
+    (TabletToolType::Eraser, "eraser"),
+    (TabletToolType::Brush, "brush"),
+    (TabletToolType::Pencil, "pencil"),
+    (TabletToolType::Airbrush, "airbrush"),
+    (TabletToolType::Mouse, "mouse"),
+    (TabletToolType::Lens, "lens"),
+    (TabletToolType::Totem, "totem"),
+    (TabletToolType::Unknown, "unknown"),
+];
+
+const AXIS_SOURCES: [(AxisSource, &str); 4] = [
+    (AxisSource::Finger, "finger"),
+    (AxisSource::Continuous, "continuous"),
+    (AxisSource::Wheel, "wheel"),
+    (AxisSource::WheelTilt, "wheel-tilt"),
+];
+
+/// Records the input events of an [`InputBackend`]
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug)]
+pub struct InputRecorder<W: Write> {
+    writer: W,
+    start: Instant,
+    next_id: u32,
+    // recorded ids of the devices, by `Device::id`
+    devices: HashMap<String, u32>,
+}
+
+impl InputRecorder<BufWriter<File>> {
+    /// Create a new recording in the file at the given path
+    pub fn create(path: impl AsRef<Path>) -> io::Result<InputRecorder<BufWriter<File>>> {
+        InputRecorder::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> InputRecorder<W> {
+    /// Start a new recording into the given writer
+    ///
+    /// The timing of the recorded events is relative to the creation of the recorder.
+    pub fn new(mut writer: W) -> io::Result<InputRecorder<W>> {
+        writeln!(writer, "{}", HEADER)?;
+        Ok(InputRecorder {
+            writer,
+            start: Instant::now(),
+            next_id: 0,
+            devices: HashMap::new(),
+        })
+    }
+
+    /// Record an input event
+    ///
+    /// Devices emitting events before being added are recorded as added just before the event.
+    pub fn record<B: InputBackend>(&mut self, event: &InputEvent<B>) -> io::Result<()> {
+        let elapsed = self.start.elapsed().as_micros();
+        let mut line = String::new();
+        match event {
+            InputEvent::DeviceAdded { device } => {
+                self.device_id(elapsed, device)?;
+            }
+            InputEvent::DeviceRemoved { device } => {
+                let id = self.device_id(elapsed, device)?;
+                self.devices.remove(&device.id());
+                line = format!("device-removed {}", id);
+            }
+            InputEvent::Keyboard { event } => {
+                line = self.event_line(elapsed, "key", event)?;
+                let state = match event.state() {
+                    KeyState::Pressed => "pressed",
+                    KeyState::Released => "released",
+                };
+                let _ = write!(line, " {} {} {}", event.key_code(), state, event.count());
+            }
+            InputEvent::PointerMotion { event } => {
+                line = self.event_line(elapsed, "pointer-motion", event)?;
+                let _ = write!(line, " {} {}", event.delta_x(), event.delta_y());
+            }
+            InputEvent::PointerMotionAbsolute { event } => {
+                line = self.event_line(elapsed, "pointer-motion-absolute", event)?;
+                let _ = write!(
+                    line,
+                    " {} {} {} {}",
+                    event.x(),
+                    event.y(),
+                    event.x_transformed(1),
+                    event.y_transformed(1)
+                );
+            }
+            InputEvent::PointerButton { event } => {
+                line = self.event_line(elapsed, "pointer-button", event)?;
+                let _ = write!(line, " {} {}", event.button_code(), button_state(event.state()));
+            }
+            InputEvent::PointerAxis { event } => {
+                line = self.event_line(elapsed, "pointer-axis", event)?;
+                let _ = write!(
+                    line,
+                    " {} {} {} {} {}",
+                    name_of(&AXIS_SOURCES, event.source()),
+                    OptionalValue(event.amount(Axis::Horizontal)),
+                    OptionalValue(event.amount(Axis::Vertical)),
+                    OptionalValue(event.amount_discrete(Axis::Horizontal)),
+                    OptionalValue(event.amount_discrete(Axis::Vertical)),
+                );
+            }
+            InputEvent::TouchDown { event } => {
+                line = self.event_line(elapsed, "touch-down", event)?;
+                let _ = write!(
+                    line,
+                    " {} {} {} {} {}",
+                    OptionalValue(event.slot().map(|slot| slot.id)),
+                    event.x(),
+                    event.y(),
+                    event.x_transformed(1),
+                    event.y_transformed(1),
+                );
+            }
+            InputEvent::TouchMotion { event } => {
+                line = self.event_line(elapsed, "touch-motion", event)?;
+                let _ = write!(
+                    line,
+                    " {} {} {} {} {}",
+                    OptionalValue(event.slot().map(|slot| slot.id)),
+                    event.x(),
+                    event.y(),
+                    event.x_transformed(1),
+                    event.y_transformed(1),
+                );
+            }
+            InputEvent::TouchUp { event } => {
+                line = self.event_line(elapsed, "touch-up", event)?;
+                let _ = write!(line, " {}", OptionalValue(event.slot().map(|slot| slot.id)));
+            }
+            InputEvent::TouchCancel { event } => {
+                line = self.event_line(elapsed, "touch-cancel", event)?;
+                let _ = write!(line, " {}", OptionalValue(event.slot().map(|slot| slot.id)));
+            }
+            InputEvent::TouchFrame { event } => {
+                line = self.event_line(elapsed, "touch-frame", event)?;
+            }
+            InputEvent::TabletToolAxis { event } => {
+                line = self.event_line(elapsed, "tablet-axis", event)?;
+                write_tablet_tool(&mut line, event);
+            }
+            InputEvent::TabletToolProximity { event } => {
+                line = self.event_line(elapsed, "tablet-proximity", event)?;
+                write_tablet_tool(&mut line, event);
+                let state = match event.state() {
+                    ProximityState::In => "in",
+                    ProximityState::Out => "out",
+                };
+                let _ = write!(line, " {}", state);
+            }
+            InputEvent::TabletToolTip { event } => {
+                line = self.event_line(elapsed, "tablet-tip", event)?;
+                write_tablet_tool(&mut line, event);
+                let state = match event.tip_state() {
+                    TabletToolTipState::Down => "down",
+                    TabletToolTipState::Up => "up",
+                };
+                let _ = write!(line, " {}", state);
+            }
+            InputEvent::TabletToolButton { event } => {
+                line = self.event_line(elapsed, "tablet-button", event)?;
+                write_tablet_tool(&mut line, event);
+                let _ = write!(
+                    line,
+                    " {} {} {}",
+                    event.button(),
+                    event.seat_button_count(),
+                    button_state(event.button_state())
+                );
+            }
+            InputEvent::Special(_) => {}
+        }
+        if !line.is_empty() {
+            writeln!(self.writer, "{} {}", elapsed, line)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the underlying writer
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Stop the recording, returning the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    // returns the recorded id of a device, recording it as added if it is not known yet
+    fn device_id<D: Device>(&mut self, elapsed: u128, device: &D) -> io::Result<u32> {
+        if let Some(id) = self.devices.get(&device.id()) {
+            return Ok(*id);
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.devices.insert(device.id(), id);
+
+        let capabilities = CAPABILITIES
+            .iter()
+            .filter(|(capability, _)| device.has_capability(*capability))
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+        let capabilities = if capabilities.is_empty() {
+            String::from("-")
+        } else {
+            capabilities.join(",")
+        };
+        let usb_id = device
+            .usb_id()
+            .map(|(product, vendor)| format!("{}:{}", product, vendor));
+        let syspath = device.syspath().map(|path| escape(&path.to_string_lossy()));
+        writeln!(
+            self.writer,
+            "{} device-added {} {} {} {} {}",
+            elapsed,
+            id,
+            capabilities,
+            OptionalValue(usb_id),
+            OptionalValue(syspath),
+            escape(&device.name())
+        )?;
+        Ok(id)
+    }
+
+    fn event_line<B: InputBackend, E: Event<B>>(
+        &mut self,
+        elapsed: u128,
+        kind: &str,
+        event: &E,
+    ) -> io::Result<String> {
+        let id = self.device_id(elapsed, &event.device())?;
+        Ok(format!("{} {} {}", kind, id, event.time()))
+    }
+}
+
+fn write_tablet_tool<B: InputBackend, E: TabletToolEvent<B>>(line: &mut String, event: &E) {
+    let tool = event.tool();
+    let mut changed = TabletToolCapabilitys::empty();
+    changed.set(TabletToolCapabilitys::TILT, event.tilt_has_changed());
+    changed.set(TabletToolCapabilitys::PRESSURE, event.pressure_has_changed());
+    changed.set(TabletToolCapabilitys::DISTANCE, event.distance_has_changed());
+    changed.set(TabletToolCapabilitys::ROTATION, event.rotation_has_changed());
+    changed.set(TabletToolCapabilitys::SLIDER, event.slider_has_changed());
+    changed.set(TabletToolCapabilitys::WHEEL, event.wheel_has_changed());
+    let _ = write!(
+        line,
+        " {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+        name_of(&TOOL_TYPES, tool.tool_type),
+        tool.hardware_serial,
+        tool.hardware_id_wacom,
+        tool.capabilitys.bits(),
+        event.x(),
+        event.y(),
+        event.x_transformed(1),
+        event.y_transformed(1),
+        event.delta_x(),
+        event.delta_y(),
+        event.distance(),
+        event.pressure(),
+        event.slider_position(),
+        event.tilt_x(),
+        event.tilt_y(),
+        event.rotation(),
+        event.wheel_delta(),
+        event.wheel_delta_discrete(),
+        changed.bits(),
+    );
+}
+
+fn button_state(state: ButtonState) -> &'static str {
+    match state {
+        ButtonState::Pressed => "pressed",
+        ButtonState::Released => "released",
+    }
+}
+
+fn name_of<T: PartialEq>(names: &[(T, &'static str)], value: T) -> &'static str {
+    names
+        .iter()
+        .find(|(v, _)| *v == value)
+        .map(|(_, name)| *name)
+        .unwrap()
+}
+
+fn value_of<T: Copy>(names: &[(T, &'static str)], name: &str) -> Option<T> {
+    names.iter().find(|(_, n)| *n == name).map(|(value, _)| *value)
+}
+
+// strings are written as a single token
+fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ' ' => escaped.push_str("\\s"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(string: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(string.len());
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            unescaped.push(match chars.next()? {
+                '\\' => '\\',
+                's' => ' ',
+                't' => '\t',
+                'n' => '\n',
+                'r' => '\r',
+                _ => return None,
+            });
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Some(unescaped)
+}
+
+// optional values are written as `-` if missing
+struct OptionalValue<T>(Option<T>);
+
+impl<T: fmt::Display> fmt::Display for OptionalValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(ref value) => value.fmt(f),
+            None => f.write_str("-"),
+        }
+    }
+}
+
+/// Error while reading an input recording
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    /// Reading the recording failed
+    #[error("Reading the recording failed")]
+    Io(#[from] io::Error),
+    /// The recording does not start with a supported header
+    #[error("Not a supported input recording")]
+    InvalidHeader,
+    /// A line of the recording could not be parsed
+    #[error("Invalid event on line {0} of the recording")]
+    InvalidLine(usize),
+    /// The replay speed is not a positive finite number
+    #[error("Invalid replay speed {0}, it needs to be positive and finite")]
+    InvalidSpeed(f64),
+}
+
+// tokens of a recorded line
+struct Fields<'a>(std::str::SplitWhitespace<'a>);
+
+impl<'a> Fields<'a> {
+    fn str(&mut self) -> Option<&'a str> {
+        self.0.next()
+    }
+
+    fn next<T: FromStr>(&mut self) -> Option<T> {
+        self.0.next()?.parse().ok()
+    }
+
+    fn optional<T: FromStr>(&mut self) -> Option<Option<T>> {
+        match self.0.next()? {
+            "-" => Some(None),
+            value => value.parse().ok().map(Some),
+        }
+    }
+
+    fn point<Kind>(&mut self) -> Option<crate::utils::Point<f64, Kind>> {
+        Some((self.next()?, self.next()?).into())
+    }
+
+    fn button_state(&mut self) -> Option<ButtonState> {
+        match self.str()? {
+            "pressed" => Some(ButtonState::Pressed),
+            "released" => Some(ButtonState::Released),
+            _ => None,
+        }
+    }
+
+    fn tablet_tool(&mut self) -> Option<(TabletToolDescriptor, SyntheticTabletToolAxes)> {
+        let tool = TabletToolDescriptor {
+            tool_type: value_of(&TOOL_TYPES, self.str()?)?,
+            hardware_serial: self.next()?,
+            hardware_id_wacom: self.next()?,
+            capabilitys: TabletToolCapabilitys::from_bits_truncate(self.next()?),
+        };
+        let axes = SyntheticTabletToolAxes {
+            position: self.point()?,
+            normalized: self.point()?,
+            delta: self.point()?,
+            distance: self.next()?,
+            pressure: self.next()?,
+            slider: self.next()?,
+            tilt: (self.next()?, self.next()?),
+            rotation: self.next()?,
+            wheel_delta: self.next()?,
+            wheel_delta_discrete: self.next()?,
+            changed: TabletToolCapabilitys::from_bits_truncate(self.next()?),
+        };
+        Some((tool, axes))
+    }
+}
+
+// parses a recorded line into the synthetic backend
+fn parse_line(
+    line: &str,
+    backend: &mut SyntheticInputBackend,
+    devices: &mut HashMap<u32, SyntheticDevice>,
+) -> Option<Duration> {
+    let mut fields = Fields(line.split_whitespace());
+    let elapsed = Duration::from_micros(fields.next()?);
+    let kind = fields.str()?;
+    let id: u32 = fields.next()?;
+
+    if kind == "device-added" {
+        let capabilities = match fields.str()? {
+            "-" => Vec::new(),
+            names => names
+                .split(',')
+                .map(|name| value_of(&CAPABILITIES, name))
+                .collect::<Option<Vec<_>>>()?,
+        };
+        let usb_id = match fields.optional::<String>()? {
+            Some(usb_id) => {
+                let mut ids = usb_id.splitn(2, ':');
+                Some((ids.next()?.parse().ok()?, ids.next()?.parse().ok()?))
+            }
+            None => None,
+        };
+        let syspath = match fields.optional::<String>()? {
+            Some(path) => Some(unescape(&path)?),
+            None => None,
+        };
+        let mut device = SyntheticDevice::new(unescape(fields.str()?)?, &capabilities);
+        if let Some(usb_id) = usb_id {
+            device = device.with_usb_id(usb_id);
+        }
+        if let Some(syspath) = syspath {
+            device = device.with_syspath(syspath);
+        }
+        devices.insert(id, backend.add_device(device));
+        return Some(elapsed);
+    }
+
+    if kind == "device-removed" {
+        backend.remove_device(&devices.remove(&id)?);
+        return Some(elapsed);
+    }
+
+    let device = devices.get(&id)?.clone();
+    let time = fields.next()?;
+    let event = match kind {
+        "key" => InputEvent::Keyboard {
+            event: SyntheticKeyboardKeyEvent {
+                device,
+                time,
+                key_code: fields.next()?,
+                state: match fields.str()? {
+                    "pressed" => KeyState::Pressed,
+                    "released" => KeyState::Released,
+                    _ => return None,
+                },
+                count: fields.next()?,
+            },
+        },
+        "pointer-motion" => InputEvent::PointerMotion {
+            event: SyntheticPointerMotionEvent {
+                device,
+                time,
+                delta: fields.point()?,
+            },
+        },
+        "pointer-motion-absolute" => InputEvent::PointerMotionAbsolute {
+            event: SyntheticPointerMotionAbsoluteEvent {
+                device,
+                time,
+                position: fields.point()?,
+                normalized: fields.point()?,
+            },
+        },
+        "pointer-button" => InputEvent::PointerButton {
+            event: SyntheticPointerButtonEvent {
+                device,
+                time,
+                button_code: fields.next()?,
+                state: fields.button_state()?,
+            },
+        },
+        "pointer-axis" => InputEvent::PointerAxis {
+            event: SyntheticPointerAxisEvent {
+                device,
+                time,
+                source: value_of(&AXIS_SOURCES, fields.str()?)?,
+                horizontal: fields.optional()?,
+                vertical: fields.optional()?,
+                horizontal_discrete: fields.optional()?,
+                vertical_discrete: fields.optional()?,
+            },
+        },
+        "touch-down" => InputEvent::TouchDown {
+            event: SyntheticTouchDownEvent {
+                device,
+                time,
+                slot: fields.optional()?,
+                position: fields.point()?,
+                normalized: fields.point()?,
+            },
+        },
+        "touch-motion" => InputEvent::TouchMotion {
+            event: SyntheticTouchMotionEvent {
+                device,
+                time,
+                slot: fields.optional()?,
+                position: fields.point()?,
+                normalized: fields.point()?,
+            },
+        },
+        "touch-up" => InputEvent::TouchUp {
+            event: SyntheticTouchUpEvent {
+                device,
+                time,
+                slot: fields.optional()?,
+            },
+        },
+        "touch-cancel" => InputEvent::TouchCancel {
+            event: SyntheticTouchCancelEvent {
+                device,
+                time,
+                slot: fields.optional()?,
+            },
+        },
+        "touch-frame" => InputEvent::TouchFrame {
+            event: SyntheticTouchFrameEvent { device, time },
+        },
+        "tablet-axis" => {
+            let (tool, axes) = fields.tablet_tool()?;
+            InputEvent::TabletToolAxis {
+                event: SyntheticTabletToolAxisEvent {
+                    device,
+                    time,
+                    tool,
+                    axes,
+                },
+            }
+        }
+        "tablet-proximity" => {
+            let (tool, axes) = fields.tablet_tool()?;
+            InputEvent::TabletToolProximity {
+                event: SyntheticTabletToolProximityEvent {
+                    device,
+                    time,
+                    tool,
+                    axes,
+                    state: match fields.str()? {
+                        "in" => ProximityState::In,
+                        "out" => ProximityState::Out,
+                        _ => return None,
+                    },
+                },
+            }
+        }
+        "tablet-tip" => {
+            let (tool, axes) = fields.tablet_tool()?;
+            InputEvent::TabletToolTip {
+                event: SyntheticTabletToolTipEvent {
+                    device,
+                    time,
+                    tool,
+                    axes,
+                    tip_state: match fields.str()? {
+                        "down" => TabletToolTipState::Down,
+                        "up" => TabletToolTipState::Up,
+                        _ => return None,
+                    },
+                },
+            }
+        }
+        "tablet-button" => {
+            let (tool, axes) = fields.tablet_tool()?;
+            InputEvent::TabletToolButton {
+                event: SyntheticTabletToolButtonEvent {
+                    device,
+                    time,
+                    tool,
+                    axes,
+                    button: fields.next()?,
+                    seat_button_count: fields.next()?,
+                    button_state: fields.button_state()?,
+                },
+            }
+        }
+        _ => return None,
+    };
+    backend.push_event(event);
+    Some(elapsed)
+}
+
+/// Replays an input recording through calloop
+///
+/// The recorded events are emitted as [`SyntheticInputBackend`] events, the devices of the recording
+/// as [`SyntheticDevice`]s. Once all events were emitted, the source removes itself from the event loop.
+#[derive(Debug)]
+pub struct InputReplay {
+    events: VecDeque<(Duration, InputEvent<SyntheticInputBackend>)>,
+    speed: f64,
+    start: Option<Instant>,
+    timer: Timer<()>,
+}
+
+impl InputReplay {
+    /// Read the recording in the file at the given path, see [`InputReplay::new`]
+    pub fn open(path: impl AsRef<Path>, speed: f64) -> Result<InputReplay, ReplayError> {
+        InputReplay::new(BufReader::new(File::open(path)?), speed)
+    }
+
+    /// Read a recording
+    ///
+    /// The events are replayed `speed` times faster than they were recorded, so `1.0` replays them with
+    /// their original timing and `f64::MAX` as fast as possible. Replaying starts once the source is
+    /// inserted into an event loop, an empty recording removes itself on the first dispatch.
+    ///
+    /// Returns [`ReplayError::InvalidSpeed`] if `speed` is not positive and finite.
+    pub fn new<R: BufRead>(reader: R, speed: f64) -> Result<InputReplay, ReplayError> {
+        if !(speed > 0.0 && speed.is_finite()) {
+            return Err(ReplayError::InvalidSpeed(speed));
+        }
+
+        let mut lines = reader.lines();
+        if lines.next().transpose()?.as_deref() != Some(HEADER) {
+            return Err(ReplayError::InvalidHeader);
+        }
+
+        let mut backend = SyntheticInputBackend::new();
+        let mut devices = HashMap::new();
+        let mut events = VecDeque::new();
+        for (idx, line) in lines.enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // line numbers start at one, after the header
+            let elapsed =
+                parse_line(&line, &mut backend, &mut devices).ok_or(ReplayError::InvalidLine(idx + 2))?;
+            backend.dispatch_new_events(|event| events.push_back((elapsed, event)));
+        }
+
+        Ok(InputReplay {
+            events,
+            speed,
+            start: None,
+            timer: Timer::new()?,
+        })
+    }
+
+    /// Number of events not yet replayed
+    pub fn remaining(&self) -> usize {
+        self.events.len()
+    }
+
+    // time after the start of the replay an event recorded at `elapsed` is emitted at
+    fn replay_offset(&self, elapsed: Duration) -> Duration {
+        Duration::try_from_secs_f64(elapsed.as_secs_f64() / self.speed).unwrap_or(Duration::MAX)
+    }
+
+    fn schedule_next(&mut self) {
+        let start = match self.start {
+            Some(start) => start,
+            None => return,
+        };
+        // without any event left, the timer fires right away to remove the source
+        let offset = self
+            .events
+            .front()
+            .map(|(elapsed, _)| self.replay_offset(*elapsed))
+            .unwrap_or_default();
+        let timeout = offset.saturating_sub(start.elapsed()).min(MAX_TIMEOUT);
+        self.timer.handle().add_timeout(timeout, ());
+    }
+}
+
+impl EventSource for InputReplay {
+    type Event = InputEvent<SyntheticInputBackend>;
+    type Metadata = ();
+    type Ret = ();
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<PostAction>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let mut fired = false;
+        self.timer.process_events(readiness, token, |_, _| fired = true)?;
+        if !fired {
+            return Ok(PostAction::Continue);
+        }
+
+        let replayed = self.start.map(|start| start.elapsed()).unwrap_or_default();
+        while let Some((elapsed, _)) = self.events.front() {
+            if self.replay_offset(*elapsed) > replayed {
+                break;
+            }
+            let (_, event) = self.events.pop_front().unwrap();
+            callback(event, &mut ());
+        }
+
+        if self.events.is_empty() {
+            Ok(PostAction::Remove)
+        } else {
+            self.schedule_next();
+            Ok(PostAction::Continue)
+        }
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.timer.register(poll, token_factory)?;
+        if self.start.is_none() {
+            self.start = Some(Instant::now());
+            self.schedule_next();
+        }
+        Ok(())
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.timer.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+        self.timer.unregister(poll)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::input::synthetic::SyntheticDevice;
+
+    #[test]
+    fn roundtrip() {
+        let mut backend = SyntheticInputBackend::new();
+        let keyboard = backend.add_device(
+            SyntheticDevice::new("Virtual keyboard", &[DeviceCapability::Keyboard]).with_usb_id((1, 2)),
+        );
+        let touch = backend.add_device(
+            SyntheticDevice::new("touch", &[DeviceCapability::Touch, DeviceCapability::Pointer])
+                .with_syspath("/sys/devices/virtual input"),
+        );
+        backend.keyboard_key(&keyboard, 5, 30, KeyState::Pressed);
+        backend.pointer_axis(&touch, 6, AxisSource::Finger, Axis::Horizontal, 0.1);
+        backend.touch_down(&touch, 7, Some(2), (0.3, 1.0 / 3.0).into());
+        backend.touch_frame(&touch, 7);
+        backend.remove_device(&keyboard);
+
+        let mut recorder = InputRecorder::new(Vec::new()).unwrap();
+        backend.dispatch_new_events(|event| recorder.record(&event).unwrap());
+        let recording = recorder.into_inner();
+
+        let mut replay = InputReplay::new(&recording[..], f64::MAX).unwrap();
+        assert_eq!(replay.remaining(), 7);
+        let mut events = replay.events.drain(..).map(|(_, event)| event);
+
+        match events.next() {
+            Some(InputEvent::DeviceAdded { device }) => {
+                assert_eq!(device.name(), "Virtual keyboard");
+                assert_eq!(device.usb_id(), Some((1, 2)));
+                assert!(device.has_capability(DeviceCapability::Keyboard));
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        match events.next() {
+            Some(InputEvent::DeviceAdded { device }) => {
+                assert_eq!(device.syspath(), Some("/sys/devices/virtual input".into()));
+                assert!(device.has_capability(DeviceCapability::Pointer));
+                assert!(!device.has_capability(DeviceCapability::Keyboard));
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        match events.next() {
+            Some(InputEvent::Keyboard { event }) => {
+                assert_eq!((event.time, event.key_code, event.count), (5, 30, 1));
+                assert_eq!(event.state, KeyState::Pressed);
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        match events.next() {
+            Some(InputEvent::PointerAxis { event }) => {
+                assert_eq!(event.source, AxisSource::Finger);
+                assert_eq!(event.horizontal, Some(0.1));
+                assert_eq!(event.vertical_discrete, None);
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        match events.next() {
+            Some(InputEvent::TouchDown { event }) => {
+                assert_eq!(event.slot, Some(2));
+                assert_eq!(event.normalized, (0.3, 1.0 / 3.0).into());
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+        assert!(matches!(events.next(), Some(InputEvent::TouchFrame { .. })));
+        match events.next() {
+            Some(InputEvent::DeviceRemoved { device }) => assert_eq!(device.name(), "Virtual keyboard"),
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+
+    #[test]
+    fn replay() {
+        let recording = format!(
+            "{}\n0 device-added 0 keyboard - - kbd\n1000 key 0 1 30 pressed 1\n2000 key 0 2 30 released 0\n",
+            HEADER
+        );
+        let replay = InputReplay::new(recording.as_bytes(), 100.0).unwrap();
+        let mut event_loop = calloop::EventLoop::<Vec<InputEvent<SyntheticInputBackend>>>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(replay, |event, _, events| events.push(event))
+            .unwrap();
+
+        let mut events = Vec::new();
+        for _ in 0..10 {
+            event_loop
+                .dispatch(Some(Duration::from_millis(10)), &mut events)
+                .unwrap();
+            if events.len() == 3 {
+                break;
+            }
+        }
+        assert_eq!(events.len(), 3);
+        assert!(matches!(events[2], InputEvent::Keyboard { ref event } if event.state == KeyState::Released));
+
+        assert!(matches!(
+            InputReplay::new(&b"not a recording\n"[..], 1.0),
+            Err(ReplayError::InvalidHeader)
+        ));
+        let invalid = format!("{}\n0 key 0 1 30 pressed 1\n", HEADER);
+        assert!(matches!(
+            InputReplay::new(invalid.as_bytes(), 1.0),
+            Err(ReplayError::InvalidLine(2))
+        ));
+    }
+
+    // forwards to the replay and records if it asked to be removed
+    struct Observed {
+        replay: InputReplay,
+        removed: std::rc::Rc<std::cell::Cell<bool>>,
+    }
+
+    impl EventSource for Observed {
+        type Event = InputEvent<SyntheticInputBackend>;
+        type Metadata = ();
+        type Ret = ();
+
+        fn process_events<F>(
+            &mut self,
+            readiness: Readiness,
+            token: Token,
+            callback: F,
+        ) -> io::Result<PostAction>
+        where
+            F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+        {
+            let action = self.replay.process_events(readiness, token, callback)?;
+            if matches!(action, PostAction::Remove) {
+                self.removed.set(true);
+            }
+            Ok(action)
+        }
+
+        fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+            self.replay.register(poll, token_factory)
+        }
+
+        fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+            self.replay.reregister(poll, token_factory)
+        }
+
+        fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+            self.replay.unregister(poll)
+        }
+    }
+
+    #[test]
+    fn empty_replay_removed() {
+        let recording = format!("{}\n", HEADER);
+        let removed = std::rc::Rc::new(std::cell::Cell::new(false));
+        let source = Observed {
+            replay: InputReplay::new(recording.as_bytes(), 1.0).unwrap(),
+            removed: removed.clone(),
+        };
+        let mut event_loop = calloop::EventLoop::<()>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(source, |_, _, _| panic!("No event was recorded"))
+            .unwrap();
+
+        event_loop
+            .dispatch(Some(Duration::from_millis(100)), &mut ())
+            .unwrap();
+        assert!(removed.get());
+    }
+
+    #[test]
+    fn invalid_speed() {
+        let recording = format!("{}\n", HEADER);
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                InputReplay::new(recording.as_bytes(), speed),
+                Err(ReplayError::InvalidSpeed(_))
+            ));
+        }
+
+        // events far in the future do not overflow the timer
+        let recording = format!("{}\n{} device-added 0 keyboard - - kbd\n", HEADER, u64::MAX);
+        let replay = InputReplay::new(recording.as_bytes(), f64::MIN_POSITIVE).unwrap();
+        assert_eq!(
+            replay.replay_offset(Duration::from_micros(u64::MAX)),
+            Duration::MAX
+        );
+        let mut event_loop = calloop::EventLoop::<()>::try_new().unwrap();
+        event_loop
+            .handle()
+            .insert_source(replay, |_, _, _| panic!("The event must not be replayed yet"))
+            .unwrap();
+        event_loop
+            .dispatch(Some(Duration::from_millis(10)), &mut ())
+            .unwrap();
+    }
+}
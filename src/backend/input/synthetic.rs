@@ -0,0 +1,975 @@
+//! Input backend emitting programmatically created events
+//!
+//! The [`SyntheticInputBackend`] does not read from any device. Its devices and their events are created
+//! by the caller, with full control over their capabilities and timestamps, which makes it possible to test
+//! the input handling of a compositor deterministically.
+//!
+//! Created events are queued and handed out by [`SyntheticInputBackend::dispatch_new_events`]:
+//!
+//! ```
+//! use smithay::backend::input::{
+//!     synthetic::{SyntheticDevice, SyntheticInputBackend},
+//!     DeviceCapability, InputEvent, KeyState, KeyboardKeyEvent,
+//! };
+//!
+//! let mut backend = SyntheticInputBackend::new();
+//! let keyboard = backend.add_device(SyntheticDevice::new("keyboard", &[DeviceCapability::Keyboard]));
+//! backend.keyboard_key(&keyboard, 10, 30, KeyState::Pressed);
+//! backend.keyboard_key(&keyboard, 20, 30, KeyState::Released);
+//!
+//! backend.dispatch_new_events(|event| match event {
+//!     InputEvent::DeviceAdded { device } => assert_eq!(device, keyboard),
+//!     InputEvent::Keyboard { event } => assert_eq!(event.key_code(), 30),
+//!     _ => unreachable!(),
+//! });
+//! ```
+//!
+//! ## Coordinates
+//!
+//! Absolute events (absolute pointer motion, touch and tablet tool events) carry two positions: the
+//! `position` in the coordinate space of the device, as returned by e.g.
+//! [`PointerMotionAbsoluteEvent::x`], and the `normalized` position in the range `[0, 1]`, used for the
+//! transformation into the target coordinate space by e.g. [`PointerMotionAbsoluteEvent::x_transformed`].
+//! The helpers of the backend use the normalized position for both.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    path::PathBuf,
+};
+
+use super::{
+    Axis, AxisSource, ButtonState, Device, DeviceCapability, Event, InputBackend, InputEvent, KeyState,
+    KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, PointerMotionEvent,
+    ProximityState, TabletToolAxisEvent, TabletToolButtonEvent, TabletToolCapabilitys, TabletToolDescriptor,
+    TabletToolEvent, TabletToolProximityEvent, TabletToolTipEvent, TabletToolTipState, TouchCancelEvent,
+    TouchDownEvent, TouchFrameEvent, TouchMotionEvent, TouchSlot, TouchUpEvent,
+};
+use crate::utils::{Logical, Point, Raw};
+
+/// A device of a [`SyntheticInputBackend`]
+///
+/// Devices are identified by an id assigned by [`SyntheticInputBackend::add_device`], two devices are
+/// equal if their ids are equal.
+#[derive(Debug, Clone)]
+pub struct SyntheticDevice {
+    id: u32,
+    name: String,
+    capabilities: Vec<DeviceCapability>,
+    usb_id: Option<(u32, u32)>,
+    syspath: Option<PathBuf>,
+}
+
+impl SyntheticDevice {
+    /// Describe a new device with the given capabilities
+    ///
+    /// The device needs to be added to a backend with [`SyntheticInputBackend::add_device`].
+    pub fn new(name: impl Into<String>, capabilities: &[DeviceCapability]) -> SyntheticDevice {
+        SyntheticDevice {
+            id: 0,
+            name: name.into(),
+            capabilities: capabilities.to_vec(),
+            usb_id: None,
+            syspath: None,
+        }
+    }
+
+    /// Set the USB (product, vendor) id of the device
+    pub fn with_usb_id(mut self, usb_id: (u32, u32)) -> SyntheticDevice {
+        self.usb_id = Some(usb_id);
+        self
+    }
+
+    /// Set the syspath of the device
+    pub fn with_syspath(mut self, syspath: impl Into<PathBuf>) -> SyntheticDevice {
+        self.syspath = Some(syspath.into());
+        self
+    }
+}
+
+impl PartialEq for SyntheticDevice {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for SyntheticDevice {}
+
+impl Hash for SyntheticDevice {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Device for SyntheticDevice {
+    fn id(&self) -> String {
+        format!("synthetic-{}", self.id)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+
+    fn usb_id(&self) -> Option<(u32, u32)> {
+        self.usb_id
+    }
+
+    fn syspath(&self) -> Option<PathBuf> {
+        self.syspath.clone()
+    }
+}
+
+macro_rules! impl_event {
+    ($($event:ty),*) => {
+        $(
+            impl Event<SyntheticInputBackend> for $event {
+                fn time(&self) -> u32 {
+                    self.time
+                }
+
+                fn device(&self) -> SyntheticDevice {
+                    self.device.clone()
+                }
+            }
+        )*
+    };
+}
+
+impl_event!(
+    SyntheticKeyboardKeyEvent,
+    SyntheticPointerMotionEvent,
+    SyntheticPointerMotionAbsoluteEvent,
+    SyntheticPointerButtonEvent,
+    SyntheticPointerAxisEvent,
+    SyntheticTouchDownEvent,
+    SyntheticTouchMotionEvent,
+    SyntheticTouchUpEvent,
+    SyntheticTouchCancelEvent,
+    SyntheticTouchFrameEvent,
+    SyntheticTabletToolAxisEvent,
+    SyntheticTabletToolProximityEvent,
+    SyntheticTabletToolTipEvent,
+    SyntheticTabletToolButtonEvent
+);
+
+/// Synthetic [`KeyboardKeyEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticKeyboardKeyEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Code of the key
+    pub key_code: u32,
+    /// State of the key
+    pub state: KeyState,
+    /// Number of keys pressed on all devices after this event
+    pub count: u32,
+}
+
+impl KeyboardKeyEvent<SyntheticInputBackend> for SyntheticKeyboardKeyEvent {
+    fn key_code(&self) -> u32 {
+        self.key_code
+    }
+
+    fn state(&self) -> KeyState {
+        self.state
+    }
+
+    fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Synthetic [`PointerMotionEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticPointerMotionEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Relative motion of the pointer
+    pub delta: Point<f64, Logical>,
+}
+
+impl PointerMotionEvent<SyntheticInputBackend> for SyntheticPointerMotionEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta.x
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.y
+    }
+}
+
+/// Synthetic [`PointerMotionAbsoluteEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticPointerMotionAbsoluteEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Position in the coordinate space of the device
+    pub position: Point<f64, Raw>,
+    /// Position normalized to `[0, 1]`
+    pub normalized: Point<f64, Raw>,
+}
+
+impl PointerMotionAbsoluteEvent<SyntheticInputBackend> for SyntheticPointerMotionAbsoluteEvent {
+    fn x(&self) -> f64 {
+        self.position.x
+    }
+
+    fn y(&self) -> f64 {
+        self.position.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        self.normalized.x * width as f64
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        self.normalized.y * height as f64
+    }
+}
+
+/// Synthetic [`PointerButtonEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticPointerButtonEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Code of the button
+    pub button_code: u32,
+    /// State of the button
+    pub state: ButtonState,
+}
+
+impl PointerButtonEvent<SyntheticInputBackend> for SyntheticPointerButtonEvent {
+    fn button_code(&self) -> u32 {
+        self.button_code
+    }
+
+    fn state(&self) -> ButtonState {
+        self.state
+    }
+}
+
+/// Synthetic [`PointerAxisEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticPointerAxisEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Source of the scroll event
+    pub source: AxisSource,
+    /// Amount of scrolling on the horizontal axis
+    pub horizontal: Option<f64>,
+    /// Amount of scrolling on the vertical axis
+    pub vertical: Option<f64>,
+    /// Discrete steps of scrolling on the horizontal axis
+    pub horizontal_discrete: Option<f64>,
+    /// Discrete steps of scrolling on the vertical axis
+    pub vertical_discrete: Option<f64>,
+}
+
+impl PointerAxisEvent<SyntheticInputBackend> for SyntheticPointerAxisEvent {
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.horizontal,
+            Axis::Vertical => self.vertical,
+        }
+    }
+
+    fn amount_discrete(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.horizontal_discrete,
+            Axis::Vertical => self.vertical_discrete,
+        }
+    }
+
+    fn source(&self) -> AxisSource {
+        self.source
+    }
+}
+
+/// Synthetic [`TouchDownEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTouchDownEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Slot of the touch point, if the device has multi-touch capabilities
+    pub slot: Option<u64>,
+    /// Position in the coordinate space of the device
+    pub position: Point<f64, Raw>,
+    /// Position normalized to `[0, 1]`
+    pub normalized: Point<f64, Raw>,
+}
+
+impl TouchDownEvent<SyntheticInputBackend> for SyntheticTouchDownEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        self.slot.map(TouchSlot::new)
+    }
+
+    fn x(&self) -> f64 {
+        self.position.x
+    }
+
+    fn y(&self) -> f64 {
+        self.position.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        self.normalized.x * width as f64
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        self.normalized.y * height as f64
+    }
+}
+
+/// Synthetic [`TouchMotionEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTouchMotionEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Slot of the touch point, if the device has multi-touch capabilities
+    pub slot: Option<u64>,
+    /// Position in the coordinate space of the device
+    pub position: Point<f64, Raw>,
+    /// Position normalized to `[0, 1]`
+    pub normalized: Point<f64, Raw>,
+}
+
+impl TouchMotionEvent<SyntheticInputBackend> for SyntheticTouchMotionEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        self.slot.map(TouchSlot::new)
+    }
+
+    fn x(&self) -> f64 {
+        self.position.x
+    }
+
+    fn y(&self) -> f64 {
+        self.position.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        self.normalized.x * width as f64
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        self.normalized.y * height as f64
+    }
+}
+
+/// Synthetic [`TouchUpEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTouchUpEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Slot of the touch point, if the device has multi-touch capabilities
+    pub slot: Option<u64>,
+}
+
+impl TouchUpEvent<SyntheticInputBackend> for SyntheticTouchUpEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        self.slot.map(TouchSlot::new)
+    }
+}
+
+/// Synthetic [`TouchCancelEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTouchCancelEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Slot of the touch point, if the device has multi-touch capabilities
+    pub slot: Option<u64>,
+}
+
+impl TouchCancelEvent<SyntheticInputBackend> for SyntheticTouchCancelEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        self.slot.map(TouchSlot::new)
+    }
+}
+
+/// Synthetic [`TouchFrameEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTouchFrameEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+}
+
+impl TouchFrameEvent<SyntheticInputBackend> for SyntheticTouchFrameEvent {}
+
+/// State of the axes of a tablet tool, as reported by the [`TabletToolEvent`]s
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticTabletToolAxes {
+    /// Position in the coordinate space of the device
+    pub position: Point<f64, Raw>,
+    /// Position normalized to `[0, 1]`
+    pub normalized: Point<f64, Raw>,
+    /// Relative motion of the tool
+    pub delta: Point<f64, Logical>,
+    /// Distance from the sensor of the tablet
+    pub distance: f64,
+    /// Pressure applied on the tool
+    pub pressure: f64,
+    /// Position of the slider of the tool
+    pub slider: f64,
+    /// Tilt along the (X, Y) axis in degrees
+    pub tilt: (f64, f64),
+    /// Z rotation of the tool in degrees
+    pub rotation: f64,
+    /// Delta of the wheel in degrees
+    pub wheel_delta: f64,
+    /// Delta of the wheel in discrete steps
+    pub wheel_delta_discrete: i32,
+    /// Axes updated by the event
+    ///
+    /// [`TabletToolCapabilitys::TILT`] marks both tilt axes as updated.
+    pub changed: TabletToolCapabilitys,
+}
+
+impl Default for SyntheticTabletToolAxes {
+    fn default() -> Self {
+        SyntheticTabletToolAxes {
+            position: Point::default(),
+            normalized: Point::default(),
+            delta: Point::default(),
+            distance: 0.0,
+            pressure: 0.0,
+            slider: 0.0,
+            tilt: (0.0, 0.0),
+            rotation: 0.0,
+            wheel_delta: 0.0,
+            wheel_delta_discrete: 0,
+            changed: TabletToolCapabilitys::empty(),
+        }
+    }
+}
+
+macro_rules! impl_tablet_tool_event {
+    ($($event:ty),*) => {
+        $(
+            impl TabletToolEvent<SyntheticInputBackend> for $event {
+                fn tool(&self) -> TabletToolDescriptor {
+                    self.tool.clone()
+                }
+                fn delta_x(&self) -> f64 {
+                    self.axes.delta.x
+                }
+                fn delta_y(&self) -> f64 {
+                    self.axes.delta.y
+                }
+                fn x(&self) -> f64 {
+                    self.axes.position.x
+                }
+                fn y(&self) -> f64 {
+                    self.axes.position.y
+                }
+                fn x_transformed(&self, width: i32) -> f64 {
+                    self.axes.normalized.x * width as f64
+                }
+                fn y_transformed(&self, height: i32) -> f64 {
+                    self.axes.normalized.y * height as f64
+                }
+                fn distance(&self) -> f64 {
+                    self.axes.distance
+                }
+                fn distance_has_changed(&self) -> bool {
+                    self.axes.changed.contains(TabletToolCapabilitys::DISTANCE)
+                }
+                fn pressure(&self) -> f64 {
+                    self.axes.pressure
+                }
+                fn pressure_has_changed(&self) -> bool {
+                    self.axes.changed.contains(TabletToolCapabilitys::PRESSURE)
+                }
+                fn slider_position(&self) -> f64 {
+                    self.axes.slider
+                }
+                fn slider_has_changed(&self) -> bool {
+                    self.axes.changed.contains(TabletToolCapabilitys::SLIDER)
+                }
+                fn tilt_x(&self) -> f64 {
+                    self.axes.tilt.0
+                }
+                fn tilt_x_has_changed(&self) -> bool {
+                    self.axes.changed.contains(TabletToolCapabilitys::TILT)
+                }
+                fn tilt_y(&self) -> f64 {
+                    self.axes.tilt.1
+                }
+                fn tilt_y_has_changed(&self) -> bool {
+                    self.axes.changed.contains(TabletToolCapabilitys::TILT)
+                }
+                fn rotation(&self) -> f64 {
+                    self.axes.rotation
+                }
+                fn rotation_has_changed(&self) -> bool {
+                    self.axes.changed.contains(TabletToolCapabilitys::ROTATION)
+                }
+                fn wheel_delta(&self) -> f64 {
+                    self.axes.wheel_delta
+                }
+                fn wheel_delta_discrete(&self) -> i32 {
+                    self.axes.wheel_delta_discrete
+                }
+                fn wheel_has_changed(&self) -> bool {
+                    self.axes.changed.contains(TabletToolCapabilitys::WHEEL)
+                }
+            }
+        )*
+    };
+}
+
+impl_tablet_tool_event!(
+    SyntheticTabletToolAxisEvent,
+    SyntheticTabletToolProximityEvent,
+    SyntheticTabletToolTipEvent,
+    SyntheticTabletToolButtonEvent
+);
+
+/// Synthetic [`TabletToolAxisEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTabletToolAxisEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Tool emitting the event
+    pub tool: TabletToolDescriptor,
+    /// State of the axes of the tool
+    pub axes: SyntheticTabletToolAxes,
+}
+
+impl TabletToolAxisEvent<SyntheticInputBackend> for SyntheticTabletToolAxisEvent {}
+
+/// Synthetic [`TabletToolProximityEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTabletToolProximityEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Tool emitting the event
+    pub tool: TabletToolDescriptor,
+    /// State of the axes of the tool
+    pub axes: SyntheticTabletToolAxes,
+    /// New proximity state of the tool
+    pub state: ProximityState,
+}
+
+impl TabletToolProximityEvent<SyntheticInputBackend> for SyntheticTabletToolProximityEvent {
+    fn state(&self) -> ProximityState {
+        self.state
+    }
+}
+
+/// Synthetic [`TabletToolTipEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTabletToolTipEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Tool emitting the event
+    pub tool: TabletToolDescriptor,
+    /// State of the axes of the tool
+    pub axes: SyntheticTabletToolAxes,
+    /// New tip state of the tool
+    pub tip_state: TabletToolTipState,
+}
+
+impl TabletToolTipEvent<SyntheticInputBackend> for SyntheticTabletToolTipEvent {
+    fn tip_state(&self) -> TabletToolTipState {
+        self.tip_state
+    }
+}
+
+/// Synthetic [`TabletToolButtonEvent`]
+#[derive(Debug, Clone)]
+pub struct SyntheticTabletToolButtonEvent {
+    /// Device emitting the event
+    pub device: SyntheticDevice,
+    /// Timestamp of the event
+    pub time: u32,
+    /// Tool emitting the event
+    pub tool: TabletToolDescriptor,
+    /// State of the axes of the tool
+    pub axes: SyntheticTabletToolAxes,
+    /// Code of the button
+    pub button: u32,
+    /// Number of buttons pressed on all devices after this event
+    pub seat_button_count: u32,
+    /// State of the button
+    pub button_state: ButtonState,
+}
+
+impl TabletToolButtonEvent<SyntheticInputBackend> for SyntheticTabletToolButtonEvent {
+    fn button(&self) -> u32 {
+        self.button
+    }
+
+    fn seat_button_count(&self) -> u32 {
+        self.seat_button_count
+    }
+
+    fn button_state(&self) -> ButtonState {
+        self.button_state
+    }
+}
+
+/// Input backend emitting programmatically created events
+///
+/// See the [module-level documentation](self) for details.
+#[derive(Debug, Default)]
+pub struct SyntheticInputBackend {
+    next_id: u32,
+    devices: Vec<SyntheticDevice>,
+    pressed_keys: HashSet<(u32, u32)>,
+    queue: VecDeque<InputEvent<SyntheticInputBackend>>,
+}
+
+impl InputBackend for SyntheticInputBackend {
+    type Device = SyntheticDevice;
+    type KeyboardKeyEvent = SyntheticKeyboardKeyEvent;
+    type PointerAxisEvent = SyntheticPointerAxisEvent;
+    type PointerButtonEvent = SyntheticPointerButtonEvent;
+    type PointerMotionEvent = SyntheticPointerMotionEvent;
+    type PointerMotionAbsoluteEvent = SyntheticPointerMotionAbsoluteEvent;
+    type TouchDownEvent = SyntheticTouchDownEvent;
+    type TouchUpEvent = SyntheticTouchUpEvent;
+    type TouchMotionEvent = SyntheticTouchMotionEvent;
+    type TouchCancelEvent = SyntheticTouchCancelEvent;
+    type TouchFrameEvent = SyntheticTouchFrameEvent;
+    type TabletToolAxisEvent = SyntheticTabletToolAxisEvent;
+    type TabletToolProximityEvent = SyntheticTabletToolProximityEvent;
+    type TabletToolTipEvent = SyntheticTabletToolTipEvent;
+    type TabletToolButtonEvent = SyntheticTabletToolButtonEvent;
+
+    type SpecialEvent = ();
+}
+
+impl SyntheticInputBackend {
+    /// Create a new backend without any devices
+    pub fn new() -> SyntheticInputBackend {
+        SyntheticInputBackend::default()
+    }
+
+    /// Add a device to the backend, queuing an [`InputEvent::DeviceAdded`] event
+    ///
+    /// Returns the added device, that is used to emit its events.
+    pub fn add_device(&mut self, mut device: SyntheticDevice) -> SyntheticDevice {
+        device.id = self.next_id;
+        self.next_id += 1;
+        self.devices.push(device.clone());
+        self.queue.push_back(InputEvent::DeviceAdded {
+            device: device.clone(),
+        });
+        device
+    }
+
+    /// Remove a device from the backend, queuing an [`InputEvent::DeviceRemoved`] event
+    ///
+    /// Keys still pressed on the device are forgotten, without emitting release events.
+    pub fn remove_device(&mut self, device: &SyntheticDevice) {
+        if let Some(idx) = self.devices.iter().position(|d| d == device) {
+            let device = self.devices.remove(idx);
+            self.pressed_keys.retain(|(id, _)| *id != device.id);
+            self.queue.push_back(InputEvent::DeviceRemoved { device });
+        }
+    }
+
+    /// Devices currently added to the backend
+    pub fn devices(&self) -> &[SyntheticDevice] {
+        &self.devices
+    }
+
+    /// Queue an event
+    ///
+    /// Unlike the other helpers, this does not update any state of the backend (e.g. the number of
+    /// pressed keys).
+    pub fn push_event(&mut self, event: InputEvent<SyntheticInputBackend>) {
+        self.queue.push_back(event);
+    }
+
+    /// Queue a keyboard event
+    ///
+    /// The key count of the event is derived from the keys pressed on all devices of the backend.
+    pub fn keyboard_key(&mut self, device: &SyntheticDevice, time: u32, key_code: u32, state: KeyState) {
+        match state {
+            KeyState::Pressed => self.pressed_keys.insert((device.id, key_code)),
+            KeyState::Released => self.pressed_keys.remove(&(device.id, key_code)),
+        };
+        let count = self.pressed_keys.len() as u32;
+        self.queue.push_back(InputEvent::Keyboard {
+            event: SyntheticKeyboardKeyEvent {
+                device: device.clone(),
+                time,
+                key_code,
+                state,
+                count,
+            },
+        });
+    }
+
+    /// Queue a relative pointer motion event
+    pub fn pointer_motion(&mut self, device: &SyntheticDevice, time: u32, delta: Point<f64, Logical>) {
+        self.queue.push_back(InputEvent::PointerMotion {
+            event: SyntheticPointerMotionEvent {
+                device: device.clone(),
+                time,
+                delta,
+            },
+        });
+    }
+
+    /// Queue an absolute pointer motion event to a position normalized to `[0, 1]`
+    pub fn pointer_motion_absolute(
+        &mut self,
+        device: &SyntheticDevice,
+        time: u32,
+        position: Point<f64, Raw>,
+    ) {
+        self.queue.push_back(InputEvent::PointerMotionAbsolute {
+            event: SyntheticPointerMotionAbsoluteEvent {
+                device: device.clone(),
+                time,
+                position,
+                normalized: position,
+            },
+        });
+    }
+
+    /// Queue a pointer button event
+    pub fn pointer_button(
+        &mut self,
+        device: &SyntheticDevice,
+        time: u32,
+        button_code: u32,
+        state: ButtonState,
+    ) {
+        self.queue.push_back(InputEvent::PointerButton {
+            event: SyntheticPointerButtonEvent {
+                device: device.clone(),
+                time,
+                button_code,
+                state,
+            },
+        });
+    }
+
+    /// Queue a pointer axis event scrolling on a single axis
+    ///
+    /// For [`AxisSource::Wheel`] and [`AxisSource::WheelTilt`] `amount` is given in discrete steps,
+    /// otherwise in pixels.
+    pub fn pointer_axis(
+        &mut self,
+        device: &SyntheticDevice,
+        time: u32,
+        source: AxisSource,
+        axis: Axis,
+        amount: f64,
+    ) {
+        let discrete = matches!(source, AxisSource::Wheel | AxisSource::WheelTilt);
+        let (continuous, discrete) = if discrete {
+            (None, Some(amount))
+        } else {
+            (Some(amount), None)
+        };
+        let mut event = SyntheticPointerAxisEvent {
+            device: device.clone(),
+            time,
+            source,
+            horizontal: None,
+            vertical: None,
+            horizontal_discrete: None,
+            vertical_discrete: None,
+        };
+        match axis {
+            Axis::Horizontal => {
+                event.horizontal = continuous;
+                event.horizontal_discrete = discrete;
+            }
+            Axis::Vertical => {
+                event.vertical = continuous;
+                event.vertical_discrete = discrete;
+            }
+        }
+        self.queue.push_back(InputEvent::PointerAxis { event });
+    }
+
+    /// Queue a touch down event at a position normalized to `[0, 1]`
+    pub fn touch_down(
+        &mut self,
+        device: &SyntheticDevice,
+        time: u32,
+        slot: Option<u64>,
+        position: Point<f64, Raw>,
+    ) {
+        self.queue.push_back(InputEvent::TouchDown {
+            event: SyntheticTouchDownEvent {
+                device: device.clone(),
+                time,
+                slot,
+                position,
+                normalized: position,
+            },
+        });
+    }
+
+    /// Queue a touch motion event to a position normalized to `[0, 1]`
+    pub fn touch_motion(
+        &mut self,
+        device: &SyntheticDevice,
+        time: u32,
+        slot: Option<u64>,
+        position: Point<f64, Raw>,
+    ) {
+        self.queue.push_back(InputEvent::TouchMotion {
+            event: SyntheticTouchMotionEvent {
+                device: device.clone(),
+                time,
+                slot,
+                position,
+                normalized: position,
+            },
+        });
+    }
+
+    /// Queue a touch up event
+    pub fn touch_up(&mut self, device: &SyntheticDevice, time: u32, slot: Option<u64>) {
+        self.queue.push_back(InputEvent::TouchUp {
+            event: SyntheticTouchUpEvent {
+                device: device.clone(),
+                time,
+                slot,
+            },
+        });
+    }
+
+    /// Queue a touch cancel event
+    pub fn touch_cancel(&mut self, device: &SyntheticDevice, time: u32, slot: Option<u64>) {
+        self.queue.push_back(InputEvent::TouchCancel {
+            event: SyntheticTouchCancelEvent {
+                device: device.clone(),
+                time,
+                slot,
+            },
+        });
+    }
+
+    /// Queue a touch frame event
+    pub fn touch_frame(&mut self, device: &SyntheticDevice, time: u32) {
+        self.queue.push_back(InputEvent::TouchFrame {
+            event: SyntheticTouchFrameEvent {
+                device: device.clone(),
+                time,
+            },
+        });
+    }
+
+    /// Number of queued events
+    pub fn pending_events(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Hand out the queued events, in the order they were created
+    pub fn dispatch_new_events<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(InputEvent<SyntheticInputBackend>),
+    {
+        while let Some(event) = self.queue.pop_front() {
+            callback(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_count() {
+        let mut backend = SyntheticInputBackend::new();
+        let first = backend.add_device(SyntheticDevice::new("first", &[DeviceCapability::Keyboard]));
+        let second = backend.add_device(SyntheticDevice::new("second", &[DeviceCapability::Keyboard]));
+        assert_ne!(first, second);
+        assert!(first.has_capability(DeviceCapability::Keyboard));
+        assert!(!first.has_capability(DeviceCapability::Pointer));
+
+        backend.keyboard_key(&first, 1, 30, KeyState::Pressed);
+        backend.keyboard_key(&second, 2, 30, KeyState::Pressed);
+        backend.keyboard_key(&first, 3, 30, KeyState::Released);
+        backend.remove_device(&second);
+        backend.keyboard_key(&first, 4, 31, KeyState::Pressed);
+
+        let mut keys = Vec::new();
+        let mut removed = Vec::new();
+        backend.dispatch_new_events(|event| match event {
+            InputEvent::Keyboard { event } => {
+                keys.push((event.device(), event.time(), event.state(), event.count()))
+            }
+            InputEvent::DeviceRemoved { device } => removed.push(device),
+            InputEvent::DeviceAdded { .. } => {}
+            _ => unreachable!(),
+        });
+        assert_eq!(
+            keys,
+            vec![
+                (first.clone(), 1, KeyState::Pressed, 1),
+                (second.clone(), 2, KeyState::Pressed, 2),
+                (first.clone(), 3, KeyState::Released, 1),
+                (first.clone(), 4, KeyState::Pressed, 1),
+            ]
+        );
+        assert_eq!(removed, vec![second]);
+        assert_eq!(backend.pending_events(), 0);
+    }
+
+    #[test]
+    fn absolute_position() {
+        let mut backend = SyntheticInputBackend::new();
+        let touch = backend.add_device(SyntheticDevice::new("touch", &[DeviceCapability::Touch]));
+        backend.touch_down(&touch, 0, Some(3), (0.25, 0.5).into());
+        backend.pointer_axis(&touch, 1, AxisSource::Wheel, Axis::Vertical, 2.0);
+
+        let mut count = 0;
+        backend.dispatch_new_events(|event| match event {
+            InputEvent::TouchDown { event } => {
+                assert_eq!(event.slot(), Some(TouchSlot::new(3)));
+                assert_eq!(
+                    event.position_transformed((800, 600).into()),
+                    Point::from((200.0, 300.0))
+                );
+                count += 1;
+            }
+            InputEvent::PointerAxis { event } => {
+                assert_eq!(event.amount(Axis::Vertical), None);
+                assert_eq!(event.amount_discrete(Axis::Vertical), Some(2.0));
+                assert_eq!(event.amount_discrete(Axis::Horizontal), None);
+                count += 1;
+            }
+            _ => {}
+        });
+        assert_eq!(count, 2);
+    }
+}
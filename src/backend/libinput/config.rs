@@ -0,0 +1,443 @@
+//! Typed configuration of libinput devices
+
+use input as libinput;
+use libinput::{AccelProfile, DeviceConfigError, ScrollMethod};
+
+use crate::backend::input::{Device, DeviceCapability};
+
+// The parts of a libinput device touched by the configuration, so it can be tested without devices
+pub(super) trait ConfigDevice {
+    fn name(&self) -> String;
+    fn usb_id(&self) -> (u32, u32);
+    fn has_capability(&self, capability: DeviceCapability) -> bool;
+    fn set_tap(&mut self, enabled: bool) -> Result<(), DeviceConfigError>;
+    fn set_natural_scroll(&mut self, enabled: bool) -> Result<(), DeviceConfigError>;
+    fn set_accel_profile(&mut self, profile: AccelProfile) -> Result<(), DeviceConfigError>;
+    fn set_accel_speed(&mut self, speed: f64) -> Result<(), DeviceConfigError>;
+    fn set_scroll_method(&mut self, method: ScrollMethod) -> Result<(), DeviceConfigError>;
+    fn set_left_handed(&mut self, enabled: bool) -> Result<(), DeviceConfigError>;
+    fn set_disable_while_typing(&mut self, enabled: bool) -> Result<(), DeviceConfigError>;
+    fn set_calibration_matrix(&mut self, matrix: [f32; 6]) -> Result<(), DeviceConfigError>;
+}
+
+impl ConfigDevice for libinput::Device {
+    fn name(&self) -> String {
+        libinput::Device::name(self).to_string()
+    }
+    fn usb_id(&self) -> (u32, u32) {
+        (self.id_vendor(), self.id_product())
+    }
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        Device::has_capability(self, capability)
+    }
+    fn set_tap(&mut self, enabled: bool) -> Result<(), DeviceConfigError> {
+        self.config_tap_set_enabled(enabled)
+    }
+    fn set_natural_scroll(&mut self, enabled: bool) -> Result<(), DeviceConfigError> {
+        self.config_scroll_set_natural_scroll_enabled(enabled)
+    }
+    fn set_accel_profile(&mut self, profile: AccelProfile) -> Result<(), DeviceConfigError> {
+        self.config_accel_set_profile(profile)
+    }
+    fn set_accel_speed(&mut self, speed: f64) -> Result<(), DeviceConfigError> {
+        self.config_accel_set_speed(speed)
+    }
+    fn set_scroll_method(&mut self, method: ScrollMethod) -> Result<(), DeviceConfigError> {
+        self.config_scroll_set_method(method)
+    }
+    fn set_left_handed(&mut self, enabled: bool) -> Result<(), DeviceConfigError> {
+        self.config_left_handed_set(enabled)
+    }
+    fn set_disable_while_typing(&mut self, enabled: bool) -> Result<(), DeviceConfigError> {
+        self.config_dwt_set_enabled(enabled)
+    }
+    fn set_calibration_matrix(&mut self, matrix: [f32; 6]) -> Result<(), DeviceConfigError> {
+        self.config_calibration_set_matrix(matrix)
+    }
+}
+
+/// Selects the devices a [`DeviceConfig`] is applied to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceMatch {
+    /// Every device
+    Any,
+    /// Devices with the given name
+    Name(String),
+    /// Devices with the given USB vendor and product id
+    UsbId {
+        /// Vendor id of the device
+        vendor: u32,
+        /// Product id of the device
+        product: u32,
+    },
+    /// Devices with the given capability
+    Capability(DeviceCapability),
+}
+
+impl DeviceMatch {
+    /// Returns `true` if the device is selected
+    pub fn matches(&self, device: &libinput::Device) -> bool {
+        self.matches_device(device)
+    }
+
+    fn matches_device<D: ConfigDevice>(&self, device: &D) -> bool {
+        match self {
+            DeviceMatch::Any => true,
+            DeviceMatch::Name(name) => device.name() == *name,
+            DeviceMatch::UsbId { vendor, product } => device.usb_id() == (*vendor, *product),
+            DeviceMatch::Capability(capability) => device.has_capability(*capability),
+        }
+    }
+}
+
+/// An option of a [`DeviceConfig`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConfigOption {
+    /// [`DeviceConfig::tap`]
+    Tap,
+    /// [`DeviceConfig::natural_scroll`]
+    NaturalScroll,
+    /// [`DeviceConfig::accel_profile`]
+    AccelProfile,
+    /// [`DeviceConfig::accel_speed`]
+    AccelSpeed,
+    /// [`DeviceConfig::scroll_method`]
+    ScrollMethod,
+    /// [`DeviceConfig::left_handed`]
+    LeftHanded,
+    /// [`DeviceConfig::disable_while_typing`]
+    DisableWhileTyping,
+    /// [`DeviceConfig::calibration_matrix`]
+    CalibrationMatrix,
+}
+
+/// Error returned when applying a [`DeviceConfig`]
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The device does not support the option
+    #[error("{option:?} is not supported by device {device}")]
+    Unsupported {
+        /// Name of the device
+        device: String,
+        /// Unsupported option
+        option: ConfigOption,
+    },
+    /// The value of the option is out of range for the device
+    #[error("Invalid value of {option:?} for device {device}")]
+    Invalid {
+        /// Name of the device
+        device: String,
+        /// Option with an invalid value
+        option: ConfigOption,
+    },
+}
+
+/// Configuration of a libinput device
+///
+/// Options set to `None` are left untouched.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceConfig {
+    /// Enable tap-to-click
+    pub tap: Option<bool>,
+    /// Enable natural (inverted) scrolling
+    pub natural_scroll: Option<bool>,
+    /// Pointer acceleration profile
+    pub accel_profile: Option<AccelProfile>,
+    /// Pointer acceleration speed, in the range `[-1, 1]`
+    pub accel_speed: Option<f64>,
+    /// Method used to generate scroll events
+    pub scroll_method: Option<ScrollMethod>,
+    /// Swap the primary and secondary buttons
+    pub left_handed: Option<bool>,
+    /// Disable the device while typing
+    pub disable_while_typing: Option<bool>,
+    /// Calibration matrix of absolute devices, see
+    /// [`Device::config_calibration_set_matrix`](libinput::Device::config_calibration_set_matrix)
+    pub calibration_matrix: Option<[f32; 6]>,
+}
+
+impl DeviceConfig {
+    /// Apply this configuration to a device
+    ///
+    /// All options are applied, even if some of them fail. The errors of the failed options are returned.
+    pub fn apply(&self, device: &mut libinput::Device) -> Result<(), Vec<ConfigError>> {
+        self.apply_to(device)
+    }
+
+    fn apply_to<D: ConfigDevice>(&self, device: &mut D) -> Result<(), Vec<ConfigError>> {
+        let mut results = Vec::new();
+        if let Some(enabled) = self.tap {
+            results.push((ConfigOption::Tap, device.set_tap(enabled)));
+        }
+        if let Some(enabled) = self.natural_scroll {
+            results.push((ConfigOption::NaturalScroll, device.set_natural_scroll(enabled)));
+        }
+        if let Some(profile) = self.accel_profile {
+            results.push((ConfigOption::AccelProfile, device.set_accel_profile(profile)));
+        }
+        if let Some(speed) = self.accel_speed {
+            results.push((ConfigOption::AccelSpeed, device.set_accel_speed(speed)));
+        }
+        if let Some(method) = self.scroll_method {
+            results.push((ConfigOption::ScrollMethod, device.set_scroll_method(method)));
+        }
+        if let Some(enabled) = self.left_handed {
+            results.push((ConfigOption::LeftHanded, device.set_left_handed(enabled)));
+        }
+        if let Some(enabled) = self.disable_while_typing {
+            results.push((
+                ConfigOption::DisableWhileTyping,
+                device.set_disable_while_typing(enabled),
+            ));
+        }
+        if let Some(matrix) = self.calibration_matrix {
+            results.push((
+                ConfigOption::CalibrationMatrix,
+                device.set_calibration_matrix(matrix),
+            ));
+        }
+
+        let errors = results
+            .into_iter()
+            .filter_map(|(option, result)| {
+                let device = device.name();
+                match result {
+                    Ok(()) => None,
+                    Err(DeviceConfigError::Unsupported) => Some(ConfigError::Unsupported { device, option }),
+                    Err(DeviceConfigError::Invalid) => Some(ConfigError::Invalid { device, option }),
+                }
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Apply every configuration selecting the device, in order
+///
+/// Configurations matching [`DeviceMatch::Any`] apply to devices of all kinds, so options the device
+/// does not support are skipped silently instead of being reported as errors.
+pub(super) fn apply_device_configs<D: ConfigDevice>(
+    configs: &[(DeviceMatch, DeviceConfig)],
+    device: &mut D,
+) -> Result<(), Vec<ConfigError>> {
+    let mut errors = Vec::new();
+    for (matcher, config) in configs {
+        if !matcher.matches_device(device) {
+            continue;
+        }
+        if let Err(err) = config.apply_to(device) {
+            errors.extend(err.into_iter().filter(|err| {
+                *matcher != DeviceMatch::Any || !matches!(err, ConfigError::Unsupported { .. })
+            }));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct TestDevice {
+        touchpad: bool,
+        tap: Option<bool>,
+        accel_speed: Option<f64>,
+    }
+
+    impl ConfigDevice for TestDevice {
+        fn name(&self) -> String {
+            if self.touchpad { "touchpad" } else { "mouse" }.to_string()
+        }
+        fn usb_id(&self) -> (u32, u32) {
+            if self.touchpad {
+                (0x06cb, 0x0001)
+            } else {
+                (0x046d, 0x0002)
+            }
+        }
+        fn has_capability(&self, capability: DeviceCapability) -> bool {
+            match capability {
+                DeviceCapability::Pointer => true,
+                DeviceCapability::Gesture => self.touchpad,
+                _ => false,
+            }
+        }
+        fn set_tap(&mut self, enabled: bool) -> Result<(), DeviceConfigError> {
+            if !self.touchpad {
+                return Err(DeviceConfigError::Unsupported);
+            }
+            self.tap = Some(enabled);
+            Ok(())
+        }
+        fn set_natural_scroll(&mut self, _enabled: bool) -> Result<(), DeviceConfigError> {
+            Ok(())
+        }
+        fn set_accel_profile(&mut self, _profile: AccelProfile) -> Result<(), DeviceConfigError> {
+            Ok(())
+        }
+        fn set_accel_speed(&mut self, speed: f64) -> Result<(), DeviceConfigError> {
+            if !(-1.0..=1.0).contains(&speed) {
+                return Err(DeviceConfigError::Invalid);
+            }
+            self.accel_speed = Some(speed);
+            Ok(())
+        }
+        fn set_scroll_method(&mut self, _method: ScrollMethod) -> Result<(), DeviceConfigError> {
+            Err(DeviceConfigError::Unsupported)
+        }
+        fn set_left_handed(&mut self, _enabled: bool) -> Result<(), DeviceConfigError> {
+            Ok(())
+        }
+        fn set_disable_while_typing(&mut self, _enabled: bool) -> Result<(), DeviceConfigError> {
+            Ok(())
+        }
+        fn set_calibration_matrix(&mut self, _matrix: [f32; 6]) -> Result<(), DeviceConfigError> {
+            Err(DeviceConfigError::Unsupported)
+        }
+    }
+
+    fn touchpad() -> TestDevice {
+        TestDevice {
+            touchpad: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn device_match() {
+        let (touchpad, mouse) = (touchpad(), TestDevice::default());
+
+        assert!(DeviceMatch::Any.matches_device(&touchpad));
+        assert!(DeviceMatch::Any.matches_device(&mouse));
+
+        let name = DeviceMatch::Name("touchpad".into());
+        assert!(name.matches_device(&touchpad));
+        assert!(!name.matches_device(&mouse));
+
+        let usb_id = DeviceMatch::UsbId {
+            vendor: 0x046d,
+            product: 0x0002,
+        };
+        assert!(!usb_id.matches_device(&touchpad));
+        assert!(usb_id.matches_device(&mouse));
+        // vendor and product must not be mixed up
+        let swapped = DeviceMatch::UsbId {
+            vendor: 0x0002,
+            product: 0x046d,
+        };
+        assert!(!swapped.matches_device(&mouse));
+
+        let gesture = DeviceMatch::Capability(DeviceCapability::Gesture);
+        assert!(gesture.matches_device(&touchpad));
+        assert!(!gesture.matches_device(&mouse));
+        assert!(DeviceMatch::Capability(DeviceCapability::Pointer).matches_device(&mouse));
+    }
+
+    #[test]
+    fn apply_collects_errors() {
+        let config = DeviceConfig {
+            tap: Some(true),
+            accel_speed: Some(2.0),
+            scroll_method: Some(ScrollMethod::TwoFinger),
+            left_handed: Some(true),
+            ..Default::default()
+        };
+        let mut device = touchpad();
+        let errors = config.apply_to(&mut device).unwrap_err();
+
+        // the failing options do not prevent the other ones from being applied
+        assert_eq!(device.tap, Some(true));
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            &errors[0],
+            ConfigError::Invalid { device, option: ConfigOption::AccelSpeed } if device == "touchpad"
+        ));
+        assert!(matches!(
+            &errors[1],
+            ConfigError::Unsupported { device, option: ConfigOption::ScrollMethod } if device == "touchpad"
+        ));
+
+        assert!(DeviceConfig::default().apply_to(&mut device).is_ok());
+    }
+
+    #[test]
+    fn apply_device_configs_in_order() {
+        let configs = vec![
+            (
+                DeviceMatch::Any,
+                DeviceConfig {
+                    tap: Some(true),
+                    accel_speed: Some(0.5),
+                    ..Default::default()
+                },
+            ),
+            (
+                DeviceMatch::Name("touchpad".into()),
+                DeviceConfig {
+                    accel_speed: Some(-0.5),
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        let mut touchpad = touchpad();
+        apply_device_configs(&configs, &mut touchpad).unwrap();
+        assert_eq!(touchpad.tap, Some(true));
+        assert_eq!(touchpad.accel_speed, Some(-0.5));
+
+        // tapping is not supported by the mouse, but was only requested for any device
+        let mut mouse = TestDevice::default();
+        apply_device_configs(&configs, &mut mouse).unwrap();
+        assert_eq!(mouse.tap, None);
+        assert_eq!(mouse.accel_speed, Some(0.5));
+    }
+
+    #[test]
+    fn apply_device_configs_errors() {
+        let configs = vec![
+            (
+                DeviceMatch::Any,
+                DeviceConfig {
+                    calibration_matrix: Some([1.0, 0.0, 0.0, 0.0, 1.0, 0.0]),
+                    accel_speed: Some(3.0),
+                    ..Default::default()
+                },
+            ),
+            (
+                DeviceMatch::Capability(DeviceCapability::Pointer),
+                DeviceConfig {
+                    tap: Some(true),
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        let mut mouse = TestDevice::default();
+        let errors = apply_device_configs(&configs, &mut mouse).unwrap_err();
+        // invalid values are still reported for any device, unsupported options only for specific matches
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(
+            errors[0],
+            ConfigError::Invalid {
+                option: ConfigOption::AccelSpeed,
+                ..
+            }
+        ));
+        assert!(matches!(
+            errors[1],
+            ConfigError::Unsupported {
+                option: ConfigOption::Tap,
+                ..
+            }
+        ));
+    }
+}
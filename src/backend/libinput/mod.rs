@@ -1,6 +1,6 @@
 //! Implementation of input backend trait for types provided by `libinput`
 
-use crate::backend::input::{self as backend, Axis, InputBackend, InputEvent};
+use crate::backend::input::{self as backend, Axis, InputBackend, InputEvent, LedHandle};
 #[cfg(feature = "backend_session")]
 use crate::{
     backend::session::{AsErrno, Session, Signal as SessionSignal},
@@ -12,16 +12,22 @@ use input::event;
 #[cfg(feature = "backend_session")]
 use std::path::Path;
 use std::{
+    cell::RefCell,
     os::unix::io::{AsRawFd, RawFd},
     path::PathBuf,
+    rc::Rc,
 };
 
 use calloop::{EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory};
 
-use slog::{info, o, trace};
+use slog::{info, o, trace, warn};
 
+mod config;
 mod tablet;
 
+use self::config::apply_device_configs;
+pub use self::config::{ConfigError, ConfigOption, DeviceConfig, DeviceMatch};
+
 // No idea if this is the same across unix platforms
 // Lets make this linux exclusive for now, once someone tries to build it for
 // any BSD-like system, they can verify if this is right and make a PR to change this.
@@ -32,9 +38,16 @@ const INPUT_MAJOR: u32 = 13;
 ///
 /// Tracks input of all devices given manually or via a udev seat to a provided libinput
 /// context.
+///
+/// Devices can be configured with [`LibinputInputBackend::set_device_config`]. The configuration is
+/// applied to every device when it is added, which includes devices re-added by libinput when the
+/// session is resumed. The same is true for the LEDs set through [`LibinputInputBackend::led_handle`].
 #[derive(Debug)]
 pub struct LibinputInputBackend {
     context: libinput::Libinput,
+    device_configs: Vec<(DeviceMatch, DeviceConfig)>,
+    devices: Vec<libinput::Device>,
+    leds: Rc<RefCell<KeyboardLeds>>,
     #[cfg(feature = "backend_session")]
     links: Vec<SignalToken>,
     logger: ::slog::Logger,
@@ -52,12 +65,52 @@ impl LibinputInputBackend {
         info!(log, "Initializing a libinput backend");
         LibinputInputBackend {
             context,
+            device_configs: Vec::new(),
+            devices: Vec::new(),
+            leds: Rc::new(RefCell::new(KeyboardLeds {
+                keyboards: Vec::new(),
+                leds: backend::Led::empty(),
+            })),
             #[cfg(feature = "backend_session")]
             links: Vec::new(),
             logger: log,
             token: Token::invalid(),
         }
     }
+
+    /// Set the configuration of the input devices
+    ///
+    /// Every configuration whose [`DeviceMatch`] selects a device is applied to it, in order, so later
+    /// entries override the options set by earlier ones. The configuration is applied to the current
+    /// devices immediately and to new devices when they are added.
+    ///
+    /// Returns the errors of the options that could not be applied to the current devices. Errors
+    /// happening on devices added later are logged. Options of configurations matching
+    /// [`DeviceMatch::Any`] are skipped on devices not supporting them.
+    pub fn set_device_config<I>(&mut self, configs: I) -> Result<(), Vec<ConfigError>>
+    where
+        I: IntoIterator<Item = (DeviceMatch, DeviceConfig)>,
+    {
+        self.device_configs = configs.into_iter().collect();
+        let mut errors = Vec::new();
+        for device in &mut self.devices {
+            if let Err(err) = apply_device_configs(&self.device_configs, device) {
+                errors.extend(err);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Get a handle to update the LEDs of all keyboards of this backend
+    ///
+    /// The LEDs are also applied to keyboards added later, see [`LibinputLedHandle`].
+    pub fn led_handle(&self) -> LibinputLedHandle {
+        LibinputLedHandle(self.leds.clone())
+    }
 }
 
 #[cfg(feature = "backend_session")]
@@ -373,6 +426,38 @@ impl InputBackend for LibinputInputBackend {
     type SpecialEvent = backend::UnusedEvent;
 }
 
+#[derive(Debug)]
+struct KeyboardLeds {
+    keyboards: Vec<libinput::Device>,
+    leds: backend::Led,
+}
+
+/// Handle to the LEDs of the keyboards of a [`LibinputInputBackend`]
+///
+/// The handle can be cloned and keeps working after the backend was inserted into the event loop.
+#[derive(Debug, Clone)]
+pub struct LibinputLedHandle(Rc<RefCell<KeyboardLeds>>);
+
+impl LedHandle for LibinputLedHandle {
+    fn update_leds(&self, leds: backend::Led) {
+        let mut inner = self.0.borrow_mut();
+        inner.leds = leds;
+        for keyboard in &mut inner.keyboards {
+            keyboard.led_update(leds.into());
+        }
+    }
+}
+
+impl From<backend::Led> for libinput::Led {
+    fn from(leds: backend::Led) -> libinput::Led {
+        let mut libinput = libinput::Led::empty();
+        libinput.set(libinput::Led::NUMLOCK, leds.contains(backend::Led::NUMLOCK));
+        libinput.set(libinput::Led::CAPSLOCK, leds.contains(backend::Led::CAPSLOCK));
+        libinput.set(libinput::Led::SCROLLLOCK, leds.contains(backend::Led::SCROLLLOCK));
+        libinput
+    }
+}
+
 impl From<event::keyboard::KeyState> for backend::KeyState {
     fn from(libinput: event::keyboard::KeyState) -> Self {
         match libinput {
@@ -474,10 +559,22 @@ impl EventSource for LibinputInputBackend {
                 match event {
                     libinput::Event::Device(device_event) => match device_event {
                         event::DeviceEvent::Added(device_added_event) => {
-                            let added = event::EventTrait::device(&device_added_event);
+                            let mut added = event::EventTrait::device(&device_added_event);
 
                             info!(self.logger, "New device {:?}", added.sysname(),);
 
+                            if let Err(errors) = apply_device_configs(&self.device_configs, &mut added) {
+                                for err in errors {
+                                    warn!(self.logger, "Failed to configure device: {}", err);
+                                }
+                            }
+                            if added.has_capability(libinput::DeviceCapability::Keyboard) {
+                                let mut leds = self.leds.borrow_mut();
+                                added.led_update(leds.leds.into());
+                                leds.keyboards.push(added.clone());
+                            }
+                            self.devices.push(added.clone());
+
                             callback(InputEvent::DeviceAdded { device: added }, &mut ());
                         }
                         event::DeviceEvent::Removed(device_removed_event) => {
@@ -485,6 +582,12 @@ impl EventSource for LibinputInputBackend {
 
                             info!(self.logger, "Removed device {:?}", removed.sysname(),);
 
+                            self.devices.retain(|device| device != &removed);
+                            self.leds
+                                .borrow_mut()
+                                .keyboards
+                                .retain(|device| device != &removed);
+
                             callback(InputEvent::DeviceRemoved { device: removed }, &mut ());
                         }
                         _ => {
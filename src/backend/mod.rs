@@ -75,6 +75,13 @@
 //! development and debugging. That backend is both a renderer and an input provider, and is
 //! accessible in the [`winit`] module, gated by the `backend_winit` cargo feature.
 //!
+//! ## Headless backend
+//!
+//! Compositors running without any display, e.g. in containers, CI or for remote sessions, can use
+//! the virtual outputs of the [`headless`] module, gated by the `backend_headless` cargo feature.
+//! Those are rendered into memory-backed buffers by any renderer capable of offscreen rendering and
+//! simulate the frame clock of a real display.
+//!
 
 pub mod allocator;
 pub mod input;
@@ -84,6 +91,8 @@ pub mod renderer;
 pub mod drm;
 #[cfg(feature = "backend_egl")]
 pub mod egl;
+#[cfg(feature = "backend_headless")]
+pub mod headless;
 #[cfg(feature = "backend_libinput")]
 pub mod libinput;
 #[cfg(feature = "backend_session")]
@@ -0,0 +1,384 @@
+//! Helper to track the damage of an output between frames
+//!
+//! Redrawing a whole output for every frame is wasteful, if only a small part of it changed
+//! (e.g. a blinking cursor in a terminal). The [`OutputDamageTracker`] keeps track of the elements
+//! rendered onto an output and computes which parts of the output have to be redrawn.
+//!
+//! Every element is described by a type implementing [`DamageElement`], that provides a unique id,
+//! the location and size of the element on the output, a [`CommitCounter`] increased every time the
+//! contents of the element change and the damage of the element since a given commit.
+//!
+//! The damage returned by [`OutputDamageTracker::damage_output`] takes the age of the rendering
+//! target into account, as reported by e.g. [`Slot::age`](crate::backend::allocator::Slot::age).
+//! It can be directly passed to the rendering operations of a [`Frame`](super::Frame), which limits
+//! all drawing to the damaged regions.
+//!
+//! Elements providing their opaque regions can additionally be culled with [`visible_damage`],
+//! which skips the parts of elements hidden by opaque elements above them.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::utils::{Physical, Rectangle, Region, Size};
+
+/// Number of frames the damage is tracked for
+///
+/// Targets with a higher age are fully redrawn.
+pub const MAX_AGE: usize = 4;
+
+// Above this number of rectangles a single bounding box is used as damage
+const MAX_DAMAGE_RECTS: usize = 16;
+
+static ELEMENT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Unique identifier of an element tracked by an [`OutputDamageTracker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ElementId(usize);
+
+impl ElementId {
+    /// Allocate a new unique id
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> ElementId {
+        ElementId(ELEMENT_ID.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+/// Counter increased every time the contents of an element change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommitCounter(usize);
+
+impl CommitCounter {
+    /// Increment the counter
+    pub fn increment(&mut self) {
+        self.0 = self.0.wrapping_add(1);
+    }
+
+    /// Number of commits between `previous` and this counter
+    ///
+    /// Returns `None`, if `previous` is `None` or newer than this counter.
+    pub fn distance(&self, previous: Option<CommitCounter>) -> Option<usize> {
+        previous
+            .filter(|previous| previous.0 <= self.0)
+            .map(|previous| self.0 - previous.0)
+    }
+}
+
+/// An element, whose damage can be tracked by an [`OutputDamageTracker`]
+pub trait DamageElement {
+    /// Unique id of this element, that stays the same across frames
+    fn id(&self) -> ElementId;
+
+    /// Location and size of this element in the coordinate space of the frame
+    fn geometry(&self) -> Rectangle<i32, Physical>;
+
+    /// Counter of the current contents of this element
+    fn current_commit(&self) -> CommitCounter;
+
+    /// Damage of this element since the given commit, relative to the location of the element
+    ///
+    /// If the damage cannot be determined (e.g. the commit is `None` or too old), the whole
+    /// element should be returned.
+    fn damage_since(&self, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>>;
+
+    /// Alpha value the element is rendered with
+    fn alpha(&self) -> f32 {
+        1.0
+    }
+
+    /// Parts of this element known to be fully opaque, relative to the location of the element
+    ///
+    /// Elements below opaque regions are hidden and do not need to be rendered,
+    /// see [`visible_damage`]. By default the element is considered transparent.
+    fn opaque_regions(&self) -> Vec<Rectangle<i32, Physical>> {
+        Vec::new()
+    }
+}
+
+impl<E: DamageElement> DamageElement for &E {
+    fn id(&self) -> ElementId {
+        (*self).id()
+    }
+    fn geometry(&self) -> Rectangle<i32, Physical> {
+        (*self).geometry()
+    }
+    fn current_commit(&self) -> CommitCounter {
+        (*self).current_commit()
+    }
+    fn damage_since(&self, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>> {
+        (*self).damage_since(commit)
+    }
+    fn alpha(&self) -> f32 {
+        (*self).alpha()
+    }
+    fn opaque_regions(&self) -> Vec<Rectangle<i32, Physical>> {
+        (*self).opaque_regions()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ElementState {
+    geometry: Rectangle<i32, Physical>,
+    commit: CommitCounter,
+    alpha: f32,
+    z_index: usize,
+}
+
+/// Tracks the damage of a single output across frames, see the [module-level documentation](self)
+#[derive(Debug, Default)]
+pub struct OutputDamageTracker {
+    size: Option<Size<i32, Physical>>,
+    last_state: HashMap<ElementId, ElementState>,
+    // damage of the last frames, the most recent one first
+    history: VecDeque<Vec<Rectangle<i32, Physical>>>,
+}
+
+impl OutputDamageTracker {
+    /// Create a new tracker without any history
+    pub fn new() -> OutputDamageTracker {
+        OutputDamageTracker::default()
+    }
+
+    /// Reset the tracked state, causing the next frame to be fully damaged
+    pub fn reset(&mut self) {
+        self.size = None;
+        self.last_state.clear();
+        self.history.clear();
+    }
+
+    /// Compute the damage of a new frame and record the state of its elements
+    ///
+    /// - `size` is the size of the frame (see [`Frame`](super::Frame)), a changed size damages the whole frame
+    /// - `age` is the age of the rendering target, `0` meaning its contents are undefined
+    /// - `elements` are the elements rendered in this frame in drawing order, bottom first
+    ///
+    /// The returned rectangles do not overlap. If the list is empty, nothing changed
+    /// and rendering can be skipped.
+    pub fn damage_output<E: DamageElement>(
+        &mut self,
+        size: Size<i32, Physical>,
+        age: usize,
+        elements: &[E],
+    ) -> Vec<Rectangle<i32, Physical>> {
+        let output_geo = Rectangle::from_loc_and_size((0, 0), size);
+        if self.size != Some(size) {
+            self.reset();
+            self.size = Some(size);
+        }
+
+        let mut damage = Vec::new();
+        let mut state = HashMap::with_capacity(elements.len());
+        for (z_index, element) in elements.iter().enumerate() {
+            let current = ElementState {
+                geometry: element.geometry(),
+                commit: element.current_commit(),
+                alpha: element.alpha(),
+                z_index,
+            };
+            match self.last_state.get(&element.id()) {
+                None => damage.push(current.geometry),
+                Some(previous)
+                    if previous.geometry != current.geometry
+                        || previous.alpha != current.alpha
+                        || previous.z_index != current.z_index =>
+                {
+                    damage.push(previous.geometry);
+                    damage.push(current.geometry);
+                }
+                Some(previous) if previous.commit != current.commit => {
+                    damage.extend(
+                        element
+                            .damage_since(Some(previous.commit))
+                            .into_iter()
+                            .filter_map(|mut rect| {
+                                rect.loc += current.geometry.loc;
+                                rect.intersection(current.geometry)
+                            }),
+                    );
+                }
+                Some(_) => {}
+            }
+            state.insert(element.id(), current);
+        }
+        for (id, previous) in self.last_state.iter() {
+            if !state.contains_key(id) {
+                damage.push(previous.geometry);
+            }
+        }
+        self.last_state = state;
+
+        let damage = damage
+            .into_iter()
+            .filter_map(|rect| rect.intersection(output_geo))
+            .filter(|rect| rect.size.w > 0 && rect.size.h > 0)
+            .collect::<Vec<_>>();
+        // the contents of the target are only known, if the frame they originate from was tracked
+        let known_frames = self.history.len();
+        self.history.push_front(damage);
+        self.history.truncate(MAX_AGE);
+
+        if age == 0 || age > known_frames {
+            return vec![output_geo];
+        }
+
+        let mut damage = self
+            .history
+            .iter()
+            .take(age)
+            .flatten()
+            .copied()
+            .collect::<Region<i32, Physical>>()
+            .into_rects();
+        if damage.len() > MAX_DAMAGE_RECTS {
+            let bbox = damage
+                .iter()
+                .skip(1)
+                .fold(damage[0], |bbox, rect| bbox.merge(*rect));
+            damage = vec![bbox];
+        }
+        damage
+    }
+}
+
+/// Splits the damage of a frame between its elements, removing the parts hidden by opaque elements above
+///
+/// `elements` are the elements of the frame in drawing order, bottom first, and `damage` the damage of
+/// the frame, e.g. as returned by [`OutputDamageTracker::damage_output`]. For every element the returned
+/// list contains the parts of `damage` it has to be drawn into, in the same order as `elements`.
+/// Elements with an empty list are hidden or not damaged at all and can be skipped.
+///
+/// The opaque regions of elements with an alpha value below `1.0` are ignored.
+pub fn visible_damage<E: DamageElement>(
+    elements: &[E],
+    damage: &[Rectangle<i32, Physical>],
+) -> Vec<Vec<Rectangle<i32, Physical>>> {
+    let mut remaining = damage.iter().copied().collect::<Region<i32, Physical>>();
+    let mut visible = elements
+        .iter()
+        .rev()
+        .map(|element| {
+            let geometry = element.geometry();
+            let mut element_damage = remaining.clone();
+            element_damage.intersect_rect(geometry);
+            if element.alpha() >= 1.0 {
+                for mut rect in element.opaque_regions() {
+                    rect.loc += geometry.loc;
+                    if let Some(rect) = rect.intersection(geometry) {
+                        remaining.subtract_rect(rect);
+                    }
+                }
+            }
+            element_damage.into_rects()
+        })
+        .collect::<Vec<_>>();
+    visible.reverse();
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestElement {
+        id: ElementId,
+        geometry: Rectangle<i32, Physical>,
+        commit: CommitCounter,
+        damage: Vec<Rectangle<i32, Physical>>,
+        opaque: Vec<Rectangle<i32, Physical>>,
+    }
+
+    impl DamageElement for TestElement {
+        fn id(&self) -> ElementId {
+            self.id
+        }
+        fn geometry(&self) -> Rectangle<i32, Physical> {
+            self.geometry
+        }
+        fn current_commit(&self) -> CommitCounter {
+            self.commit
+        }
+        fn damage_since(&self, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>> {
+            match self.commit.distance(commit) {
+                Some(1) => self.damage.clone(),
+                _ => vec![Rectangle::from_loc_and_size((0, 0), self.geometry.size)],
+            }
+        }
+        fn opaque_regions(&self) -> Vec<Rectangle<i32, Physical>> {
+            self.opaque.clone()
+        }
+    }
+
+    fn area(damage: &[Rectangle<i32, Physical>]) -> i32 {
+        damage.iter().map(|rect| rect.size.w * rect.size.h).sum()
+    }
+
+    #[test]
+    fn element_damage() {
+        let size = Size::from((100, 100));
+        let mut tracker = OutputDamageTracker::new();
+        let mut element = TestElement {
+            id: ElementId::new(),
+            geometry: Rectangle::from_loc_and_size((10, 10), (20, 20)),
+            commit: CommitCounter::default(),
+            damage: Vec::new(),
+            opaque: Vec::new(),
+        };
+
+        // first frame is fully damaged
+        let damage = tracker.damage_output(size, 1, &[&element]);
+        assert_eq!(damage, vec![Rectangle::from_loc_and_size((0, 0), (100, 100))]);
+        // nothing changed
+        assert!(tracker.damage_output(size, 1, &[&element]).is_empty());
+
+        // damage of a commit is relative to the element
+        element.commit.increment();
+        element.damage = vec![Rectangle::from_loc_and_size((5, 5), (2, 2))];
+        let damage = tracker.damage_output(size, 1, &[&element]);
+        assert_eq!(damage, vec![Rectangle::from_loc_and_size((15, 15), (2, 2))]);
+
+        // older buffers include the damage of the previous frames
+        let damage = tracker.damage_output(size, 2, &[&element]);
+        assert_eq!(damage, vec![Rectangle::from_loc_and_size((15, 15), (2, 2))]);
+        // unknown contents or too old buffers are fully damaged
+        assert_eq!(area(&tracker.damage_output(size, 0, &[&element])), 100 * 100);
+        assert_eq!(
+            area(&tracker.damage_output(size, MAX_AGE + 1, &[&element])),
+            100 * 100
+        );
+
+        // moving damages the old and the new location, without overlapping rectangles
+        element.geometry.loc = (20, 10).into();
+        let damage = tracker.damage_output(size, 1, &[&element]);
+        assert_eq!(area(&damage), 30 * 20);
+
+        // removing damages the old location
+        let damage = tracker.damage_output::<&TestElement>(size, 1, &[]);
+        assert_eq!(damage, vec![Rectangle::from_loc_and_size((20, 10), (20, 20))]);
+    }
+
+    #[test]
+    fn occlusion() {
+        let element = |loc: (i32, i32), opaque: Vec<Rectangle<i32, Physical>>| TestElement {
+            id: ElementId::new(),
+            geometry: Rectangle::from_loc_and_size(loc, (20, 20)),
+            commit: CommitCounter::default(),
+            damage: Vec::new(),
+            opaque,
+        };
+        let bottom = element((0, 0), Vec::new());
+        let middle = element((10, 0), vec![Rectangle::from_loc_and_size((0, 0), (20, 20))]);
+        let top = element((0, 0), vec![Rectangle::from_loc_and_size((0, 0), (5, 5))]);
+
+        let damage = [Rectangle::from_loc_and_size((0, 0), (100, 100))];
+        let visible = visible_damage(&[&bottom, &middle, &top], &damage);
+        // the bottom element is hidden by the opaque middle one on the right and the top-left corner of the top one
+        assert_eq!(area(&visible[0]), 10 * 20 - 5 * 5);
+        assert_eq!(area(&visible[1]), 20 * 20);
+        assert_eq!(area(&visible[2]), 20 * 20);
+
+        // undamaged elements are skipped
+        let damage = [Rectangle::from_loc_and_size((50, 50), (10, 10))];
+        assert!(visible_damage(&[&bottom, &middle, &top], &damage)
+            .iter()
+            .all(|damage| damage.is_empty()));
+    }
+}
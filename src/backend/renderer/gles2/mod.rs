@@ -1,5 +1,6 @@
 //! Implementation of the rendering traits using OpenGL ES 2
 
+use std::cell::Cell;
 use std::convert::TryFrom;
 use std::ffi::CStr;
 use std::fmt;
@@ -14,19 +15,31 @@ use std::{collections::HashSet, os::raw::c_char};
 use cgmath::{prelude::*, Matrix3, Vector2, Vector3};
 
 mod shaders;
+mod uniform;
 mod version;
+#[cfg(feature = "wayland_frontend")]
+mod yuv;
+
+use self::uniform::{
+    apply_uniforms, check_uniform_names, unbind_uniform_textures, uniform_descs, UniformDesc,
+};
+pub use self::uniform::{Uniform, UniformName, UniformType, UniformValue};
+#[cfg(feature = "wayland_frontend")]
+pub use self::yuv::{YuvColorSpace, YuvRange};
 
-use super::{Bind, Frame, Renderer, Texture, TextureFilter, Transform, Unbind};
+use super::{
+    Bind, ExportMem, Frame, Offscreen, Renderer, Texture, TextureFilter, TextureMapping, Transform, Unbind,
+};
 use crate::backend::allocator::{
     dmabuf::{Dmabuf, WeakDmabuf},
-    Format,
+    Buffer as _, Format, Fourcc,
 };
 use crate::backend::egl::{
     ffi::egl::{self as ffi_egl, types::EGLImage},
     EGLContext, EGLSurface, MakeCurrentError,
 };
 use crate::backend::SwapBuffersError;
-use crate::utils::{Buffer, Physical, Rectangle, Size};
+use crate::utils::{Buffer, Physical, Rectangle, Region, Size};
 
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
 use super::ImportEgl;
@@ -61,6 +74,67 @@ struct Gles2Program {
     attrib_tex_coords: ffi::types::GLint,
 }
 
+#[derive(Debug, Clone)]
+struct Gles2SolidProgram {
+    program: ffi::types::GLuint,
+    uniform_matrix: ffi::types::GLint,
+    uniform_color: ffi::types::GLint,
+    attrib_vert: ffi::types::GLint,
+}
+
+#[derive(Debug)]
+struct Gles2PixelProgramInternal {
+    program: ffi::types::GLuint,
+    uniform_matrix: ffi::types::GLint,
+    uniform_size: ffi::types::GLint,
+    uniform_alpha: ffi::types::GLint,
+    attrib_vert: ffi::types::GLint,
+    additional_uniforms: Vec<UniformDesc>,
+    destruction_callback_sender: Sender<CleanupResource>,
+}
+
+impl Drop for Gles2PixelProgramInternal {
+    fn drop(&mut self) {
+        let _ = self
+            .destruction_callback_sender
+            .send(CleanupResource::Program(self.program));
+    }
+}
+
+/// A custom pixel shader, see [`Gles2Renderer::compile_custom_pixel_shader`]
+///
+/// This is a cheaply clonable handle, the program is freed once all clones are dropped.
+#[derive(Debug, Clone)]
+pub struct Gles2PixelProgram(Rc<Gles2PixelProgramInternal>);
+
+#[derive(Debug)]
+struct Gles2TexProgramVariant {
+    program: Gles2Program,
+    additional_uniforms: Vec<UniformDesc>,
+}
+
+#[derive(Debug)]
+struct Gles2TexProgramInternal {
+    variants: [Gles2TexProgramVariant; shaders::FRAGMENT_COUNT],
+    destruction_callback_sender: Sender<CleanupResource>,
+}
+
+impl Drop for Gles2TexProgramInternal {
+    fn drop(&mut self) {
+        for variant in &self.variants {
+            let _ = self
+                .destruction_callback_sender
+                .send(CleanupResource::Program(variant.program.program));
+        }
+    }
+}
+
+/// A custom texture shader, see [`Gles2Renderer::compile_custom_texture_shader`]
+///
+/// This is a cheaply clonable handle, the program is freed once all clones are dropped.
+#[derive(Debug, Clone)]
+pub struct Gles2TexProgram(Rc<Gles2TexProgramInternal>);
+
 /// A handle to a GLES2 texture
 #[derive(Debug, Clone)]
 pub struct Gles2Texture(Rc<Gles2TextureInternal>);
@@ -130,6 +204,8 @@ impl Drop for Gles2TextureInternal {
 enum CleanupResource {
     Texture(ffi::types::GLuint),
     EGLImage(EGLImage),
+    Mapping(ffi::types::GLuint, *const nix::libc::c_void),
+    Program(ffi::types::GLuint),
 }
 
 impl Texture for Gles2Texture {
@@ -144,6 +220,48 @@ impl Texture for Gles2Texture {
     }
 }
 
+/// Texture mapping of a GLES2 framebuffer or texture, see [`ExportMem`]
+///
+/// The pixel data is stored in a pixel buffer object owned by the renderer, that created it.
+#[derive(Debug)]
+pub struct Gles2Mapping {
+    pbo: ffi::types::GLuint,
+    format: Fourcc,
+    size: Size<i32, Buffer>,
+    flipped: bool,
+    mapping: Cell<*const nix::libc::c_void>,
+    destruction_callback_sender: Sender<CleanupResource>,
+}
+
+impl Texture for Gles2Mapping {
+    fn width(&self) -> u32 {
+        self.size.w as u32
+    }
+    fn height(&self) -> u32 {
+        self.size.h as u32
+    }
+    fn size(&self) -> Size<i32, Buffer> {
+        self.size
+    }
+}
+
+impl TextureMapping for Gles2Mapping {
+    fn flipped(&self) -> bool {
+        self.flipped
+    }
+    fn format(&self) -> Fourcc {
+        self.format
+    }
+}
+
+impl Drop for Gles2Mapping {
+    fn drop(&mut self) {
+        let _ = self
+            .destruction_callback_sender
+            .send(CleanupResource::Mapping(self.pbo, self.mapping.get()));
+    }
+}
+
 #[derive(Debug, Clone)]
 struct WeakGles2Buffer {
     dmabuf: WeakDmabuf,
@@ -155,7 +273,13 @@ struct WeakGles2Buffer {
 #[derive(Debug)]
 struct Gles2Buffer {
     internal: WeakGles2Buffer,
-    _dmabuf: Dmabuf,
+    dmabuf: Dmabuf,
+}
+
+#[derive(Debug)]
+struct Gles2TextureTarget {
+    texture: Gles2Texture,
+    fbo: ffi::types::GLuint,
 }
 
 #[cfg(feature = "wayland_frontend")]
@@ -185,8 +309,15 @@ pub struct Gles2Renderer {
     buffers: Vec<WeakGles2Buffer>,
     target_buffer: Option<Gles2Buffer>,
     target_surface: Option<Rc<EGLSurface>>,
+    target_texture: Option<Gles2TextureTarget>,
     extensions: Vec<String>,
+    gl_version: version::GlVersion,
     programs: [Gles2Program; shaders::FRAGMENT_COUNT],
+    solid_program: Gles2SolidProgram,
+    #[cfg(feature = "wayland_frontend")]
+    yuv_programs: yuv::Gles2YuvPrograms,
+    #[cfg(feature = "wayland_frontend")]
+    yuv_conversion: (YuvColorSpace, YuvRange),
     #[cfg(feature = "wayland_frontend")]
     dmabuf_cache: std::collections::HashMap<WeakDmabuf, Gles2Texture>,
     egl: EGLContext,
@@ -205,15 +336,21 @@ pub struct Gles2Renderer {
 /// Handle to the currently rendered frame during [`Gles2Renderer::render`](Renderer::render)
 pub struct Gles2Frame {
     current_projection: Matrix3<f32>,
+    size: Size<i32, Physical>,
     gl: ffi::Gles2,
     programs: [Gles2Program; shaders::FRAGMENT_COUNT],
+    solid_program: Gles2SolidProgram,
+    tex_program_override: Option<(Gles2TexProgram, Vec<Uniform<'static>>)>,
 }
 
 impl fmt::Debug for Gles2Frame {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Gles2Frame")
             .field("current_projection", &self.current_projection)
+            .field("size", &self.size)
             .field("programs", &self.programs)
+            .field("solid_program", &self.solid_program)
+            .field("tex_program_override", &self.tex_program_override)
             .finish_non_exhaustive()
     }
 }
@@ -225,8 +362,11 @@ impl fmt::Debug for Gles2Renderer {
             .field("buffers", &self.buffers)
             .field("target_buffer", &self.target_buffer)
             .field("target_surface", &self.target_surface)
+            .field("target_texture", &self.target_texture)
             .field("extensions", &self.extensions)
+            .field("gl_version", &self.gl_version)
             .field("programs", &self.programs)
+            .field("solid_program", &self.solid_program)
             // ffi::Gles2 does not implement Debug
             .field("egl", &self.egl)
             .field("logger", &self.logger)
@@ -240,6 +380,13 @@ pub enum Gles2Error {
     /// A shader could not be compiled
     #[error("Failed to compile Shader: {0}")]
     ShaderCompileError(&'static str),
+    /// A custom shader could not be compiled, contains the log of the shader compiler
+    #[error("Failed to compile custom shader: {0}")]
+    CustomShaderCompileError(String),
+    /// An additional uniform was not declared when compiling the shader, has a different type
+    /// or an invalid value, or was declared twice or with a name containing a NUL byte
+    #[error("Uniform {0} is not declared correctly or has an invalid value")]
+    InvalidUniform(String),
     /// A program could not be linked
     #[error("Failed to link Program")]
     ProgramLinkError,
@@ -262,6 +409,10 @@ pub enum Gles2Error {
     #[error("Unsupported pixel format: {0:?}")]
     #[cfg(feature = "wayland_frontend")]
     UnsupportedPixelFormat(wl_shm::Format),
+    /// The planes of the given shm buffer do not fit into its pool
+    #[error("Invalid layout of a shm buffer of format: {0:?}")]
+    #[cfg(feature = "wayland_frontend")]
+    InvalidShmBufferLayout(wl_shm::Format),
     /// The given buffer was not accessible
     #[error("Error accessing the buffer ({0:?})")]
     #[cfg(feature = "wayland_frontend")]
@@ -273,6 +424,21 @@ pub enum Gles2Error {
     /// This rendering operation was called without a previous `begin`-call
     #[error("Call begin before doing any rendering operations")]
     UnconstraintRenderingOperation,
+    /// The GL version of the context is too old for the requested operation
+    #[error("The requested operation requires at least OpenGL ES {0}.{1}")]
+    GLVersionNotSupported(i32, i32),
+    /// The requested pixel format is not supported for exporting
+    #[error("Unsupported pixel format for exporting: {0:?}")]
+    UnsupportedExportFormat(Fourcc),
+    /// A pixel buffer could not be mapped into memory
+    #[error("Failed to map the pixel buffer into memory")]
+    MappingError,
+    /// The requested region is empty or exceeds the bounds of the framebuffer or texture to copy from
+    #[error("Region {0:?} is empty or exceeds the bounds of the copied framebuffer or texture")]
+    InvalidRegion(Rectangle<i32, Buffer>),
+    /// The requested pixel format is not supported for offscreen rendering
+    #[error("Unsupported pixel format for offscreen rendering: {0:?}")]
+    UnsupportedOffscreenFormat(Fourcc),
 }
 
 impl From<Gles2Error> for SwapBuffersError {
@@ -288,8 +454,16 @@ impl From<Gles2Error> for SwapBuffersError {
             x @ Gles2Error::FramebufferBindingError
             | x @ Gles2Error::BindBufferEGLError(_)
             | x @ Gles2Error::UnsupportedPixelFormat(_)
+            | x @ Gles2Error::InvalidShmBufferLayout(_)
             | x @ Gles2Error::BufferAccessError(_)
-            | x @ Gles2Error::EGLBufferAccessError(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
+            | x @ Gles2Error::EGLBufferAccessError(_)
+            | x @ Gles2Error::GLVersionNotSupported(_, _)
+            | x @ Gles2Error::UnsupportedExportFormat(_)
+            | x @ Gles2Error::MappingError
+            | x @ Gles2Error::InvalidRegion(_)
+            | x @ Gles2Error::UnsupportedOffscreenFormat(_)
+            | x @ Gles2Error::CustomShaderCompileError(_)
+            | x @ Gles2Error::InvalidUniform(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
         }
     }
     #[cfg(not(feature = "wayland_frontend"))]
@@ -301,9 +475,15 @@ impl From<Gles2Error> for SwapBuffersError {
             | x @ Gles2Error::GLExtensionNotSupported(_)
             | x @ Gles2Error::UnconstraintRenderingOperation => SwapBuffersError::ContextLost(Box::new(x)),
             Gles2Error::ContextActivationError(err) => err.into(),
-            x @ Gles2Error::FramebufferBindingError | x @ Gles2Error::BindBufferEGLError(_) => {
-                SwapBuffersError::TemporaryFailure(Box::new(x))
-            }
+            x @ Gles2Error::FramebufferBindingError
+            | x @ Gles2Error::BindBufferEGLError(_)
+            | x @ Gles2Error::GLVersionNotSupported(_, _)
+            | x @ Gles2Error::UnsupportedExportFormat(_)
+            | x @ Gles2Error::MappingError
+            | x @ Gles2Error::InvalidRegion(_)
+            | x @ Gles2Error::UnsupportedOffscreenFormat(_)
+            | x @ Gles2Error::CustomShaderCompileError(_)
+            | x @ Gles2Error::InvalidUniform(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
         }
     }
 }
@@ -332,11 +512,12 @@ extern "system" fn gl_debug_log(
     });
 }
 
+// Returns the info log of the shader compiler on failure
 unsafe fn compile_shader(
     gl: &ffi::Gles2,
     variant: ffi::types::GLuint,
-    src: &'static str,
-) -> Result<ffi::types::GLuint, Gles2Error> {
+    src: &str,
+) -> Result<ffi::types::GLuint, String> {
     let shader = gl.CreateShader(variant);
     gl.ShaderSource(
         shader,
@@ -349,20 +530,28 @@ unsafe fn compile_shader(
     let mut status = ffi::FALSE as i32;
     gl.GetShaderiv(shader, ffi::COMPILE_STATUS, &mut status as *mut _);
     if status == ffi::FALSE as i32 {
+        let mut len = 0;
+        gl.GetShaderiv(shader, ffi::INFO_LOG_LENGTH, &mut len as *mut _);
+        let mut log = vec![0u8; len.max(1) as usize];
+        gl.GetShaderInfoLog(
+            shader,
+            log.len() as i32,
+            ptr::null_mut(),
+            log.as_mut_ptr() as *mut ffi::types::GLchar,
+        );
         gl.DeleteShader(shader);
-        return Err(Gles2Error::ShaderCompileError(src));
+        let len = log.iter().position(|c| *c == 0).unwrap_or(log.len());
+        return Err(String::from_utf8_lossy(&log[..len]).into_owned());
     }
 
     Ok(shader)
 }
 
-unsafe fn link_program(
+unsafe fn link_shaders(
     gl: &ffi::Gles2,
-    vert_src: &'static str,
-    frag_src: &'static str,
+    vert: ffi::types::GLuint,
+    frag: ffi::types::GLuint,
 ) -> Result<ffi::types::GLuint, Gles2Error> {
-    let vert = compile_shader(gl, ffi::VERTEX_SHADER, vert_src)?;
-    let frag = compile_shader(gl, ffi::FRAGMENT_SHADER, frag_src)?;
     let program = gl.CreateProgram();
     gl.AttachShader(program, vert);
     gl.AttachShader(program, frag);
@@ -382,9 +571,61 @@ unsafe fn link_program(
     Ok(program)
 }
 
+unsafe fn link_program(
+    gl: &ffi::Gles2,
+    vert_src: &'static str,
+    frag_src: &'static str,
+) -> Result<ffi::types::GLuint, Gles2Error> {
+    let vert = compile_shader(gl, ffi::VERTEX_SHADER, vert_src)
+        .map_err(|_| Gles2Error::ShaderCompileError(vert_src))?;
+    let frag = match compile_shader(gl, ffi::FRAGMENT_SHADER, frag_src) {
+        Ok(frag) => frag,
+        Err(_) => {
+            gl.DeleteShader(vert);
+            return Err(Gles2Error::ShaderCompileError(frag_src));
+        }
+    };
+    link_shaders(gl, vert, frag)
+}
+
+unsafe fn link_custom_program(
+    gl: &ffi::Gles2,
+    vert_src: &'static str,
+    frag_src: &str,
+) -> Result<ffi::types::GLuint, Gles2Error> {
+    let vert = compile_shader(gl, ffi::VERTEX_SHADER, vert_src)
+        .map_err(|_| Gles2Error::ShaderCompileError(vert_src))?;
+    let frag = match compile_shader(gl, ffi::FRAGMENT_SHADER, frag_src) {
+        Ok(frag) => frag,
+        Err(log) => {
+            gl.DeleteShader(vert);
+            return Err(Gles2Error::CustomShaderCompileError(log));
+        }
+    };
+    link_shaders(gl, vert, frag)
+}
+
+// Inserts the given defines into a shader source, after the `#version` directive if any
+fn insert_defines(src: &str, defines: &[&str]) -> String {
+    let defines = defines
+        .iter()
+        .map(|define| format!("#define {}\n", define))
+        .collect::<String>();
+    let trimmed = src.trim_start();
+    if trimmed.starts_with("#version") {
+        let (version, rest) = trimmed.split_at(trimmed.find('\n').unwrap_or(trimmed.len()));
+        format!("{}\n{}{}", version, defines, rest)
+    } else {
+        format!("{}{}", defines, src)
+    }
+}
+
 unsafe fn texture_program(gl: &ffi::Gles2, frag: &'static str) -> Result<Gles2Program, Gles2Error> {
     let program = link_program(gl, shaders::VERTEX_SHADER, frag)?;
+    Ok(texture_program_locations(gl, program))
+}
 
+unsafe fn texture_program_locations(gl: &ffi::Gles2, program: ffi::types::GLuint) -> Gles2Program {
     let position = CStr::from_bytes_with_nul(b"position\0").expect("NULL terminated");
     let tex_coords = CStr::from_bytes_with_nul(b"tex_coords\0").expect("NULL terminated");
     let tex = CStr::from_bytes_with_nul(b"tex\0").expect("NULL terminated");
@@ -392,7 +633,7 @@ unsafe fn texture_program(gl: &ffi::Gles2, frag: &'static str) -> Result<Gles2Pr
     let invert_y = CStr::from_bytes_with_nul(b"invert_y\0").expect("NULL terminated");
     let alpha = CStr::from_bytes_with_nul(b"alpha\0").expect("NULL terminated");
 
-    Ok(Gles2Program {
+    Gles2Program {
         program,
         uniform_tex: gl.GetUniformLocation(program, tex.as_ptr() as *const ffi::types::GLchar),
         uniform_matrix: gl.GetUniformLocation(program, matrix.as_ptr() as *const ffi::types::GLchar),
@@ -400,6 +641,21 @@ unsafe fn texture_program(gl: &ffi::Gles2, frag: &'static str) -> Result<Gles2Pr
         uniform_alpha: gl.GetUniformLocation(program, alpha.as_ptr() as *const ffi::types::GLchar),
         attrib_position: gl.GetAttribLocation(program, position.as_ptr() as *const ffi::types::GLchar),
         attrib_tex_coords: gl.GetAttribLocation(program, tex_coords.as_ptr() as *const ffi::types::GLchar),
+    }
+}
+
+unsafe fn solid_program(gl: &ffi::Gles2) -> Result<Gles2SolidProgram, Gles2Error> {
+    let program = link_program(gl, shaders::VERTEX_SHADER_SOLID, shaders::FRAGMENT_SHADER_SOLID)?;
+
+    let matrix = CStr::from_bytes_with_nul(b"matrix\0").expect("NULL terminated");
+    let color = CStr::from_bytes_with_nul(b"color\0").expect("NULL terminated");
+    let vert = CStr::from_bytes_with_nul(b"vert\0").expect("NULL terminated");
+
+    Ok(Gles2SolidProgram {
+        program,
+        uniform_matrix: gl.GetUniformLocation(program, matrix.as_ptr() as *const ffi::types::GLchar),
+        uniform_color: gl.GetUniformLocation(program, color.as_ptr() as *const ffi::types::GLchar),
+        attrib_vert: gl.GetAttribLocation(program, vert.as_ptr() as *const ffi::types::GLchar),
     })
 }
 
@@ -427,7 +683,7 @@ impl Gles2Renderer {
 
         context.make_current()?;
 
-        let (gl, exts, logger_ptr) = {
+        let (gl, exts, gl_version, logger_ptr) = {
             let gl = ffi::Gles2::load_with(|s| crate::backend::egl::get_proc_address(s) as *const _);
             let ext_ptr = gl.GetString(ffi::EXTENSIONS) as *const c_char;
             if ext_ptr.is_null() {
@@ -484,7 +740,7 @@ impl Gles2Renderer {
                 None
             };
 
-            (gl, exts, logger)
+            (gl, exts, gl_version, logger)
         };
 
         let programs = [
@@ -492,6 +748,9 @@ impl Gles2Renderer {
             texture_program(&gl, shaders::FRAGMENT_SHADER_XBGR)?,
             texture_program(&gl, shaders::FRAGMENT_SHADER_EXTERNAL)?,
         ];
+        let solid_program = solid_program(&gl)?;
+        #[cfg(feature = "wayland_frontend")]
+        let yuv_programs = yuv::yuv_programs(&gl)?;
 
         let (tx, rx) = channel();
         let mut renderer = Gles2Renderer {
@@ -501,9 +760,16 @@ impl Gles2Renderer {
             #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
             egl_reader: None,
             extensions: exts,
+            gl_version,
             programs,
+            solid_program,
+            #[cfg(feature = "wayland_frontend")]
+            yuv_programs,
+            #[cfg(feature = "wayland_frontend")]
+            yuv_conversion: (YuvColorSpace::Bt601, YuvRange::Limited),
             target_buffer: None,
             target_surface: None,
+            target_texture: None,
             buffers: Vec::new(),
             #[cfg(feature = "wayland_frontend")]
             dmabuf_cache: std::collections::HashMap::new(),
@@ -542,6 +808,17 @@ impl Gles2Renderer {
                 CleanupResource::EGLImage(image) => unsafe {
                     ffi_egl::DestroyImageKHR(**self.egl.display.display, image);
                 },
+                CleanupResource::Program(program) => unsafe {
+                    self.gl.DeleteProgram(program);
+                },
+                CleanupResource::Mapping(pbo, mapping) => unsafe {
+                    if !mapping.is_null() {
+                        self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, pbo);
+                        self.gl.UnmapBuffer(ffi::PIXEL_PACK_BUFFER);
+                        self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, 0);
+                    }
+                    self.gl.DeleteBuffers(1, &pbo);
+                },
             }
         }
         Ok(())
@@ -561,6 +838,10 @@ impl ImportShm for Gles2Renderer {
         with_buffer_contents(buffer, |slice, data| {
             self.make_current()?;
 
+            if yuv::YUV_SHM_FORMATS.contains(&data.format) {
+                return self.import_yuv_shm_buffer(slice, data);
+            }
+
             let offset = data.offset as i32;
             let width = data.width as i32;
             let height = data.height as i32;
@@ -664,6 +945,9 @@ impl ImportShm for Gles2Renderer {
             wl_shm::Format::Xbgr8888,
             wl_shm::Format::Argb8888,
             wl_shm::Format::Xrgb8888,
+            wl_shm::Format::Nv12,
+            wl_shm::Format::Yuyv,
+            wl_shm::Format::Yuv420,
         ]
     }
 }
@@ -832,6 +1116,257 @@ impl Gles2Renderer {
 
         Ok(tex)
     }
+
+    fn import_yuv_shm_buffer(
+        &self,
+        slice: &[u8],
+        data: crate::wayland::shm::BufferData,
+    ) -> Result<Gles2Texture, Gles2Error> {
+        trace!(self.logger, "Converting {:?} shm buffer", data.format);
+        let texture = Gles2Texture(Rc::new(Gles2TextureInternal {
+            texture: unsafe {
+                let mut tex = 0;
+                self.gl.GenTextures(1, &mut tex);
+                tex
+            },
+            texture_kind: 1,
+            is_external: false,
+            y_inverted: false,
+            size: (data.width, data.height).into(),
+            egl_images: None,
+            destruction_callback_sender: self.destruction_callback_sender.clone(),
+        }));
+
+        unsafe {
+            self.gl.BindTexture(ffi::TEXTURE_2D, texture.0.texture);
+            self.gl
+                .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+            self.gl
+                .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+            self.gl.TexImage2D(
+                ffi::TEXTURE_2D,
+                0,
+                ffi::RGBA as i32,
+                data.width,
+                data.height,
+                0,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                ptr::null(),
+            );
+            self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+
+            let (color_space, range) = self.yuv_conversion;
+            yuv::convert_yuv(
+                &self.gl,
+                &self.yuv_programs,
+                slice,
+                &data,
+                texture.0.texture,
+                color_space,
+                range,
+            )?;
+        }
+
+        Ok(texture)
+    }
+
+    /// Set the conversion used to import YUV shm buffers.
+    ///
+    /// As shm buffers carry no information about their color space, all YUV buffers are converted
+    /// using the same parameters. Defaults to BT.601 with limited range, which is used by most videos.
+    pub fn set_yuv_conversion(&mut self, color_space: YuvColorSpace, range: YuvRange) {
+        self.yuv_conversion = (color_space, range);
+    }
+}
+
+impl Gles2Renderer {
+    fn target_size(&self) -> Option<Size<i32, Buffer>> {
+        if let Some(buffer) = self.target_buffer.as_ref() {
+            Some(buffer.dmabuf.size())
+        } else if let Some(target) = self.target_texture.as_ref() {
+            Some(target.texture.size())
+        } else {
+            self.target_surface
+                .as_ref()
+                .and_then(|surface| surface.get_size())
+                .map(|size| Size::from((size.w, size.h)))
+        }
+    }
+
+    fn target_fbo(&self) -> ffi::types::GLuint {
+        if let Some(buffer) = self.target_buffer.as_ref() {
+            buffer.internal.fbo
+        } else if let Some(target) = self.target_texture.as_ref() {
+            target.fbo
+        } else {
+            0
+        }
+    }
+
+    fn export_format(&self, format: Fourcc) -> Result<(ffi::types::GLenum, ffi::types::GLenum), Gles2Error> {
+        match format {
+            Fourcc::Abgr8888 | Fourcc::Xbgr8888 => Ok((ffi::RGBA, ffi::UNSIGNED_BYTE)),
+            Fourcc::Argb8888 | Fourcc::Xrgb8888
+                if self.extensions.iter().any(|ext| ext == "GL_EXT_read_format_bgra") =>
+            {
+                Ok((ffi::BGRA_EXT, ffi::UNSIGNED_BYTE))
+            }
+            format => Err(Gles2Error::UnsupportedExportFormat(format)),
+        }
+    }
+
+    // Reads the given region of the currently bound read framebuffer into a new pixel buffer object.
+    // The copy happens asynchronously, the data is ready, once the buffer can be mapped.
+    // The region needs to be validated using `check_read_region`.
+    unsafe fn read_pixels(
+        &self,
+        region: Rectangle<i32, Buffer>,
+        format: ffi::types::GLenum,
+        type_: ffi::types::GLenum,
+    ) -> ffi::types::GLuint {
+        let mut pbo = 0;
+        self.gl.GenBuffers(1, &mut pbo);
+        self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, pbo);
+        self.gl.BufferData(
+            ffi::PIXEL_PACK_BUFFER,
+            (region.size.w * region.size.h * 4) as ffi::types::GLsizeiptr,
+            ptr::null(),
+            ffi::STREAM_READ,
+        );
+        self.gl.PixelStorei(ffi::PACK_ALIGNMENT, 4);
+        self.gl.ReadPixels(
+            region.loc.x,
+            region.loc.y,
+            region.size.w,
+            region.size.h,
+            format,
+            type_,
+            ptr::null_mut(),
+        );
+        self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, 0);
+        pbo
+    }
+}
+
+// Checks that a region to read is not empty and lies inside of the source of the given size
+fn check_read_region(region: Rectangle<i32, Buffer>, size: Size<i32, Buffer>) -> Result<(), Gles2Error> {
+    let inside = |loc: i32, len: i32, max: i32| {
+        loc >= 0 && len > 0 && loc.checked_add(len).map(|end| end <= max).unwrap_or(false)
+    };
+    if inside(region.loc.x, region.size.w, size.w) && inside(region.loc.y, region.size.h, size.h) {
+        Ok(())
+    } else {
+        Err(Gles2Error::InvalidRegion(region))
+    }
+}
+
+impl ExportMem for Gles2Renderer {
+    type TextureMapping = Gles2Mapping;
+
+    fn copy_framebuffer(
+        &mut self,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<Gles2Mapping, Gles2Error> {
+        if self.gl_version < version::GLES_3_0 {
+            return Err(Gles2Error::GLVersionNotSupported(3, 0));
+        }
+        let (gl_format, gl_type) = self.export_format(format)?;
+        self.make_current()?;
+        let target_size = self.target_size().ok_or(Gles2Error::FramebufferBindingError)?;
+        check_read_region(region, target_size)?;
+
+        // textures are rendered top-down, other targets upside-down to match
+        // the orientation of the gl framebuffer starting at the bottom-left
+        let flipped = self.target_texture.is_none();
+        let mut gl_region = region;
+        if flipped {
+            gl_region.loc.y = target_size.h - region.loc.y - region.size.h;
+        }
+        let pbo = unsafe { self.read_pixels(gl_region, gl_format, gl_type) };
+
+        Ok(Gles2Mapping {
+            pbo,
+            format,
+            size: region.size,
+            flipped,
+            mapping: Cell::new(ptr::null()),
+            destruction_callback_sender: self.destruction_callback_sender.clone(),
+        })
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: &Gles2Texture,
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<Gles2Mapping, Gles2Error> {
+        if self.gl_version < version::GLES_3_0 {
+            return Err(Gles2Error::GLVersionNotSupported(3, 0));
+        }
+        // external textures cannot be attached to a framebuffer
+        if texture.0.is_external {
+            return Err(Gles2Error::FramebufferBindingError);
+        }
+        check_read_region(region, texture.0.size)?;
+        self.make_current()?;
+
+        let pbo = unsafe {
+            let mut fbo = 0;
+            self.gl.GenFramebuffers(1, &mut fbo as *mut _);
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+            self.gl.FramebufferTexture2D(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::TEXTURE_2D,
+                texture.0.texture,
+                0,
+            );
+            let status = self.gl.CheckFramebufferStatus(ffi::FRAMEBUFFER);
+            let pbo = if status == ffi::FRAMEBUFFER_COMPLETE {
+                Some(self.read_pixels(region, ffi::RGBA, ffi::UNSIGNED_BYTE))
+            } else {
+                None
+            };
+            // restore the currently bound target
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, self.target_fbo());
+            self.gl.DeleteFramebuffers(1, &fbo as *const _);
+            pbo
+        }
+        .ok_or(Gles2Error::FramebufferBindingError)?;
+
+        Ok(Gles2Mapping {
+            pbo,
+            format: Fourcc::Abgr8888,
+            size: region.size,
+            flipped: texture.0.y_inverted,
+            mapping: Cell::new(ptr::null()),
+            destruction_callback_sender: self.destruction_callback_sender.clone(),
+        })
+    }
+
+    fn map_texture<'a>(&mut self, texture_mapping: &'a Gles2Mapping) -> Result<&'a [u8], Gles2Error> {
+        let len = (texture_mapping.size.w * texture_mapping.size.h * 4) as usize;
+        if texture_mapping.mapping.get().is_null() {
+            self.make_current()?;
+            let mapping = unsafe {
+                self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, texture_mapping.pbo);
+                let mapping = self.gl.MapBufferRange(
+                    ffi::PIXEL_PACK_BUFFER,
+                    0,
+                    len as ffi::types::GLsizeiptr,
+                    ffi::MAP_READ_BIT,
+                );
+                self.gl.BindBuffer(ffi::PIXEL_PACK_BUFFER, 0);
+                mapping
+            };
+            if mapping.is_null() {
+                return Err(Gles2Error::MappingError);
+            }
+            texture_mapping.mapping.set(mapping);
+        }
+        Ok(unsafe { std::slice::from_raw_parts(texture_mapping.mapping.get() as *const u8, len) })
+    }
 }
 
 impl Bind<Rc<EGLSurface>> for Gles2Renderer {
@@ -883,7 +1418,7 @@ impl Bind<Dmabuf> for Gles2Renderer {
                 Ok(Gles2Buffer {
                     internal: buf.clone(),
                     // we keep the dmabuf alive as long as we are bound
-                    _dmabuf: dmabuf,
+                    dmabuf,
                 })
             })
             .unwrap_or_else(|| {
@@ -930,7 +1465,7 @@ impl Bind<Dmabuf> for Gles2Renderer {
 
                     Ok(Gles2Buffer {
                         internal: weak,
-                        _dmabuf: dmabuf,
+                        dmabuf,
                     })
                 }
             })?;
@@ -948,12 +1483,94 @@ impl Bind<Dmabuf> for Gles2Renderer {
     }
 }
 
+impl Bind<Gles2Texture> for Gles2Renderer {
+    fn bind(&mut self, texture: Gles2Texture) -> Result<(), Gles2Error> {
+        self.unbind()?;
+        // external textures cannot be attached to a framebuffer
+        if texture.0.is_external {
+            return Err(Gles2Error::FramebufferBindingError);
+        }
+        unsafe {
+            self.egl.make_current()?;
+
+            let mut fbo = 0;
+            self.gl.GenFramebuffers(1, &mut fbo as *mut _);
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+            self.gl.FramebufferTexture2D(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::TEXTURE_2D,
+                texture.0.texture,
+                0,
+            );
+            let status = self.gl.CheckFramebufferStatus(ffi::FRAMEBUFFER);
+            if status != ffi::FRAMEBUFFER_COMPLETE {
+                self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+                self.gl.DeleteFramebuffers(1, &fbo as *const _);
+                return Err(Gles2Error::FramebufferBindingError);
+            }
+
+            self.target_texture = Some(Gles2TextureTarget { texture, fbo });
+        }
+        Ok(())
+    }
+}
+
+impl Offscreen<Gles2Texture> for Gles2Renderer {
+    fn create_buffer(&mut self, format: Fourcc, size: Size<i32, Buffer>) -> Result<Gles2Texture, Gles2Error> {
+        let bgra = self
+            .extensions
+            .iter()
+            .any(|ext| ext == "GL_EXT_texture_format_BGRA8888");
+        let (gl_format, texture_kind) = match format {
+            Fourcc::Abgr8888 => (ffi::RGBA, 0),
+            Fourcc::Xbgr8888 => (ffi::RGBA, 1),
+            Fourcc::Argb8888 if bgra => (ffi::BGRA_EXT, 0),
+            Fourcc::Xrgb8888 if bgra => (ffi::BGRA_EXT, 1),
+            format => return Err(Gles2Error::UnsupportedOffscreenFormat(format)),
+        };
+        self.make_current()?;
+
+        let tex = unsafe {
+            let mut tex = 0;
+            self.gl.GenTextures(1, &mut tex);
+            self.gl.BindTexture(ffi::TEXTURE_2D, tex);
+            self.gl.TexImage2D(
+                ffi::TEXTURE_2D,
+                0,
+                gl_format as i32,
+                size.w,
+                size.h,
+                0,
+                gl_format,
+                ffi::UNSIGNED_BYTE,
+                ptr::null(),
+            );
+            self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+            tex
+        };
+
+        Ok(Gles2Texture(Rc::new(Gles2TextureInternal {
+            texture: tex,
+            texture_kind,
+            is_external: false,
+            y_inverted: false,
+            size,
+            egl_images: None,
+            destruction_callback_sender: self.destruction_callback_sender.clone(),
+        })))
+    }
+}
+
 impl Unbind for Gles2Renderer {
     fn unbind(&mut self) -> Result<(), <Self as Renderer>::Error> {
         unsafe {
             self.egl.make_current()?;
         }
         unsafe { self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0) };
+        if let Some(target) = self.target_texture.take() {
+            unsafe { self.gl.DeleteFramebuffers(1, &target.fbo as *const _) };
+        }
         self.target_buffer = None;
         self.target_surface = None;
         self.egl.unbind()?;
@@ -966,9 +1583,15 @@ impl Drop for Gles2Renderer {
         unsafe {
             if self.egl.make_current().is_ok() {
                 self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+                if let Some(target) = self.target_texture.take() {
+                    self.gl.DeleteFramebuffers(1, &target.fbo as *const _);
+                }
                 for program in &self.programs {
                     self.gl.DeleteProgram(program.program);
                 }
+                self.gl.DeleteProgram(self.solid_program.program);
+                #[cfg(feature = "wayland_frontend")]
+                yuv::delete_yuv_programs(&self.gl, &self.yuv_programs);
 
                 if self.extensions.iter().any(|ext| ext == "GL_KHR_debug") {
                     self.gl.Disable(ffi::DEBUG_OUTPUT);
@@ -1001,6 +1624,101 @@ impl Gles2Renderer {
         let gl = self.gl.clone();
         Ok(func(self, &gl))
     }
+
+    /// Compile a custom pixel shader, that can be drawn with [`Gles2Frame::render_pixel_shader_to`].
+    ///
+    /// `src` is the source of a GLSL ES 1.00 fragment shader, which is expected to output premultiplied colors.
+    /// Additionally to the declared `additional_uniforms`, the shader can use the following inputs:
+    /// - `varying vec2 v_coords`: location inside the drawn rectangle, normalized to `0.0..=1.0`
+    /// - `uniform vec2 size`: size of the drawn rectangle in physical pixels
+    /// - `uniform float alpha`: alpha value passed to the draw call
+    pub fn compile_custom_pixel_shader(
+        &mut self,
+        src: impl AsRef<str>,
+        additional_uniforms: &[UniformName<'_>],
+    ) -> Result<Gles2PixelProgram, Gles2Error> {
+        let c_names = check_uniform_names(additional_uniforms)?;
+        self.make_current()?;
+        unsafe {
+            let program = link_custom_program(&self.gl, shaders::VERTEX_SHADER_PIXEL, src.as_ref())?;
+
+            let vert = CStr::from_bytes_with_nul(b"vert\0").expect("NULL terminated");
+            let matrix = CStr::from_bytes_with_nul(b"matrix\0").expect("NULL terminated");
+            let size = CStr::from_bytes_with_nul(b"size\0").expect("NULL terminated");
+            let alpha = CStr::from_bytes_with_nul(b"alpha\0").expect("NULL terminated");
+
+            Ok(Gles2PixelProgram(Rc::new(Gles2PixelProgramInternal {
+                program,
+                uniform_matrix: self
+                    .gl
+                    .GetUniformLocation(program, matrix.as_ptr() as *const ffi::types::GLchar),
+                uniform_size: self
+                    .gl
+                    .GetUniformLocation(program, size.as_ptr() as *const ffi::types::GLchar),
+                uniform_alpha: self
+                    .gl
+                    .GetUniformLocation(program, alpha.as_ptr() as *const ffi::types::GLchar),
+                attrib_vert: self
+                    .gl
+                    .GetAttribLocation(program, vert.as_ptr() as *const ffi::types::GLchar),
+                additional_uniforms: uniform_descs(&self.gl, program, additional_uniforms, &c_names),
+                destruction_callback_sender: self.destruction_callback_sender.clone(),
+            })))
+        }
+    }
+
+    /// Compile a custom texture shader, that can replace the default one using
+    /// [`Gles2Frame::override_default_tex_program`].
+    ///
+    /// `src` is the source of a GLSL ES 1.00 fragment shader, which is expected to output premultiplied colors.
+    /// It is compiled once for every kind of texture, using the following defines:
+    /// - `NO_ALPHA` for textures without an alpha channel, whose alpha values have to be ignored
+    /// - `EXTERNAL` for external textures, requiring `#extension GL_OES_EGL_image_external : require`
+    ///   and a `samplerExternalOES` instead of a `sampler2D`
+    ///
+    /// Additionally to the declared `additional_uniforms`, the shader can use the following inputs:
+    /// - `uniform sampler2D tex` (or `samplerExternalOES`): the rendered texture
+    /// - `varying vec2 v_tex_coords`: texture coordinates of the current fragment
+    /// - `uniform float alpha`: alpha value passed to the draw call
+    pub fn compile_custom_texture_shader(
+        &mut self,
+        src: impl AsRef<str>,
+        additional_uniforms: &[UniformName<'_>],
+    ) -> Result<Gles2TexProgram, Gles2Error> {
+        let c_names = check_uniform_names(additional_uniforms)?;
+        self.make_current()?;
+        let src = src.as_ref();
+        // same order as `Gles2Renderer::programs`
+        let sources = [
+            insert_defines(src, &[]),
+            insert_defines(src, &["NO_ALPHA"]),
+            insert_defines(src, &["EXTERNAL"]),
+        ];
+
+        let mut programs = Vec::with_capacity(shaders::FRAGMENT_COUNT);
+        for src in &sources {
+            match unsafe { link_custom_program(&self.gl, shaders::VERTEX_SHADER, src) } {
+                Ok(program) => programs.push(program),
+                Err(err) => {
+                    for program in programs {
+                        unsafe { self.gl.DeleteProgram(program) };
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        let variant = |program| unsafe {
+            Gles2TexProgramVariant {
+                program: texture_program_locations(&self.gl, program),
+                additional_uniforms: uniform_descs(&self.gl, program, additional_uniforms, &c_names),
+            }
+        };
+        Ok(Gles2TexProgram(Rc::new(Gles2TexProgramInternal {
+            variants: [variant(programs[0]), variant(programs[1]), variant(programs[2])],
+            destruction_callback_sender: self.destruction_callback_sender.clone(),
+        })))
+    }
 }
 
 impl Renderer for Gles2Renderer {
@@ -1077,11 +1795,21 @@ impl Renderer for Gles2Renderer {
         renderer[2][0] = -(1.0f32.copysign(renderer[0][0] + renderer[1][0]));
         renderer[2][1] = -(1.0f32.copysign(renderer[0][1] + renderer[1][1]));
 
+        // output transformation passed in by the user
+        let mut projection = transform.matrix() * renderer;
+        if self.target_texture.is_some() {
+            // textures are stored top-down, unlike the default framebuffer,
+            // so they can be sampled like any imported texture
+            projection = Matrix3::new(1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0) * projection;
+        }
+
         let mut frame = Gles2Frame {
             gl: self.gl.clone(),
             programs: self.programs.clone(),
-            // output transformation passed in by the user
-            current_projection: transform.matrix() * renderer,
+            solid_program: self.solid_program.clone(),
+            tex_program_override: None,
+            size,
+            current_projection: projection,
         };
 
         let result = rendering(self, &mut frame);
@@ -1136,6 +1864,7 @@ impl Frame for Gles2Frame {
         src: Rectangle<i32, Buffer>,
         dest: Rectangle<f64, Physical>,
         transform: Transform,
+        damage: &[Rectangle<i32, Physical>],
         alpha: f32,
     ) -> Result<(), Self::Error> {
         let mut mat = Matrix3::<f32>::identity();
@@ -1170,20 +1899,106 @@ impl Frame for Gles2Frame {
             .truncate(), // bottom-right
             (texture_mat * Vector3::new(src.loc.x as f32, (src.loc.y + src.size.h) as f32, 0.0)).truncate(), // bottom-left
         ];
-        self.render_texture(texture, mat, verts, alpha)
+        self.render_texture(texture, mat, verts, damage, alpha)
+    }
+
+    fn render_texture_from_to_opaque(
+        &mut self,
+        texture: &Self::TextureId,
+        src: Rectangle<i32, Buffer>,
+        dst: Rectangle<f64, Physical>,
+        src_transform: Transform,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        if alpha < 1.0 || opaque_regions.is_empty() {
+            return self.render_texture_from_to(texture, src, dst, src_transform, damage, alpha);
+        }
+
+        let damage = damage.iter().copied().collect::<Region<i32, Physical>>();
+        let opaque = opaque_regions.iter().copied().collect::<Region<i32, Physical>>();
+        let blended = damage.subtract(&opaque);
+        let opaque = damage.intersection(&opaque);
+
+        unsafe { self.gl.Disable(ffi::BLEND) };
+        let result = self.render_texture_from_to(texture, src, dst, src_transform, opaque.rects(), alpha);
+        unsafe { self.gl.Enable(ffi::BLEND) };
+        result?;
+        self.render_texture_from_to(texture, src, dst, src_transform, blended.rects(), alpha)
+    }
+
+    fn draw_solid(
+        &mut self,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        color: [f32; 4],
+    ) -> Result<(), Self::Error> {
+        let damage = damage
+            .iter()
+            .filter_map(|rect| rect.intersection(dst))
+            .collect::<Vec<_>>();
+        if damage.is_empty() {
+            return Ok(());
+        }
+
+        let mut matrix = Matrix3::<f32>::identity();
+        matrix = matrix * Matrix3::from_translation(Vector2::new(dst.loc.x as f32, dst.loc.y as f32));
+        matrix = matrix * Matrix3::from_nonuniform_scale(dst.size.w as f32, dst.size.h as f32);
+        //apply output transformation
+        matrix = self.current_projection * matrix;
+
+        unsafe {
+            self.gl.UseProgram(self.solid_program.program);
+            self.gl.Uniform4f(
+                self.solid_program.uniform_color,
+                color[0],
+                color[1],
+                color[2],
+                color[3],
+            );
+            self.gl
+                .UniformMatrix3fv(self.solid_program.uniform_matrix, 1, ffi::FALSE, matrix.as_ptr());
+
+            self.gl.VertexAttribPointer(
+                self.solid_program.attrib_vert as u32,
+                2,
+                ffi::FLOAT,
+                ffi::FALSE,
+                0,
+                VERTS.as_ptr() as *const _,
+            );
+            self.gl
+                .EnableVertexAttribArray(self.solid_program.attrib_vert as u32);
+
+            self.draw_damaged(&damage);
+
+            self.gl
+                .DisableVertexAttribArray(self.solid_program.attrib_vert as u32);
+        }
+
+        Ok(())
     }
 }
 
 impl Gles2Frame {
     /// Render a texture to the current target using given projection matrix and alpha.
-    /// The given vertices are used to source the texture. This is mostly useful for cropping the texture.    
+    /// The given vertices are used to source the texture. This is mostly useful for cropping the texture.
+    ///
+    /// Only the parts intersecting the `damage` rectangles, given in the coordinate space of the frame,
+    /// are drawn.
     pub fn render_texture(
         &mut self,
         tex: &Gles2Texture,
         mut matrix: Matrix3<f32>,
         tex_coords: [Vector2<f32>; 4],
+        damage: &[Rectangle<i32, Physical>],
         alpha: f32,
     ) -> Result<(), Gles2Error> {
+        if damage.is_empty() {
+            return Ok(());
+        }
+
         //apply output transformation
         matrix = self.current_projection * matrix;
 
@@ -1193,31 +2008,46 @@ impl Gles2Frame {
             ffi::TEXTURE_2D
         };
 
+        let (program, additional_uniforms) = match self.tex_program_override {
+            Some((ref program, ref uniforms)) => {
+                let variant = &program.0.variants[tex.0.texture_kind];
+                (
+                    &variant.program,
+                    Some((&variant.additional_uniforms[..], &uniforms[..])),
+                )
+            }
+            None => (&self.programs[tex.0.texture_kind], None),
+        };
+
         // render
         unsafe {
             self.gl.ActiveTexture(ffi::TEXTURE0);
             self.gl.BindTexture(target, tex.0.texture);
             self.gl
                 .TexParameteri(target, ffi::TEXTURE_MIN_FILTER, ffi::LINEAR as i32);
-            self.gl.UseProgram(self.programs[tex.0.texture_kind].program);
+            self.gl.UseProgram(program.program);
 
+            self.gl.Uniform1i(program.uniform_tex, 0);
             self.gl
-                .Uniform1i(self.programs[tex.0.texture_kind].uniform_tex, 0);
-            self.gl.UniformMatrix3fv(
-                self.programs[tex.0.texture_kind].uniform_matrix,
-                1,
-                ffi::FALSE,
-                matrix.as_ptr(),
-            );
-            self.gl.Uniform1i(
-                self.programs[tex.0.texture_kind].uniform_invert_y,
-                if tex.0.y_inverted { 1 } else { 0 },
-            );
+                .UniformMatrix3fv(program.uniform_matrix, 1, ffi::FALSE, matrix.as_ptr());
             self.gl
-                .Uniform1f(self.programs[tex.0.texture_kind].uniform_alpha, alpha);
+                .Uniform1i(program.uniform_invert_y, if tex.0.y_inverted { 1 } else { 0 });
+            self.gl.Uniform1f(program.uniform_alpha, alpha);
+
+            // additional textures use the units following the rendered texture
+            let bound_textures = match additional_uniforms {
+                Some((descs, uniforms)) => match apply_uniforms(&self.gl, descs, uniforms, 1) {
+                    Ok(bound_textures) => bound_textures,
+                    Err(err) => {
+                        self.gl.BindTexture(target, 0);
+                        return Err(err);
+                    }
+                },
+                None => 0,
+            };
 
             self.gl.VertexAttribPointer(
-                self.programs[tex.0.texture_kind].attrib_position as u32,
+                program.attrib_position as u32,
                 2,
                 ffi::FLOAT,
                 ffi::FALSE,
@@ -1225,7 +2055,7 @@ impl Gles2Frame {
                 VERTS.as_ptr() as *const _,
             );
             self.gl.VertexAttribPointer(
-                self.programs[tex.0.texture_kind].attrib_tex_coords as u32,
+                program.attrib_tex_coords as u32,
                 2,
                 ffi::FLOAT,
                 ffi::FALSE,
@@ -1233,21 +2063,170 @@ impl Gles2Frame {
                 tex_coords.as_ptr() as *const _, // cgmath::Vector2 is marked as repr(C), this cast should be safe
             );
 
-            self.gl
-                .EnableVertexAttribArray(self.programs[tex.0.texture_kind].attrib_position as u32);
-            self.gl
-                .EnableVertexAttribArray(self.programs[tex.0.texture_kind].attrib_tex_coords as u32);
+            self.gl.EnableVertexAttribArray(program.attrib_position as u32);
+            self.gl.EnableVertexAttribArray(program.attrib_tex_coords as u32);
 
-            self.gl.DrawArrays(ffi::TRIANGLE_STRIP, 0, 4);
+            self.draw_damaged(damage);
+
+            self.gl.DisableVertexAttribArray(program.attrib_position as u32);
+            self.gl.DisableVertexAttribArray(program.attrib_tex_coords as u32);
+
+            unbind_uniform_textures(&self.gl, 1, bound_textures);
+            self.gl.BindTexture(target, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Use a custom texture shader for all following texture draws of this frame,
+    /// instead of the default one.
+    ///
+    /// The `additional_uniforms` are set for every draw and have to be declared
+    /// when compiling the shader (see [`Gles2Renderer::compile_custom_texture_shader`]).
+    pub fn override_default_tex_program(
+        &mut self,
+        program: Gles2TexProgram,
+        additional_uniforms: Vec<Uniform<'static>>,
+    ) {
+        self.tex_program_override = Some((program, additional_uniforms));
+    }
+
+    /// Restore the default texture shader after a call to [`Gles2Frame::override_default_tex_program`]
+    pub fn clear_tex_program_override(&mut self) {
+        self.tex_program_override = None;
+    }
 
+    /// Render a custom pixel shader into the given rectangle of the frame.
+    ///
+    /// Only the parts intersecting the `damage` rectangles, given in the coordinate space of the frame,
+    /// are drawn. The `additional_uniforms` have to be declared when compiling the shader
+    /// (see [`Gles2Renderer::compile_custom_pixel_shader`]).
+    pub fn render_pixel_shader_to(
+        &mut self,
+        program: &Gles2PixelProgram,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        alpha: f32,
+        additional_uniforms: &[Uniform<'_>],
+    ) -> Result<(), Gles2Error> {
+        let damage = damage
+            .iter()
+            .filter_map(|rect| rect.intersection(dst))
+            .collect::<Vec<_>>();
+        if damage.is_empty() {
+            return Ok(());
+        }
+
+        let mut matrix = Matrix3::<f32>::identity();
+        matrix = matrix * Matrix3::from_translation(Vector2::new(dst.loc.x as f32, dst.loc.y as f32));
+        matrix = matrix * Matrix3::from_nonuniform_scale(dst.size.w as f32, dst.size.h as f32);
+        //apply output transformation
+        matrix = self.current_projection * matrix;
+
+        let program = &program.0;
+        unsafe {
+            self.gl.UseProgram(program.program);
             self.gl
-                .DisableVertexAttribArray(self.programs[tex.0.texture_kind].attrib_position as u32);
+                .UniformMatrix3fv(program.uniform_matrix, 1, ffi::FALSE, matrix.as_ptr());
             self.gl
-                .DisableVertexAttribArray(self.programs[tex.0.texture_kind].attrib_tex_coords as u32);
+                .Uniform2f(program.uniform_size, dst.size.w as f32, dst.size.h as f32);
+            self.gl.Uniform1f(program.uniform_alpha, alpha);
+            let bound_textures =
+                apply_uniforms(&self.gl, &program.additional_uniforms, additional_uniforms, 0)?;
 
-            self.gl.BindTexture(target, 0);
+            self.gl.VertexAttribPointer(
+                program.attrib_vert as u32,
+                2,
+                ffi::FLOAT,
+                ffi::FALSE,
+                0,
+                VERTS.as_ptr() as *const _,
+            );
+            self.gl.EnableVertexAttribArray(program.attrib_vert as u32);
+
+            self.draw_damaged(&damage);
+
+            self.gl.DisableVertexAttribArray(program.attrib_vert as u32);
+            unbind_uniform_textures(&self.gl, 0, bound_textures);
         }
 
         Ok(())
     }
+
+    /// Draws the current vertices once per damage rectangle, limiting each draw to the rectangle
+    unsafe fn draw_damaged(&self, damage: &[Rectangle<i32, Physical>]) {
+        for rect in damage {
+            // the projection maps the frame onto the normalized device coordinates,
+            // which are then mapped onto the viewport covering the whole target
+            let corners = [
+                (rect.loc.x, rect.loc.y),
+                (rect.loc.x + rect.size.w, rect.loc.y + rect.size.h),
+            ]
+            .map(|(x, y)| {
+                let ndc = self.current_projection * Vector3::new(x as f32, y as f32, 1.0);
+                (
+                    ((ndc.x + 1.0) / 2.0 * self.size.w as f32).round() as i32,
+                    ((ndc.y + 1.0) / 2.0 * self.size.h as f32).round() as i32,
+                )
+            });
+            let x = corners[0].0.min(corners[1].0);
+            let y = corners[0].1.min(corners[1].1);
+            let w = (corners[0].0 - corners[1].0).abs();
+            let h = (corners[0].1 - corners[1].1).abs();
+            if w == 0 || h == 0 {
+                continue;
+            }
+
+            self.gl.Scissor(x, y, w, h);
+            self.gl.DrawArrays(ffi::TRIANGLE_STRIP, 0, 4);
+        }
+        self.gl.Scissor(0, 0, self.size.w, self.size.h);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // negative sizes can only be created by setting the fields directly
+    fn region(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Buffer> {
+        let mut region = Rectangle::from_loc_and_size((x, y), (0, 0));
+        region.size.w = w;
+        region.size.h = h;
+        region
+    }
+
+    #[test]
+    fn read_region_inside() {
+        let size = Size::from((64, 32));
+        assert!(check_read_region(region(0, 0, 64, 32), size).is_ok());
+        assert!(check_read_region(region(10, 5, 1, 1), size).is_ok());
+        assert!(check_read_region(region(63, 31, 1, 1), size).is_ok());
+    }
+
+    #[test]
+    fn read_region_invalid() {
+        let size = Size::from((64, 32));
+        for invalid in [
+            // empty or negative
+            region(0, 0, 0, 32),
+            region(0, 0, 64, 0),
+            region(10, 10, -5, 5),
+            region(10, 10, 5, -5),
+            // outside of the source
+            region(-1, 0, 10, 10),
+            region(0, -1, 10, 10),
+            region(60, 0, 10, 10),
+            region(0, 30, 10, 10),
+            region(64, 32, 1, 1),
+            // the end would overflow
+            region(1, 0, i32::MAX, 1),
+        ] {
+            assert!(
+                matches!(check_read_region(invalid, size), Err(Gles2Error::InvalidRegion(r)) if r == invalid),
+                "{:?} was not rejected",
+                invalid
+            );
+        }
+    }
 }
@@ -52,3 +52,56 @@ void main() {
     gl_FragColor = texture2D(tex, v_tex_coords) * alpha;
 }
 "#;
+
+pub const VERTEX_SHADER_SOLID: &str = r#"
+#version 100
+uniform mat3 matrix;
+attribute vec2 vert;
+void main() {
+    gl_Position = vec4(matrix * vec3(vert, 1.0), 1.0);
+}"#;
+
+pub const FRAGMENT_SHADER_SOLID: &str = r#"
+#version 100
+precision mediump float;
+uniform vec4 color;
+void main() {
+    gl_FragColor = color;
+}
+"#;
+
+pub const VERTEX_SHADER_PIXEL: &str = r#"
+#version 100
+uniform mat3 matrix;
+attribute vec2 vert;
+varying vec2 v_coords;
+void main() {
+    v_coords = vert;
+    gl_Position = vec4(matrix * vec3(vert, 1.0), 1.0);
+}"#;
+
+pub const FRAGMENT_SHADER_YUV: &str = r#"
+#version 100
+precision mediump float;
+uniform sampler2D tex_y;
+uniform sampler2D tex_u;
+uniform sampler2D tex_v;
+uniform mat3 yuv_matrix;
+uniform vec3 yuv_offset;
+uniform float width;
+varying vec2 v_coords;
+void main() {
+    vec3 yuv;
+#if defined(NV12)
+    yuv = vec3(texture2D(tex_y, v_coords).r, texture2D(tex_u, v_coords).ra);
+#elif defined(YUYV)
+    // every texel holds two pixels: Y0, Cb, Y1, Cr
+    vec4 texel = texture2D(tex_y, v_coords);
+    float y = mod(floor(v_coords.x * width), 2.0) < 0.5 ? texel.r : texel.b;
+    yuv = vec3(y, texel.g, texel.a);
+#else
+    yuv = vec3(texture2D(tex_y, v_coords).r, texture2D(tex_u, v_coords).r, texture2D(tex_v, v_coords).r);
+#endif
+    gl_FragColor = vec4(yuv_matrix * (yuv - yuv_offset), 1.0);
+}
+"#;
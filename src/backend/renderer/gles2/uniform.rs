@@ -0,0 +1,316 @@
+//! Uniforms of custom shaders
+
+use std::{borrow::Cow, ffi::CString};
+
+use super::{ffi, Gles2Error, Gles2Texture};
+
+/// Type of a uniform declared by a custom shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UniformType {
+    /// A single `float`
+    _1f,
+    /// A `vec2`
+    _2f,
+    /// A `vec3`
+    _3f,
+    /// A `vec4`
+    _4f,
+    /// A `mat2`
+    Matrix2x2,
+    /// A `mat3`
+    Matrix3x3,
+    /// A `mat4`
+    Matrix4x4,
+    /// A `sampler2D`
+    Texture,
+}
+
+/// Declaration of an additional uniform of a custom shader
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformName<'a> {
+    /// Name of the uniform in the shader source
+    pub name: Cow<'a, str>,
+    /// Type of the uniform
+    pub type_: UniformType,
+}
+
+impl<'a> UniformName<'a> {
+    /// Declare a new uniform
+    pub fn new(name: impl Into<Cow<'a, str>>, type_: UniformType) -> Self {
+        UniformName {
+            name: name.into(),
+            type_,
+        }
+    }
+}
+
+/// Value of a uniform of a custom shader
+///
+/// Matrices are given in column-major order.
+#[derive(Debug, Clone)]
+pub enum UniformValue {
+    /// A single `float`
+    _1f(f32),
+    /// A `vec2`
+    _2f(f32, f32),
+    /// A `vec3`
+    _3f(f32, f32, f32),
+    /// A `vec4`
+    _4f(f32, f32, f32, f32),
+    /// A `mat2`
+    Matrix2x2([f32; 4]),
+    /// A `mat3`
+    Matrix3x3([f32; 9]),
+    /// A `mat4`
+    Matrix4x4([f32; 16]),
+    /// A texture bound to a `sampler2D`
+    ///
+    /// External textures (e.g. some dmabufs) cannot be used as additional textures.
+    Texture(Gles2Texture),
+}
+
+impl UniformValue {
+    /// Type of this value
+    pub fn type_(&self) -> UniformType {
+        match self {
+            UniformValue::_1f(_) => UniformType::_1f,
+            UniformValue::_2f(_, _) => UniformType::_2f,
+            UniformValue::_3f(_, _, _) => UniformType::_3f,
+            UniformValue::_4f(_, _, _, _) => UniformType::_4f,
+            UniformValue::Matrix2x2(_) => UniformType::Matrix2x2,
+            UniformValue::Matrix3x3(_) => UniformType::Matrix3x3,
+            UniformValue::Matrix4x4(_) => UniformType::Matrix4x4,
+            UniformValue::Texture(_) => UniformType::Texture,
+        }
+    }
+}
+
+impl From<f32> for UniformValue {
+    fn from(value: f32) -> Self {
+        UniformValue::_1f(value)
+    }
+}
+
+impl From<[f32; 2]> for UniformValue {
+    fn from(value: [f32; 2]) -> Self {
+        UniformValue::_2f(value[0], value[1])
+    }
+}
+
+impl From<[f32; 3]> for UniformValue {
+    fn from(value: [f32; 3]) -> Self {
+        UniformValue::_3f(value[0], value[1], value[2])
+    }
+}
+
+impl From<[f32; 4]> for UniformValue {
+    fn from(value: [f32; 4]) -> Self {
+        UniformValue::_4f(value[0], value[1], value[2], value[3])
+    }
+}
+
+impl From<Gles2Texture> for UniformValue {
+    fn from(texture: Gles2Texture) -> Self {
+        UniformValue::Texture(texture)
+    }
+}
+
+/// Value of an additional uniform passed to a custom shader for a draw call
+#[derive(Debug, Clone)]
+pub struct Uniform<'a> {
+    /// Name of the uniform, as declared when compiling the shader
+    pub name: Cow<'a, str>,
+    /// Value of the uniform
+    pub value: UniformValue,
+}
+
+impl<'a> Uniform<'a> {
+    /// Create a new uniform value
+    pub fn new(name: impl Into<Cow<'a, str>>, value: impl Into<UniformValue>) -> Self {
+        Uniform {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
+
+// Location and type of an additional uniform of a compiled program
+#[derive(Debug, Clone)]
+pub(super) struct UniformDesc {
+    pub name: String,
+    pub location: ffi::types::GLint,
+    pub type_: UniformType,
+}
+
+/// Checks the uniforms declared for a custom shader
+///
+/// Names have to be unique and must not contain NUL bytes. Returns the names as C strings.
+pub(super) fn check_uniform_names(names: &[UniformName<'_>]) -> Result<Vec<CString>, Gles2Error> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, uniform)| {
+            if names[..i].iter().any(|other| other.name == uniform.name) {
+                return Err(Gles2Error::InvalidUniform(uniform.name.to_string()));
+            }
+            CString::new(uniform.name.as_bytes())
+                .map_err(|_| Gles2Error::InvalidUniform(uniform.name.to_string()))
+        })
+        .collect()
+}
+
+/// Looks up the declared uniforms in a linked program
+///
+/// `c_names` are the names returned by [`check_uniform_names`].
+pub(super) unsafe fn uniform_descs(
+    gl: &ffi::Gles2,
+    program: ffi::types::GLuint,
+    names: &[UniformName<'_>],
+    c_names: &[CString],
+) -> Vec<UniformDesc> {
+    names
+        .iter()
+        .zip(c_names)
+        .map(|(uniform, c_name)| UniformDesc {
+            name: uniform.name.to_string(),
+            location: gl.GetUniformLocation(program, c_name.as_ptr() as *const ffi::types::GLchar),
+            type_: uniform.type_,
+        })
+        .collect()
+}
+
+// Finds the declaration of every given uniform and checks its value, before anything is bound
+fn resolve_uniforms<'a, 'b>(
+    descs: &'a [UniformDesc],
+    uniforms: &'b [Uniform<'_>],
+) -> Result<Vec<(&'a UniformDesc, &'b UniformValue)>, Gles2Error> {
+    uniforms
+        .iter()
+        .map(|uniform| {
+            let desc = descs
+                .iter()
+                .find(|desc| desc.name == uniform.name)
+                .filter(|desc| desc.type_ == uniform.value.type_())
+                .ok_or_else(|| Gles2Error::InvalidUniform(uniform.name.to_string()))?;
+            if let UniformValue::Texture(ref texture) = uniform.value {
+                if texture.0.is_external {
+                    return Err(Gles2Error::InvalidUniform(uniform.name.to_string()));
+                }
+            }
+            Ok((desc, &uniform.value))
+        })
+        .collect()
+}
+
+/// Sets the given uniform values, binding textures to the units following `first_unit`.
+///
+/// All values are checked first, nothing is bound if one of them is invalid.
+/// Returns the number of bound texture units, that need to be unbound after drawing.
+pub(super) unsafe fn apply_uniforms(
+    gl: &ffi::Gles2,
+    descs: &[UniformDesc],
+    uniforms: &[Uniform<'_>],
+    first_unit: u32,
+) -> Result<u32, Gles2Error> {
+    let resolved = resolve_uniforms(descs, uniforms)?;
+    let mut unit = first_unit;
+    for (desc, value) in resolved {
+        match *value {
+            UniformValue::_1f(x) => gl.Uniform1f(desc.location, x),
+            UniformValue::_2f(x, y) => gl.Uniform2f(desc.location, x, y),
+            UniformValue::_3f(x, y, z) => gl.Uniform3f(desc.location, x, y, z),
+            UniformValue::_4f(x, y, z, w) => gl.Uniform4f(desc.location, x, y, z, w),
+            UniformValue::Matrix2x2(ref m) => gl.UniformMatrix2fv(desc.location, 1, ffi::FALSE, m.as_ptr()),
+            UniformValue::Matrix3x3(ref m) => gl.UniformMatrix3fv(desc.location, 1, ffi::FALSE, m.as_ptr()),
+            UniformValue::Matrix4x4(ref m) => gl.UniformMatrix4fv(desc.location, 1, ffi::FALSE, m.as_ptr()),
+            UniformValue::Texture(ref texture) => {
+                gl.ActiveTexture(ffi::TEXTURE0 + unit);
+                gl.BindTexture(ffi::TEXTURE_2D, texture.0.texture);
+                gl.Uniform1i(desc.location, unit as i32);
+                unit += 1;
+            }
+        }
+    }
+    gl.ActiveTexture(ffi::TEXTURE0);
+    Ok(unit - first_unit)
+}
+
+/// Unbinds the texture units bound by [`apply_uniforms`]
+pub(super) unsafe fn unbind_uniform_textures(gl: &ffi::Gles2, first_unit: u32, count: u32) {
+    for unit in first_unit..first_unit + count {
+        gl.ActiveTexture(ffi::TEXTURE0 + unit);
+        gl.BindTexture(ffi::TEXTURE_2D, 0);
+    }
+    gl.ActiveTexture(ffi::TEXTURE0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(name: &str, type_: UniformType) -> UniformDesc {
+        UniformDesc {
+            name: name.into(),
+            location: 0,
+            type_,
+        }
+    }
+
+    #[test]
+    fn uniform_names() {
+        let names = [
+            UniformName::new("color", UniformType::_4f),
+            UniformName::new("strength", UniformType::_1f),
+        ];
+        let c_names = check_uniform_names(&names).unwrap();
+        assert_eq!(c_names[0].as_bytes(), b"color");
+        assert_eq!(c_names[1].as_bytes(), b"strength");
+
+        assert!(matches!(
+            check_uniform_names(&[UniformName::new("col\0or", UniformType::_4f)]),
+            Err(Gles2Error::InvalidUniform(name)) if name == "col\0or"
+        ));
+        assert!(matches!(
+            check_uniform_names(&[
+                UniformName::new("color", UniformType::_4f),
+                UniformName::new("color", UniformType::_1f),
+            ]),
+            Err(Gles2Error::InvalidUniform(name)) if name == "color"
+        ));
+    }
+
+    #[test]
+    fn uniform_values() {
+        let descs = [
+            desc("color", UniformType::_4f),
+            desc("strength", UniformType::_1f),
+        ];
+
+        let uniforms = [
+            Uniform::new("strength", 0.5),
+            Uniform::new("color", [1.0, 0.0, 0.0, 1.0]),
+        ];
+        let resolved = resolve_uniforms(&descs, &uniforms).unwrap();
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].0.name, "strength");
+        assert_eq!(resolved[1].0.name, "color");
+
+        // a single invalid value rejects all of them
+        let wrong_type = [
+            Uniform::new("color", [1.0, 0.0, 0.0, 1.0]),
+            Uniform::new("strength", [0.5, 0.5]),
+        ];
+        assert!(matches!(
+            resolve_uniforms(&descs, &wrong_type),
+            Err(Gles2Error::InvalidUniform(name)) if name == "strength"
+        ));
+        let undeclared = [
+            Uniform::new("color", [1.0, 0.0, 0.0, 1.0]),
+            Uniform::new("offset", 1.0),
+        ];
+        assert!(matches!(
+            resolve_uniforms(&descs, &undeclared),
+            Err(Gles2Error::InvalidUniform(name)) if name == "offset"
+        ));
+    }
+}
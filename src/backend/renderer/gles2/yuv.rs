@@ -0,0 +1,418 @@
+//! Conversion of YUV shm buffers into RGB textures
+
+use std::ffi::CStr;
+
+use wayland_server::protocol::wl_shm;
+
+use super::{ffi, insert_defines, link_custom_program, shaders, Gles2Error};
+use crate::wayland::shm::BufferData;
+
+/// Color space used to convert YUV buffers into RGB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvColorSpace {
+    /// ITU-R BT.601, commonly used for standard definition video
+    Bt601,
+    /// ITU-R BT.709, commonly used for high definition video
+    Bt709,
+}
+
+/// Range of the values of YUV buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvRange {
+    /// Luma values range from 16 to 235, chroma values from 16 to 240
+    Limited,
+    /// All values range from 0 to 255
+    Full,
+}
+
+/// Shm formats imported through the YUV conversion shader
+pub(super) const YUV_SHM_FORMATS: [wl_shm::Format; 3] =
+    [wl_shm::Format::Nv12, wl_shm::Format::Yuyv, wl_shm::Format::Yuv420];
+
+// Layout of a plane of a shm buffer
+#[derive(Debug, Clone, Copy)]
+struct Plane {
+    offset: usize,
+    stride: i32,
+    // size in texels
+    width: i32,
+    height: i32,
+    format: ffi::types::GLenum,
+    bytes_per_texel: i32,
+}
+
+impl Plane {
+    fn row_size(&self) -> i32 {
+        self.width * self.bytes_per_texel
+    }
+
+    fn end(&self) -> usize {
+        self.offset + (self.stride as usize) * (self.height as usize - 1) + self.row_size() as usize
+    }
+}
+
+// Computes the planes of a YUV buffer, `None` if the layout is invalid
+fn planes(data: &BufferData, len: usize) -> Option<Vec<Plane>> {
+    if data.width <= 0 || data.height <= 0 || data.offset < 0 || data.stride <= 0 {
+        return None;
+    }
+    let offset = data.offset as usize;
+    let (width, height, stride) = (data.width, data.height, data.stride);
+    let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+    let luma = Plane {
+        offset,
+        stride,
+        width,
+        height,
+        format: ffi::LUMINANCE,
+        bytes_per_texel: 1,
+    };
+    let planes = match data.format {
+        wl_shm::Format::Nv12 => vec![
+            luma,
+            Plane {
+                offset: offset + (stride as usize) * (height as usize),
+                stride,
+                width: chroma_width,
+                height: chroma_height,
+                format: ffi::LUMINANCE_ALPHA,
+                bytes_per_texel: 2,
+            },
+        ],
+        wl_shm::Format::Yuv420 => {
+            let u_offset = offset + (stride as usize) * (height as usize);
+            let chroma_stride = stride / 2;
+            vec![
+                luma,
+                Plane {
+                    offset: u_offset,
+                    stride: chroma_stride,
+                    width: chroma_width,
+                    height: chroma_height,
+                    format: ffi::LUMINANCE,
+                    bytes_per_texel: 1,
+                },
+                Plane {
+                    offset: u_offset + (chroma_stride as usize) * (chroma_height as usize),
+                    stride: chroma_stride,
+                    width: chroma_width,
+                    height: chroma_height,
+                    format: ffi::LUMINANCE,
+                    bytes_per_texel: 1,
+                },
+            ]
+        }
+        // a texel holds two pixels sharing their chroma values
+        wl_shm::Format::Yuyv => vec![Plane {
+            offset,
+            stride,
+            width: chroma_width,
+            height,
+            format: ffi::RGBA,
+            bytes_per_texel: 4,
+        }],
+        _ => return None,
+    };
+
+    let valid = planes.iter().all(|plane| {
+        plane.row_size() <= plane.stride && plane.stride % plane.bytes_per_texel == 0 && plane.end() <= len
+    });
+    if valid {
+        Some(planes)
+    } else {
+        None
+    }
+}
+
+// Column-major matrix and offset converting YUV values into RGB: `rgb = matrix * (yuv - offset)`
+fn conversion(color_space: YuvColorSpace, range: YuvRange) -> ([f32; 9], [f32; 3]) {
+    // red and blue coefficients of the luma
+    let (kr, kb) = match color_space {
+        YuvColorSpace::Bt601 => (0.299f32, 0.114f32),
+        YuvColorSpace::Bt709 => (0.2126f32, 0.0722f32),
+    };
+    let kg = 1.0 - kr - kb;
+    let (y_scale, c_scale, y_offset) = match range {
+        YuvRange::Limited => (255.0 / 219.0, 255.0 / 224.0, 16.0 / 255.0),
+        YuvRange::Full => (1.0, 1.0, 0.0),
+    };
+
+    let cr_r = 2.0 * (1.0 - kr);
+    let cb_b = 2.0 * (1.0 - kb);
+    let cb_g = -cb_b * kb / kg;
+    let cr_g = -cr_r * kr / kg;
+    (
+        [
+            // Y column
+            y_scale,
+            y_scale,
+            y_scale,
+            // Cb column
+            0.0,
+            cb_g * c_scale,
+            cb_b * c_scale,
+            // Cr column
+            cr_r * c_scale,
+            cr_g * c_scale,
+            0.0,
+        ],
+        [y_offset, 128.0 / 255.0, 128.0 / 255.0],
+    )
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Gles2YuvProgram {
+    program: ffi::types::GLuint,
+    uniform_matrix: ffi::types::GLint,
+    uniform_yuv_matrix: ffi::types::GLint,
+    uniform_yuv_offset: ffi::types::GLint,
+    uniform_width: ffi::types::GLint,
+    uniform_planes: [ffi::types::GLint; 3],
+    attrib_vert: ffi::types::GLint,
+}
+
+// One program per format, in the order of `YUV_SHM_FORMATS`
+pub(super) type Gles2YuvPrograms = [Gles2YuvProgram; 3];
+
+pub(super) unsafe fn yuv_programs(gl: &ffi::Gles2) -> Result<Gles2YuvPrograms, Gles2Error> {
+    Ok([
+        yuv_program(gl, "NV12")?,
+        yuv_program(gl, "YUYV")?,
+        yuv_program(gl, "YUV420")?,
+    ])
+}
+
+unsafe fn yuv_program(gl: &ffi::Gles2, define: &str) -> Result<Gles2YuvProgram, Gles2Error> {
+    let frag = insert_defines(shaders::FRAGMENT_SHADER_YUV, &[define]);
+    let program = link_custom_program(gl, shaders::VERTEX_SHADER_PIXEL, &frag).map_err(|err| match err {
+        Gles2Error::CustomShaderCompileError(_) => {
+            Gles2Error::ShaderCompileError(shaders::FRAGMENT_SHADER_YUV)
+        }
+        err => err,
+    })?;
+    let location = |name: &[u8]| {
+        let name = CStr::from_bytes_with_nul(name).expect("NULL terminated");
+        gl.GetUniformLocation(program, name.as_ptr() as *const ffi::types::GLchar)
+    };
+    let vert = CStr::from_bytes_with_nul(b"vert\0").expect("NULL terminated");
+
+    Ok(Gles2YuvProgram {
+        program,
+        uniform_matrix: location(b"matrix\0"),
+        uniform_yuv_matrix: location(b"yuv_matrix\0"),
+        uniform_yuv_offset: location(b"yuv_offset\0"),
+        uniform_width: location(b"width\0"),
+        uniform_planes: [location(b"tex_y\0"), location(b"tex_u\0"), location(b"tex_v\0")],
+        attrib_vert: gl.GetAttribLocation(program, vert.as_ptr() as *const ffi::types::GLchar),
+    })
+}
+
+pub(super) unsafe fn delete_yuv_programs(gl: &ffi::Gles2, programs: &Gles2YuvPrograms) {
+    for program in programs {
+        gl.DeleteProgram(program.program);
+    }
+}
+
+static VERTS: [ffi::types::GLfloat; 8] = [
+    1.0, 0.0, // top right
+    0.0, 0.0, // top left
+    1.0, 1.0, // bottom right
+    0.0, 1.0, // bottom left
+];
+
+/// Uploads the planes of a YUV shm buffer and converts them into the given RGBA texture.
+///
+/// The texture has to be allocated with the size of the buffer.
+/// All modified GL state is restored afterwards.
+pub(super) unsafe fn convert_yuv(
+    gl: &ffi::Gles2,
+    programs: &Gles2YuvPrograms,
+    slice: &[u8],
+    data: &BufferData,
+    texture: ffi::types::GLuint,
+    color_space: YuvColorSpace,
+    range: YuvRange,
+) -> Result<(), Gles2Error> {
+    let program_idx = YUV_SHM_FORMATS
+        .iter()
+        .position(|format| *format == data.format)
+        .ok_or(Gles2Error::UnsupportedPixelFormat(data.format))?;
+    let program = &programs[program_idx];
+    let planes = planes(data, slice.len()).ok_or(Gles2Error::InvalidShmBufferLayout(data.format))?;
+
+    // save the state modified by the conversion
+    let mut framebuffer = 0;
+    gl.GetIntegerv(ffi::FRAMEBUFFER_BINDING, &mut framebuffer);
+    let mut viewport = [0; 4];
+    gl.GetIntegerv(ffi::VIEWPORT, viewport.as_mut_ptr());
+    let blend = gl.IsEnabled(ffi::BLEND) == ffi::TRUE;
+    let scissor = gl.IsEnabled(ffi::SCISSOR_TEST) == ffi::TRUE;
+
+    let mut textures = [0; 3];
+    gl.GenTextures(planes.len() as i32, textures.as_mut_ptr());
+    gl.PixelStorei(ffi::UNPACK_ALIGNMENT, 1);
+    for (unit, (plane, plane_texture)) in planes.iter().zip(textures.iter()).enumerate() {
+        gl.ActiveTexture(ffi::TEXTURE1 + unit as u32);
+        gl.BindTexture(ffi::TEXTURE_2D, *plane_texture);
+        // the packed pixels of YUYV must not be interpolated
+        let filter = if data.format == wl_shm::Format::Yuyv {
+            ffi::NEAREST
+        } else {
+            ffi::LINEAR
+        };
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_MIN_FILTER, filter as i32);
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_MAG_FILTER, filter as i32);
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+        gl.PixelStorei(ffi::UNPACK_ROW_LENGTH, plane.stride / plane.bytes_per_texel);
+        gl.TexImage2D(
+            ffi::TEXTURE_2D,
+            0,
+            plane.format as i32,
+            plane.width,
+            plane.height,
+            0,
+            plane.format,
+            ffi::UNSIGNED_BYTE,
+            slice.as_ptr().add(plane.offset) as *const _,
+        );
+    }
+    gl.PixelStorei(ffi::UNPACK_ROW_LENGTH, 0);
+    gl.PixelStorei(ffi::UNPACK_ALIGNMENT, 4);
+
+    let mut fbo = 0;
+    gl.GenFramebuffers(1, &mut fbo);
+    gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+    gl.FramebufferTexture2D(
+        ffi::FRAMEBUFFER,
+        ffi::COLOR_ATTACHMENT0,
+        ffi::TEXTURE_2D,
+        texture,
+        0,
+    );
+    let result = if gl.CheckFramebufferStatus(ffi::FRAMEBUFFER) == ffi::FRAMEBUFFER_COMPLETE {
+        gl.Viewport(0, 0, data.width, data.height);
+        gl.Disable(ffi::BLEND);
+        gl.Disable(ffi::SCISSOR_TEST);
+
+        // maps the unit square onto the whole framebuffer, the first row of the
+        // buffer ends up in the first row of the texture
+        let matrix: [f32; 9] = [2.0, 0.0, 0.0, 0.0, 2.0, 0.0, -1.0, -1.0, 1.0];
+        let (yuv_matrix, yuv_offset) = conversion(color_space, range);
+        gl.UseProgram(program.program);
+        gl.UniformMatrix3fv(program.uniform_matrix, 1, ffi::FALSE, matrix.as_ptr());
+        gl.UniformMatrix3fv(program.uniform_yuv_matrix, 1, ffi::FALSE, yuv_matrix.as_ptr());
+        gl.Uniform3f(
+            program.uniform_yuv_offset,
+            yuv_offset[0],
+            yuv_offset[1],
+            yuv_offset[2],
+        );
+        gl.Uniform1f(program.uniform_width, data.width as f32);
+        for (unit, location) in program.uniform_planes.iter().enumerate() {
+            gl.Uniform1i(*location, unit as i32 + 1);
+        }
+
+        gl.VertexAttribPointer(
+            program.attrib_vert as u32,
+            2,
+            ffi::FLOAT,
+            ffi::FALSE,
+            0,
+            VERTS.as_ptr() as *const _,
+        );
+        gl.EnableVertexAttribArray(program.attrib_vert as u32);
+        gl.DrawArrays(ffi::TRIANGLE_STRIP, 0, 4);
+        gl.DisableVertexAttribArray(program.attrib_vert as u32);
+        Ok(())
+    } else {
+        Err(Gles2Error::FramebufferBindingError)
+    };
+
+    // restore the previous state
+    gl.BindFramebuffer(ffi::FRAMEBUFFER, framebuffer as u32);
+    gl.DeleteFramebuffers(1, &fbo);
+    gl.Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+    if blend {
+        gl.Enable(ffi::BLEND);
+    }
+    if scissor {
+        gl.Enable(ffi::SCISSOR_TEST);
+    }
+    for unit in 0..planes.len() {
+        gl.ActiveTexture(ffi::TEXTURE1 + unit as u32);
+        gl.BindTexture(ffi::TEXTURE_2D, 0);
+    }
+    gl.ActiveTexture(ffi::TEXTURE0);
+    gl.DeleteTextures(planes.len() as i32, textures.as_ptr());
+    gl.UseProgram(0);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(color_space: YuvColorSpace, range: YuvRange, yuv: [f32; 3]) -> [f32; 3] {
+        let (m, offset) = conversion(color_space, range);
+        let v = [yuv[0] - offset[0], yuv[1] - offset[1], yuv[2] - offset[2]];
+        [
+            m[0] * v[0] + m[3] * v[1] + m[6] * v[2],
+            m[1] * v[0] + m[4] * v[1] + m[7] * v[2],
+            m[2] * v[0] + m[5] * v[1] + m[8] * v[2],
+        ]
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        for i in 0..3 {
+            assert!((a[i] - b[i]).abs() < 0.01, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn conversion_matrix() {
+        // black and white
+        assert_close(
+            apply(YuvColorSpace::Bt601, YuvRange::Limited, [16.0 / 255.0, 0.5, 0.5]),
+            [0.0, 0.0, 0.0],
+        );
+        assert_close(
+            apply(YuvColorSpace::Bt709, YuvRange::Limited, [235.0 / 255.0, 0.5, 0.5]),
+            [1.0, 1.0, 1.0],
+        );
+        // pure red in full range BT.601
+        assert_close(
+            apply(YuvColorSpace::Bt601, YuvRange::Full, [0.299, 0.331264, 1.0]),
+            [1.0, 0.0, 0.0],
+        );
+        // pure blue in full range BT.709
+        assert_close(
+            apply(YuvColorSpace::Bt709, YuvRange::Full, [0.0722, 1.0, 0.5 - 0.0458]),
+            [0.0, 0.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn plane_layout() {
+        let data = |format, stride| BufferData {
+            offset: 0,
+            width: 4,
+            height: 3,
+            stride,
+            format,
+        };
+        let nv12 = planes(&data(wl_shm::Format::Nv12, 4), 4 * 3 + 4 * 2).unwrap();
+        assert_eq!(nv12[1].offset, 12);
+        assert_eq!((nv12[1].width, nv12[1].height), (2, 2));
+        assert!(planes(&data(wl_shm::Format::Nv12, 4), 4 * 3 + 4).is_none());
+
+        let yuv420 = planes(&data(wl_shm::Format::Yuv420, 4), 12 + 4 + 4).unwrap();
+        assert_eq!((yuv420[1].offset, yuv420[2].offset), (12, 16));
+
+        let yuyv = planes(&data(wl_shm::Format::Yuyv, 8), 8 * 3).unwrap();
+        assert_eq!((yuyv[0].width, yuyv[0].height), (2, 3));
+        // rows do not fit into the stride
+        assert!(planes(&data(wl_shm::Format::Yuyv, 4), 8 * 3).is_none());
+    }
+}
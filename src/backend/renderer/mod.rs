@@ -6,11 +6,13 @@
 //! Supported rendering apis:
 //!
 //! - Raw OpenGL ES 2
+//! - Software rendering on the cpu
 
 use std::collections::HashSet;
 use std::error::Error;
 
-use crate::utils::{Buffer, Physical, Point, Rectangle, Size};
+use crate::backend::allocator::Fourcc;
+use crate::utils::{Buffer, Coordinate, Physical, Point, Rectangle, Size};
 
 #[cfg(feature = "wayland_frontend")]
 use crate::wayland::compositor::SurfaceData;
@@ -18,8 +20,13 @@ use cgmath::Matrix3;
 #[cfg(feature = "wayland_frontend")]
 use wayland_server::protocol::{wl_buffer, wl_shm};
 
+pub mod damage;
 #[cfg(feature = "renderer_gl")]
 pub mod gles2;
+#[cfg(feature = "renderer_software")]
+pub mod software;
+#[cfg(feature = "wayland_frontend")]
+pub mod utils;
 #[cfg(feature = "wayland_frontend")]
 use crate::backend::allocator::{dmabuf::Dmabuf, Format};
 #[cfg(all(
@@ -105,6 +112,36 @@ impl Transform {
             (width, height)
         }
     }
+
+    /// Transforms a rectangle inside an area of the given size
+    ///
+    /// The rotations are counter-clockwise and flipping happens around the vertical axis
+    /// before rotating, like for `wl_output.transform`. `area` is the size of the untransformed
+    /// area and the returned rectangle is relative to the transformed area.
+    pub fn transform_rect_in<N: Coordinate, Kind>(
+        &self,
+        rect: Rectangle<N, Kind>,
+        area: &Size<N, Kind>,
+    ) -> Rectangle<N, Kind> {
+        let (w, h) = (area.w, area.h);
+        let transform_point = |point: Point<N, Kind>| -> Point<N, Kind> {
+            let (x, y) = (point.x, point.y);
+            match self {
+                Transform::Normal => (x, y),
+                Transform::_90 => (y, w - x),
+                Transform::_180 => (w - x, h - y),
+                Transform::_270 => (h - y, x),
+                Transform::Flipped => (w - x, y),
+                Transform::Flipped90 => (y, x),
+                Transform::Flipped180 => (x, h - y),
+                Transform::Flipped270 => (h - y, w - x),
+            }
+            .into()
+        };
+        let a = transform_point(rect.loc);
+        let b = transform_point(rect.loc + rect.size);
+        Rectangle::from_extemities((a.x.min(b.x), a.y.min(b.y)), (a.x.max(b.x), a.y.max(b.y)))
+    }
 }
 
 #[cfg(feature = "wayland_frontend")]
@@ -139,6 +176,18 @@ pub trait Bind<Target>: Unbind {
     }
 }
 
+/// Functionality to create new rendering targets, that are not backed by any display device
+///
+/// The created buffers can be bound using [`Bind`]. Depending on the target type, the rendering
+/// results can afterwards be used for further rendering (e.g. textures for effects or thumbnails).
+pub trait Offscreen<Target>: Renderer + Bind<Target> {
+    /// Create a new, blank buffer of the given pixel format and size, suitable to be bound
+    /// as a rendering target.
+    ///
+    /// The contents of the new buffer are undefined until it was rendered into.
+    fn create_buffer(&mut self, format: Fourcc, size: Size<i32, Buffer>) -> Result<Target, Self::Error>;
+}
+
 /// Functionality to unbind the current rendering target
 pub trait Unbind: Renderer {
     /// Unbind the current rendering target.
@@ -176,6 +225,9 @@ pub trait Frame {
     /// Render a texture to the current target as a flat 2d-plane at a given
     /// position and applying the given transformation with the given alpha value.
     /// (Meaning `src_transform` should match the orientation of surface being rendered).
+    ///
+    /// Only the parts intersecting the `damage` rectangles are drawn, see [`Frame::render_texture_from_to`].
+    #[allow(clippy::too_many_arguments)]
     fn render_texture_at(
         &mut self,
         texture: &Self::TextureId,
@@ -183,6 +235,7 @@ pub trait Frame {
         texture_scale: i32,
         output_scale: f64,
         src_transform: Transform,
+        damage: &[Rectangle<i32, Physical>],
         alpha: f32,
     ) -> Result<(), Self::Error> {
         self.render_texture_from_to(
@@ -197,6 +250,7 @@ pub trait Frame {
                     .to_physical(output_scale),
             ),
             src_transform,
+            damage,
             alpha,
         )
     }
@@ -204,14 +258,52 @@ pub trait Frame {
     /// Render part of a texture as given by src to the current target into the rectangle described by dst
     /// as a flat 2d-plane after applying the inverse of the given transformation.
     /// (Meaning `src_transform` should match the orientation of surface being rendered).
+    ///
+    /// Only the parts of `dst` intersecting one of the `damage` rectangles are drawn, all other
+    /// pixels of the target are left untouched. The `damage` rectangles are given in the coordinate
+    /// space of the frame, like `dst`, and should not overlap, as overlapping parts of translucent
+    /// textures may otherwise be blended multiple times.
     fn render_texture_from_to(
         &mut self,
         texture: &Self::TextureId,
         src: Rectangle<i32, Buffer>,
         dst: Rectangle<f64, Physical>,
         src_transform: Transform,
+        damage: &[Rectangle<i32, Physical>],
         alpha: f32,
     ) -> Result<(), Self::Error>;
+
+    /// Render part of a texture like [`Frame::render_texture_from_to`], given the parts of it known to be opaque
+    ///
+    /// The `opaque_regions` are given in the coordinate space of the frame. Renderers may draw the damaged
+    /// parts of them without blending, which is cheaper. They are ignored for an `alpha` below `1.0`.
+    /// The default implementation ignores them completely.
+    #[allow(clippy::too_many_arguments)]
+    fn render_texture_from_to_opaque(
+        &mut self,
+        texture: &Self::TextureId,
+        src: Rectangle<i32, Buffer>,
+        dst: Rectangle<f64, Physical>,
+        src_transform: Transform,
+        damage: &[Rectangle<i32, Physical>],
+        opaque_regions: &[Rectangle<i32, Physical>],
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        let _ = opaque_regions;
+        self.render_texture_from_to(texture, src, dst, src_transform, damage, alpha)
+    }
+
+    /// Draw a solid color into the rectangle described by dst
+    ///
+    /// The color is expected to be premultiplied and blended onto the current contents of the target.
+    /// Like with [`Frame::render_texture_from_to`], only the parts of `dst` intersecting one of the
+    /// `damage` rectangles are drawn.
+    fn draw_solid(
+        &mut self,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        color: [f32; 4],
+    ) -> Result<(), Self::Error>;
 }
 
 /// Abstraction of commonly used rendering operations for compositors.
@@ -245,6 +337,58 @@ pub trait Renderer {
         F: FnOnce(&mut Self, &mut Self::Frame) -> R;
 }
 
+/// A texture copied into memory accessible by the cpu, see [`ExportMem`]
+pub trait TextureMapping: Texture {
+    /// Returns if the mapped memory is flipped on the y-axis.
+    ///
+    /// Flipped mappings contain the bottom row of the copied region first.
+    fn flipped(&self) -> bool;
+    /// Pixel format of the mapped memory
+    fn format(&self) -> Fourcc;
+}
+
+/// Trait for renderers supporting reading back their contents into memory.
+///
+/// This can be used to implement screenshots, screencopy into shm buffers or
+/// to compare rendering results in tests.
+pub trait ExportMem: Renderer {
+    /// Texture type representing a copy of renderer contents, that can be mapped.
+    type TextureMapping: TextureMapping;
+
+    /// Copies a region of the currently bound rendering target.
+    ///
+    /// The region is given relative to the top-left corner of the target.
+    /// The copy may be executed asynchronously, the contents are only guaranteed
+    /// to be available once the returned mapping is passed to [`ExportMem::map_texture`].
+    ///
+    /// This function *may* fail, if no target is bound, the given format is not supported
+    /// by the renderer or the region does not lie inside of the target.
+    fn copy_framebuffer(
+        &mut self,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<Self::TextureMapping, Self::Error>;
+
+    /// Copies a region of a given texture.
+    ///
+    /// The format of the copy is chosen by the renderer and can be queried via [`TextureMapping::format`].
+    /// Like [`ExportMem::copy_framebuffer`], the copy may be executed asynchronously and fails if the
+    /// region does not lie inside of the texture.
+    fn copy_texture(
+        &mut self,
+        texture: &Self::TextureId,
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<Self::TextureMapping, Self::Error>;
+
+    /// Returns the contents of a mapping.
+    ///
+    /// The returned slice contains tightly packed rows of the copied region in the format
+    /// of the mapping (see [`TextureMapping::format`] and [`TextureMapping::flipped`]).
+    /// This call may block until a pending copy has finished.
+    fn map_texture<'a>(&mut self, texture_mapping: &'a Self::TextureMapping)
+        -> Result<&'a [u8], Self::Error>;
+}
+
 #[cfg(feature = "wayland_frontend")]
 /// Trait for Renderers supporting importing shm-based buffers.
 pub trait ImportShm: Renderer {
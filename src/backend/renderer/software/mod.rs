@@ -0,0 +1,1017 @@
+//! Implementation of the rendering traits on the cpu
+//!
+//! The [`SoftwareRenderer`] does not require any graphics hardware or driver. It renders into
+//! memory-backed [`SoftwareImage`]s, which makes it suitable for headless setups, virtual machines
+//! without a gpu or for tests requiring reproducible, pixel-exact output.
+//!
+//! All pixels are stored as premultiplied `Argb8888`, matching the memory layout of
+//! [`wl_shm::Format::Argb8888`] buffers (one native-endian `u32` per pixel, `0xAARRGGBB`).
+//!
+//! ## Coordinate spaces
+//!
+//! [`Renderer::render`] takes the size of the bound image and the transformation of the output.
+//! The frame coordinates used by [`Frame::render_texture_from_to`] are the coordinates *before* applying
+//! the transformation, so for 90 or 270 degree rotations the frame is `size` with width and height swapped
+//! (see [`Transform::transform_size`]).
+//!
+//! Pixels are covered by a rendering operation if their center lies inside the destination rectangle.
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{
+    Bind, ExportMem, Frame, Offscreen, Renderer, Texture, TextureFilter, TextureMapping, Transform, Unbind,
+};
+use crate::backend::{allocator::Fourcc, SwapBuffersError};
+use crate::utils::{Buffer, Physical, Rectangle, Size};
+
+#[cfg(feature = "wayland_frontend")]
+use super::{ImportDma, ImportShm};
+#[cfg(feature = "wayland_frontend")]
+use crate::backend::allocator::dmabuf::Dmabuf;
+#[cfg(feature = "wayland_frontend")]
+use wayland_server::protocol::{wl_buffer, wl_shm};
+
+use slog::{o, trace};
+
+// Used to differentiate the cached textures of different renderers in the surface data
+static RENDERER_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Error returned during rendering on the cpu
+#[derive(thiserror::Error, Debug)]
+pub enum SoftwareError {
+    /// No image was bound before starting to render
+    #[error("No rendering target bound")]
+    NoTarget,
+    /// The requested rendering size exceeds the size of the bound image
+    #[error("Rendering size {requested:?} exceeds the target size {target:?}")]
+    InvalidSize {
+        /// Size requested by the rendering call
+        requested: Size<i32, Physical>,
+        /// Size of the bound image
+        target: Size<i32, Physical>,
+    },
+    /// The requested region exceeds the bounds of the image or texture to copy from
+    #[error("Region {0:?} exceeds the bounds of the copied image")]
+    InvalidRegion(Rectangle<i32, Buffer>),
+    /// The requested pixel format is not supported for exporting
+    #[error("Unsupported pixel format for exporting: {0:?}")]
+    UnsupportedExportFormat(Fourcc),
+    /// The requested pixel format is not supported for offscreen rendering
+    #[error("Unsupported pixel format for offscreen rendering: {0:?}")]
+    UnsupportedOffscreenFormat(Fourcc),
+    /// The given buffer has an unsupported pixel format
+    #[error("Unsupported pixel format: {0:?}")]
+    #[cfg(feature = "wayland_frontend")]
+    UnsupportedPixelFormat(wl_shm::Format),
+    /// The given buffer was not accessible
+    #[error("Error accessing the buffer ({0:?})")]
+    #[cfg(feature = "wayland_frontend")]
+    BufferAccessError(crate::wayland::shm::BufferAccessError),
+    /// Dmabufs cannot be read by the software renderer
+    #[error("Dmabuf import is not supported by the software renderer")]
+    #[cfg(feature = "wayland_frontend")]
+    DmabufNotSupported,
+}
+
+impl From<SoftwareError> for SwapBuffersError {
+    fn from(err: SoftwareError) -> SwapBuffersError {
+        SwapBuffersError::TemporaryFailure(Box::new(err))
+    }
+}
+
+#[derive(Debug)]
+struct ImageData {
+    size: Size<i32, Physical>,
+    pixels: Vec<u32>,
+}
+
+/// A memory-backed image, that can be bound as rendering target of a [`SoftwareRenderer`]
+///
+/// This is a cheaply clonable handle, all clones refer to the same pixels.
+#[derive(Debug, Clone)]
+pub struct SoftwareImage(Rc<RefCell<ImageData>>);
+
+impl SoftwareImage {
+    /// Create a new image of the given size, initialized to transparent black
+    pub fn new(size: Size<i32, Physical>) -> SoftwareImage {
+        let size = Size::from((size.w.max(0), size.h.max(0)));
+        SoftwareImage(Rc::new(RefCell::new(ImageData {
+            size,
+            pixels: vec![0; (size.w * size.h) as usize],
+        })))
+    }
+
+    /// Size of this image
+    pub fn size(&self) -> Size<i32, Physical> {
+        self.0.borrow().size
+    }
+
+    /// Access the pixels of this image
+    ///
+    /// Pixels are stored row by row, as premultiplied `0xAARRGGBB` values.
+    ///
+    /// *Note*: Rendering into the image while the returned reference is held panics.
+    pub fn pixels(&self) -> Ref<'_, [u32]> {
+        Ref::map(self.0.borrow(), |data| &data.pixels[..])
+    }
+
+    /// Returns the pixel at the given location, if inside the image
+    pub fn pixel(&self, x: i32, y: i32) -> Option<u32> {
+        let data = self.0.borrow();
+        if x < 0 || y < 0 || x >= data.size.w || y >= data.size.h {
+            return None;
+        }
+        Some(data.pixels[(y * data.size.w + x) as usize])
+    }
+
+    /// Copy the contents of this image into a byte vector
+    ///
+    /// The returned bytes have the memory layout of a `wl_shm::Format::Argb8888` buffer
+    /// with a stride of `4 * width`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0
+            .borrow()
+            .pixels
+            .iter()
+            .flat_map(|pixel| pixel.to_ne_bytes())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct SoftwareTextureInternal {
+    size: Size<i32, Buffer>,
+    pixels: RefCell<Vec<u32>>,
+}
+
+/// A texture stored in memory, created by a [`SoftwareRenderer`]
+#[derive(Debug, Clone)]
+pub struct SoftwareTexture(Rc<SoftwareTextureInternal>);
+
+impl SoftwareTexture {
+    /// Create a texture from premultiplied `0xAARRGGBB` pixels, stored row by row
+    ///
+    /// Returns `None` if the number of pixels does not match the given size.
+    pub fn from_pixels(size: Size<i32, Buffer>, pixels: Vec<u32>) -> Option<SoftwareTexture> {
+        if size.w < 0 || size.h < 0 || pixels.len() != (size.w * size.h) as usize {
+            return None;
+        }
+        Some(SoftwareTexture(Rc::new(SoftwareTextureInternal {
+            size,
+            pixels: RefCell::new(pixels),
+        })))
+    }
+}
+
+impl Texture for SoftwareTexture {
+    fn width(&self) -> u32 {
+        self.0.size.w as u32
+    }
+    fn height(&self) -> u32 {
+        self.0.size.h as u32
+    }
+    fn size(&self) -> Size<i32, Buffer> {
+        self.0.size
+    }
+}
+
+/// Copy of the contents of an image or texture, see [`ExportMem`]
+#[derive(Debug)]
+pub struct SoftwareMapping {
+    size: Size<i32, Buffer>,
+    format: Fourcc,
+    data: Vec<u8>,
+}
+
+impl Texture for SoftwareMapping {
+    fn width(&self) -> u32 {
+        self.size.w as u32
+    }
+    fn height(&self) -> u32 {
+        self.size.h as u32
+    }
+    fn size(&self) -> Size<i32, Buffer> {
+        self.size
+    }
+}
+
+impl TextureMapping for SoftwareMapping {
+    fn flipped(&self) -> bool {
+        false
+    }
+    fn format(&self) -> Fourcc {
+        self.format
+    }
+}
+
+/// Copies a region out of row-by-row stored pixels of the given width and height
+fn copy_region(
+    pixels: &[u32],
+    size: Size<i32, Buffer>,
+    region: Rectangle<i32, Buffer>,
+    format: Fourcc,
+) -> Result<SoftwareMapping, SoftwareError> {
+    let convert: fn(u32) -> u32 = match format {
+        Fourcc::Argb8888 | Fourcc::Xrgb8888 => |pixel| pixel,
+        Fourcc::Abgr8888 | Fourcc::Xbgr8888 => swap_red_blue,
+        format => return Err(SoftwareError::UnsupportedExportFormat(format)),
+    };
+    if region.loc.x < 0
+        || region.loc.y < 0
+        || region.size.w < 0
+        || region.size.h < 0
+        || region.loc.x + region.size.w > size.w
+        || region.loc.y + region.size.h > size.h
+    {
+        return Err(SoftwareError::InvalidRegion(region));
+    }
+
+    let mut data = Vec::with_capacity((region.size.w * region.size.h * 4) as usize);
+    for y in region.loc.y..region.loc.y + region.size.h {
+        let start = (y * size.w + region.loc.x) as usize;
+        for pixel in &pixels[start..start + region.size.w as usize] {
+            data.extend_from_slice(&convert(*pixel).to_le_bytes());
+        }
+    }
+    Ok(SoftwareMapping {
+        size: region.size,
+        format,
+        data,
+    })
+}
+
+#[cfg(feature = "wayland_frontend")]
+#[derive(Debug)]
+struct TextureCache {
+    renderer_id: usize,
+    texture: Rc<SoftwareTextureInternal>,
+}
+
+/// A renderer drawing on the cpu into [`SoftwareImage`]s
+#[derive(Debug)]
+pub struct SoftwareRenderer {
+    // This field is only accessed if the wayland_frontend feature is active
+    #[allow(dead_code)]
+    id: usize,
+    target: Option<SoftwareImage>,
+    upscale_filter: TextureFilter,
+    downscale_filter: TextureFilter,
+    logger: ::slog::Logger,
+}
+
+/// Handle to the currently rendered frame during [`SoftwareRenderer::render`](Renderer::render)
+#[derive(Debug)]
+pub struct SoftwareFrame {
+    target: SoftwareImage,
+    size: Size<i32, Physical>,
+    transform: Transform,
+    upscale_filter: TextureFilter,
+    downscale_filter: TextureFilter,
+}
+
+impl SoftwareRenderer {
+    /// Creates a new software renderer
+    ///
+    /// No target is bound initially, use [`Bind::bind`] with a [`SoftwareImage`] before rendering.
+    pub fn new<L>(logger: L) -> SoftwareRenderer
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "renderer_software"));
+        SoftwareRenderer {
+            id: RENDERER_COUNTER.fetch_add(1, Ordering::SeqCst),
+            target: None,
+            upscale_filter: TextureFilter::Linear,
+            downscale_filter: TextureFilter::Linear,
+            logger: log,
+        }
+    }
+
+    /// The currently bound image, if any
+    pub fn target(&self) -> Option<&SoftwareImage> {
+        self.target.as_ref()
+    }
+}
+
+impl Bind<SoftwareImage> for SoftwareRenderer {
+    fn bind(&mut self, target: SoftwareImage) -> Result<(), SoftwareError> {
+        self.target = Some(target);
+        Ok(())
+    }
+}
+
+impl Offscreen<SoftwareImage> for SoftwareRenderer {
+    fn create_buffer(
+        &mut self,
+        format: Fourcc,
+        size: Size<i32, Buffer>,
+    ) -> Result<SoftwareImage, SoftwareError> {
+        match format {
+            Fourcc::Argb8888 | Fourcc::Xrgb8888 => Ok(SoftwareImage::new(Size::from((size.w, size.h)))),
+            format => Err(SoftwareError::UnsupportedOffscreenFormat(format)),
+        }
+    }
+}
+
+impl Unbind for SoftwareRenderer {
+    fn unbind(&mut self) -> Result<(), SoftwareError> {
+        self.target = None;
+        Ok(())
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    type Error = SoftwareError;
+    type TextureId = SoftwareTexture;
+    type Frame = SoftwareFrame;
+
+    fn downscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
+        self.downscale_filter = filter;
+        Ok(())
+    }
+    fn upscale_filter(&mut self, filter: TextureFilter) -> Result<(), Self::Error> {
+        self.upscale_filter = filter;
+        Ok(())
+    }
+
+    fn render<F, R>(
+        &mut self,
+        size: Size<i32, Physical>,
+        transform: Transform,
+        rendering: F,
+    ) -> Result<R, Self::Error>
+    where
+        F: FnOnce(&mut Self, &mut Self::Frame) -> R,
+    {
+        let target = self.target.clone().ok_or(SoftwareError::NoTarget)?;
+        let target_size = target.size();
+        if size.w > target_size.w || size.h > target_size.h {
+            return Err(SoftwareError::InvalidSize {
+                requested: size,
+                target: target_size,
+            });
+        }
+
+        trace!(self.logger, "Rendering {:?} frame into {:?}", size, target_size);
+        let mut frame = SoftwareFrame {
+            target,
+            size,
+            transform,
+            upscale_filter: self.upscale_filter,
+            downscale_filter: self.downscale_filter,
+        };
+        Ok(rendering(self, &mut frame))
+    }
+}
+
+impl ExportMem for SoftwareRenderer {
+    type TextureMapping = SoftwareMapping;
+
+    fn copy_framebuffer(
+        &mut self,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<SoftwareMapping, SoftwareError> {
+        let target = self.target.as_ref().ok_or(SoftwareError::NoTarget)?;
+        let data = target.0.borrow();
+        let size = Size::from((data.size.w, data.size.h));
+        copy_region(&data.pixels, size, region, format)
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: &SoftwareTexture,
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<SoftwareMapping, SoftwareError> {
+        copy_region(
+            &texture.0.pixels.borrow(),
+            texture.0.size,
+            region,
+            Fourcc::Argb8888,
+        )
+    }
+
+    fn map_texture<'a>(&mut self, texture_mapping: &'a SoftwareMapping) -> Result<&'a [u8], SoftwareError> {
+        Ok(&texture_mapping.data)
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl ImportShm for SoftwareRenderer {
+    fn import_shm_buffer(
+        &mut self,
+        buffer: &wl_buffer::WlBuffer,
+        surface: Option<&crate::wayland::compositor::SurfaceData>,
+        damage: &[Rectangle<i32, Buffer>],
+    ) -> Result<SoftwareTexture, SoftwareError> {
+        use crate::wayland::shm::with_buffer_contents;
+
+        with_buffer_contents(buffer, |slice, data| {
+            let offset = data.offset as usize;
+            let width = data.width;
+            let height = data.height;
+            let stride = data.stride as usize;
+
+            let convert: fn(u32) -> u32 = match data.format {
+                wl_shm::Format::Argb8888 => |p| p,
+                wl_shm::Format::Xrgb8888 => |p| p | 0xff00_0000,
+                wl_shm::Format::Abgr8888 => swap_red_blue,
+                wl_shm::Format::Xbgr8888 => |p| swap_red_blue(p) | 0xff00_0000,
+                format => return Err(SoftwareError::UnsupportedPixelFormat(format)),
+            };
+
+            // ensure consistency, the SHM handler of smithay should ensure this
+            assert!(
+                offset + (height as usize).saturating_sub(1) * stride + width as usize * 4 <= slice.len()
+            );
+
+            let size = Size::<i32, Buffer>::from((width, height));
+            let cached = surface.and_then(|surface| {
+                surface
+                    .data_map
+                    .get::<RefCell<Option<TextureCache>>>()
+                    .and_then(|cache| {
+                        cache
+                            .borrow()
+                            .as_ref()
+                            .filter(|cache| cache.renderer_id == self.id && cache.texture.size == size)
+                            .map(|cache| cache.texture.clone())
+                    })
+            });
+
+            let (texture, regions) = match cached {
+                Some(texture) if !damage.is_empty() => (texture, damage.to_vec()),
+                Some(texture) => (texture, vec![Rectangle::from_loc_and_size((0, 0), size)]),
+                None => {
+                    let texture = Rc::new(SoftwareTextureInternal {
+                        size,
+                        pixels: RefCell::new(vec![0; (width * height) as usize]),
+                    });
+                    if let Some(surface) = surface {
+                        surface
+                            .data_map
+                            .insert_if_missing(|| RefCell::new(None::<TextureCache>));
+                        *surface
+                            .data_map
+                            .get::<RefCell<Option<TextureCache>>>()
+                            .unwrap()
+                            .borrow_mut() = Some(TextureCache {
+                            renderer_id: self.id,
+                            texture: texture.clone(),
+                        });
+                    }
+                    // new texture, upload in full
+                    (texture, vec![Rectangle::from_loc_and_size((0, 0), size)])
+                }
+            };
+
+            trace!(self.logger, "Uploading shm texture for {:?}", buffer);
+            let full = Rectangle::from_loc_and_size((0, 0), size);
+            let mut pixels = texture.pixels.borrow_mut();
+            for region in regions.iter().filter_map(|region| region.intersection(full)) {
+                for y in region.loc.y..region.loc.y + region.size.h {
+                    let row = offset + y as usize * stride;
+                    for x in region.loc.x..region.loc.x + region.size.w {
+                        let idx = row + x as usize * 4;
+                        let pixel =
+                            u32::from_le_bytes([slice[idx], slice[idx + 1], slice[idx + 2], slice[idx + 3]]);
+                        pixels[(y * width + x) as usize] = convert(pixel);
+                    }
+                }
+            }
+            drop(pixels);
+
+            Ok(SoftwareTexture(texture))
+        })
+        .map_err(SoftwareError::BufferAccessError)?
+    }
+
+    fn shm_formats(&self) -> &[wl_shm::Format] {
+        &[
+            wl_shm::Format::Abgr8888,
+            wl_shm::Format::Xbgr8888,
+            wl_shm::Format::Argb8888,
+            wl_shm::Format::Xrgb8888,
+        ]
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl ImportDma for SoftwareRenderer {
+    // no dmabuf formats are advertised, so clients should never create any dmabuf for us
+    fn import_dmabuf(&mut self, _dmabuf: &Dmabuf) -> Result<SoftwareTexture, SoftwareError> {
+        Err(SoftwareError::DmabufNotSupported)
+    }
+}
+
+fn swap_red_blue(pixel: u32) -> u32 {
+    (pixel & 0xff00_ff00) | ((pixel & 0x00ff_0000) >> 16) | ((pixel & 0x0000_00ff) << 16)
+}
+
+/// Maps normalized coordinates of a plane onto the plane transformed by `transform`
+fn transform_normalized(transform: Transform, (u, v): (f64, f64)) -> (f64, f64) {
+    match transform {
+        Transform::Normal => (u, v),
+        Transform::_90 => (v, 1.0 - u),
+        Transform::_180 => (1.0 - u, 1.0 - v),
+        Transform::_270 => (1.0 - v, u),
+        Transform::Flipped => (1.0 - u, v),
+        Transform::Flipped90 => (v, u),
+        Transform::Flipped180 => (u, 1.0 - v),
+        Transform::Flipped270 => (1.0 - v, 1.0 - u),
+    }
+}
+
+/// `a * b / 255`, correctly rounded
+fn mul_div_255(a: u32, b: u32) -> u32 {
+    let t = a * b + 128;
+    (t + (t >> 8)) >> 8
+}
+
+/// Blends a premultiplied source pixel, scaled by `alpha` (0-255), over a destination pixel
+fn blend(src: u32, dst: u32, alpha: u32) -> u32 {
+    let src_a = mul_div_255(src >> 24, alpha);
+    if src_a == 255 {
+        return src;
+    }
+    let inv = 255 - src_a;
+    let mut out = 0;
+    for shift in [0, 8, 16, 24] {
+        let s = mul_div_255((src >> shift) & 0xff, alpha);
+        let d = mul_div_255((dst >> shift) & 0xff, inv);
+        out |= (s + d).min(255) << shift;
+    }
+    out
+}
+
+/// Interpolates two pixels channel-wise, `weight` (0-256) being the weight of `b`
+fn lerp(a: u32, b: u32, weight: u32) -> u32 {
+    let mut out = 0;
+    for shift in [0, 8, 16, 24] {
+        let ca = (a >> shift) & 0xff;
+        let cb = (b >> shift) & 0xff;
+        out |= ((ca * (256 - weight) + cb * weight + 128) >> 8) << shift;
+    }
+    out
+}
+
+/// Converts a `[r, g, b, a]` color into a `0xAARRGGBB` pixel
+fn pack_color(color: [f32; 4]) -> u32 {
+    let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u32;
+    channel(color[3]) << 24 | channel(color[0]) << 16 | channel(color[1]) << 8 | channel(color[2])
+}
+
+impl SoftwareFrame {
+    /// The parts of `rect` intersecting one of the damage rectangles, limited to the frame
+    fn clip(
+        &self,
+        rect: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+    ) -> Vec<Rectangle<i32, Physical>> {
+        let frame = Rectangle::from_loc_and_size((0, 0), self.frame_size());
+        damage
+            .iter()
+            .filter_map(|damage| damage.intersection(rect))
+            .filter_map(|clipped| clipped.intersection(frame))
+            .filter(|clipped| clipped.size.w > 0 && clipped.size.h > 0)
+            .collect()
+    }
+
+    /// Size of the frame coordinate space, before applying the output transformation
+    fn frame_size(&self) -> Size<i32, Physical> {
+        let (w, h) = self
+            .transform
+            .transform_size(self.size.w as u32, self.size.h as u32);
+        (w as i32, h as i32).into()
+    }
+
+    /// Index of the image pixel displaying the given frame pixel
+    fn target_index(&self, x: i32, y: i32, frame_size: Size<i32, Physical>, image_width: i32) -> usize {
+        let (u, v) = transform_normalized(
+            self.transform,
+            (
+                (x as f64 + 0.5) / frame_size.w as f64,
+                (y as f64 + 0.5) / frame_size.h as f64,
+            ),
+        );
+        let tx = ((u * self.size.w as f64) as i32).clamp(0, self.size.w - 1);
+        let ty = ((v * self.size.h as f64) as i32).clamp(0, self.size.h - 1);
+        (ty * image_width + tx) as usize
+    }
+}
+
+impl Frame for SoftwareFrame {
+    type Error = SoftwareError;
+    type TextureId = SoftwareTexture;
+
+    fn clear(&mut self, color: [f32; 4]) -> Result<(), Self::Error> {
+        let pixel = pack_color(color);
+
+        let mut data = self.target.0.borrow_mut();
+        let width = data.size.w;
+        for y in 0..self.size.h {
+            let row = (y * width) as usize;
+            data.pixels[row..row + self.size.w as usize].fill(pixel);
+        }
+        Ok(())
+    }
+
+    fn render_texture_from_to(
+        &mut self,
+        texture: &Self::TextureId,
+        src: Rectangle<i32, Buffer>,
+        dst: Rectangle<f64, Physical>,
+        src_transform: Transform,
+        damage: &[Rectangle<i32, Physical>],
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        let tex_size = texture.size();
+        if src.size.w <= 0 || src.size.h <= 0 || dst.size.w <= 0.0 || dst.size.h <= 0.0 {
+            return Ok(());
+        }
+        let clipped = match src.intersection(Rectangle::from_loc_and_size((0, 0), tex_size)) {
+            Some(clipped) if clipped.size.w > 0 && clipped.size.h > 0 => clipped,
+            _ => return Ok(()),
+        };
+        // shrink dst by the part of src outside of the texture
+        let dst = if clipped != src {
+            let corner = |x: i32, y: i32| {
+                transform_normalized(
+                    src_transform.invert(),
+                    (
+                        (x - src.loc.x) as f64 / src.size.w as f64,
+                        (y - src.loc.y) as f64 / src.size.h as f64,
+                    ),
+                )
+            };
+            let (u0, v0) = corner(clipped.loc.x, clipped.loc.y);
+            let (u1, v1) = corner(clipped.loc.x + clipped.size.w, clipped.loc.y + clipped.size.h);
+            Rectangle::from_loc_and_size(
+                (
+                    dst.loc.x + u0.min(u1) * dst.size.w,
+                    dst.loc.y + v0.min(v1) * dst.size.h,
+                ),
+                ((u1 - u0).abs() * dst.size.w, (v1 - v0).abs() * dst.size.h),
+            )
+        } else {
+            dst
+        };
+        let src = clipped;
+        let alpha = (alpha.clamp(0.0, 1.0) * 255.0).round() as u32;
+        if alpha == 0 {
+            return Ok(());
+        }
+
+        // the size of the source, as displayed
+        let (src_w, src_h) = src_transform.transform_size(src.size.w as u32, src.size.h as u32);
+        let filter = if dst.size.w * dst.size.h > (src_w * src_h) as f64 {
+            self.upscale_filter
+        } else {
+            self.downscale_filter
+        };
+
+        // only pixels whose center lies inside dst are covered
+        let covered = Rectangle::from_extemities(
+            ((dst.loc.x - 0.5).ceil() as i32, (dst.loc.y - 0.5).ceil() as i32),
+            (
+                (dst.loc.x + dst.size.w - 0.5).ceil() as i32,
+                (dst.loc.y + dst.size.h - 0.5).ceil() as i32,
+            ),
+        );
+        let frame_size = self.frame_size();
+
+        let texels = texture.0.pixels.borrow();
+        let texel = |x: i32, y: i32| {
+            let x = x.clamp(src.loc.x, src.loc.x + src.size.w - 1);
+            let y = y.clamp(src.loc.y, src.loc.y + src.size.h - 1);
+            texels[(y * tex_size.w + x) as usize]
+        };
+
+        let mut data = self.target.0.borrow_mut();
+        let image_width = data.size.w;
+        for rect in self.clip(covered, damage) {
+            for y in rect.loc.y..rect.loc.y + rect.size.h {
+                for x in rect.loc.x..rect.loc.x + rect.size.w {
+                    let (u, v) = transform_normalized(
+                        src_transform,
+                        (
+                            (x as f64 + 0.5 - dst.loc.x) / dst.size.w,
+                            (y as f64 + 0.5 - dst.loc.y) / dst.size.h,
+                        ),
+                    );
+                    let sx = src.loc.x as f64 + u * src.size.w as f64;
+                    let sy = src.loc.y as f64 + v * src.size.h as f64;
+
+                    let color = match filter {
+                        TextureFilter::Nearest => texel(sx.floor() as i32, sy.floor() as i32),
+                        TextureFilter::Linear => {
+                            let (sx, sy) = (sx - 0.5, sy - 0.5);
+                            let (x0, y0) = (sx.floor(), sy.floor());
+                            let wx = ((sx - x0) * 256.0).round() as u32;
+                            let wy = ((sy - y0) * 256.0).round() as u32;
+                            let (x0, y0) = (x0 as i32, y0 as i32);
+                            let top = lerp(texel(x0, y0), texel(x0 + 1, y0), wx);
+                            let bottom = lerp(texel(x0, y0 + 1), texel(x0 + 1, y0 + 1), wx);
+                            lerp(top, bottom, wy)
+                        }
+                    };
+
+                    let idx = self.target_index(x, y, frame_size, image_width);
+                    data.pixels[idx] = blend(color, data.pixels[idx], alpha);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_solid(
+        &mut self,
+        dst: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        color: [f32; 4],
+    ) -> Result<(), Self::Error> {
+        let pixel = pack_color(color);
+        let frame_size = self.frame_size();
+        let mut data = self.target.0.borrow_mut();
+        let image_width = data.size.w;
+        for rect in self.clip(dst, damage) {
+            for y in rect.loc.y..rect.loc.y + rect.size.h {
+                for x in rect.loc.x..rect.loc.x + rect.size.w {
+                    let idx = self.target_index(x, y, frame_size, image_width);
+                    data.pixels[idx] = blend(pixel, data.pixels[idx], 255);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: u32 = 0xffff_0000;
+    const GREEN: u32 = 0xff00_ff00;
+    const BLUE: u32 = 0xff00_00ff;
+    const WHITE: u32 = 0xffff_ffff;
+
+    fn render<F: FnOnce(&mut SoftwareFrame)>(size: (i32, i32), transform: Transform, f: F) -> SoftwareImage {
+        let image = SoftwareImage::new(size.into());
+        let mut renderer = SoftwareRenderer::new(None);
+        renderer.bind(image.clone()).unwrap();
+        renderer.upscale_filter(TextureFilter::Nearest).unwrap();
+        renderer.downscale_filter(TextureFilter::Nearest).unwrap();
+        renderer
+            .render(size.into(), transform, |_, frame| f(frame))
+            .unwrap();
+        image
+    }
+
+    fn full() -> [Rectangle<i32, Physical>; 1] {
+        [Rectangle::from_loc_and_size((0, 0), (100, 100))]
+    }
+
+    // a 2x2 texture: red, green / blue, white
+    fn quad() -> SoftwareTexture {
+        SoftwareTexture::from_pixels((2, 2).into(), vec![RED, GREEN, BLUE, WHITE]).unwrap()
+    }
+
+    #[test]
+    fn render_without_target() {
+        let mut renderer = SoftwareRenderer::new(None);
+        assert!(matches!(
+            renderer.render((1, 1).into(), Transform::Normal, |_, _| ()),
+            Err(SoftwareError::NoTarget)
+        ));
+    }
+
+    #[test]
+    fn clear_and_place() {
+        let image = render((4, 4), Transform::Normal, |frame| {
+            frame.clear([0.0, 0.0, 0.0, 1.0]).unwrap();
+            frame
+                .render_texture_at(
+                    &quad(),
+                    (1.0, 2.0).into(),
+                    1,
+                    1.0,
+                    Transform::Normal,
+                    &full(),
+                    1.0,
+                )
+                .unwrap();
+        });
+        assert_eq!(image.pixel(0, 0), Some(0xff00_0000));
+        assert_eq!(image.pixel(1, 2), Some(RED));
+        assert_eq!(image.pixel(2, 2), Some(GREEN));
+        assert_eq!(image.pixel(1, 3), Some(BLUE));
+        assert_eq!(image.pixel(2, 3), Some(WHITE));
+        assert_eq!(image.pixel(3, 3), Some(0xff00_0000));
+    }
+
+    #[test]
+    fn src_clipping_and_scaling() {
+        let image = render((4, 4), Transform::Normal, |frame| {
+            // only the green texel, stretched over the whole frame
+            frame
+                .render_texture_from_to(
+                    &quad(),
+                    Rectangle::from_loc_and_size((1, 0), (1, 1)),
+                    Rectangle::from_loc_and_size((0.0, 0.0), (4.0, 4.0)),
+                    Transform::Normal,
+                    &full(),
+                    1.0,
+                )
+                .unwrap();
+        });
+        assert!(image.pixels().iter().all(|p| *p == GREEN));
+    }
+
+    #[test]
+    fn src_outside_of_texture() {
+        // the left half of src lies outside of the texture, so only the right half of dst is drawn
+        let image = render((4, 2), Transform::Normal, |frame| {
+            frame.clear([0.0, 0.0, 0.0, 1.0]).unwrap();
+            frame
+                .render_texture_from_to(
+                    &quad(),
+                    Rectangle::from_loc_and_size((-2, 0), (4, 2)),
+                    Rectangle::from_loc_and_size((0.0, 0.0), (4.0, 2.0)),
+                    Transform::Normal,
+                    &full(),
+                    1.0,
+                )
+                .unwrap();
+        });
+        assert_eq!(
+            &*image.pixels(),
+            &[
+                0xff00_0000,
+                0xff00_0000,
+                RED,
+                GREEN,
+                0xff00_0000,
+                0xff00_0000,
+                BLUE,
+                WHITE
+            ]
+        );
+
+        // the clipped part follows the transform: once rotated, the missing left half of the
+        // buffer is the top half of the displayed source
+        let image = render((2, 4), Transform::Normal, |frame| {
+            frame.clear([0.0, 0.0, 0.0, 1.0]).unwrap();
+            frame
+                .render_texture_from_to(
+                    &quad(),
+                    Rectangle::from_loc_and_size((-2, 0), (4, 2)),
+                    Rectangle::from_loc_and_size((0.0, 0.0), (2.0, 4.0)),
+                    Transform::_90,
+                    &full(),
+                    1.0,
+                )
+                .unwrap();
+        });
+        assert_eq!(
+            &*image.pixels(),
+            &[
+                0xff00_0000,
+                0xff00_0000,
+                0xff00_0000,
+                0xff00_0000,
+                BLUE,
+                RED,
+                WHITE,
+                GREEN
+            ]
+        );
+    }
+
+    #[test]
+    fn transforms() {
+        let image = render((2, 2), Transform::Normal, |frame| {
+            frame
+                .render_texture_at(&quad(), (0.0, 0.0).into(), 1, 1.0, Transform::_90, &full(), 1.0)
+                .unwrap();
+        });
+        // the buffer is rotated by 90 degrees counter-clockwise, displaying it rotates it back
+        assert_eq!(&*image.pixels(), &[BLUE, RED, WHITE, GREEN]);
+
+        let image = render((2, 2), Transform::Flipped, |frame| {
+            frame
+                .render_texture_at(
+                    &quad(),
+                    (0.0, 0.0).into(),
+                    1,
+                    1.0,
+                    Transform::Normal,
+                    &full(),
+                    1.0,
+                )
+                .unwrap();
+        });
+        assert_eq!(&*image.pixels(), &[GREEN, RED, WHITE, BLUE]);
+    }
+
+    #[test]
+    fn alpha_blending() {
+        let image = render((1, 1), Transform::Normal, |frame| {
+            frame.clear([0.0, 0.0, 1.0, 1.0]).unwrap();
+            let red = SoftwareTexture::from_pixels((1, 1).into(), vec![RED]).unwrap();
+            frame
+                .render_texture_at(&red, (0.0, 0.0).into(), 1, 1.0, Transform::Normal, &full(), 0.5)
+                .unwrap();
+        });
+        assert_eq!(image.pixel(0, 0), Some(0xff80_007f));
+    }
+
+    #[test]
+    fn export() {
+        let image = render((2, 2), Transform::Normal, |frame| {
+            frame
+                .render_texture_from_to(
+                    &quad(),
+                    Rectangle::from_loc_and_size((0, 0), (2, 2)),
+                    Rectangle::from_loc_and_size((0.0, 0.0), (2.0, 2.0)),
+                    Transform::Normal,
+                    &full(),
+                    1.0,
+                )
+                .unwrap();
+        });
+        let mut renderer = SoftwareRenderer::new(None);
+        renderer.bind(image).unwrap();
+
+        let mapping = renderer
+            .copy_framebuffer(Rectangle::from_loc_and_size((1, 0), (1, 2)), Fourcc::Argb8888)
+            .unwrap();
+        assert_eq!(mapping.size(), (1, 2).into());
+        assert!(!mapping.flipped());
+        let bytes = renderer.map_texture(&mapping).unwrap();
+        assert_eq!(bytes, [0x00, 0xff, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff]);
+
+        let mapping = renderer
+            .copy_framebuffer(Rectangle::from_loc_and_size((0, 0), (1, 1)), Fourcc::Abgr8888)
+            .unwrap();
+        assert_eq!(renderer.map_texture(&mapping).unwrap(), [0xff, 0x00, 0x00, 0xff]);
+
+        let mapping = renderer
+            .copy_texture(&quad(), Rectangle::from_loc_and_size((0, 1), (2, 1)))
+            .unwrap();
+        assert_eq!(mapping.format(), Fourcc::Argb8888);
+        assert_eq!(
+            renderer.map_texture(&mapping).unwrap(),
+            [0xff, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+
+        assert!(matches!(
+            renderer.copy_framebuffer(Rectangle::from_loc_and_size((1, 1), (2, 2)), Fourcc::Argb8888),
+            Err(SoftwareError::InvalidRegion(_))
+        ));
+        assert!(matches!(
+            renderer.copy_framebuffer(Rectangle::from_loc_and_size((0, 0), (1, 1)), Fourcc::Rgb565),
+            Err(SoftwareError::UnsupportedExportFormat(_))
+        ));
+    }
+
+    #[test]
+    fn offscreen() {
+        let mut renderer = SoftwareRenderer::new(None);
+        let image = renderer.create_buffer(Fourcc::Argb8888, (3, 2).into()).unwrap();
+        assert_eq!(image.size(), (3, 2).into());
+        renderer.bind(image.clone()).unwrap();
+        renderer
+            .render((3, 2).into(), Transform::Normal, |_, frame| {
+                frame.clear([1.0, 0.0, 0.0, 1.0]).unwrap()
+            })
+            .unwrap();
+        assert!(image.pixels().iter().all(|pixel| *pixel == RED));
+
+        assert!(matches!(
+            renderer.create_buffer(Fourcc::Nv12, (3, 2).into()),
+            Err(SoftwareError::UnsupportedOffscreenFormat(_))
+        ));
+    }
+
+    #[test]
+    fn solid_and_damage() {
+        let image = render((4, 1), Transform::Normal, |frame| {
+            frame.clear([0.0, 0.0, 0.0, 1.0]).unwrap();
+            let damage = [
+                Rectangle::from_loc_and_size((0, 0), (1, 1)),
+                Rectangle::from_loc_and_size((2, 0), (2, 1)),
+            ];
+            frame
+                .draw_solid(
+                    Rectangle::from_loc_and_size((0, 0), (3, 1)),
+                    &damage,
+                    [0.0, 1.0, 0.0, 1.0],
+                )
+                .unwrap();
+        });
+        assert_eq!(&*image.pixels(), &[GREEN, 0xff00_0000, GREEN, 0xff00_0000]);
+    }
+}
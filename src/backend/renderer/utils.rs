@@ -0,0 +1,383 @@
+//! Helpers to import and draw the surface trees of wayland clients
+//!
+//! Call [`on_commit_buffer_handler`] in the commit callback of your compositor to keep track of the
+//! buffers attached to the surfaces. Their textures are imported lazily by [`import_surface_tree`] or
+//! [`draw_surface_tree`] and cached in the [`SurfaceData`](crate::wayland::compositor::SurfaceData)
+//! of every surface until a new buffer is committed.
+//!
+//! Once a buffer is no longer used, it is released. If the client uses explicit synchronization
+//! through [`drm_syncobj`](crate::wayland::drm_syncobj), the release point of the commit is signaled
+//! at the same time.
+
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+};
+
+use slog::warn;
+use wayland_server::protocol::{wl_buffer::WlBuffer, wl_surface::WlSurface};
+
+#[cfg(feature = "backend_drm")]
+use crate::wayland::drm_syncobj::{take_release_point, DrmSyncReleasePoint};
+use crate::{
+    backend::renderer::{buffer_dimensions, Frame, ImportAll, Renderer, Transform},
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Size},
+    wayland::compositor::{
+        is_sync_subsurface, with_states, with_surface_tree_upward, BufferAssignment, Damage,
+        SubsurfaceCachedState, SurfaceAttributes, SurfaceData, TraversalAction,
+    },
+};
+
+/// Rendering related state of a surface, as tracked by [`on_commit_buffer_handler`]
+///
+/// It is stored in the `data_map` of the surface as a `RefCell<RendererSurfaceState>`.
+#[derive(Debug)]
+pub struct RendererSurfaceState {
+    buffer_dimensions: Option<Size<i32, Buffer>>,
+    buffer_scale: i32,
+    buffer_transform: Transform,
+    buffer: Option<SurfaceBuffer>,
+    // damage accumulated since the textures were last imported, in buffer coordinates
+    damage: Vec<Rectangle<i32, Buffer>>,
+    // one texture per `Renderer::TextureId` type
+    textures: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl Default for RendererSurfaceState {
+    fn default() -> Self {
+        RendererSurfaceState {
+            buffer_dimensions: None,
+            buffer_scale: 1,
+            buffer_transform: Transform::Normal,
+            buffer: None,
+            damage: Vec::new(),
+            textures: HashMap::new(),
+        }
+    }
+}
+
+// A committed buffer along with its explicit synchronization state
+#[derive(Debug)]
+struct SurfaceBuffer {
+    buffer: WlBuffer,
+    // signaled once dropped
+    #[cfg(feature = "backend_drm")]
+    _release_point: Option<DrmSyncReleasePoint>,
+}
+
+impl SurfaceBuffer {
+    fn new(buffer: WlBuffer, states: &SurfaceData) -> SurfaceBuffer {
+        #[cfg(not(feature = "backend_drm"))]
+        let _ = states;
+        SurfaceBuffer {
+            buffer,
+            #[cfg(feature = "backend_drm")]
+            _release_point: take_release_point(states),
+        }
+    }
+
+    /// The buffer is no longer used by this commit
+    ///
+    /// The `wl_buffer` itself is only released if `release_buffer` is set, as the same buffer may be
+    /// committed again. The release point of the commit is signaled in any case.
+    fn release(self, release_buffer: bool) {
+        if release_buffer {
+            self.buffer.release();
+        }
+    }
+}
+
+impl RendererSurfaceState {
+    fn update_buffer(&mut self, states: &SurfaceData) {
+        let mut attrs = states.cached_state.current::<SurfaceAttributes>();
+        match attrs.buffer.take() {
+            Some(BufferAssignment::NewBuffer { buffer, .. }) => {
+                let dimensions = buffer_dimensions(&buffer).map(|dims| Size::from((dims.w, dims.h)));
+                // a changed buffer geometry invalidates the contents of the textures
+                if dimensions != self.buffer_dimensions
+                    || attrs.buffer_scale != self.buffer_scale
+                    || Transform::from(attrs.buffer_transform) != self.buffer_transform
+                {
+                    self.damage.clear();
+                    self.damage.push(Rectangle::from_loc_and_size(
+                        (0, 0),
+                        dimensions.unwrap_or_default(),
+                    ));
+                }
+                self.buffer_dimensions = dimensions;
+                self.buffer_scale = attrs.buffer_scale;
+                self.buffer_transform = attrs.buffer_transform.into();
+
+                let surface_size = self.surface_size().map(|size| size.to_buffer(self.buffer_scale));
+                let transform = self.buffer_transform;
+                let scale = self.buffer_scale;
+                self.damage
+                    .extend(attrs.damage.drain(..).map(|damage| match damage {
+                        Damage::Buffer(rect) => rect,
+                        Damage::Surface(rect) => match surface_size {
+                            Some(size) => transform.transform_rect_in(rect.to_buffer(scale), &size),
+                            None => rect.to_buffer(scale),
+                        },
+                    }));
+
+                let buffer = SurfaceBuffer::new(buffer, states);
+                if let Some(old_buffer) = self.buffer.replace(buffer) {
+                    let is_same = Some(&old_buffer.buffer) == self.wl_buffer();
+                    old_buffer.release(!is_same);
+                }
+                self.textures.clear();
+            }
+            Some(BufferAssignment::Removed) => {
+                if let Some(buffer) = self.buffer.take() {
+                    buffer.release(true);
+                }
+                self.buffer_dimensions = None;
+                self.damage.clear();
+                self.textures.clear();
+            }
+            None => {}
+        }
+    }
+
+    /// Size of the attached buffer in buffer coordinates
+    pub fn buffer_size(&self) -> Option<Size<i32, Buffer>> {
+        self.buffer_dimensions
+    }
+
+    /// Scale of the attached buffer
+    pub fn buffer_scale(&self) -> i32 {
+        self.buffer_scale
+    }
+
+    /// Transform of the attached buffer
+    pub fn buffer_transform(&self) -> Transform {
+        self.buffer_transform
+    }
+
+    /// Size of the surface, taking the scale and transform of its buffer into account
+    ///
+    /// Returns `None` if no buffer is attached.
+    pub fn surface_size(&self) -> Option<Size<i32, Logical>> {
+        self.buffer_dimensions.map(|dims| {
+            let (w, h) = self.buffer_transform.transform_size(dims.w as u32, dims.h as u32);
+            Size::<i32, Buffer>::from((w as i32, h as i32)).to_logical(self.buffer_scale.max(1))
+        })
+    }
+
+    /// The currently attached buffer
+    pub fn wl_buffer(&self) -> Option<&WlBuffer> {
+        self.buffer.as_ref().map(|buffer| &buffer.buffer)
+    }
+
+    /// The texture of the attached buffer imported by a renderer of type `R`, if any
+    pub fn texture<R>(&self) -> Option<&R::TextureId>
+    where
+        R: Renderer,
+        R::TextureId: 'static,
+    {
+        self.textures
+            .get(&TypeId::of::<R::TextureId>())
+            .and_then(|texture| texture.downcast_ref::<R::TextureId>())
+    }
+}
+
+impl Drop for RendererSurfaceState {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            buffer.release(true);
+        }
+    }
+}
+
+/// Keep track of the buffers attached to the surfaces of a committed surface tree
+///
+/// This handler needs to be called in the commit callback of your compositor (see
+/// [`compositor_init`](crate::wayland::compositor::compositor_init)) for every surface drawn with
+/// [`draw_surface_tree`]. It takes the buffer and the damage out of the
+/// [`SurfaceAttributes`], so if you also use
+/// [`desktop::on_commit_buffer_handler`](crate::desktop::on_commit_buffer_handler), it has to be called
+/// before this one.
+///
+/// Buffers are kept until they are replaced by a new one, and released afterwards. The release
+/// points of [`drm_syncobj`](crate::wayland::drm_syncobj) commits are signaled at the same time.
+pub fn on_commit_buffer_handler(surface: &WlSurface) {
+    if is_sync_subsurface(surface) {
+        return;
+    }
+    with_surface_tree_upward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |_, states, _| {
+            states
+                .data_map
+                .insert_if_missing(|| RefCell::new(RendererSurfaceState::default()));
+            let mut data = states
+                .data_map
+                .get::<RefCell<RendererSurfaceState>>()
+                .unwrap()
+                .borrow_mut();
+            data.update_buffer(states);
+        },
+        |_, _, _| true,
+    );
+}
+
+/// Imports the buffers of all surfaces of a surface tree not yet imported by this renderer
+///
+/// The textures are cached until a new buffer is committed. Buffers failing to import are skipped,
+/// their surfaces (and subsurfaces) are not drawn. The last encountered error is returned.
+pub fn import_surface_tree<R>(
+    renderer: &mut R,
+    surface: &WlSurface,
+    log: &slog::Logger,
+) -> Result<(), <R as Renderer>::Error>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    let mut result = Ok(());
+    with_surface_tree_upward(
+        surface,
+        (),
+        |_, states, _| {
+            let data = match states.data_map.get::<RefCell<RendererSurfaceState>>() {
+                Some(data) => data,
+                None => return TraversalAction::SkipChildren,
+            };
+            let mut data = data.borrow_mut();
+            let key = TypeId::of::<<R as Renderer>::TextureId>();
+            if !data.textures.contains_key(&key) {
+                let buffer = match data.wl_buffer().cloned() {
+                    Some(buffer) => buffer,
+                    None => return TraversalAction::SkipChildren,
+                };
+                // renderers importing the buffer after the first one update their whole texture
+                let damage = if data.textures.is_empty() {
+                    std::mem::take(&mut data.damage)
+                } else {
+                    Vec::new()
+                };
+                match renderer.import_buffer(&buffer, Some(states), &damage) {
+                    Some(Ok(texture)) => {
+                        data.textures.insert(key, Box::new(texture));
+                    }
+                    Some(Err(err)) => {
+                        warn!(log, "Error importing buffer: {:?}", err);
+                        result = Err(err);
+                    }
+                    None => {
+                        warn!(log, "Unknown buffer format for: {:?}", buffer);
+                    }
+                }
+            }
+            if data.textures.contains_key(&key) {
+                TraversalAction::DoChildren(())
+            } else {
+                // not displayed, so are the children
+                TraversalAction::SkipChildren
+            }
+        },
+        |_, _, _| {},
+        |_, _, _| true,
+    );
+    result
+}
+
+/// Draws a surface tree, including all its subsurfaces, importing its buffers if necessary
+///
+/// `location` is the location of the root surface relative to the frame and `scale` the scale of the
+/// output. Only the parts intersecting the `damage` rectangles of the frame are drawn.
+/// Errors while importing the buffers are logged and the affected surfaces skipped, see
+/// [`import_surface_tree`].
+pub fn draw_surface_tree<R, E, F, T>(
+    renderer: &mut R,
+    frame: &mut F,
+    surface: &WlSurface,
+    scale: f64,
+    location: Point<i32, Logical>,
+    damage: &[Rectangle<i32, Physical>],
+    log: &slog::Logger,
+) -> Result<(), E>
+where
+    R: Renderer<Error = E, TextureId = T, Frame = F> + ImportAll,
+    F: Frame<Error = E, TextureId = T>,
+    E: std::error::Error,
+    T: 'static,
+{
+    let _ = import_surface_tree(renderer, surface, log);
+
+    let mut result = Ok(());
+    with_surface_tree_upward(
+        surface,
+        location,
+        |_, states, location| {
+            let mut location = *location;
+            let drawn = states
+                .data_map
+                .get::<RefCell<RendererSurfaceState>>()
+                .map(|data| data.borrow().texture::<R>().is_some())
+                .unwrap_or(false);
+            if drawn {
+                if states.role == Some("subsurface") {
+                    let current = states.cached_state.current::<SubsurfaceCachedState>();
+                    location += current.location;
+                }
+                TraversalAction::DoChildren(location)
+            } else {
+                // not displayed, so are the children
+                TraversalAction::SkipChildren
+            }
+        },
+        |_, states, location| {
+            let mut location = *location;
+            let data = match states.data_map.get::<RefCell<RendererSurfaceState>>() {
+                Some(data) => data.borrow(),
+                None => return,
+            };
+            let (texture, buffer_size, surface_size) =
+                match (data.texture::<R>(), data.buffer_size(), data.surface_size()) {
+                    (Some(texture), Some(buffer_size), Some(surface_size)) => {
+                        (texture, buffer_size, surface_size)
+                    }
+                    _ => return,
+                };
+            // the offset of subsurfaces is only passed to the children by the previous closure
+            if states.role == Some("subsurface") {
+                let current = states.cached_state.current::<SubsurfaceCachedState>();
+                location += current.location;
+            }
+            let dst = Rectangle::from_loc_and_size(location, surface_size)
+                .to_f64()
+                .to_physical(scale);
+            if let Err(err) = frame.render_texture_from_to(
+                texture,
+                Rectangle::from_loc_and_size((0, 0), buffer_size),
+                dst,
+                data.buffer_transform(),
+                damage,
+                1.0,
+            ) {
+                result = Err(err);
+            }
+        },
+        |_, _, _| true,
+    );
+    result
+}
+
+/// Runs the given closure with the [`RendererSurfaceState`] of a surface, if it has one
+pub fn with_renderer_surface_state<F, T>(surface: &WlSurface, f: F) -> Option<T>
+where
+    F: FnOnce(&RendererSurfaceState) -> T,
+{
+    with_states(surface, |states| {
+        states
+            .data_map
+            .get::<RefCell<RendererSurfaceState>>()
+            .map(|data| f(&data.borrow()))
+    })
+    .ok()
+    .flatten()
+}
@@ -3,6 +3,7 @@ use std::{cell::RefCell, path::PathBuf, rc::Rc};
 use winit::{
     dpi::LogicalPosition,
     event::{ElementState, MouseButton as WinitMouseButton, MouseScrollDelta},
+    window::WindowId,
 };
 
 use crate::backend::input::{
@@ -53,6 +54,14 @@ pub struct WinitKeyboardInputEvent {
     pub(crate) key: u32,
     pub(crate) count: u32,
     pub(crate) state: ElementState,
+    pub(crate) window_id: WindowId,
+}
+
+impl WinitKeyboardInputEvent {
+    /// Returns the id of the window the event originates from.
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
 }
 
 impl Event<WinitInput> for WinitKeyboardInputEvent {
@@ -85,6 +94,14 @@ pub struct WinitMouseMovedEvent {
     pub(crate) size: Rc<RefCell<WindowSize>>,
     pub(crate) time: u32,
     pub(crate) logical_position: LogicalPosition<f64>,
+    pub(crate) window_id: WindowId,
+}
+
+impl WinitMouseMovedEvent {
+    /// Returns the id of the window the event originates from.
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
 }
 
 impl Event<WinitInput> for WinitMouseMovedEvent {
@@ -127,6 +144,14 @@ impl PointerMotionAbsoluteEvent<WinitInput> for WinitMouseMovedEvent {
 pub struct WinitMouseWheelEvent {
     pub(crate) time: u32,
     pub(crate) delta: MouseScrollDelta,
+    pub(crate) window_id: WindowId,
+}
+
+impl WinitMouseWheelEvent {
+    /// Returns the id of the window the event originates from.
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
 }
 
 impl Event<WinitInput> for WinitMouseWheelEvent {
@@ -171,6 +196,14 @@ pub struct WinitMouseInputEvent {
     pub(crate) button: WinitMouseButton,
     pub(crate) state: ElementState,
     pub(crate) is_x11: bool,
+    pub(crate) window_id: WindowId,
+}
+
+impl WinitMouseInputEvent {
+    /// Returns the id of the window the event originates from.
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
 }
 
 impl Event<WinitInput> for WinitMouseInputEvent {
@@ -211,6 +244,14 @@ pub struct WinitTouchStartedEvent {
     pub(crate) time: u32,
     pub(crate) location: LogicalPosition<f64>,
     pub(crate) id: u64,
+    pub(crate) window_id: WindowId,
+}
+
+impl WinitTouchStartedEvent {
+    /// Returns the id of the window the event originates from.
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
 }
 
 impl Event<WinitInput> for WinitTouchStartedEvent {
@@ -258,6 +299,14 @@ pub struct WinitTouchMovedEvent {
     pub(crate) time: u32,
     pub(crate) location: LogicalPosition<f64>,
     pub(crate) id: u64,
+    pub(crate) window_id: WindowId,
+}
+
+impl WinitTouchMovedEvent {
+    /// Returns the id of the window the event originates from.
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
 }
 
 impl Event<WinitInput> for WinitTouchMovedEvent {
@@ -303,6 +352,14 @@ impl TouchMotionEvent<WinitInput> for WinitTouchMovedEvent {
 pub struct WinitTouchEndedEvent {
     pub(crate) time: u32,
     pub(crate) id: u64,
+    pub(crate) window_id: WindowId,
+}
+
+impl WinitTouchEndedEvent {
+    /// Returns the id of the window the event originates from.
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
 }
 
 impl Event<WinitInput> for WinitTouchEndedEvent {
@@ -326,6 +383,14 @@ impl TouchUpEvent<WinitInput> for WinitTouchEndedEvent {
 pub struct WinitTouchCancelledEvent {
     pub(crate) time: u32,
     pub(crate) id: u64,
+    pub(crate) window_id: WindowId,
+}
+
+impl WinitTouchCancelledEvent {
+    /// Returns the id of the window the event originates from.
+    pub fn window_id(&self) -> WindowId {
+        self.window_id
+    }
 }
 
 impl Event<WinitInput> for WinitTouchCancelledEvent {
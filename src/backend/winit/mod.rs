@@ -17,6 +17,24 @@
 //!
 //! The other types in this module are the instances of the associated types of these
 //! two traits for the winit backend.
+//!
+//! ## Multiple windows
+//!
+//! Further windows, e.g. to emulate several outputs, are created with
+//! [`WinitEventLoop::create_window`]. All windows share the event loop, events applying to a
+//! single window carry its [`WindowId`], see [`WinitEvent::window_id`]. The renderers of all
+//! windows share their resources, so textures may be used with any of them.
+//!
+//! ## Text input
+//!
+//! The text typed into a window is forwarded as [`WinitEvent::ReceivedText`], in addition to the
+//! [`InputEvent::Keyboard`] events of the key presses producing it, so only one of them should be
+//! handed to clients.
+//!
+//! Input methods of the host are enabled for all windows. Their state, the preedit text and the
+//! composed text are forwarded as [`WinitEvent::Ime`]. Key presses consumed by the input method
+//! do not generate [`WinitEvent::ReceivedText`], so the text of both events can be handed to
+//! clients, e.g. through the `text-input` protocol.
 
 mod input;
 
@@ -33,15 +51,15 @@ use crate::{
     },
     utils::{Logical, Physical, Size},
 };
-use std::{cell::RefCell, rc::Rc, time::Instant};
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Instant};
 use wayland_egl as wegl;
 use winit::{
     dpi::LogicalSize,
-    event::{ElementState, Event, KeyboardInput, Touch, TouchPhase, WindowEvent},
+    event::{ElementState, Event, Ime, KeyboardInput, Touch, TouchPhase, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     platform::run_return::EventLoopExtRunReturn,
     platform::unix::WindowExtUnix,
-    window::{Window as WinitWindow, WindowBuilder},
+    window::{Window as WinitWindow, WindowBuilder, WindowId},
 };
 
 use slog::{debug, error, info, o, trace, warn};
@@ -99,16 +117,26 @@ pub struct WinitGraphicsBackend {
 /// periodically to receive any events.
 #[derive(Debug)]
 pub struct WinitEventLoop {
-    window: Rc<WinitWindow>,
+    windows: HashMap<WindowId, WindowState>,
     events_loop: EventLoop<()>,
     time: Instant,
     key_counter: u32,
     logger: ::slog::Logger,
     initialized: bool,
-    size: Rc<RefCell<WindowSize>>,
-    resize_notification: Rc<Cell<Option<Size<i32, Physical>>>>,
     /// Whether winit is using Wayland or X11 as it's backend.
     is_x11: bool,
+    display: EGLDisplay,
+    /// Context the contexts of further windows share their resources with.
+    context: EGLContext,
+    attributes: GlAttributes,
+}
+
+/// State of a window shared between its [`WinitGraphicsBackend`] and the [`WinitEventLoop`]
+#[derive(Debug)]
+struct WindowState {
+    window: Rc<WinitWindow>,
+    size: Rc<RefCell<WindowSize>>,
+    resize_notification: Rc<Cell<Option<Size<i32, Physical>>>>,
 }
 
 /// Create a new [`WinitGraphicsBackend`], which implements the [`Renderer`] trait and a corresponding
@@ -166,50 +194,77 @@ where
 
     debug!(log, "Window created");
 
-    let reqs = Default::default();
-    let (display, context, surface, is_x11) = {
-        let display = EGLDisplay::new(&winit_window, log.clone())?;
-        let context = EGLContext::new_with_config(&display, attributes, reqs, log.clone())?;
-
-        let (surface, is_x11) = if let Some(wl_surface) = winit_window.wayland_surface() {
-            debug!(log, "Winit backend: Wayland");
-            let size = winit_window.inner_size();
-            let surface = unsafe {
-                wegl::WlEglSurface::new_from_raw(wl_surface as *mut _, size.width as i32, size.height as i32)
-            };
-            (
-                EGLSurface::new(
-                    &display,
-                    context.pixel_format().unwrap(),
-                    context.config_id(),
-                    surface,
-                    log.clone(),
-                )
-                .map_err(EGLError::CreationFailed)?,
-                false,
-            )
-        } else if let Some(xlib_window) = winit_window.xlib_window().map(native::XlibWindow) {
-            debug!(log, "Winit backend: X11");
-            (
-                EGLSurface::new(
-                    &display,
-                    context.pixel_format().unwrap(),
-                    context.config_id(),
-                    xlib_window,
-                    log.clone(),
-                )
-                .map_err(EGLError::CreationFailed)?,
-                true,
-            )
-        } else {
-            unreachable!("No backends for winit other then Wayland and X11 are supported")
-        };
+    let display = EGLDisplay::new(&winit_window, log.clone())?;
+    let context = EGLContext::new_with_config(&display, attributes, Default::default(), log.clone())?;
+    let shared_context =
+        EGLContext::new_shared_with_config(&display, &context, attributes, Default::default(), log.clone())?;
+    let (backend, state, is_x11) = create_graphics_backend(winit_window, &display, context, &log)?;
 
-        let _ = context.unbind();
+    let mut windows = HashMap::new();
+    windows.insert(state.window.id(), state);
+
+    Ok((
+        backend,
+        WinitEventLoop {
+            windows,
+            events_loop,
+            time: Instant::now(),
+            key_counter: 0,
+            initialized: false,
+            logger: log.new(o!("smithay_winit_component" => "event_loop")),
+            is_x11,
+            display,
+            context: shared_context,
+            attributes,
+        },
+    ))
+}
 
-        (display, context, surface, is_x11)
+fn create_graphics_backend(
+    winit_window: WinitWindow,
+    display: &EGLDisplay,
+    context: EGLContext,
+    log: &::slog::Logger,
+) -> Result<(WinitGraphicsBackend, WindowState, bool), Error> {
+    // Forward the events of the input method of the host, see `WinitEvent::Ime`
+    winit_window.set_ime_allowed(true);
+
+    let (surface, is_x11) = if let Some(wl_surface) = winit_window.wayland_surface() {
+        debug!(log, "Winit backend: Wayland");
+        let size = winit_window.inner_size();
+        let surface = unsafe {
+            wegl::WlEglSurface::new_from_raw(wl_surface as *mut _, size.width as i32, size.height as i32)
+        };
+        (
+            EGLSurface::new(
+                display,
+                context.pixel_format().unwrap(),
+                context.config_id(),
+                surface,
+                log.clone(),
+            )
+            .map_err(EGLError::CreationFailed)?,
+            false,
+        )
+    } else if let Some(xlib_window) = winit_window.xlib_window().map(native::XlibWindow) {
+        debug!(log, "Winit backend: X11");
+        (
+            EGLSurface::new(
+                display,
+                context.pixel_format().unwrap(),
+                context.config_id(),
+                xlib_window,
+                log.clone(),
+            )
+            .map_err(EGLError::CreationFailed)?,
+            true,
+        )
+    } else {
+        unreachable!("No backends for winit other then Wayland and X11 are supported")
     };
 
+    let _ = context.unbind();
+
     let (w, h): (u32, u32) = winit_window.inner_size().into();
     let size = Rc::new(RefCell::new(WindowSize {
         physical_size: (w as i32, h as i32).into(),
@@ -224,23 +279,18 @@ where
     Ok((
         WinitGraphicsBackend {
             window: window.clone(),
-            _display: display,
+            _display: display.clone(),
             egl,
             renderer,
             size: size.clone(),
             resize_notification: resize_notification.clone(),
         },
-        WinitEventLoop {
-            resize_notification,
-            events_loop,
+        WindowState {
             window,
-            time: Instant::now(),
-            key_counter: 0,
-            initialized: false,
-            logger: log.new(o!("smithay_winit_component" => "event_loop")),
             size,
-            is_x11,
+            resize_notification,
         },
+        is_x11,
     ))
 }
 
@@ -253,16 +303,113 @@ pub enum WinitEvent {
         size: Size<i32, Physical>,
         /// The new scale factor
         scale_factor: f64,
+        /// Id of the window
+        window_id: WindowId,
     },
 
     /// The focus state of the window changed
-    Focus(bool),
+    Focus {
+        /// Whether the window is focused
+        focused: bool,
+        /// Id of the window
+        window_id: WindowId,
+    },
 
     /// An input event occurred.
     Input(InputEvent<WinitInput>),
 
+    /// Text was typed into the window
+    ///
+    /// The key presses producing the text are reported as [`InputEvent::Keyboard`] as well.
+    ReceivedText {
+        /// The received text
+        text: String,
+        /// Id of the window
+        window_id: WindowId,
+    },
+
+    /// The state of the input method of the window changed
+    Ime {
+        /// The input method event
+        event: ImeEvent,
+        /// Id of the window
+        window_id: WindowId,
+    },
+
     /// A redraw was requested
-    Refresh,
+    Refresh {
+        /// Id of the window
+        window_id: WindowId,
+    },
+
+    /// A window created with [`WinitEventLoop::create_window`] was closed.
+    ///
+    /// Its [`WinitGraphicsBackend`] should be dropped. Closing the last window is reported as
+    /// [`WinitError::WindowClosed`] instead.
+    WindowClosed {
+        /// Id of the window
+        window_id: WindowId,
+    },
+}
+
+impl WinitEvent {
+    /// Returns the id of the window the event applies to.
+    ///
+    /// Returns [`None`] for events applying to the whole backend, like added or removed input devices.
+    pub fn window_id(&self) -> Option<WindowId> {
+        match self {
+            WinitEvent::Resized { window_id, .. }
+            | WinitEvent::Focus { window_id, .. }
+            | WinitEvent::ReceivedText { window_id, .. }
+            | WinitEvent::Ime { window_id, .. }
+            | WinitEvent::Refresh { window_id }
+            | WinitEvent::WindowClosed { window_id } => Some(*window_id),
+            WinitEvent::Input(InputEvent::Keyboard { event }) => Some(event.window_id()),
+            WinitEvent::Input(InputEvent::PointerMotionAbsolute { event }) => Some(event.window_id()),
+            WinitEvent::Input(InputEvent::PointerAxis { event }) => Some(event.window_id()),
+            WinitEvent::Input(InputEvent::PointerButton { event }) => Some(event.window_id()),
+            WinitEvent::Input(InputEvent::TouchDown { event }) => Some(event.window_id()),
+            WinitEvent::Input(InputEvent::TouchMotion { event }) => Some(event.window_id()),
+            WinitEvent::Input(InputEvent::TouchUp { event }) => Some(event.window_id()),
+            WinitEvent::Input(InputEvent::TouchCancel { event }) => Some(event.window_id()),
+            WinitEvent::Input(_) => None,
+        }
+    }
+}
+
+/// Input method events of a window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImeEvent {
+    /// The input method was enabled
+    ///
+    /// It is followed by [`ImeEvent::Preedit`] and [`ImeEvent::Commit`] events.
+    Enabled,
+    /// The text being composed changed
+    ///
+    /// An empty text clears the preedit text.
+    Preedit {
+        /// The text being composed
+        text: String,
+        /// Byte offsets of the start and the end of the cursor in the text, if it should be shown
+        cursor: Option<(usize, usize)>,
+    },
+    /// The composed text was committed
+    ///
+    /// The preedit text is cleared before.
+    Commit(String),
+    /// The input method was disabled
+    Disabled,
+}
+
+impl From<Ime> for ImeEvent {
+    fn from(ime: Ime) -> ImeEvent {
+        match ime {
+            Ime::Enabled => ImeEvent::Enabled,
+            Ime::Preedit(text, cursor) => ImeEvent::Preedit { text, cursor },
+            Ime::Commit(text) => ImeEvent::Commit(text),
+            Ime::Disabled => ImeEvent::Disabled,
+        }
+    }
 }
 
 impl WinitGraphicsBackend {
@@ -316,13 +463,36 @@ pub enum WinitError {
 }
 
 impl WinitEventLoop {
+    /// Create a further window sharing the event loop of the backend.
+    ///
+    /// The renderer of the returned [`WinitGraphicsBackend`] shares its resources with the
+    /// renderers of the other windows. Events of the window are dispatched by this event loop.
+    pub fn create_window(&mut self, builder: WindowBuilder) -> Result<WinitGraphicsBackend, Error> {
+        let winit_window = builder.build(&self.events_loop).map_err(Error::InitFailed)?;
+
+        debug!(self.logger, "Window created");
+
+        let context = EGLContext::new_shared_with_config(
+            &self.display,
+            &self.context,
+            self.attributes,
+            Default::default(),
+            self.logger.clone(),
+        )?;
+        let (backend, state, _) =
+            create_graphics_backend(winit_window, &self.display, context, &self.logger)?;
+        self.windows.insert(state.window.id(), state);
+
+        Ok(backend)
+    }
+
     /// Processes new events of the underlying event loop and calls the provided callback.
     ///
     /// You need to periodically call this function to keep the underlying event loop and
     /// [`WinitWindow`] active. Otherwise the window may not respond to user interaction.
     ///
-    /// Returns an error if the [`WinitWindow`] the window has been closed. Calling
-    /// `dispatch_new_events` again after the [`WinitWindow`] has been closed is considered an
+    /// Returns an error if the last [`WinitWindow`] has been closed. Calling
+    /// `dispatch_new_events` again after the last [`WinitWindow`] has been closed is considered an
     /// application error and unspecified behaviour may occur.
     ///
     /// The linked [`WinitGraphicsBackend`] will error with a lost context and should
@@ -344,10 +514,8 @@ impl WinitEventLoop {
             let closed_ptr = &mut closed;
             let key_counter = &mut self.key_counter;
             let time = &self.time;
-            let window = &self.window;
-            let resize_notification = &self.resize_notification;
+            let windows = &mut self.windows;
             let logger = &self.logger;
-            let window_size = &self.size;
             let is_x11 = self.is_x11;
 
             if !self.initialized {
@@ -362,10 +530,19 @@ impl WinitEventLoop {
                     Event::RedrawEventsCleared => {
                         *control_flow = ControlFlow::Exit;
                     }
-                    Event::RedrawRequested(_id) => {
-                        callback(WinitEvent::Refresh);
+                    Event::RedrawRequested(window_id) => {
+                        callback(WinitEvent::Refresh { window_id });
                     }
-                    Event::WindowEvent { event, .. } => {
+                    Event::WindowEvent { event, window_id } => {
+                        // Ignore the remaining events of closed windows.
+                        let state = match windows.get(&window_id) {
+                            Some(state) => state,
+                            None => return,
+                        };
+                        let window = &state.window;
+                        let window_size = &state.size;
+                        let resize_notification = &state.resize_notification;
+
                         let duration = Instant::now().duration_since(*time);
                         let nanos = duration.subsec_nanos() as u64;
                         let time = ((1000 * duration.as_secs()) + (nanos / 1_000_000)) as u32;
@@ -383,10 +560,11 @@ impl WinitEventLoop {
                                 callback(WinitEvent::Resized {
                                     size: wsize.physical_size,
                                     scale_factor,
+                                    window_id,
                                 });
                             }
-                            WindowEvent::Focused(focus) => {
-                                callback(WinitEvent::Focus(focus));
+                            WindowEvent::Focused(focused) => {
+                                callback(WinitEvent::Focus { focused, window_id });
                             }
 
                             WindowEvent::ScaleFactorChanged {
@@ -402,6 +580,7 @@ impl WinitEventLoop {
                                 callback(WinitEvent::Resized {
                                     size: (pw as i32, ph as i32).into(),
                                     scale_factor: wsize.scale_factor,
+                                    window_id,
                                 });
                             }
                             WindowEvent::KeyboardInput {
@@ -420,9 +599,24 @@ impl WinitEventLoop {
                                         key: scancode,
                                         count: *key_counter,
                                         state,
+                                        window_id,
                                     },
                                 }));
                             }
+                            // Control characters are the result of key presses like backspace or
+                            // return and are not text.
+                            WindowEvent::ReceivedCharacter(character) if !character.is_control() => {
+                                callback(WinitEvent::ReceivedText {
+                                    text: character.to_string(),
+                                    window_id,
+                                });
+                            }
+                            WindowEvent::Ime(ime) => {
+                                callback(WinitEvent::Ime {
+                                    event: ime.into(),
+                                    window_id,
+                                });
+                            }
                             WindowEvent::CursorMoved { position, .. } => {
                                 let lpos = position.to_logical(window_size.borrow().scale_factor);
                                 callback(Input(InputEvent::PointerMotionAbsolute {
@@ -430,11 +624,16 @@ impl WinitEventLoop {
                                         size: window_size.clone(),
                                         time,
                                         logical_position: lpos,
+                                        window_id,
                                     },
                                 }));
                             }
                             WindowEvent::MouseWheel { delta, .. } => {
-                                let event = WinitMouseWheelEvent { time, delta };
+                                let event = WinitMouseWheelEvent {
+                                    time,
+                                    delta,
+                                    window_id,
+                                };
                                 callback(Input(InputEvent::PointerAxis { event }));
                             }
                             WindowEvent::MouseInput { state, button, .. } => {
@@ -444,6 +643,7 @@ impl WinitEventLoop {
                                         button,
                                         state,
                                         is_x11,
+                                        window_id,
                                     },
                                 }));
                             }
@@ -461,6 +661,7 @@ impl WinitEventLoop {
                                         time,
                                         location,
                                         id,
+                                        window_id,
                                     },
                                 }));
                             }
@@ -477,6 +678,7 @@ impl WinitEventLoop {
                                         time,
                                         location,
                                         id,
+                                        window_id,
                                     },
                                 }));
                             }
@@ -494,10 +696,11 @@ impl WinitEventLoop {
                                         time,
                                         location,
                                         id,
+                                        window_id,
                                     },
                                 }));
                                 callback(Input(InputEvent::TouchUp {
-                                    event: WinitTouchEndedEvent { time, id },
+                                    event: WinitTouchEndedEvent { time, id, window_id },
                                 }))
                             }
 
@@ -507,15 +710,20 @@ impl WinitEventLoop {
                                 ..
                             }) => {
                                 callback(Input(InputEvent::TouchCancel {
-                                    event: WinitTouchCancelledEvent { time, id },
+                                    event: WinitTouchCancelledEvent { time, id, window_id },
                                 }));
                             }
                             WindowEvent::CloseRequested | WindowEvent::Destroyed => {
-                                callback(Input(InputEvent::DeviceRemoved {
-                                    device: WinitVirtualDevice,
-                                }));
-                                warn!(logger, "Window closed");
-                                *closed_ptr = true;
+                                windows.remove(&window_id);
+                                if windows.is_empty() {
+                                    callback(Input(InputEvent::DeviceRemoved {
+                                        device: WinitVirtualDevice,
+                                    }));
+                                    warn!(logger, "Window closed");
+                                    *closed_ptr = true;
+                                } else {
+                                    callback(WinitEvent::WindowClosed { window_id });
+                                }
                             }
                             _ => {}
                         }
@@ -531,3 +739,32 @@ impl WinitEventLoop {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ime_event() {
+        assert_eq!(ImeEvent::from(Ime::Enabled), ImeEvent::Enabled);
+        assert_eq!(
+            ImeEvent::from(Ime::Preedit("にほ".into(), Some((3, 6)))),
+            ImeEvent::Preedit {
+                text: "にほ".into(),
+                cursor: Some((3, 6)),
+            }
+        );
+        assert_eq!(
+            ImeEvent::from(Ime::Preedit(String::new(), None)),
+            ImeEvent::Preedit {
+                text: String::new(),
+                cursor: None,
+            }
+        );
+        assert_eq!(
+            ImeEvent::from(Ime::Commit("日本".into())),
+            ImeEvent::Commit("日本".into())
+        );
+        assert_eq!(ImeEvent::from(Ime::Disabled), ImeEvent::Disabled);
+    }
+}
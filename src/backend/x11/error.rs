@@ -131,6 +131,10 @@ pub enum AllocateBuffersError {
     /// The window has been destroyed
     #[error("The window has been destroyed")]
     WindowDestroyed,
+
+    /// Allocating a buffer shared with the X server failed.
+    #[error("Allocating a buffer shared with the X server failed.")]
+    SharedMemory(#[source] io::Error),
 }
 
 impl From<Errno> for AllocateBuffersError {
@@ -9,8 +9,10 @@ use super::{MissingExtensionError, X11Error};
 /// extensions! {
 ///     // The extension to check for. This should correspond to the name of the extension inside x11rb's `x11rb::protocol::xproto::<name>` module path.
 ///     xfixes {
-///         // The function used to query the available version of the extension. This will be inside the module path as explained above
-///         xfixes_query_version,
+///         // The function used to query the available version of the extension and its arguments. This will be inside the module path as explained above
+///         xfixes_query_version(4, 0),
+///         // Whether the backend fails to initialize without the extension.
+///         required: true,
 ///         // The minimum version of the extension that will be accepted.
 ///         minimum: (4, 0),
 ///         // The version of the extension to request.
@@ -24,7 +26,7 @@ macro_rules! extensions {
     (
         $(
             $extension:ident { // Extension name for path lookup
-                $extension_fn:ident, // Function used to look up the version of the extension
+                $extension_fn:ident($($arg:expr),*), // Function used to look up the version of the extension
                 required: $required:expr,
                 minimum: ($min_major:expr, $min_minor:expr),
                 request: ($req_major:expr, $req_minor:expr),
@@ -46,29 +48,32 @@ macro_rules! extensions {
                         use x11rb::protocol::$extension::{ConnectionExt as _, X11_EXTENSION_NAME};
 
                         if connection.extension_information(X11_EXTENSION_NAME)?.is_some() {
-                            let version = connection.$extension_fn($req_major, $req_minor)?.reply()?;
+                            let version = connection.$extension_fn($($arg),*)?.reply()?;
+                            // Not all extensions use the same integer type for their version.
+                            let (major_version, minor_version): (u32, u32) =
+                                (version.major_version.into(), version.minor_version.into());
 
                             #[allow(unused_comparisons)] // Macro comparisons
-                            if version.major_version >= $req_major
-                                || (version.major_version == $req_major && version.minor_version >= $req_minor)
+                            if major_version >= $req_major
+                                || (major_version == $req_major && minor_version >= $req_minor)
                             {
                                 slog::info!(
                                     logger,
                                     "Loaded extension {} version {}.{}",
                                     X11_EXTENSION_NAME,
-                                    version.major_version,
-                                    version.minor_version,
+                                    major_version,
+                                    minor_version,
                                 );
 
-                                Some((version.major_version, version.minor_version))
+                                Some((major_version, minor_version))
                             } else {
                                 if $required {
                                     slog::error!(
                                         logger,
                                         "required extension {} version is too low (have {}.{}, expected {}.{})",
                                         X11_EXTENSION_NAME,
-                                        version.major_version,
-                                        version.minor_version,
+                                        major_version,
+                                        minor_version,
                                         $req_major,
                                         $req_minor,
                                     );
@@ -77,8 +82,8 @@ macro_rules! extensions {
                                         name: X11_EXTENSION_NAME,
                                         required_major: $req_major,
                                         required_minor: $req_minor,
-                                        available_major: version.major_version,
-                                        available_minor: version.minor_version,
+                                        available_major: major_version,
+                                        available_minor: minor_version,
                                     }.into());
                                 } else {
                                     None
@@ -113,23 +118,37 @@ macro_rules! extensions {
 
 extensions! {
     present {
-        present_query_version,
-        required: true,
+        present_query_version(1, 0),
+        required: false,
         minimum: (1, 0),
         request: (1, 0),
     },
 
     xfixes {
-        xfixes_query_version,
+        xfixes_query_version(4, 0),
         required: true,
         minimum: (4, 0),
         request: (4, 0),
     },
 
     dri3 {
-        dri3_query_version,
-        required: true,
+        dri3_query_version(1, 2),
+        required: false,
         minimum: (1, 0),
         request: (1, 2),
     },
+
+    shm {
+        shm_query_version(),
+        required: false,
+        minimum: (1, 2),
+        request: (1, 2),
+    },
+
+    xinput {
+        xinput_xi_query_version(2, 2),
+        required: false,
+        minimum: (2, 2),
+        request: (2, 2),
+    },
 }
@@ -4,7 +4,9 @@ use super::{window_inner::WindowInner, Window, WindowTemporary};
 use crate::{
     backend::input::{
         self, Axis, AxisSource, ButtonState, Device, DeviceCapability, InputBackend, KeyState,
-        KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, UnusedEvent,
+        KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent,
+        PointerMotionEvent, TouchDownEvent, TouchFrameEvent, TouchMotionEvent, TouchSlot, TouchUpEvent,
+        UnusedEvent,
     },
     utils::{Logical, Size},
 };
@@ -14,24 +16,55 @@ use std::sync::Weak;
 #[derive(Debug)]
 pub struct X11Input;
 
-/// Virtual input device used by the backend to associate input events.
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub struct X11VirtualDevice;
+/// Input device of the X11 backend.
+///
+/// Core X11 events do not tell which device they originate from and are associated with a virtual
+/// device. If the X server supports the `XInput2` extension, pointer and touch events are associated
+/// with the physical device that generated them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct X11InputDevice {
+    pub(crate) xinput_id: Option<u16>,
+    pub(crate) name: String,
+    pub(crate) touch: bool,
+}
+
+impl X11InputDevice {
+    pub(crate) fn virtual_device() -> X11InputDevice {
+        X11InputDevice {
+            xinput_id: None,
+            name: "x11 virtual input".to_owned(),
+            touch: false,
+        }
+    }
 
-impl Device for X11VirtualDevice {
+    /// Returns the `XInput2` id of the device.
+    ///
+    /// Returns [`None`] for the virtual device associated with core X11 events.
+    pub fn xinput_id(&self) -> Option<u16> {
+        self.xinput_id
+    }
+}
+
+impl Device for X11InputDevice {
     fn id(&self) -> String {
-        "x11".to_owned()
+        match self.xinput_id {
+            Some(id) => format!("x11-xi-{}", id),
+            None => "x11".to_owned(),
+        }
     }
 
     fn name(&self) -> String {
-        "x11 virtual input".to_owned()
+        self.name.clone()
     }
 
     fn has_capability(&self, capability: DeviceCapability) -> bool {
-        matches!(
-            capability,
-            DeviceCapability::Keyboard | DeviceCapability::Pointer | DeviceCapability::Touch
-        )
+        match self.xinput_id {
+            Some(_) => {
+                capability == DeviceCapability::Pointer
+                    || (self.touch && capability == DeviceCapability::Touch)
+            }
+            None => matches!(capability, DeviceCapability::Keyboard | DeviceCapability::Pointer),
+        }
     }
 
     fn usb_id(&self) -> Option<(u32, u32)> {
@@ -52,6 +85,7 @@ pub struct X11KeyboardInputEvent {
     pub(crate) count: u32,
     pub(crate) state: KeyState,
     pub(crate) window: Weak<WindowInner>,
+    pub(crate) window_id: u32,
 }
 
 impl X11KeyboardInputEvent {
@@ -61,6 +95,13 @@ impl X11KeyboardInputEvent {
     pub fn window(&self) -> Option<impl AsRef<Window> + '_> {
         self.window.upgrade().map(Window).map(WindowTemporary)
     }
+
+    /// Returns the XID of the window belonging to this event.
+    ///
+    /// Unlike [`window`](Self::window), this is available after the window was destroyed.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
 }
 
 impl input::Event<X11Input> for X11KeyboardInputEvent {
@@ -68,8 +109,8 @@ impl input::Event<X11Input> for X11KeyboardInputEvent {
         self.time
     }
 
-    fn device(&self) -> X11VirtualDevice {
-        X11VirtualDevice
+    fn device(&self) -> X11InputDevice {
+        X11InputDevice::virtual_device()
     }
 }
 
@@ -88,13 +129,19 @@ impl KeyboardKeyEvent<X11Input> for X11KeyboardInputEvent {
 }
 
 /// X11-Backend internal event wrapping `X11`'s types into a [`PointerAxisEvent`]
+///
+/// Scroll wheels generate discrete amounts. With `XInput2`, smooth scrolling devices generate
+/// continuous amounts, scaled so one step of the device scrolls by `15`.
 #[allow(missing_docs)]
 #[derive(Debug, Clone)]
 pub struct X11MouseWheelEvent {
     pub(crate) time: u32,
     pub(crate) axis: Axis,
     pub(crate) amount: f64,
+    pub(crate) source: AxisSource,
+    pub(crate) device: X11InputDevice,
     pub(crate) window: Weak<WindowInner>,
+    pub(crate) window_id: u32,
 }
 
 impl X11MouseWheelEvent {
@@ -104,6 +151,21 @@ impl X11MouseWheelEvent {
     pub fn window(&self) -> Option<impl AsRef<Window> + '_> {
         self.window.upgrade().map(Window).map(WindowTemporary)
     }
+
+    /// Returns the XID of the window belonging to this event.
+    ///
+    /// Unlike [`window`](Self::window), this is available after the window was destroyed.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
+
+    fn amount_on(&self, axis: Axis) -> f64 {
+        if self.axis == axis {
+            self.amount
+        } else {
+            0.0
+        }
+    }
 }
 
 impl input::Event<X11Input> for X11MouseWheelEvent {
@@ -111,27 +173,28 @@ impl input::Event<X11Input> for X11MouseWheelEvent {
         self.time
     }
 
-    fn device(&self) -> X11VirtualDevice {
-        X11VirtualDevice
+    fn device(&self) -> X11InputDevice {
+        self.device.clone()
     }
 }
 
 impl PointerAxisEvent<X11Input> for X11MouseWheelEvent {
-    fn amount(&self, _axis: Axis) -> Option<f64> {
-        None
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        match self.source {
+            AxisSource::Wheel => None,
+            _ => Some(self.amount_on(axis)),
+        }
     }
 
     fn amount_discrete(&self, axis: Axis) -> Option<f64> {
-        if self.axis == axis {
-            Some(self.amount)
-        } else {
-            Some(0.0)
+        match self.source {
+            AxisSource::Wheel => Some(self.amount_on(axis)),
+            _ => None,
         }
     }
 
     fn source(&self) -> AxisSource {
-        // X11 seems to act within the scope of individual rachets of a scroll wheel.
-        AxisSource::Wheel
+        self.source
     }
 }
 
@@ -142,7 +205,9 @@ pub struct X11MouseInputEvent {
     pub(crate) time: u32,
     pub(crate) raw: u32,
     pub(crate) state: ButtonState,
+    pub(crate) device: X11InputDevice,
     pub(crate) window: Weak<WindowInner>,
+    pub(crate) window_id: u32,
 }
 
 impl X11MouseInputEvent {
@@ -152,6 +217,13 @@ impl X11MouseInputEvent {
     pub fn window(&self) -> Option<impl AsRef<Window> + '_> {
         self.window.upgrade().map(Window).map(WindowTemporary)
     }
+
+    /// Returns the XID of the window belonging to this event.
+    ///
+    /// Unlike [`window`](Self::window), this is available after the window was destroyed.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
 }
 
 impl input::Event<X11Input> for X11MouseInputEvent {
@@ -159,8 +231,8 @@ impl input::Event<X11Input> for X11MouseInputEvent {
         self.time
     }
 
-    fn device(&self) -> X11VirtualDevice {
-        X11VirtualDevice
+    fn device(&self) -> X11InputDevice {
+        self.device.clone()
     }
 }
 
@@ -182,7 +254,9 @@ pub struct X11MouseMovedEvent {
     pub(crate) x: f64,
     pub(crate) y: f64,
     pub(crate) size: Size<u16, Logical>,
+    pub(crate) device: X11InputDevice,
     pub(crate) window: Weak<WindowInner>,
+    pub(crate) window_id: u32,
 }
 
 impl X11MouseMovedEvent {
@@ -192,6 +266,13 @@ impl X11MouseMovedEvent {
     pub fn window(&self) -> Option<impl AsRef<Window> + '_> {
         self.window.upgrade().map(Window).map(WindowTemporary)
     }
+
+    /// Returns the XID of the window belonging to this event.
+    ///
+    /// Unlike [`window`](Self::window), this is available after the window was destroyed.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
 }
 
 impl input::Event<X11Input> for X11MouseMovedEvent {
@@ -199,8 +280,8 @@ impl input::Event<X11Input> for X11MouseMovedEvent {
         self.time
     }
 
-    fn device(&self) -> X11VirtualDevice {
-        X11VirtualDevice
+    fn device(&self) -> X11InputDevice {
+        self.device.clone()
     }
 }
 
@@ -222,21 +303,230 @@ impl PointerMotionAbsoluteEvent<X11Input> for X11MouseMovedEvent {
     }
 }
 
+/// X11-Backend internal event wrapping `XInput2` raw motion into a [`PointerMotionEvent`]
+///
+/// These events are only generated while the pointer is inside of a window of the backend, see
+/// [`Window::grab_pointer`] to keep the pointer inside.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+pub struct X11PointerMotionEvent {
+    pub(crate) time: u32,
+    pub(crate) delta_x: f64,
+    pub(crate) delta_y: f64,
+    pub(crate) device: X11InputDevice,
+    pub(crate) window: Weak<WindowInner>,
+    pub(crate) window_id: u32,
+}
+
+impl X11PointerMotionEvent {
+    /// Returns a temporary reference to the window belonging to this event.
+    ///
+    /// Returns None if the window is not alive anymore.
+    pub fn window(&self) -> Option<impl AsRef<Window> + '_> {
+        self.window.upgrade().map(Window).map(WindowTemporary)
+    }
+
+    /// Returns the XID of the window belonging to this event.
+    ///
+    /// Unlike [`window`](Self::window), this is available after the window was destroyed.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
+}
+
+impl input::Event<X11Input> for X11PointerMotionEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> X11InputDevice {
+        self.device.clone()
+    }
+}
+
+impl PointerMotionEvent<X11Input> for X11PointerMotionEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta_x
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta_y
+    }
+}
+
+/// X11-Backend internal event wrapping `XInput2` touch events into a [`TouchDownEvent`] or a
+/// [`TouchMotionEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+pub struct X11TouchEvent {
+    pub(crate) time: u32,
+    pub(crate) slot: u64,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) size: Size<u16, Logical>,
+    pub(crate) device: X11InputDevice,
+    pub(crate) window: Weak<WindowInner>,
+    pub(crate) window_id: u32,
+}
+
+impl X11TouchEvent {
+    /// Returns a temporary reference to the window belonging to this event.
+    ///
+    /// Returns None if the window is not alive anymore.
+    pub fn window(&self) -> Option<impl AsRef<Window> + '_> {
+        self.window.upgrade().map(Window).map(WindowTemporary)
+    }
+
+    /// Returns the XID of the window belonging to this event.
+    ///
+    /// Unlike [`window`](Self::window), this is available after the window was destroyed.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
+}
+
+impl input::Event<X11Input> for X11TouchEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> X11InputDevice {
+        self.device.clone()
+    }
+}
+
+impl TouchDownEvent<X11Input> for X11TouchEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        Some(TouchSlot::new(self.slot))
+    }
+
+    fn x(&self) -> f64 {
+        self.x
+    }
+
+    fn y(&self) -> f64 {
+        self.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        f64::max(self.x * width as f64 / self.size.w as f64, 0.0)
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        f64::max(self.y * height as f64 / self.size.h as f64, 0.0)
+    }
+}
+
+impl TouchMotionEvent<X11Input> for X11TouchEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        Some(TouchSlot::new(self.slot))
+    }
+
+    fn x(&self) -> f64 {
+        self.x
+    }
+
+    fn y(&self) -> f64 {
+        self.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        f64::max(self.x * width as f64 / self.size.w as f64, 0.0)
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        f64::max(self.y * height as f64 / self.size.h as f64, 0.0)
+    }
+}
+
+/// X11-Backend internal event wrapping `XInput2` touch end events into a [`TouchUpEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+pub struct X11TouchUpEvent {
+    pub(crate) time: u32,
+    pub(crate) slot: u64,
+    pub(crate) device: X11InputDevice,
+    pub(crate) window: Weak<WindowInner>,
+    pub(crate) window_id: u32,
+}
+
+impl X11TouchUpEvent {
+    /// Returns a temporary reference to the window belonging to this event.
+    ///
+    /// Returns None if the window is not alive anymore.
+    pub fn window(&self) -> Option<impl AsRef<Window> + '_> {
+        self.window.upgrade().map(Window).map(WindowTemporary)
+    }
+
+    /// Returns the XID of the window belonging to this event.
+    ///
+    /// Unlike [`window`](Self::window), this is available after the window was destroyed.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
+}
+
+impl input::Event<X11Input> for X11TouchUpEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> X11InputDevice {
+        self.device.clone()
+    }
+}
+
+impl TouchUpEvent<X11Input> for X11TouchUpEvent {
+    fn slot(&self) -> Option<TouchSlot> {
+        Some(TouchSlot::new(self.slot))
+    }
+}
+
+/// X11-Backend internal event marking the end of a set of touch events, see [`TouchFrameEvent`]
+///
+/// X11 does not group touch events, so a frame is emitted after every touch event.
+#[allow(missing_docs)]
+#[derive(Debug, Clone)]
+pub struct X11TouchFrameEvent {
+    pub(crate) time: u32,
+    pub(crate) device: X11InputDevice,
+    pub(crate) window_id: u32,
+}
+
+impl X11TouchFrameEvent {
+    /// Returns the XID of the window belonging to this event.
+    pub fn window_id(&self) -> u32 {
+        self.window_id
+    }
+}
+
+impl input::Event<X11Input> for X11TouchFrameEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> X11InputDevice {
+        self.device.clone()
+    }
+}
+
+impl TouchFrameEvent<X11Input> for X11TouchFrameEvent {}
+
 impl InputBackend for X11Input {
-    type Device = X11VirtualDevice;
+    type Device = X11InputDevice;
     type KeyboardKeyEvent = X11KeyboardInputEvent;
     type PointerAxisEvent = X11MouseWheelEvent;
     type PointerButtonEvent = X11MouseInputEvent;
 
-    type PointerMotionEvent = UnusedEvent;
+    type PointerMotionEvent = X11PointerMotionEvent;
 
     type PointerMotionAbsoluteEvent = X11MouseMovedEvent;
 
-    type TouchDownEvent = UnusedEvent;
-    type TouchUpEvent = UnusedEvent;
-    type TouchMotionEvent = UnusedEvent;
+    type TouchDownEvent = X11TouchEvent;
+    type TouchUpEvent = X11TouchUpEvent;
+    type TouchMotionEvent = X11TouchEvent;
     type TouchCancelEvent = UnusedEvent;
-    type TouchFrameEvent = UnusedEvent;
+    type TouchFrameEvent = X11TouchFrameEvent;
     type TabletToolAxisEvent = UnusedEvent;
     type TabletToolProximityEvent = UnusedEvent;
     type TabletToolTipEvent = UnusedEvent;
@@ -60,9 +60,33 @@
 //! }
 //! ```
 //!
+//! ## Multiple windows
+//!
+//! Any number of windows may be created from one [`X11Handle`] using the [`WindowBuilder`], each
+//! with its own surface, e.g. to emulate several outputs. All events of the backend are tagged with
+//! the XID of the window they apply to, see [`X11Event::window_id`]. Input events also give access
+//! to the window they originate from, so pointer positions may be mapped to the matching output.
+//!
+//! ## Input devices
+//!
+//! Core X11 input is associated with a single virtual [`X11InputDevice`]. If the X server supports
+//! `XInput2` 2.2, pointer events are instead attributed to the physical device generating them, and
+//! the backend additionally reports touch events, smooth scrolling with
+//! [`AxisSource::Finger`](crate::backend::input::AxisSource::Finger) or
+//! [`AxisSource::Continuous`](crate::backend::input::AxisSource::Continuous) and relative pointer
+//! motion, which keeps being reported while the pointer is confined using [`Window::grab_pointer`].
+//!
 //! ## EGL
 //!
 //! When using [`EGL`](crate::backend::egl), an [`X11Surface`] may be used to create an [`EGLDisplay`](crate::backend::egl::EGLDisplay).
+//!
+//! ## Without direct rendering
+//!
+//! The [`X11Surface`] requires the `DRI3` and `Present` extensions, which are not available in `Xvfb` or
+//! on remote X servers. In that case [`X11Handle::create_shm_surface`] creates an [`X11ShmSurface`]
+//! instead, which presents buffers rendered by the [`SoftwareRenderer`](crate::backend::renderer::software::SoftwareRenderer)
+//! through the `MIT-SHM` extension, or core X11 requests if shared memory is not available.
+//! [`X11Handle::is_direct_rendering_capable`] may be used to choose between both.
 
 /*
 A note for future contributors and maintainers:
@@ -72,6 +96,8 @@ Do take a look at some useful reading in order to understand this backend more d
 DRI3 protocol documentation: https://gitlab.freedesktop.org/xorg/proto/xorgproto/-/blob/master/dri3proto.txt
 
 Present protocol documentation: https://gitlab.freedesktop.org/xorg/proto/xorgproto/-/blob/master/presentproto.txt
+
+XInput2 protocol documentation: https://gitlab.freedesktop.org/xorg/proto/xorgproto/-/blob/master/specs/XI2proto.txt
 */
 
 mod buffer;
@@ -79,15 +105,18 @@ mod error;
 #[macro_use]
 mod extension;
 mod input;
+#[cfg(feature = "renderer_software")]
+mod shm;
 mod surface;
 mod window_inner;
+mod xinput;
 
 use crate::{
     backend::{
         allocator::Swapchain,
         drm::{node::path_to_type, CreateDrmNodeError, DrmNode, NodeType},
         egl::{native::X11DefaultDisplay, EGLDevice, EGLDisplay, Error as EGLError},
-        input::{Axis, ButtonState, InputEvent, KeyState},
+        input::{Axis, AxisSource, ButtonState, InputEvent, KeyState},
     },
     utils::{x11rb::X11Source, Logical, Size},
 };
@@ -119,10 +148,12 @@ use x11rb::{
     rust_connection::{ReplyError, RustConnection},
 };
 
-use self::{extension::Extensions, window_inner::WindowInner};
+use self::{extension::Extensions, window_inner::WindowInner, xinput::XInputState};
 
 pub use self::error::*;
 pub use self::input::*;
+#[cfg(feature = "renderer_software")]
+pub use self::shm::X11ShmSurface;
 pub use self::surface::*;
 
 /// An event emitted by the X11 backend.
@@ -160,6 +191,30 @@ pub enum X11Event {
     },
 }
 
+impl X11Event {
+    /// Returns the XID of the window the event applies to.
+    ///
+    /// Returns [`None`] for events applying to the whole backend, like added or removed input devices.
+    pub fn window_id(&self) -> Option<u32> {
+        match self {
+            X11Event::Refresh { window_id }
+            | X11Event::Resized { window_id, .. }
+            | X11Event::PresentCompleted { window_id }
+            | X11Event::CloseRequested { window_id } => Some(*window_id),
+            X11Event::Input(InputEvent::Keyboard { event }) => Some(event.window_id()),
+            X11Event::Input(InputEvent::PointerAxis { event }) => Some(event.window_id()),
+            X11Event::Input(InputEvent::PointerButton { event }) => Some(event.window_id()),
+            X11Event::Input(InputEvent::PointerMotion { event }) => Some(event.window_id()),
+            X11Event::Input(InputEvent::PointerMotionAbsolute { event }) => Some(event.window_id()),
+            X11Event::Input(InputEvent::TouchDown { event }) => Some(event.window_id()),
+            X11Event::Input(InputEvent::TouchMotion { event }) => Some(event.window_id()),
+            X11Event::Input(InputEvent::TouchUp { event }) => Some(event.window_id()),
+            X11Event::Input(InputEvent::TouchFrame { event }) => Some(event.window_id()),
+            X11Event::Input(_) => None,
+        }
+    }
+}
+
 /// Represents an active connection to the X to manage events on the Window provided by the backend.
 #[derive(Debug)]
 pub struct X11Backend {
@@ -243,6 +298,22 @@ impl X11Backend {
             logger.clone(),
         );
 
+        let xinput = if extensions.xinput >= Some((2, 2)) {
+            match XInputState::new(&*connection, screen.root) {
+                Ok(xinput) => Some(xinput),
+                Err(err) => {
+                    slog::warn!(
+                        logger,
+                        "Failed to initialize XInput2, falling back to core input: {}",
+                        err
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
         let inner = X11Inner {
             log: logger.clone(),
             connection: connection.clone(),
@@ -256,6 +327,8 @@ impl X11Backend {
             depth,
             visual_id,
             devices: false,
+            xinput,
+            pointer_focus: None,
         };
 
         Ok(X11Backend {
@@ -310,6 +383,14 @@ impl X11Handle {
         self.inner.lock().unwrap().window_format
     }
 
+    /// Returns `true` if the X server supports the extensions needed to create an [`X11Surface`].
+    ///
+    /// This does not guarantee [`X11Handle::drm_node`] succeeds.
+    pub fn is_direct_rendering_capable(&self) -> bool {
+        let extensions = self.inner.lock().unwrap().extensions;
+        extensions.dri3.is_some() && extensions.present.is_some()
+    }
+
     /// Returns the DRM node the X server uses for direct rendering.
     ///
     /// The DRM node may be used to create a [`gbm::Device`] to allocate buffers.
@@ -367,6 +448,8 @@ impl X11Handle {
             return Err(X11Error::InvalidWindow);
         }
 
+        inner_guard.require_direct_rendering()?;
+
         let mut modifiers = modifiers.collect::<Vec<_>>();
         // older dri3 versions do only support buffers with one plane.
         // we need to make sure, we don't accidently allocate buffers with more.
@@ -397,6 +480,62 @@ impl X11Handle {
         })
     }
 
+    /// Creates a surface that presents buffers rendered on the cpu to the window.
+    ///
+    /// Unlike [`X11Handle::create_surface`] this does not require the X server to be capable of direct
+    /// rendering.
+    ///
+    /// This will fail if the window has already been used to create a surface.
+    #[cfg(feature = "renderer_software")]
+    pub fn create_shm_surface(&self, window: &Window) -> Result<X11ShmSurface, X11Error> {
+        let has_resize = { window.0.resize.lock().unwrap().is_some() };
+
+        if has_resize {
+            return Err(X11Error::SurfaceExists);
+        }
+
+        let inner_guard = self.inner.lock().unwrap();
+
+        // Fail if the window is not managed by this backend or is destroyed
+        if !inner_guard.windows.contains_key(&window.id()) {
+            return Err(X11Error::InvalidWindow);
+        }
+
+        // MIT-SHM 1.2 is needed to share memory using file descriptors.
+        let use_shm = inner_guard.extensions.shm >= Some((1, 2));
+        if !use_shm {
+            slog::warn!(
+                &self.log,
+                "MIT-SHM 1.2 is not available, buffers are sent over the connection"
+            );
+        }
+
+        let gc = shm::create_gc(&self.connection, window)?;
+        let size = window.size();
+        let (sender, recv) = mpsc::channel();
+
+        {
+            let mut resize = window.0.resize.lock().unwrap();
+            *resize = Some(sender);
+        }
+
+        Ok(X11ShmSurface {
+            connection: Arc::downgrade(&inner_guard.connection),
+            window: Arc::downgrade(&window.0),
+            resize: recv,
+            log: self.log.clone(),
+            gc,
+            use_shm,
+            // Pixels are sent in the byte order of the X server, which may differ for remote servers
+            byte_order: inner_guard.connection.setup().image_byte_order,
+            segment: None,
+            image: crate::backend::renderer::software::SoftwareImage::new(
+                (size.w as i32, size.h as i32).into(),
+            ),
+            age: 0,
+        })
+    }
+
     /// Get a temporary reference to a window by its XID
     pub fn window_ref_from_id(&self, id: u32) -> Option<impl AsRef<Window> + '_> {
         X11Inner::window_ref_from_id(&self.inner, &id)
@@ -504,6 +643,21 @@ impl Window {
         self.0.set_cursor_visible(visible);
     }
 
+    /// Grabs the pointer, confining it to the window.
+    ///
+    /// While the pointer is grabbed, relative motion is still reported through
+    /// [`InputEvent::PointerMotion`] when the X server supports `XInput2`, which allows the compositor
+    /// to implement pointer locking. Returns `false` if the pointer could not be grabbed, e.g. because
+    /// another client holds a grab or the window is not viewable.
+    pub fn grab_pointer(&self) -> bool {
+        self.0.grab_pointer()
+    }
+
+    /// Releases a pointer grab taken with [`grab_pointer`](Self::grab_pointer).
+    pub fn ungrab_pointer(&self) {
+        self.0.ungrab_pointer();
+    }
+
     /// Returns the XID of the window.
     pub fn id(&self) -> u32 {
         self.0.id
@@ -585,6 +739,7 @@ atom_manager! {
         _NET_WM_NAME,
         UTF8_STRING,
         _SMITHAY_X11_BACKEND_CLOSE,
+        _SMITHAY_X11_PUT_IMAGE_COMPLETE,
     }
 }
 
@@ -602,126 +757,261 @@ pub(crate) struct X11Inner {
     depth: x11::xproto::Depth,
     visual_id: u32,
     devices: bool,
+    xinput: Option<XInputState>,
+    /// Window the pointer is inside of
+    pointer_focus: Option<u32>,
 }
 
 impl X11Inner {
+    fn require_direct_rendering(&self) -> Result<(), X11Error> {
+        if self.extensions.dri3.is_none() {
+            return Err(MissingExtensionError::NotFound {
+                name: x11::dri3::X11_EXTENSION_NAME,
+                major: 1,
+                minor: 0,
+            }
+            .into());
+        }
+        if self.extensions.present.is_none() {
+            return Err(MissingExtensionError::NotFound {
+                name: x11::present::X11_EXTENSION_NAME,
+                major: 1,
+                minor: 0,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
     fn window_ref_from_id(inner: &Arc<Mutex<X11Inner>>, id: &u32) -> Option<Weak<WindowInner>> {
         let mut inner = inner.lock().unwrap();
         inner.windows.retain(|_, weak| weak.upgrade().is_some());
         inner.windows.get(id).cloned()
     }
 
+    fn xinput_device(inner: &Arc<Mutex<X11Inner>>, id: u16) -> X11InputDevice {
+        let inner = inner.lock().unwrap();
+        inner
+            .xinput
+            .as_ref()
+            .map(|xinput| xinput.device(id))
+            .unwrap_or_else(X11InputDevice::virtual_device)
+    }
+
     fn process_event<F>(inner: &Arc<Mutex<X11Inner>>, log: &Logger, event: x11::Event, callback: &mut F)
     where
         F: FnMut(X11Event, &mut ()),
     {
-        {
+        let devices = {
             let mut inner = inner.lock().unwrap();
-            if !inner.windows.is_empty() && !inner.devices {
-                callback(
-                    Input(InputEvent::DeviceAdded {
-                        device: X11VirtualDevice,
-                    }),
-                    &mut (),
-                );
-                inner.devices = true;
-            } else if inner.windows.is_empty() && inner.devices {
-                callback(
-                    Input(InputEvent::DeviceRemoved {
-                        device: X11VirtualDevice,
-                    }),
-                    &mut (),
-                );
-                inner.devices = false;
+            let added = !inner.windows.is_empty() && !inner.devices;
+            let removed = inner.windows.is_empty() && inner.devices;
+            if added || removed {
+                inner.devices = added;
+                let mut devices = vec![X11InputDevice::virtual_device()];
+                if let Some(xinput) = inner.xinput.as_ref() {
+                    devices.extend(xinput.devices().cloned());
+                }
+                Some((added, devices))
+            } else {
+                None
             }
-        }
+        };
 
         use self::X11Event::Input;
 
+        // Do not hold the lock while emitting the device events.
+        if let Some((added, devices)) = devices {
+            for device in devices {
+                let event = if added {
+                    InputEvent::DeviceAdded { device }
+                } else {
+                    InputEvent::DeviceRemoved { device }
+                };
+                callback(Input(event), &mut ());
+            }
+        }
+
         // If X11 is deadlocking somewhere here, make sure you drop your mutex guards.
 
         match event {
             x11::Event::ButtonPress(button_press) => {
                 if let Some(window) = X11Inner::window_ref_from_id(inner, &button_press.event) {
-                    // X11 decided to associate scroll wheel with a button, 4, 5, 6 and 7 for
-                    // up, down, right and left. For scrolling, a press event is emitted and a
-                    // release is them immediately followed for scrolling. This means we can
-                    // ignore release for scrolling.
-
-                    // Ideally we would use `ButtonIndex` from XCB, however it does not cover 6 and 7
-                    // for horizontal scroll and does not work nicely in match statements, so we
-                    // use magic constants here:
-                    //
-                    // 1 => MouseButton::Left
-                    // 2 => MouseButton::Middle
-                    // 3 => MouseButton::Right
-                    // 4 => Axis::Vertical +1.0
-                    // 5 => Axis::Vertical -1.0
-                    // 6 => Axis::Horizontal -1.0
-                    // 7 => Axis::Horizontal +1.0
-                    // Others => ??
-
-                    // Scrolling
-                    if button_press.detail >= 4 && button_press.detail <= 7 {
+                    button_event(
+                        callback,
+                        window,
+                        button_press.event,
+                        button_press.time,
+                        button_press.detail as u32,
+                        ButtonState::Pressed,
+                        X11InputDevice::virtual_device(),
+                    );
+                }
+            }
+
+            x11::Event::ButtonRelease(button_release) => {
+                if let Some(window) = X11Inner::window_ref_from_id(inner, &button_release.event) {
+                    button_event(
+                        callback,
+                        window,
+                        button_release.event,
+                        button_release.time,
+                        button_release.detail as u32,
+                        ButtonState::Released,
+                        X11InputDevice::virtual_device(),
+                    );
+                }
+            }
+
+            x11::Event::XinputButtonPress(event) | x11::Event::XinputButtonRelease(event) => {
+                // Wheel buttons emulated for scroll valuators are reported through motion events.
+                if xinput::is_emulated(event.flags) {
+                    return;
+                }
+
+                if let Some(window) = X11Inner::window_ref_from_id(inner, &event.event) {
+                    let device = X11Inner::xinput_device(inner, event.sourceid);
+                    let state = if event.event_type == x11::xinput::BUTTON_PRESS_EVENT {
+                        ButtonState::Pressed
+                    } else {
+                        ButtonState::Released
+                    };
+                    button_event(
+                        callback,
+                        window,
+                        event.event,
+                        event.time,
+                        event.detail,
+                        state,
+                        device,
+                    );
+                }
+            }
+
+            x11::Event::XinputMotion(event) => {
+                if let Some(window) =
+                    X11Inner::window_ref_from_id(inner, &event.event).and_then(|w| w.upgrade())
+                {
+                    let (device, (amounts, source, moved)) = {
+                        let mut inner = inner.lock().unwrap();
+                        match inner.xinput.as_mut() {
+                            Some(xinput) => (xinput.device(event.sourceid), xinput.motion(&event)),
+                            None => return,
+                        }
+                    };
+
+                    if moved {
+                        let window_size = { *window.size.lock().unwrap() };
+
                         callback(
-                            Input(InputEvent::PointerAxis {
-                                event: X11MouseWheelEvent {
-                                    time: button_press.time,
-                                    axis: match button_press.detail {
-                                        // Up | Down
-                                        4 | 5 => Axis::Vertical,
-
-                                        // Right | Left
-                                        6 | 7 => Axis::Horizontal,
-
-                                        _ => unreachable!(),
-                                    },
-                                    amount: match button_press.detail {
-                                        // Up | Right
-                                        4 | 7 => 1.0,
-
-                                        // Down | Left
-                                        5 | 6 => -1.0,
-
-                                        _ => unreachable!(),
-                                    },
-                                    window,
+                            Input(InputEvent::PointerMotionAbsolute {
+                                event: X11MouseMovedEvent {
+                                    time: event.time,
+                                    x: xinput::fp1616(event.event_x),
+                                    y: xinput::fp1616(event.event_y),
+                                    size: window_size,
+                                    device: device.clone(),
+                                    window: Arc::downgrade(&window),
+                                    window_id: event.event,
                                 },
                             }),
                             &mut (),
-                        )
-                    } else {
+                        );
+                    }
+
+                    for (axis, amount) in amounts {
                         callback(
-                            Input(InputEvent::PointerButton {
-                                event: X11MouseInputEvent {
-                                    time: button_press.time,
-                                    raw: button_press.detail as u32,
-                                    state: ButtonState::Pressed,
-                                    window,
+                            Input(InputEvent::PointerAxis {
+                                event: X11MouseWheelEvent {
+                                    time: event.time,
+                                    axis,
+                                    amount,
+                                    source,
+                                    device: device.clone(),
+                                    window: Arc::downgrade(&window),
+                                    window_id: event.event,
                                 },
                             }),
                             &mut (),
-                        )
+                        );
                     }
                 }
             }
 
-            x11::Event::ButtonRelease(button_release) => {
-                // Ignore release tick because this event is always sent immediately after the press
-                // tick for scrolling and the backend will dispatch release event automatically during
-                // the press event.
-                if button_release.detail >= 4 && button_release.detail <= 7 {
-                    return;
-                }
+            x11::Event::XinputRawMotion(event) => {
+                // Raw events are not associated with a window, attribute them to the window containing
+                // the pointer.
+                let (window_id, device, (delta_x, delta_y)) = {
+                    let inner = inner.lock().unwrap();
+                    let xinput = match inner.xinput.as_ref() {
+                        Some(xinput) => xinput,
+                        None => return,
+                    };
+                    match (inner.pointer_focus, xinput.raw_motion(&event)) {
+                        (Some(window_id), Some(delta)) => (window_id, xinput.device(event.sourceid), delta),
+                        _ => return,
+                    }
+                };
 
-                if let Some(window) = X11Inner::window_ref_from_id(inner, &button_release.event) {
+                if let Some(window) = X11Inner::window_ref_from_id(inner, &window_id) {
                     callback(
-                        Input(InputEvent::PointerButton {
-                            event: X11MouseInputEvent {
-                                time: button_release.time,
-                                raw: button_release.detail as u32,
-                                state: ButtonState::Released,
+                        Input(InputEvent::PointerMotion {
+                            event: X11PointerMotionEvent {
+                                time: event.time,
+                                delta_x,
+                                delta_y,
+                                device,
                                 window,
+                                window_id,
+                            },
+                        }),
+                        &mut (),
+                    );
+                }
+            }
+
+            x11::Event::XinputTouchBegin(event)
+            | x11::Event::XinputTouchUpdate(event)
+            | x11::Event::XinputTouchEnd(event) => {
+                if let Some(window) =
+                    X11Inner::window_ref_from_id(inner, &event.event).and_then(|w| w.upgrade())
+                {
+                    let device = X11Inner::xinput_device(inner, event.sourceid);
+                    let window_size = { *window.size.lock().unwrap() };
+                    let slot = event.detail as u64;
+
+                    let touch_event = || X11TouchEvent {
+                        time: event.time,
+                        slot,
+                        x: xinput::fp1616(event.event_x),
+                        y: xinput::fp1616(event.event_y),
+                        size: window_size,
+                        device: device.clone(),
+                        window: Arc::downgrade(&window),
+                        window_id: event.event,
+                    };
+                    let input_event = match event.event_type {
+                        x11::xinput::TOUCH_BEGIN_EVENT => InputEvent::TouchDown { event: touch_event() },
+                        x11::xinput::TOUCH_UPDATE_EVENT => InputEvent::TouchMotion { event: touch_event() },
+                        _ => InputEvent::TouchUp {
+                            event: X11TouchUpEvent {
+                                time: event.time,
+                                slot,
+                                device: device.clone(),
+                                window: Arc::downgrade(&window),
+                                window_id: event.event,
+                            },
+                        },
+                    };
+                    callback(Input(input_event), &mut ());
+
+                    // Every touch event is a frame of its own.
+                    callback(
+                        Input(InputEvent::TouchFrame {
+                            event: X11TouchFrameEvent {
+                                time: event.time,
+                                device,
+                                window_id: event.event,
                             },
                         }),
                         &mut (),
@@ -729,6 +1019,36 @@ impl X11Inner {
                 }
             }
 
+            x11::Event::XinputHierarchy(event) => {
+                let (added, removed) = {
+                    let mut inner = inner.lock().unwrap();
+                    let devices = inner.devices;
+                    let X11Inner {
+                        xinput, connection, ..
+                    } = &mut *inner;
+                    let xinput = match xinput.as_mut() {
+                        Some(xinput) => xinput,
+                        None => return,
+                    };
+                    match xinput.hierarchy_changed(&**connection, &event) {
+                        // Devices are only announced while windows exist.
+                        Ok(_) if !devices => return,
+                        Ok(changes) => changes,
+                        Err(err) => {
+                            error!(log, "Failed to query the changed input devices: {}", err);
+                            return;
+                        }
+                    }
+                };
+
+                for device in added {
+                    callback(Input(InputEvent::DeviceAdded { device }), &mut ());
+                }
+                for device in removed {
+                    callback(Input(InputEvent::DeviceRemoved { device }), &mut ());
+                }
+            }
+
             x11::Event::KeyPress(key_press) => {
                 if let Some(window) = X11Inner::window_ref_from_id(inner, &key_press.event) {
                     // Do not hold the lock.
@@ -747,6 +1067,7 @@ impl X11Inner {
                                 count,
                                 state: KeyState::Pressed,
                                 window,
+                                window_id: key_press.event,
                             },
                         }),
                         &mut (),
@@ -780,6 +1101,7 @@ impl X11Inner {
                                 count,
                                 state: KeyState::Released,
                                 window,
+                                window_id: key_release.event,
                             },
                         }),
                         &mut (),
@@ -804,7 +1126,9 @@ impl X11Inner {
                                 x,
                                 y,
                                 size: window_size,
+                                device: X11InputDevice::virtual_device(),
                                 window: Arc::downgrade(&window),
+                                window_id: motion_notify.event,
                             },
                         }),
                         &mut (),
@@ -851,6 +1175,12 @@ impl X11Inner {
                     X11Inner::window_ref_from_id(inner, &enter_notify.event).and_then(|w| w.upgrade())
                 {
                     window.cursor_enter();
+
+                    let mut inner = inner.lock().unwrap();
+                    inner.pointer_focus = Some(enter_notify.event);
+                    if let Some(xinput) = inner.xinput.as_mut() {
+                        xinput.reset_scroll();
+                    }
                 }
             }
 
@@ -859,6 +1189,11 @@ impl X11Inner {
                     X11Inner::window_ref_from_id(inner, &leave_notify.event).and_then(|w| w.upgrade())
                 {
                     window.cursor_leave();
+
+                    let mut inner = inner.lock().unwrap();
+                    if inner.pointer_focus == Some(leave_notify.event) {
+                        inner.pointer_focus = None;
+                    }
                 }
             }
 
@@ -866,7 +1201,15 @@ impl X11Inner {
                 if let Some(window) =
                     X11Inner::window_ref_from_id(inner, &client_message.window).and_then(|w| w.upgrade())
                 {
-                    if client_message.data.as_data32()[0] == window.atoms.WM_DELETE_WINDOW
+                    if client_message.type_ == window.atoms._SMITHAY_X11_PUT_IMAGE_COMPLETE {
+                        // Sent to ourselves after presenting a buffer without MIT-SHM
+                        (callback)(
+                            X11Event::PresentCompleted {
+                                window_id: client_message.window,
+                            },
+                            &mut (),
+                        );
+                    } else if client_message.data.as_data32()[0] == window.atoms.WM_DELETE_WINDOW
                     // Destroy the window?
                     {
                         (callback)(
@@ -905,6 +1248,17 @@ impl X11Inner {
                 }
             }
 
+            x11::Event::ShmCompletion(completion)
+                if X11Inner::window_ref_from_id(inner, &completion.drawable).is_some() =>
+            {
+                (callback)(
+                    X11Event::PresentCompleted {
+                        window_id: completion.drawable,
+                    },
+                    &mut (),
+                );
+            }
+
             x11::Event::PresentIdleNotify(_) => {
                 // Pixmap is reference counted in the X server, so we do not need to take and drop.
             }
@@ -918,6 +1272,92 @@ impl X11Inner {
     }
 }
 
+/// Emit the event of a pressed or released pointer button
+fn button_event<F>(
+    callback: &mut F,
+    window: Weak<WindowInner>,
+    window_id: u32,
+    time: u32,
+    button: u32,
+    state: ButtonState,
+    device: X11InputDevice,
+) where
+    F: FnMut(X11Event, &mut ()),
+{
+    // X11 decided to associate scroll wheel with a button, 4, 5, 6 and 7 for
+    // up, down, right and left. For scrolling, a press event is emitted and a
+    // release is them immediately followed for scrolling. This means we can
+    // ignore release for scrolling.
+
+    // Ideally we would use `ButtonIndex` from XCB, however it does not cover 6 and 7
+    // for horizontal scroll and does not work nicely in match statements, so we
+    // use magic constants here:
+    //
+    // 1 => MouseButton::Left
+    // 2 => MouseButton::Middle
+    // 3 => MouseButton::Right
+    // 4 => Axis::Vertical +1.0
+    // 5 => Axis::Vertical -1.0
+    // 6 => Axis::Horizontal -1.0
+    // 7 => Axis::Horizontal +1.0
+    // Others => ??
+
+    // Scrolling
+    if (4..=7).contains(&button) {
+        // Ignore release tick because this event is always sent immediately after the press
+        // tick for scrolling and the backend will dispatch release event automatically during
+        // the press event.
+        if state == ButtonState::Released {
+            return;
+        }
+
+        callback(
+            X11Event::Input(InputEvent::PointerAxis {
+                event: X11MouseWheelEvent {
+                    time,
+                    axis: match button {
+                        // Up | Down
+                        4 | 5 => Axis::Vertical,
+
+                        // Right | Left
+                        6 | 7 => Axis::Horizontal,
+
+                        _ => unreachable!(),
+                    },
+                    amount: match button {
+                        // Up | Right
+                        4 | 7 => 1.0,
+
+                        // Down | Left
+                        5 | 6 => -1.0,
+
+                        _ => unreachable!(),
+                    },
+                    source: AxisSource::Wheel,
+                    device,
+                    window,
+                    window_id,
+                },
+            }),
+            &mut (),
+        )
+    } else {
+        callback(
+            X11Event::Input(InputEvent::PointerButton {
+                event: X11MouseInputEvent {
+                    time,
+                    raw: button,
+                    state,
+                    device,
+                    window,
+                    window_id,
+                },
+            }),
+            &mut (),
+        )
+    }
+}
+
 fn egl_init(_: &X11Inner) -> Result<DrmNode, EGLInitError> {
     let display = EGLDisplay::new(&X11DefaultDisplay, None)?;
     let device = EGLDevice::device_for_display(&display)?;
@@ -934,6 +1374,7 @@ fn egl_init(_: &X11Inner) -> Result<DrmNode, EGLInitError> {
 }
 
 fn dri3_init(x11: &X11Inner) -> Result<DrmNode, X11Error> {
+    x11.require_direct_rendering()?;
     let connection = &x11.connection;
 
     // Determine which drm-device the Display is using.
@@ -0,0 +1,377 @@
+//! Presentation of cpu rendered buffers to an X11 window.
+//!
+//! This is used if the X server is not capable of direct rendering, e.g. inside of `Xvfb` or when
+//! connected to a remote X server.
+//!
+//! The contents of the window are rendered into a [`SoftwareImage`] and copied into a shared memory
+//! segment, which is presented using the [`MIT-SHM`](x11rb::protocol::shm) extension. If the extension
+//! is not available or the segment cannot be shared with the X server (which is the case for remote
+//! X servers), the pixels are sent over the connection using core `PutImage` requests.
+//!
+//! In both cases the X server notifies the backend when it finished reading the buffer, which is
+//! emitted as [`X11Event::PresentCompleted`](super::X11Event::PresentCompleted).
+
+use std::{
+    ffi::CStr,
+    os::unix::io::RawFd,
+    ptr::{self, NonNull},
+    sync::{mpsc::Receiver, Arc, Weak},
+};
+
+use drm_fourcc::DrmFourcc;
+use nix::{
+    libc::off_t,
+    sys::{
+        memfd::{memfd_create, MemFdCreateFlag},
+        mman::{mmap, munmap, MapFlags, ProtFlags},
+    },
+    unistd::{close, ftruncate},
+};
+use slog::{warn, Logger};
+use x11rb::{
+    connection::{Connection, RequestConnection},
+    protocol::{
+        shm::ConnectionExt as _,
+        xproto::{ClientMessageEvent, ConnectionExt as _, CreateGCAux, EventMask, ImageFormat, ImageOrder},
+    },
+    rust_connection::RustConnection,
+    utils::RawFdContainer,
+};
+
+use crate::{
+    backend::{
+        renderer::software::SoftwareImage,
+        x11::{window_inner::WindowInner, AllocateBuffersError, Window, WindowTemporary, X11Error},
+    },
+    utils::{Logical, Size},
+};
+
+/// An X11 surface which presents buffers rendered on the cpu.
+///
+/// The surface is created using [`X11Handle::create_shm_surface`](super::X11Handle::create_shm_surface)
+/// and does not require the X server to support direct rendering.
+#[derive(Debug)]
+pub struct X11ShmSurface {
+    pub(crate) connection: Weak<RustConnection>,
+    pub(crate) window: Weak<WindowInner>,
+    pub(crate) resize: Receiver<Size<u16, Logical>>,
+    pub(crate) log: Logger,
+    pub(crate) gc: u32,
+    pub(crate) use_shm: bool,
+    pub(crate) byte_order: ImageOrder,
+    pub(crate) segment: Option<ShmSegment>,
+    pub(crate) image: SoftwareImage,
+    pub(crate) age: u8,
+}
+
+impl X11ShmSurface {
+    /// Returns the window the surface presents to.
+    ///
+    /// This will return [`None`] if the window has been destroyed.
+    pub fn window(&self) -> Option<impl AsRef<Window> + '_> {
+        self.window.upgrade().map(Window).map(WindowTemporary)
+    }
+
+    /// Returns the format of the buffers the surface accepts.
+    pub fn format(&self) -> DrmFourcc {
+        self.window
+            .upgrade()
+            .map(|window| window.format)
+            .unwrap_or(DrmFourcc::Argb8888)
+    }
+
+    /// Returns `true` if the buffers are presented using the `MIT-SHM` extension.
+    ///
+    /// If this is `false`, the buffers are sent over the connection to the X server.
+    pub fn is_shm(&self) -> bool {
+        self.use_shm
+    }
+
+    /// Returns the buffer that will be presented to the window next and its age.
+    ///
+    /// The buffer may be bound to a [`SoftwareRenderer`](crate::backend::renderer::software::SoftwareRenderer)
+    /// to render. Its contents are kept after [`submit`](Self::submit), so the age is `1` unless the
+    /// window was resized or [`reset_buffers`](Self::reset_buffers) was called.
+    pub fn buffer(&mut self) -> (SoftwareImage, u8) {
+        if let Some(new_size) = self.resize.try_iter().last() {
+            self.resize(new_size);
+        }
+        (self.image.clone(), self.age)
+    }
+
+    /// Submit the buffer to the window.
+    ///
+    /// An [`X11Event::PresentCompleted`](super::X11Event::PresentCompleted) is emitted once the X server
+    /// has finished reading the buffer. The buffer should not be submitted again before.
+    pub fn submit(&mut self) -> Result<(), X11Error> {
+        let connection = match self.connection.upgrade() {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+        let window = self
+            .window
+            .upgrade()
+            .ok_or(AllocateBuffersError::WindowDestroyed)?;
+        let size = self.image.size();
+        if size.w == 0 || size.h == 0 {
+            return Ok(());
+        }
+        let stride = size.w as usize * 4;
+
+        if self.use_shm && self.segment.is_none() {
+            match ShmSegment::new(&connection, stride * size.h as usize) {
+                Ok(segment) => self.segment = Some(segment),
+                Err(err) => {
+                    warn!(
+                        self.log,
+                        "Failed to share memory with the X server, falling back to PutImage: {}", err
+                    );
+                    self.use_shm = false;
+                }
+            }
+        }
+
+        let pixels = self.image.pixels();
+        if let Some(segment) = self.segment.as_mut() {
+            copy_pixels(&pixels, self.byte_order, segment.as_mut_slice());
+            connection.shm_put_image(
+                window.id,
+                self.gc,
+                size.w as u16,
+                size.h as u16,
+                0,
+                0,
+                size.w as u16,
+                size.h as u16,
+                0,
+                0,
+                window.depth.depth,
+                ImageFormat::Z_PIXMAP.into(),
+                true, // Send a completion event once the X server has read the segment
+                segment.seg,
+                0,
+            )?;
+        } else {
+            let mut data = vec![0; stride * size.h as usize];
+            copy_pixels(&pixels, self.byte_order, &mut data);
+            // The image may exceed the maximum request size, so split it into bands of rows.
+            for (y, rows) in bands(size.h as usize, stride, connection.maximum_request_bytes()) {
+                connection.put_image(
+                    ImageFormat::Z_PIXMAP,
+                    window.id,
+                    self.gc,
+                    size.w as u16,
+                    rows as u16,
+                    0,
+                    y as i16,
+                    0,
+                    window.depth.depth,
+                    &data[y * stride..(y + rows) * stride],
+                )?;
+            }
+
+            // Core requests have no completion event. The X server processes requests in order, so
+            // an event sent to ourselves arrives once the image was copied.
+            let event = ClientMessageEvent::new(
+                32,
+                window.id,
+                window.atoms._SMITHAY_X11_PUT_IMAGE_COMPLETE,
+                [0u32; 5],
+            );
+            connection.send_event(false, window.id, EventMask::NO_EVENT, event)?;
+        }
+        drop(pixels);
+        self.age = 1;
+
+        // Flush the connection after presenting to the window to ensure we don't run out of buffer space in the X11 connection.
+        let _ = connection.flush();
+        Ok(())
+    }
+
+    /// Resets the contents of the buffer, e.g. to reset the age value
+    pub fn reset_buffers(&mut self) {
+        self.age = 0;
+    }
+
+    fn resize(&mut self, size: Size<u16, Logical>) {
+        self.image = SoftwareImage::new((size.w as i32, size.h as i32).into());
+        self.segment = None;
+        self.age = 0;
+    }
+}
+
+impl Drop for X11ShmSurface {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.upgrade() {
+            let _ = connection.free_gc(self.gc);
+        }
+    }
+}
+
+/// Create the graphics context used to present buffers to the window
+pub(crate) fn create_gc(connection: &RustConnection, window: &Window) -> Result<u32, X11Error> {
+    let gc = connection.generate_id()?;
+    // Graphics exposures are not needed, we always redraw the whole window.
+    connection.create_gc(gc, window.id(), &CreateGCAux::new().graphics_exposures(0))?;
+    Ok(gc)
+}
+
+/// A memory segment shared with the X server.
+#[derive(Debug)]
+pub(crate) struct ShmSegment {
+    connection: Weak<RustConnection>,
+    seg: u32,
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl ShmSegment {
+    fn new(connection: &Arc<RustConnection>, len: usize) -> Result<ShmSegment, X11Error> {
+        let name = CStr::from_bytes_with_nul(b"smithay-x11-shm\0").unwrap();
+        let fd = memfd_create(name, MemFdCreateFlag::MFD_CLOEXEC).map_err(shm_error)?;
+        let ptr = match map_memfd(fd, len) {
+            Ok(ptr) => ptr,
+            Err(err) => {
+                let _ = close(fd);
+                return Err(err);
+            }
+        };
+
+        // The file descriptor cannot be passed to remote X servers, which fails here.
+        let seg = match attach_fd(connection, fd) {
+            Ok(seg) => seg,
+            Err(err) => {
+                // Safety: the mapping was never handed out.
+                let _ = unsafe { munmap(ptr.as_ptr() as *mut _, len) };
+                return Err(err);
+            }
+        };
+        Ok(ShmSegment {
+            connection: Arc::downgrade(connection),
+            seg,
+            ptr,
+            len,
+        })
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        // Safety: the mapping is valid and writable for `len` bytes until it is dropped.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        if let Some(connection) = self.connection.upgrade() {
+            let _ = connection.shm_detach(self.seg);
+        }
+        // Safety: the mapping is not accessed anymore.
+        let _ = unsafe { munmap(self.ptr.as_ptr() as *mut _, self.len) };
+    }
+}
+
+fn attach_fd(connection: &RustConnection, fd: RawFd) -> Result<u32, X11Error> {
+    let seg = match connection.generate_id() {
+        Ok(seg) => seg,
+        Err(err) => {
+            let _ = close(fd);
+            return Err(err.into());
+        }
+    };
+    // The X server only reads from the segment.
+    connection
+        .shm_attach_fd(seg, RawFdContainer::new(fd), true)?
+        .check()?;
+    Ok(seg)
+}
+
+fn map_memfd(fd: RawFd, len: usize) -> Result<NonNull<u8>, X11Error> {
+    ftruncate(fd, len as off_t).map_err(shm_error)?;
+    // Safety: the file descriptor refers to a freshly created memfd of the given size.
+    let ptr = unsafe {
+        mmap(
+            ptr::null_mut(),
+            len,
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            MapFlags::MAP_SHARED,
+            fd,
+            0,
+        )
+    }
+    .map_err(shm_error)?;
+    Ok(NonNull::new(ptr as *mut u8).unwrap())
+}
+
+fn shm_error(err: nix::Error) -> X11Error {
+    AllocateBuffersError::SharedMemory(err.into()).into()
+}
+
+/// Copy the pixels into `out`, in the byte order of the images of the X server
+fn copy_pixels(pixels: &[u32], byte_order: ImageOrder, out: &mut [u8]) {
+    for (pixel, out) in pixels.iter().zip(out.chunks_exact_mut(4)) {
+        let bytes = if byte_order == ImageOrder::MSB_FIRST {
+            pixel.to_be_bytes()
+        } else {
+            pixel.to_le_bytes()
+        };
+        out.copy_from_slice(&bytes);
+    }
+}
+
+// size of the fixed part of a PutImage request, the header of the image data
+const PUT_IMAGE_HEADER_BYTES: usize = 24;
+
+/// Split an image into bands of rows fitting into a single `PutImage` request
+///
+/// Returns the first row and the number of rows of each band.
+fn bands(height: usize, stride: usize, max_request_bytes: usize) -> impl Iterator<Item = (usize, usize)> {
+    let max_rows = (max_request_bytes.saturating_sub(PUT_IMAGE_HEADER_BYTES) / stride).max(1);
+    (0..height)
+        .step_by(max_rows)
+        .map(move |y| (y, max_rows.min(height - y)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_byte_order() {
+        let pixels = [0x8011_2233, 0xff00_00ff];
+        let mut out = [0u8; 8];
+
+        copy_pixels(&pixels, ImageOrder::LSB_FIRST, &mut out);
+        assert_eq!(out, [0x33, 0x22, 0x11, 0x80, 0xff, 0x00, 0x00, 0xff]);
+
+        copy_pixels(&pixels, ImageOrder::MSB_FIRST, &mut out);
+        assert_eq!(out, [0x80, 0x11, 0x22, 0x33, 0xff, 0x00, 0x00, 0xff]);
+    }
+
+    #[test]
+    fn put_image_bands() {
+        let stride = 100 * 4;
+
+        // everything fits into a single request
+        assert_eq!(bands(50, stride, 1 << 20).collect::<Vec<_>>(), vec![(0, 50)]);
+
+        // 10 rows per request, the last band is shorter
+        let max = PUT_IMAGE_HEADER_BYTES + 10 * stride + 1;
+        let banded = bands(25, stride, max).collect::<Vec<_>>();
+        assert_eq!(banded, vec![(0, 10), (10, 10), (20, 5)]);
+        assert!(banded
+            .iter()
+            .all(|(_, rows)| PUT_IMAGE_HEADER_BYTES + rows * stride <= max));
+
+        // the bands cover every row exactly once
+        let mut next = 0;
+        for (y, rows) in bands(1080, 1920 * 4, 262_140) {
+            assert_eq!(y, next);
+            next += rows;
+        }
+        assert_eq!(next, 1080);
+
+        // at least one row per request, even if it is too big
+        assert_eq!(bands(2, stride, 16).collect::<Vec<_>>(), vec![(0, 1), (1, 1)]);
+        assert_eq!(bands(0, stride, 1 << 20).count(), 0);
+    }
+}
@@ -11,7 +11,7 @@ A link to the ICCCM Section 4: https://tronche.com/gui/x/icccm/sec-4.html
 */
 use crate::utils::{Logical, Size};
 
-use super::{extension::Extensions, Atoms, Window, X11Error};
+use super::{extension::Extensions, xinput, Atoms, Window, X11Error};
 use drm_fourcc::DrmFourcc;
 use std::sync::{
     atomic::{AtomicU32, AtomicU64},
@@ -24,8 +24,8 @@ use x11rb::{
         present::{self, ConnectionExt as _},
         xfixes::ConnectionExt as _,
         xproto::{
-            self as x11, AtomEnum, ConnectionExt, CreateWindowAux, Depth, EventMask, PropMode, Screen,
-            UnmapNotifyEvent, WindowClass,
+            self as x11, AtomEnum, ConnectionExt, CreateWindowAux, Depth, EventMask, GrabMode, GrabStatus,
+            PropMode, Screen, UnmapNotifyEvent, WindowClass,
         },
     },
     rust_connection::RustConnection,
@@ -138,13 +138,20 @@ impl WindowInner {
             &window_aux,
         )?;
 
-        // We only ever need one event id since we will only ever have one event context.
-        let present_event_id = connection.generate_id()?;
-        connection.present_select_input(
-            present_event_id,
-            window,
-            present::EventMask::COMPLETE_NOTIFY | present::EventMask::IDLE_NOTIFY,
-        )?;
+        if extensions.present.is_some() {
+            // We only ever need one event id since we will only ever have one event context.
+            let present_event_id = connection.generate_id()?;
+            connection.present_select_input(
+                present_event_id,
+                window,
+                present::EventMask::COMPLETE_NOTIFY | present::EventMask::IDLE_NOTIFY,
+            )?;
+        }
+
+        if extensions.xinput >= Some((2, 2)) {
+            // Replaces the core button and motion events of the window.
+            xinput::select_window_events(&*connection, window)?;
+        }
 
         // Send requests to change window properties while we wait for the window creation request to complete.
         let window = WindowInner {
@@ -272,6 +279,44 @@ impl WindowInner {
         }
     }
 
+    pub fn grab_pointer(&self) -> bool {
+        let connection = match self.connection.upgrade() {
+            Some(connection) => connection,
+            None => return false,
+        };
+
+        // Pointer event masks fit into the 16 bits used by the request.
+        let event_mask = u32::from(
+            EventMask::BUTTON_PRESS
+                | EventMask::BUTTON_RELEASE
+                | EventMask::POINTER_MOTION
+                | EventMask::ENTER_WINDOW
+                | EventMask::LEAVE_WINDOW,
+        ) as u16;
+        let reply = connection
+            .grab_pointer(
+                true,
+                self.id,
+                event_mask,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                self.id, // Confine the pointer to the window
+                x11rb::NONE,
+                x11rb::CURRENT_TIME,
+            )
+            .ok()
+            .and_then(|cookie| cookie.reply().ok());
+
+        matches!(reply, Some(reply) if reply.status == GrabStatus::SUCCESS)
+    }
+
+    pub fn ungrab_pointer(&self) {
+        if let Some(connection) = self.connection.upgrade() {
+            let _ = connection.ungrab_pointer(x11rb::CURRENT_TIME);
+            let _ = connection.flush();
+        }
+    }
+
     fn update_cursor<C: ConnectionExt>(&self, connection: &C, visible: bool) {
         let _ = match visible {
             // This generates a Match error if we did not call Show/HideCursor before. Ignore that error.
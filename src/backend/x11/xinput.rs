@@ -0,0 +1,562 @@
+//! Support of the `XInput2` extension.
+//!
+//! Core X11 events do not identify the device they originate from and lack touch, smooth scrolling
+//! and relative motion. If the X server supports `XInput2` 2.2, the backend selects the following
+//! events instead:
+//!
+//! - button, motion and touch events on every window, for all master devices. This replaces the
+//!   core button and motion events of the window.
+//! - raw motion and hierarchy changes on the root window, to receive relative motion and learn about
+//!   new and removed devices.
+//!
+//! Smooth scrolling is reported through scroll valuators of motion events. The X server emulates
+//! scroll valuators for wheel buttons and wheel buttons for scroll valuators, the emulated events
+//! carry the `POINTER_EMULATED` flag and are ignored.
+//!
+//! If you do need to modify any of the logic pertaining to `XInput2`, do ensure you read the
+//! `XI2proto.txt` file (link in the non-public comments of the x11 mod.rs).
+
+use std::collections::HashMap;
+
+use x11rb::{
+    connection::Connection,
+    protocol::xinput::{
+        self, ConnectionExt as _, DeviceClassData, DeviceType, Fp3232, HierarchyMask, PointerEventFlags,
+        ScrollType, TouchMode, ValuatorMode, XIDeviceInfo, XIEventMask,
+    },
+};
+
+use super::{input::X11InputDevice, X11Error};
+use crate::backend::input::{Axis, AxisSource};
+
+/// Amount scrolled by one step of a smooth scrolling device
+const SCROLL_STEP: f64 = 15.0;
+
+/// Select the `XInput2` events of a window of the backend.
+pub(crate) fn select_window_events<C: Connection>(connection: &C, window: u32) -> Result<(), X11Error> {
+    let mask = XIEventMask::BUTTON_PRESS
+        | XIEventMask::BUTTON_RELEASE
+        | XIEventMask::MOTION
+        | XIEventMask::TOUCH_BEGIN
+        | XIEventMask::TOUCH_UPDATE
+        | XIEventMask::TOUCH_END;
+    connection.xinput_xi_select_events(
+        window,
+        &[xinput::EventMask {
+            deviceid: xinput::Device::ALL_MASTER.into(),
+            mask: vec![mask.into()],
+        }],
+    )?;
+    Ok(())
+}
+
+/// Select the `XInput2` events of the root window.
+///
+/// Raw events are only delivered to the root window.
+fn select_root_events<C: Connection>(connection: &C, root: u32) -> Result<(), X11Error> {
+    connection.xinput_xi_select_events(
+        root,
+        &[
+            xinput::EventMask {
+                deviceid: xinput::Device::ALL_MASTER.into(),
+                mask: vec![XIEventMask::RAW_MOTION.into()],
+            },
+            xinput::EventMask {
+                deviceid: xinput::Device::ALL.into(),
+                mask: vec![XIEventMask::HIERARCHY.into()],
+            },
+        ],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug)]
+struct ScrollValuator {
+    number: u16,
+    axis: Axis,
+    increment: f64,
+    last: Option<f64>,
+}
+
+#[derive(Debug)]
+struct XInputDevice {
+    device: X11InputDevice,
+    /// Whether the first two valuators report relative motion
+    relative: bool,
+    /// Whether the device is a touchpad
+    touchpad: bool,
+    scroll: Vec<ScrollValuator>,
+}
+
+impl XInputDevice {
+    /// Returns `None` for devices not generating pointer events
+    fn from_info(info: &XIDeviceInfo) -> Option<XInputDevice> {
+        if info.type_ != DeviceType::SLAVE_POINTER || !info.enabled {
+            return None;
+        }
+
+        let mut device = XInputDevice {
+            device: X11InputDevice {
+                xinput_id: Some(info.deviceid),
+                name: String::from_utf8_lossy(&info.name).into_owned(),
+                touch: false,
+            },
+            relative: false,
+            touchpad: false,
+            scroll: Vec::new(),
+        };
+        for class in &info.classes {
+            match &class.data {
+                DeviceClassData::Valuator(valuator) if valuator.number == 0 => {
+                    device.relative = valuator.mode == ValuatorMode::RELATIVE;
+                }
+                DeviceClassData::Scroll(scroll) => device.scroll.push(ScrollValuator {
+                    number: scroll.number,
+                    axis: if scroll.scroll_type == ScrollType::HORIZONTAL {
+                        Axis::Horizontal
+                    } else {
+                        Axis::Vertical
+                    },
+                    increment: fp3232(&scroll.increment),
+                    last: None,
+                }),
+                DeviceClassData::Touch(touch) => {
+                    // Direct touch devices are touchscreens, dependent ones touchpads.
+                    if touch.mode == TouchMode::DIRECT {
+                        device.device.touch = true;
+                    } else {
+                        device.touchpad = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(device)
+    }
+}
+
+/// State of the `XInput2` devices
+#[derive(Debug)]
+pub(crate) struct XInputState {
+    devices: HashMap<u16, XInputDevice>,
+}
+
+impl XInputState {
+    /// Query the current devices and select the events of the root window
+    pub fn new<C: Connection>(connection: &C, root: u32) -> Result<XInputState, X11Error> {
+        select_root_events(connection, root)?;
+        let devices = connection
+            .xinput_xi_query_device(xinput::Device::ALL)?
+            .reply()?
+            .infos
+            .iter()
+            .filter_map(XInputDevice::from_info)
+            .map(|device| (device.device.xinput_id.unwrap(), device))
+            .collect();
+        Ok(XInputState { devices })
+    }
+
+    /// The devices generating pointer events
+    pub fn devices(&self) -> impl Iterator<Item = &X11InputDevice> {
+        self.devices.values().map(|device| &device.device)
+    }
+
+    /// Returns the device with the given id, or the virtual device if the device is unknown
+    pub fn device(&self, id: u16) -> X11InputDevice {
+        self.devices
+            .get(&id)
+            .map(|device| device.device.clone())
+            .unwrap_or_else(X11InputDevice::virtual_device)
+    }
+
+    /// Forget the last values of the scroll valuators
+    ///
+    /// The values keep changing while the pointer is outside of the windows, so they have to be
+    /// reset when the pointer enters a window again.
+    pub fn reset_scroll(&mut self) {
+        for valuator in self
+            .devices
+            .values_mut()
+            .flat_map(|device| device.scroll.iter_mut())
+        {
+            valuator.last = None;
+        }
+    }
+
+    /// Returns the amounts scrolled by a motion event and their source
+    ///
+    /// Also returns if the event moved the pointer.
+    pub fn motion(&mut self, event: &xinput::MotionEvent) -> (Vec<(Axis, f64)>, AxisSource, bool) {
+        let device = match self.devices.get_mut(&event.sourceid) {
+            Some(device) => device,
+            None => return (Vec::new(), AxisSource::Continuous, true),
+        };
+        let source = if device.touchpad {
+            AxisSource::Finger
+        } else {
+            AxisSource::Continuous
+        };
+        let emulated = is_emulated(event.flags);
+
+        let mut amounts = Vec::new();
+        // Events without valuators, e.g. caused by warping the pointer, move the pointer as well.
+        let mut moved = event.valuator_mask.iter().all(|mask| *mask == 0);
+        for (number, value) in valuators(&event.valuator_mask, &event.axisvalues) {
+            let valuator = match device
+                .scroll
+                .iter_mut()
+                .find(|valuator| valuator.number == number)
+            {
+                Some(valuator) => valuator,
+                None => {
+                    moved = true;
+                    continue;
+                }
+            };
+            // The values are absolute, keep track of them even if the event is emulated.
+            if let Some(last) = valuator.last.replace(value) {
+                if !emulated && value != last {
+                    // Positive values scroll down or right, the backend reports scrolling up or left
+                    // as positive amounts.
+                    amounts.push((valuator.axis, (last - value) / valuator.increment * SCROLL_STEP));
+                }
+            }
+        }
+        (amounts, source, moved)
+    }
+
+    /// Returns the relative motion of a raw motion event
+    pub fn raw_motion(&self, event: &xinput::RawMotionEvent) -> Option<(f64, f64)> {
+        if !self.devices.get(&event.sourceid)?.relative {
+            return None;
+        }
+        let mut delta = (0.0, 0.0);
+        for (number, value) in valuators(&event.valuator_mask, &event.axisvalues) {
+            match number {
+                0 => delta.0 = value,
+                1 => delta.1 = value,
+                _ => {}
+            }
+        }
+        Some(delta)
+    }
+
+    /// Update the devices after a hierarchy change
+    ///
+    /// Returns the added and removed devices.
+    pub fn hierarchy_changed<C: Connection>(
+        &mut self,
+        connection: &C,
+        event: &xinput::HierarchyEvent,
+    ) -> Result<(Vec<X11InputDevice>, Vec<X11InputDevice>), X11Error> {
+        let added_mask = u32::from(HierarchyMask::SLAVE_ADDED | HierarchyMask::DEVICE_ENABLED);
+        let removed_mask = u32::from(HierarchyMask::SLAVE_REMOVED | HierarchyMask::DEVICE_DISABLED);
+
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+        for info in &event.infos {
+            if info.flags & removed_mask != 0 {
+                if let Some(device) = self.devices.remove(&info.deviceid) {
+                    removed.push(device.device);
+                }
+            } else if info.flags & added_mask != 0 && !self.devices.contains_key(&info.deviceid) {
+                let reply = connection.xinput_xi_query_device(info.deviceid)?.reply()?;
+                for device in reply.infos.iter().filter_map(XInputDevice::from_info) {
+                    added.push(device.device.clone());
+                    self.devices.insert(info.deviceid, device);
+                }
+            }
+        }
+        Ok((added, removed))
+    }
+}
+
+/// Returns `true` if the flags of a pointer event mark it as emulated by the X server
+pub(crate) fn is_emulated(flags: u32) -> bool {
+    flags & u32::from(PointerEventFlags::POINTER_EMULATED) != 0
+}
+
+/// Iterate over the numbers and values of the valuators set in a valuator mask
+fn valuators<'a>(mask: &'a [u32], values: &'a [Fp3232]) -> impl Iterator<Item = (u16, f64)> + 'a {
+    (0..mask.len() * 32)
+        .filter(move |bit| mask[bit / 32] & (1 << (bit % 32)) != 0)
+        .zip(values)
+        .map(|(bit, value)| (bit as u16, fp3232(value)))
+}
+
+pub(crate) fn fp1616(value: xinput::Fp1616) -> f64 {
+    value as f64 / 65536.0
+}
+
+fn fp3232(value: &Fp3232) -> f64 {
+    value.integral as f64 + value.frac as f64 / (1u64 << 32) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x11rb::protocol::xinput::{
+        DeviceClass, DeviceClassDataScroll, DeviceClassDataTouch, DeviceClassDataValuator, GroupInfo,
+        ModifierInfo, MotionEvent,
+    };
+
+    const MOUSE: u16 = 10;
+    const TOUCHPAD: u16 = 11;
+
+    fn fp(value: f64) -> Fp3232 {
+        let integral = value.floor();
+        Fp3232 {
+            integral: integral as i32,
+            frac: ((value - integral) * (1u64 << 32) as f64) as u32,
+        }
+    }
+
+    fn class(data: DeviceClassData) -> DeviceClass {
+        DeviceClass {
+            len: 0,
+            sourceid: 0,
+            data,
+        }
+    }
+
+    fn valuator(number: u16, mode: ValuatorMode) -> DeviceClass {
+        class(DeviceClassData::Valuator(DeviceClassDataValuator {
+            number,
+            label: 0,
+            min: fp(0.0),
+            max: fp(0.0),
+            value: fp(0.0),
+            resolution: 0,
+            mode,
+        }))
+    }
+
+    fn scroll(number: u16, scroll_type: ScrollType, increment: f64) -> DeviceClass {
+        class(DeviceClassData::Scroll(DeviceClassDataScroll {
+            number,
+            scroll_type,
+            flags: 0,
+            increment: fp(increment),
+        }))
+    }
+
+    fn device(deviceid: u16, classes: Vec<DeviceClass>) -> XIDeviceInfo {
+        XIDeviceInfo {
+            deviceid,
+            type_: DeviceType::SLAVE_POINTER,
+            attachment: 2,
+            enabled: true,
+            name: b"device".to_vec(),
+            classes,
+        }
+    }
+
+    // a mouse with a wheel, and a touchpad scrolling vertically and horizontally
+    fn state() -> XInputState {
+        let mouse = device(
+            MOUSE,
+            vec![
+                valuator(0, ValuatorMode::RELATIVE),
+                valuator(1, ValuatorMode::RELATIVE),
+                scroll(3, ScrollType::VERTICAL, 1.0),
+            ],
+        );
+        let touchpad = device(
+            TOUCHPAD,
+            vec![
+                valuator(0, ValuatorMode::ABSOLUTE),
+                valuator(1, ValuatorMode::ABSOLUTE),
+                scroll(2, ScrollType::HORIZONTAL, 30.0),
+                scroll(3, ScrollType::VERTICAL, 30.0),
+                class(DeviceClassData::Touch(DeviceClassDataTouch {
+                    mode: TouchMode::DEPENDENT,
+                    num_touches: 2,
+                })),
+            ],
+        );
+        let devices = [mouse, touchpad]
+            .iter()
+            .filter_map(XInputDevice::from_info)
+            .map(|device| (device.device.xinput_id.unwrap(), device))
+            .collect();
+        XInputState { devices }
+    }
+
+    fn motion(sourceid: u16, flags: u32, values: &[(u16, f64)]) -> MotionEvent {
+        let mut valuator_mask = vec![0];
+        for (number, _) in values {
+            valuator_mask[0] |= 1 << number;
+        }
+        MotionEvent {
+            response_type: 0,
+            extension: 0,
+            sequence: 0,
+            length: 0,
+            event_type: xinput::MOTION_EVENT,
+            deviceid: 2,
+            time: 0,
+            detail: 0,
+            root: 0,
+            event: 0,
+            child: 0,
+            root_x: 0,
+            root_y: 0,
+            event_x: 0,
+            event_y: 0,
+            sourceid,
+            flags,
+            mods: ModifierInfo {
+                base: 0,
+                latched: 0,
+                locked: 0,
+                effective: 0,
+            },
+            group: GroupInfo {
+                base: 0,
+                latched: 0,
+                locked: 0,
+                effective: 0,
+            },
+            button_mask: Vec::new(),
+            valuator_mask,
+            axisvalues: values.iter().map(|(_, value)| fp(*value)).collect(),
+        }
+    }
+
+    #[test]
+    fn fixed_point() {
+        assert_eq!(fp3232(&Fp3232 { integral: 3, frac: 0 }), 3.0);
+        assert_eq!(
+            fp3232(&Fp3232 {
+                integral: 1,
+                frac: 1 << 31
+            }),
+            1.5
+        );
+        // the fraction is added to the integral part, also for negative numbers
+        assert_eq!(
+            fp3232(&Fp3232 {
+                integral: -2,
+                frac: 3 << 30
+            }),
+            -1.25
+        );
+
+        assert_eq!(fp1616(0x0001_8000), 1.5);
+        assert_eq!(fp1616(-0x0001_8000), -1.5);
+        assert_eq!(fp1616(0x00ff_4000), 255.25);
+    }
+
+    #[test]
+    fn valuator_mask() {
+        let values = [fp(1.0), fp(2.0), fp(3.0)];
+        let set = valuators(&[0b1010, 1], &values).collect::<Vec<_>>();
+        assert_eq!(set, vec![(1, 1.0), (3, 2.0), (32, 3.0)]);
+    }
+
+    #[test]
+    fn device_info() {
+        let state = state();
+        let mouse = &state.devices[&MOUSE];
+        assert!(mouse.relative);
+        assert!(!mouse.touchpad);
+        assert_eq!(mouse.scroll.len(), 1);
+
+        let touchpad = &state.devices[&TOUCHPAD];
+        assert!(!touchpad.relative);
+        assert!(touchpad.touchpad);
+        // dependent touch devices do not generate touch events
+        assert!(!touchpad.device.touch);
+        assert_eq!(touchpad.scroll[0].axis, Axis::Horizontal);
+        assert_eq!(touchpad.scroll[0].increment, 30.0);
+        assert_eq!(touchpad.scroll[1].axis, Axis::Vertical);
+
+        let mut keyboard = device(12, Vec::new());
+        keyboard.type_ = DeviceType::SLAVE_KEYBOARD;
+        assert!(XInputDevice::from_info(&keyboard).is_none());
+    }
+
+    #[test]
+    fn scroll_delta() {
+        let mut state = state();
+
+        // the first value of a valuator is only its starting point
+        let (amounts, _, moved) = state.motion(&motion(TOUCHPAD, 0, &[(3, 300.0)]));
+        assert!(amounts.is_empty());
+        assert!(!moved);
+
+        // one increment scrolls one step, positive valuator values scroll down
+        let (amounts, _, _) = state.motion(&motion(TOUCHPAD, 0, &[(3, 330.0)]));
+        assert_eq!(amounts, vec![(Axis::Vertical, -SCROLL_STEP)]);
+        let (amounts, _, _) = state.motion(&motion(TOUCHPAD, 0, &[(3, 315.0)]));
+        assert_eq!(amounts, vec![(Axis::Vertical, SCROLL_STEP / 2.0)]);
+
+        // valuators are tracked independently
+        let (amounts, _, _) = state.motion(&motion(TOUCHPAD, 0, &[(2, 0.0), (3, 255.0)]));
+        assert_eq!(amounts, vec![(Axis::Vertical, 2.0 * SCROLL_STEP)]);
+        let (amounts, _, moved) = state.motion(&motion(TOUCHPAD, 0, &[(0, 5.0), (2, -60.0)]));
+        assert_eq!(amounts, vec![(Axis::Horizontal, 2.0 * SCROLL_STEP)]);
+        assert!(moved);
+
+        // emulated events update the value without scrolling
+        let emulated = PointerEventFlags::POINTER_EMULATED.into();
+        let (amounts, _, _) = state.motion(&motion(TOUCHPAD, emulated, &[(3, 195.0)]));
+        assert!(amounts.is_empty());
+        let (amounts, _, _) = state.motion(&motion(TOUCHPAD, 0, &[(3, 225.0)]));
+        assert_eq!(amounts, vec![(Axis::Vertical, -SCROLL_STEP)]);
+    }
+
+    #[test]
+    fn reset_scroll() {
+        let mut state = state();
+        state.motion(&motion(MOUSE, 0, &[(3, 10.0)]));
+        state.motion(&motion(TOUCHPAD, 0, &[(3, 10.0)]));
+
+        // the values changed while the pointer was outside of the window
+        state.reset_scroll();
+        let (amounts, _, _) = state.motion(&motion(MOUSE, 0, &[(3, 20.0)]));
+        assert!(amounts.is_empty());
+        let (amounts, _, _) = state.motion(&motion(TOUCHPAD, 0, &[(3, 400.0)]));
+        assert!(amounts.is_empty());
+
+        let (amounts, _, _) = state.motion(&motion(MOUSE, 0, &[(3, 21.0)]));
+        assert_eq!(amounts, vec![(Axis::Vertical, -SCROLL_STEP)]);
+    }
+
+    #[test]
+    fn axis_source() {
+        let mut state = state();
+        let (_, source, _) = state.motion(&motion(TOUCHPAD, 0, &[(3, 1.0)]));
+        assert_eq!(source, AxisSource::Finger);
+        let (_, source, _) = state.motion(&motion(MOUSE, 0, &[(3, 1.0)]));
+        assert_eq!(source, AxisSource::Continuous);
+        // unknown devices only move the pointer
+        let (amounts, source, moved) = state.motion(&motion(42, 0, &[(3, 1.0)]));
+        assert!(amounts.is_empty());
+        assert_eq!(source, AxisSource::Continuous);
+        assert!(moved);
+    }
+
+    #[test]
+    fn raw_motion() {
+        let state = state();
+        let mut event = xinput::RawMotionEvent {
+            response_type: 0,
+            extension: 0,
+            sequence: 0,
+            length: 0,
+            event_type: xinput::RAW_MOTION_EVENT,
+            deviceid: 2,
+            time: 0,
+            detail: 0,
+            sourceid: MOUSE,
+            flags: 0,
+            valuator_mask: vec![0b11],
+            axisvalues: vec![fp(1.5), fp(-2.0)],
+            axisvalues_raw: vec![fp(1.5), fp(-2.0)],
+        };
+        assert_eq!(state.raw_motion(&event), Some((1.5, -2.0)));
+        event.sourceid = TOUCHPAD;
+        assert_eq!(state.raw_motion(&event), None);
+    }
+}
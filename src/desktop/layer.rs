@@ -0,0 +1,466 @@
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+use wayland_server::{protocol::wl_surface::WlSurface, UserDataMap};
+
+use crate::{
+    desktop::utils::{
+        bbox_from_surface_tree, damage_elements_from_surface_tree, output_leave, output_logical_size,
+        output_update, send_frames_surface_tree, under_from_surface_tree, SurfaceDamageElement,
+    },
+    utils::{Logical, Point, Rectangle, Size},
+    wayland::{
+        compositor::with_states,
+        output::Output,
+        shell::wlr_layer::{self, Anchor, ExclusiveZone, Layer, LayerSurfaceCachedState},
+    },
+};
+
+#[derive(Debug)]
+struct LayerSurfaceInner {
+    surface: wlr_layer::LayerSurface,
+    namespace: String,
+    location: Cell<Point<i32, Logical>>,
+    user_data: UserDataMap,
+}
+
+/// A layer surface of the desktop
+///
+/// This is a cheaply clonable handle, all clones refer to the same layer surface.
+#[derive(Debug, Clone)]
+pub struct LayerSurface(Rc<LayerSurfaceInner>);
+
+impl PartialEq for LayerSurface {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for LayerSurface {}
+
+impl Hash for LayerSurface {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (&*self.0 as *const LayerSurfaceInner).hash(state);
+    }
+}
+
+impl LayerSurface {
+    /// Create a new layer surface from a wlr-layer-shell surface and its namespace
+    pub fn new(surface: wlr_layer::LayerSurface, namespace: String) -> LayerSurface {
+        LayerSurface(Rc::new(LayerSurfaceInner {
+            surface,
+            namespace,
+            location: Cell::new((0, 0).into()),
+            user_data: UserDataMap::new(),
+        }))
+    }
+
+    /// The underlying wlr-layer-shell surface
+    pub fn layer_surface(&self) -> &wlr_layer::LayerSurface {
+        &self.0.surface
+    }
+
+    /// Access the underlying `wl_surface`
+    ///
+    /// Returns `None` if the layer surface has been destroyed.
+    pub fn get_surface(&self) -> Option<&WlSurface> {
+        self.0.surface.get_surface()
+    }
+
+    /// Checks if the layer surface is still alive
+    pub fn alive(&self) -> bool {
+        self.0.surface.alive()
+    }
+
+    /// The namespace of this layer surface, describing its purpose
+    pub fn namespace(&self) -> &str {
+        &self.0.namespace
+    }
+
+    /// The layer this surface is currently displayed on
+    pub fn layer(&self) -> Option<Layer> {
+        self.cached_state().map(|state| state.layer)
+    }
+
+    /// The bounding box of this layer surface and all its subsurfaces, relative to its location
+    pub fn bbox(&self) -> Rectangle<i32, Logical> {
+        match self.get_surface() {
+            Some(surface) => bbox_from_surface_tree(surface, (0, 0)),
+            None => Rectangle::from_loc_and_size((0, 0), (0, 0)),
+        }
+    }
+
+    /// Finds the topmost surface of this layer surface under the given point
+    ///
+    /// The point is relative to the location of the layer surface. Returns the found surface
+    /// together with its location relative to the location of the layer surface.
+    pub fn surface_under(&self, point: Point<f64, Logical>) -> Option<(WlSurface, Point<i32, Logical>)> {
+        self.get_surface()
+            .and_then(|surface| under_from_surface_tree(surface, point, (0, 0)))
+    }
+
+    /// Sends the frame callback to all the surfaces of this layer surface that requested it
+    pub fn send_frame(&self, time: u32) {
+        if let Some(surface) = self.get_surface() {
+            send_frames_surface_tree(surface, time);
+        }
+    }
+
+    /// Access the `UserDataMap` associated with this `LayerSurface`
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.0.user_data
+    }
+
+    fn cached_state(&self) -> Option<LayerSurfaceCachedState> {
+        self.get_surface().and_then(|surface| {
+            with_states(surface, |states| {
+                *states.cached_state.current::<LayerSurfaceCachedState>()
+            })
+            .ok()
+        })
+    }
+}
+
+/// The layer surfaces mapped on a single [`Output`]
+///
+/// Retrieve the map of an output using [`layer_map_for_output`]. All locations are relative
+/// to the output.
+#[derive(Debug, Default)]
+pub struct LayerMap {
+    layers: Vec<LayerSurface>,
+    output_size: Size<i32, Logical>,
+    zone: Rectangle<i32, Logical>,
+}
+
+/// Retrieve the [`LayerMap`] of an output
+///
+/// The map is created on first use. Do not hold on to the returned reference, as any
+/// other call of this function for the same output panics until it is dropped.
+pub fn layer_map_for_output(output: &Output) -> RefMut<'_, LayerMap> {
+    let userdata = output.user_data();
+    userdata.insert_if_missing(|| RefCell::new(LayerMap::default()));
+    let mut map = userdata.get::<RefCell<LayerMap>>().unwrap().borrow_mut();
+    let size = output_logical_size(output);
+    if map.output_size != size {
+        map.output_size = size;
+        map.arrange();
+    }
+    map
+}
+
+impl LayerMap {
+    /// Map a layer surface and arrange all layers
+    pub fn map_layer(&mut self, layer: &LayerSurface) {
+        if !self.layers.contains(layer) {
+            self.layers.push(layer.clone());
+            self.arrange();
+        }
+    }
+
+    /// Unmap a layer surface and arrange the remaining layers
+    ///
+    /// This does not send `wl_surface.leave` for the output of this map, use
+    /// [`LayerMap::unmap_layer_from`] if the output was entered.
+    pub fn unmap_layer(&mut self, layer: &LayerSurface) {
+        if self.layers.contains(layer) {
+            self.layers.retain(|l| l != layer);
+            self.arrange();
+        }
+    }
+
+    /// Unmap a layer surface, making it leave the given output, and arrange the remaining layers
+    pub fn unmap_layer_from(&mut self, layer: &LayerSurface, output: &Output) {
+        if let Some(surface) = layer.get_surface() {
+            output_leave(output, surface);
+        }
+        self.unmap_layer(layer);
+    }
+
+    /// The area of the output not covered by exclusive zones of any layer surface
+    ///
+    /// This is where the windows of the output should be placed.
+    pub fn non_exclusive_zone(&self) -> Rectangle<i32, Logical> {
+        self.zone
+    }
+
+    /// The geometry of a mapped layer surface, including its subsurfaces
+    pub fn layer_geometry(&self, layer: &LayerSurface) -> Option<Rectangle<i32, Logical>> {
+        if !self.layers.contains(layer) {
+            return None;
+        }
+        let mut bbox = layer.bbox();
+        bbox.loc += layer.0.location.get();
+        Some(bbox)
+    }
+
+    /// Finds the topmost layer surface on the given layer under the given point
+    pub fn layer_under<P: Into<Point<f64, Logical>>>(&self, layer: Layer, point: P) -> Option<&LayerSurface> {
+        let point = point.into();
+        self.layers_on(layer)
+            .rev()
+            .find(|l| l.surface_under(point - l.0.location.get().to_f64()).is_some())
+    }
+
+    /// Iterate over all mapped layer surfaces, in the order they were mapped
+    pub fn layers(&self) -> impl DoubleEndedIterator<Item = &LayerSurface> {
+        self.layers.iter()
+    }
+
+    /// Iterate over all mapped layer surfaces on the given layer, from bottom to top
+    pub fn layers_on(&self, layer: Layer) -> impl DoubleEndedIterator<Item = &LayerSurface> {
+        self.layers.iter().filter(move |l| l.layer() == Some(layer))
+    }
+
+    /// Finds the mapped layer surface of the given `wl_surface`
+    pub fn layer_for_surface(&self, surface: &WlSurface) -> Option<&LayerSurface> {
+        self.layers
+            .iter()
+            .find(|l| l.get_surface().map(|s| s == surface).unwrap_or(false))
+    }
+
+    /// Remove dead layer surfaces and arrange the remaining ones
+    ///
+    /// This should be called after layer surfaces were committed, as the client may
+    /// have changed their size, anchors or exclusive zone.
+    pub fn refresh(&mut self) {
+        self.layers.retain(|l| l.alive());
+        self.arrange();
+    }
+
+    /// Sends the frame callback to all mapped layer surfaces that requested it
+    pub fn send_frames(&self, time: u32) {
+        for layer in &self.layers {
+            layer.send_frame(time);
+        }
+    }
+
+    /// Sends `wl_surface.enter`/`wl_surface.leave` for all mapped layer surfaces
+    ///
+    /// `output` must be the output of this map.
+    pub(crate) fn update_outputs(&self, output: &Output, output_geometry: Rectangle<i32, Logical>) {
+        for layer in &self.layers {
+            if let Some(surface) = layer.get_surface() {
+                output_update(
+                    output,
+                    output_geometry,
+                    surface,
+                    output_geometry.loc + layer.0.location.get(),
+                );
+            }
+        }
+    }
+
+    /// Collects the damage elements of all layer surfaces on the given layer, bottom first
+    pub(crate) fn damage_elements(&self, layer: Layer, scale: f64) -> Vec<SurfaceDamageElement> {
+        self.layers_on(layer)
+            .filter_map(|l| {
+                l.get_surface()
+                    .map(|surface| damage_elements_from_surface_tree(surface, l.0.location.get(), scale))
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Sends `wl_surface.leave` for all mapped layer surfaces
+    pub(crate) fn leave_output(&self, output: &Output) {
+        for layer in &self.layers {
+            if let Some(surface) = layer.get_surface() {
+                output_leave(output, surface);
+            }
+        }
+    }
+
+    /// Recompute the location of all layer surfaces and the non-exclusive zone
+    ///
+    /// Layer surfaces with an exclusive zone are placed first, from the overlay down to the
+    /// background layer, each one shrinking the area available to the following ones.
+    pub fn arrange(&mut self) {
+        let output_rect = Rectangle::from_loc_and_size((0, 0), self.output_size);
+        let mut zone = output_rect;
+
+        let order = [Layer::Overlay, Layer::Top, Layer::Bottom, Layer::Background];
+        for exclusive in [true, false] {
+            for layer in order.iter() {
+                for surface in self.layers.iter() {
+                    let state = match surface.cached_state() {
+                        Some(state) => state,
+                        None => continue,
+                    };
+                    let is_exclusive = matches!(state.exclusive_zone, ExclusiveZone::Exclusive(_));
+                    if state.layer != *layer || is_exclusive != exclusive {
+                        continue;
+                    }
+                    let (geometry, remaining) = arrange_layer(&state, output_rect, zone);
+                    surface.0.location.set(geometry.loc);
+                    // only sends a configure if the size actually changed
+                    if surface
+                        .0
+                        .surface
+                        .with_pending_state(|pending| pending.size = Some(geometry.size))
+                        .is_ok()
+                    {
+                        surface.0.surface.send_configure();
+                    }
+                    zone = remaining;
+                }
+            }
+        }
+
+        self.zone = zone;
+    }
+}
+
+/// Computes the geometry of a single layer surface and the remaining non-exclusive zone
+fn arrange_layer(
+    state: &LayerSurfaceCachedState,
+    output_rect: Rectangle<i32, Logical>,
+    zone: Rectangle<i32, Logical>,
+) -> (Rectangle<i32, Logical>, Rectangle<i32, Logical>) {
+    let bounds = match state.exclusive_zone {
+        ExclusiveZone::DontCare => output_rect,
+        _ => zone,
+    };
+    let anchor = state.anchor;
+    let margin = state.margin;
+
+    let mut size = state.size;
+    if size.w == 0 {
+        size.w = bounds.size.w - margin.left - margin.right;
+    }
+    if size.h == 0 {
+        size.h = bounds.size.h - margin.top - margin.bottom;
+    }
+
+    let x = if anchor.contains(Anchor::LEFT) && !anchor.contains(Anchor::RIGHT) {
+        bounds.loc.x + margin.left
+    } else if anchor.contains(Anchor::RIGHT) && !anchor.contains(Anchor::LEFT) {
+        bounds.loc.x + bounds.size.w - margin.right - size.w
+    } else {
+        bounds.loc.x + margin.left + (bounds.size.w - margin.left - margin.right - size.w) / 2
+    };
+    let y = if anchor.contains(Anchor::TOP) && !anchor.contains(Anchor::BOTTOM) {
+        bounds.loc.y + margin.top
+    } else if anchor.contains(Anchor::BOTTOM) && !anchor.contains(Anchor::TOP) {
+        bounds.loc.y + bounds.size.h - margin.bottom - size.h
+    } else {
+        bounds.loc.y + margin.top + (bounds.size.h - margin.top - margin.bottom - size.h) / 2
+    };
+    let geometry = Rectangle::from_loc_and_size((x, y), size);
+
+    let mut zone = zone;
+    if let ExclusiveZone::Exclusive(amount) = state.exclusive_zone {
+        let amount = amount as i32;
+        let horizontal = Anchor::LEFT | Anchor::RIGHT;
+        let vertical = Anchor::TOP | Anchor::BOTTOM;
+        if anchor == Anchor::TOP || anchor == Anchor::TOP | horizontal {
+            let amount = amount + margin.top;
+            zone.loc.y += amount;
+            zone.size.h -= amount;
+        } else if anchor == Anchor::BOTTOM || anchor == Anchor::BOTTOM | horizontal {
+            zone.size.h -= amount + margin.bottom;
+        } else if anchor == Anchor::LEFT || anchor == Anchor::LEFT | vertical {
+            let amount = amount + margin.left;
+            zone.loc.x += amount;
+            zone.size.w -= amount;
+        } else if anchor == Anchor::RIGHT || anchor == Anchor::RIGHT | vertical {
+            zone.size.w -= amount + margin.right;
+        }
+    }
+    (geometry, zone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::arrange_layer;
+    use crate::{
+        utils::{Logical, Rectangle},
+        wayland::shell::wlr_layer::{Anchor, ExclusiveZone, LayerSurfaceCachedState, Margins},
+    };
+
+    fn output_rect() -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((0, 0), (1920, 1080))
+    }
+
+    #[test]
+    fn exclusive_top_bar() {
+        let state = LayerSurfaceCachedState {
+            size: (0, 30).into(),
+            anchor: Anchor::TOP | Anchor::LEFT | Anchor::RIGHT,
+            exclusive_zone: ExclusiveZone::Exclusive(30),
+            ..Default::default()
+        };
+        let (geometry, zone) = arrange_layer(&state, output_rect(), output_rect());
+        assert_eq!(geometry, Rectangle::from_loc_and_size((0, 0), (1920, 30)));
+        assert_eq!(zone, Rectangle::from_loc_and_size((0, 30), (1920, 1050)));
+    }
+
+    #[test]
+    fn exclusive_zone_includes_margin() {
+        let state = LayerSurfaceCachedState {
+            size: (200, 50).into(),
+            anchor: Anchor::BOTTOM,
+            exclusive_zone: ExclusiveZone::Exclusive(50),
+            margin: Margins {
+                bottom: 10,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (geometry, zone) = arrange_layer(&state, output_rect(), output_rect());
+        // centered horizontally, as it is not anchored to the left or right edge
+        assert_eq!(geometry, Rectangle::from_loc_and_size((860, 1020), (200, 50)));
+        assert_eq!(zone, Rectangle::from_loc_and_size((0, 0), (1920, 1020)));
+
+        let state = LayerSurfaceCachedState {
+            size: (100, 0).into(),
+            anchor: Anchor::LEFT | Anchor::TOP | Anchor::BOTTOM,
+            exclusive_zone: ExclusiveZone::Exclusive(100),
+            margin: Margins {
+                top: 10,
+                bottom: 10,
+                left: 5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (geometry, zone) = arrange_layer(&state, output_rect(), zone);
+        assert_eq!(geometry, Rectangle::from_loc_and_size((5, 10), (100, 1000)));
+        assert_eq!(zone, Rectangle::from_loc_and_size((105, 0), (1815, 1020)));
+    }
+
+    #[test]
+    fn neutral_layer_placed_in_zone() {
+        let zone = Rectangle::from_loc_and_size((0, 30), (1920, 1050));
+        let state = LayerSurfaceCachedState {
+            size: (300, 200).into(),
+            anchor: Anchor::TOP | Anchor::RIGHT,
+            exclusive_zone: ExclusiveZone::Neutral,
+            margin: Margins {
+                top: 5,
+                right: 20,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (geometry, remaining) = arrange_layer(&state, output_rect(), zone);
+        assert_eq!(geometry, Rectangle::from_loc_and_size((1600, 35), (300, 200)));
+        assert_eq!(remaining, zone);
+    }
+
+    #[test]
+    fn dont_care_layer_ignores_zone() {
+        let zone = Rectangle::from_loc_and_size((0, 30), (1920, 1050));
+        let state = LayerSurfaceCachedState {
+            size: (0, 0).into(),
+            anchor: Anchor::all(),
+            exclusive_zone: ExclusiveZone::DontCare,
+            ..Default::default()
+        };
+        let (geometry, remaining) = arrange_layer(&state, output_rect(), zone);
+        assert_eq!(geometry, output_rect());
+        assert_eq!(remaining, zone);
+    }
+}
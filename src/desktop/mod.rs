@@ -0,0 +1,70 @@
+//! Desktop management helpers
+//!
+//! This module contains helpers to organize and interact with desktop-style shells.
+//!
+//! It is therefore a lot more opinionated than for example the [xdg-shell handler](crate::wayland::shell::xdg::xdg_shell_init)
+//! and tightly integrates with some protocols (e.g. xdg-shell). It is fully optional and can be used
+//! to skip a lot of boilerplate in desktop compositors.
+//!
+//! ## How to use it
+//!
+//! ### Initialization
+//!
+//! To use the desktop helpers, your commit handler needs to call [`on_commit_buffer_handler`], so the
+//! helpers can track the size of all surfaces:
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::{desktop::on_commit_buffer_handler, wayland::compositor::compositor_init};
+//!
+//! # let mut display = wayland_server::Display::new();
+//! compositor_init(
+//!     &mut display,
+//!     |surface, _dispatch_data| {
+//!         on_commit_buffer_handler(&surface);
+//!         /* your own commit handling */
+//!     },
+//!     None, /* You can insert a logger here */
+//! );
+//! ```
+//!
+//! ### Windows
+//!
+//! Create a [`Window`] for every toplevel of the xdg-shell or wl_shell (or for every paired X11
+//! window, if you use XWayland) and map it into a [`Space`]. The space keeps the windows stacked in
+//! a global logical coordinate space, that is shared with the [`Output`](crate::wayland::output::Output)s
+//! you map into it.
+//!
+//! ### Popups
+//!
+//! Track every new xdg-shell popup with a [`PopupManager`] and call [`PopupManager::commit`] in your
+//! commit handler. Popups are attached to their parent surface and are part of the bounding box, input
+//! handling, damage tracking and frame callbacks of the [`Window`] they belong to, including nested popups.
+//!
+//! ### Layer surfaces
+//!
+//! Layer surfaces are bound to a single output. Create a [`LayerSurface`] for every new
+//! wlr-layer-shell surface and map it into the [`LayerMap`] of its output, retrieved using
+//! [`layer_map_for_output`]. The map arranges the layer surfaces according to their anchors, margins
+//! and exclusive zones, and provides the area left for windows with [`LayerMap::non_exclusive_zone`].
+//!
+//! ### Per-frame handling
+//!
+//! Call [`Space::refresh`] once per loop iteration. It cleans up dead windows, arranges the layer
+//! surfaces of all mapped outputs and sends `wl_surface.enter`/`wl_surface.leave` events to all surfaces
+//! whenever they start or stop overlapping an output. After rendering, call [`Space::send_frames`] to
+//! notify the clients of all visible surfaces.
+
+mod layer;
+mod popup;
+mod space;
+pub mod utils;
+mod window;
+
+pub use self::layer::{layer_map_for_output, LayerMap, LayerSurface};
+pub use self::popup::{PopupKind, PopupManager};
+pub use self::space::Space;
+pub use self::utils::{on_commit_buffer_handler, SurfaceDamageElement};
+#[cfg(feature = "xwayland")]
+pub use self::window::X11Surface;
+pub use self::window::{Kind, Window};
@@ -0,0 +1,230 @@
+use std::{cell::RefCell, sync::Mutex};
+
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    utils::{DeadResource, Logical, Point},
+    wayland::{
+        compositor::with_states,
+        shell::xdg::{PopupSurface, SurfaceCachedState, XdgPopupSurfaceRoleAttributes},
+    },
+};
+
+/// The shell surface backing a popup
+#[derive(Debug, Clone, PartialEq)]
+pub enum PopupKind {
+    /// A xdg-shell popup
+    Xdg(PopupSurface),
+}
+
+impl PopupKind {
+    /// Checks if the popup is still alive
+    pub fn alive(&self) -> bool {
+        match *self {
+            PopupKind::Xdg(ref t) => t.alive(),
+        }
+    }
+
+    /// Access the underlying `wl_surface`
+    ///
+    /// Returns `None` if the popup has been destroyed.
+    pub fn get_surface(&self) -> Option<&WlSurface> {
+        match *self {
+            PopupKind::Xdg(ref t) => t.get_surface(),
+        }
+    }
+
+    /// The parent surface of this popup, if it has been set yet
+    pub fn parent(&self) -> Option<WlSurface> {
+        match *self {
+            PopupKind::Xdg(ref t) => t.get_parent_surface(),
+        }
+    }
+
+    /// The location of this popup relative to the window geometry of its parent
+    ///
+    /// This is the location of the last configure committed by the client.
+    pub fn location(&self) -> Point<i32, Logical> {
+        let surface = match self.get_surface() {
+            Some(surface) => surface,
+            None => return (0, 0).into(),
+        };
+        match *self {
+            PopupKind::Xdg(_) => with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<Mutex<XdgPopupSurfaceRoleAttributes>>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .current
+                    .geometry
+                    .loc
+            })
+            .unwrap_or_default(),
+        }
+    }
+}
+
+// The popups of a surface, in the order they were mapped, stored in the data map of the parent
+#[derive(Debug, Default)]
+struct PopupTree {
+    children: Vec<PopupKind>,
+}
+
+/// Tracks the popups of all surfaces
+///
+/// Popups are attached to their parent surface, which can be the surface of a
+/// [`Window`](crate::desktop::Window) or another popup. Once tracked, they are included
+/// in the bounding box, input handling, damage tracking and frame callbacks of the window they belong to.
+#[derive(Debug)]
+pub struct PopupManager {
+    // tracked popups together with their parent
+    popups: Vec<(PopupKind, WlSurface)>,
+    // popups without a parent yet
+    unmapped: Vec<PopupKind>,
+    logger: ::slog::Logger,
+}
+
+impl PopupManager {
+    /// Create a new empty popup manager
+    pub fn new<L>(logger: L) -> PopupManager
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        PopupManager {
+            popups: Vec::new(),
+            unmapped: Vec::new(),
+            logger: crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "desktop_popup")),
+        }
+    }
+
+    /// Start tracking a new popup
+    ///
+    /// If the parent of the popup is not known yet, the popup is attached to it
+    /// once it is set, see [`PopupManager::commit`].
+    pub fn track_popup(&mut self, popup: PopupKind) -> Result<(), DeadResource> {
+        if !popup.alive() {
+            return Err(DeadResource);
+        }
+        match popup.parent() {
+            Some(parent) => self.attach(popup, parent),
+            None => {
+                self.unmapped.push(popup);
+                Ok(())
+            }
+        }
+    }
+
+    /// Attach the popups whose parent was set by this commit
+    ///
+    /// This needs to be called in the commit callback of your compositor for every committed surface.
+    pub fn commit(&mut self, surface: &WlSurface) {
+        let idx = match self
+            .unmapped
+            .iter()
+            .position(|p| p.get_surface().map(|s| s == surface).unwrap_or(false))
+        {
+            Some(idx) => idx,
+            None => return,
+        };
+        if let Some(parent) = self.unmapped[idx].parent() {
+            let popup = self.unmapped.remove(idx);
+            if self.attach(popup, parent).is_err() {
+                slog::debug!(self.logger, "Parent of the committed popup is already dead");
+            }
+        }
+    }
+
+    fn attach(&mut self, popup: PopupKind, parent: WlSurface) -> Result<(), DeadResource> {
+        with_states(&parent, |states| {
+            states
+                .data_map
+                .insert_if_missing(|| RefCell::new(PopupTree::default()));
+            states
+                .data_map
+                .get::<RefCell<PopupTree>>()
+                .unwrap()
+                .borrow_mut()
+                .children
+                .push(popup.clone());
+        })?;
+        slog::trace!(self.logger, "Tracking new popup");
+        self.popups.push((popup, parent));
+        Ok(())
+    }
+
+    /// Finds the tracked popup of the given `wl_surface`
+    pub fn find_popup(&self, surface: &WlSurface) -> Option<&PopupKind> {
+        self.popups
+            .iter()
+            .map(|(popup, _)| popup)
+            .chain(self.unmapped.iter())
+            .find(|p| p.get_surface().map(|s| s == surface).unwrap_or(false))
+    }
+
+    /// Stop tracking dead popups
+    ///
+    /// This should be called regularly, for example once per loop iteration.
+    pub fn cleanup(&mut self) {
+        for (_, parent) in self.popups.iter().filter(|(popup, _)| !popup.alive()) {
+            let _ = with_states(parent, |states| {
+                if let Some(tree) = states.data_map.get::<RefCell<PopupTree>>() {
+                    tree.borrow_mut().children.retain(|p| p.alive());
+                }
+            });
+        }
+        self.popups.retain(|(popup, _)| popup.alive());
+        self.unmapped.retain(|popup| popup.alive());
+    }
+
+    /// The popups of a surface, including the popups of these popups, bottom first
+    ///
+    /// Every popup is returned together with the location of its `wl_surface` relative to the given surface.
+    pub fn popups_for_surface(surface: &WlSurface) -> Vec<(PopupKind, Point<i32, Logical>)> {
+        let mut popups = Vec::new();
+        collect_popups(surface, (0, 0).into(), &mut popups);
+        popups
+    }
+}
+
+// The location of the window geometry of a xdg surface, relative to the surface
+fn geometry_offset(surface: &WlSurface) -> Point<i32, Logical> {
+    with_states(surface, |states| {
+        states
+            .cached_state
+            .current::<SurfaceCachedState>()
+            .geometry
+            .map(|geometry| geometry.loc)
+    })
+    .ok()
+    .flatten()
+    .unwrap_or_default()
+}
+
+fn collect_popups(
+    surface: &WlSurface,
+    location: Point<i32, Logical>,
+    popups: &mut Vec<(PopupKind, Point<i32, Logical>)>,
+) {
+    let children = with_states(surface, |states| {
+        states
+            .data_map
+            .get::<RefCell<PopupTree>>()
+            .map(|tree| tree.borrow().children.clone())
+    })
+    .ok()
+    .flatten()
+    .unwrap_or_default();
+
+    let offset = location + geometry_offset(surface);
+    for popup in children {
+        let popup_surface = match popup.get_surface() {
+            Some(popup_surface) => popup_surface.clone(),
+            None => continue,
+        };
+        let popup_location = offset + popup.location() - geometry_offset(&popup_surface);
+        popups.push((popup, popup_location));
+        collect_popups(&popup_surface, popup_location, popups);
+    }
+}
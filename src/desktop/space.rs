@@ -0,0 +1,573 @@
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    desktop::{
+        layer::layer_map_for_output,
+        utils::{
+            damage_elements_from_surface_tree, output_leave, output_logical_size, output_update,
+            SurfaceDamageElement,
+        },
+        window::Window,
+    },
+    utils::{Logical, Point, Rectangle},
+    wayland::{output::Output, shell::wlr_layer::Layer},
+};
+
+#[derive(Debug)]
+struct MappedWindow {
+    window: Window,
+    location: Point<i32, Logical>,
+}
+
+#[derive(Debug)]
+struct MappedOutput {
+    output: Output,
+    location: Point<i32, Logical>,
+}
+
+/// A two-dimensional plane of windows and outputs
+///
+/// Windows and outputs are mapped at a location in a global logical coordinate space.
+/// The space keeps track of which outputs display which window and sends the
+/// `wl_surface.enter`/`wl_surface.leave` events accordingly when [`Space::refresh`] is called.
+///
+/// Windows are stacked, the most recently mapped or raised window being on top.
+#[derive(Debug)]
+pub struct Space {
+    // topmost window first
+    windows: Vec<MappedWindow>,
+    outputs: Vec<MappedOutput>,
+    logger: ::slog::Logger,
+}
+
+impl Space {
+    /// Create a new empty space
+    pub fn new<L>(logger: L) -> Space
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        Space {
+            windows: Vec::new(),
+            outputs: Vec::new(),
+            logger: crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "desktop_space")),
+        }
+    }
+
+    /// Map a window at the given location and raise it to the top
+    ///
+    /// The location is the one of the window's main surface. If the window is already mapped, it is
+    /// moved to the new location.
+    pub fn map_window<P: Into<Point<i32, Logical>>>(&mut self, window: &Window, location: P) {
+        self.windows.retain(|w| &w.window != window);
+        self.windows.insert(
+            0,
+            MappedWindow {
+                window: window.clone(),
+                location: location.into(),
+            },
+        );
+    }
+
+    /// Raise a mapped window to the top
+    ///
+    /// If `activate` is set, the window is activated and all other windows are deactivated.
+    pub fn raise_window(&mut self, window: &Window, activate: bool) {
+        let idx = match self.windows.iter().position(|w| &w.window == window) {
+            Some(idx) => idx,
+            None => return,
+        };
+        let mapped = self.windows.remove(idx);
+        self.windows.insert(0, mapped);
+
+        if activate {
+            for mapped in &self.windows {
+                if mapped.window.set_activated(&mapped.window == window) {
+                    mapped.window.configure();
+                }
+            }
+        }
+    }
+
+    /// Unmap a window
+    ///
+    /// The surfaces of the window and its popups leave all outputs of this space.
+    pub fn unmap_window(&mut self, window: &Window) {
+        for (surface, _) in window_surfaces(window) {
+            for mapped in &self.outputs {
+                output_leave(&mapped.output, &surface);
+            }
+        }
+        self.windows.retain(|w| &w.window != window);
+    }
+
+    /// Iterate over all mapped windows, from top to bottom
+    pub fn windows(&self) -> impl DoubleEndedIterator<Item = &Window> {
+        self.windows.iter().map(|w| &w.window)
+    }
+
+    /// The location of a mapped window
+    pub fn window_location(&self, window: &Window) -> Option<Point<i32, Logical>> {
+        self.windows
+            .iter()
+            .find(|w| &w.window == window)
+            .map(|w| w.location)
+    }
+
+    /// The window geometry of a mapped window in the global coordinate space
+    ///
+    /// See [`Window::geometry`].
+    pub fn window_geometry(&self, window: &Window) -> Option<Rectangle<i32, Logical>> {
+        self.window_location(window).map(|location| {
+            let mut geometry = window.geometry();
+            geometry.loc += location;
+            geometry
+        })
+    }
+
+    /// The bounding box of a mapped window in the global coordinate space
+    ///
+    /// See [`Window::bbox`].
+    pub fn window_bbox(&self, window: &Window) -> Option<Rectangle<i32, Logical>> {
+        self.window_location(window).map(|location| {
+            let mut bbox = window.bbox();
+            bbox.loc += location;
+            bbox
+        })
+    }
+
+    /// Finds the topmost window under the given point
+    pub fn window_under<P: Into<Point<f64, Logical>>>(&self, point: P) -> Option<&Window> {
+        let point = point.into();
+        self.windows
+            .iter()
+            .find(|w| w.window.surface_under(point - w.location.to_f64()).is_some())
+            .map(|w| &w.window)
+    }
+
+    /// Finds the topmost surface of any window under the given point
+    ///
+    /// Returns the found surface together with its location in the global coordinate space.
+    pub fn surface_under<P: Into<Point<f64, Logical>>>(
+        &self,
+        point: P,
+    ) -> Option<(WlSurface, Point<i32, Logical>)> {
+        let point = point.into();
+        self.windows.iter().find_map(|w| {
+            w.window
+                .surface_under(point - w.location.to_f64())
+                .map(|(surface, location)| (surface, location + w.location))
+        })
+    }
+
+    /// Finds the mapped window of the given toplevel `wl_surface`
+    pub fn window_for_surface(&self, surface: &WlSurface) -> Option<&Window> {
+        self.windows
+            .iter()
+            .map(|w| &w.window)
+            .find(|w| w.toplevel().get_surface().map(|s| s == surface).unwrap_or(false))
+    }
+
+    /// Map an output at the given location
+    ///
+    /// The size of the output in the global coordinate space is derived from its current mode,
+    /// transform and scale. If the output is already mapped, it is moved to the new location.
+    pub fn map_output<P: Into<Point<i32, Logical>>>(&mut self, output: &Output, location: P) {
+        let location = location.into();
+        match self.outputs.iter_mut().find(|o| &o.output == output) {
+            Some(mapped) => mapped.location = location,
+            None => self.outputs.push(MappedOutput {
+                output: output.clone(),
+                location,
+            }),
+        }
+    }
+
+    /// Unmap an output
+    ///
+    /// All windows and layer surfaces of this space leave the output.
+    pub fn unmap_output(&mut self, output: &Output) {
+        if !self.outputs.iter().any(|o| &o.output == output) {
+            return;
+        }
+        for mapped in &self.windows {
+            for (surface, _) in window_surfaces(&mapped.window) {
+                output_leave(output, &surface);
+            }
+        }
+        layer_map_for_output(output).leave_output(output);
+        self.outputs.retain(|o| &o.output != output);
+    }
+
+    /// Iterate over all mapped outputs
+    pub fn outputs(&self) -> impl Iterator<Item = &Output> {
+        self.outputs.iter().map(|o| &o.output)
+    }
+
+    /// The geometry of a mapped output in the global coordinate space
+    pub fn output_geometry(&self, output: &Output) -> Option<Rectangle<i32, Logical>> {
+        self.outputs
+            .iter()
+            .find(|o| &o.output == output)
+            .map(|o| Rectangle::from_loc_and_size(o.location, output_logical_size(&o.output)))
+    }
+
+    /// Finds the output containing the given point
+    pub fn output_under<P: Into<Point<f64, Logical>>>(&self, point: P) -> Option<&Output> {
+        let point = point.into();
+        self.outputs()
+            .find(|o| self.output_geometry(o).unwrap().to_f64().contains(point))
+    }
+
+    /// The outputs overlapping the bounding box of a mapped window
+    pub fn outputs_for_window(&self, window: &Window) -> Vec<Output> {
+        let bbox = match self.window_bbox(window) {
+            Some(bbox) => bbox,
+            None => return Vec::new(),
+        };
+        self.outputs()
+            .filter(|o| self.output_geometry(o).unwrap().overlaps(bbox))
+            .cloned()
+            .collect()
+    }
+
+    /// Collects the damage elements of all surfaces displayed on a mapped output, bottom first
+    ///
+    /// This includes the layer surfaces of the output and all windows with their popups, in the order
+    /// they have to be rendered. The elements can be passed to the
+    /// [`OutputDamageTracker`](crate::backend::renderer::damage::OutputDamageTracker) of the output
+    /// to compute the damage of the next frame. Returns `None` if the output is not mapped.
+    pub fn damage_elements(&self, output: &Output) -> Option<Vec<SurfaceDamageElement>> {
+        let output_geometry = self.output_geometry(output)?;
+        let scale = output.current_scale().max(1) as f64;
+        let layer_map = layer_map_for_output(output);
+
+        let mut elements = layer_map.damage_elements(Layer::Background, scale);
+        elements.extend(layer_map.damage_elements(Layer::Bottom, scale));
+        for mapped in self.windows.iter().rev() {
+            let bbox = mapped.window.bbox();
+            if !output_geometry.overlaps(Rectangle::from_loc_and_size(
+                bbox.loc + mapped.location,
+                bbox.size,
+            )) {
+                continue;
+            }
+            for (surface, location) in window_surfaces(&mapped.window) {
+                elements.extend(damage_elements_from_surface_tree(
+                    &surface,
+                    mapped.location + location - output_geometry.loc,
+                    scale,
+                ));
+            }
+        }
+        elements.extend(layer_map.damage_elements(Layer::Top, scale));
+        elements.extend(layer_map.damage_elements(Layer::Overlay, scale));
+        Some(elements)
+    }
+
+    /// Refresh the state of the space
+    ///
+    /// This removes dead windows, updates the bounding boxes of the remaining ones, arranges the
+    /// layer surfaces of every mapped output and sends the `wl_surface.enter`/`wl_surface.leave`
+    /// events to all their surfaces, including the ones of their popups. It should be called after
+    /// surfaces were committed and after windows or outputs were moved, usually once per loop iteration.
+    pub fn refresh(&mut self) {
+        let logger = &self.logger;
+        self.windows.retain(|w| {
+            let alive = w.window.alive();
+            if !alive {
+                slog::trace!(logger, "Removing dead window from space");
+            }
+            alive
+        });
+
+        for mapped in &self.windows {
+            mapped.window.refresh();
+        }
+
+        for mapped_output in &self.outputs {
+            let output_geometry = Rectangle::from_loc_and_size(
+                mapped_output.location,
+                output_logical_size(&mapped_output.output),
+            );
+            for mapped in &self.windows {
+                for (surface, location) in window_surfaces(&mapped.window) {
+                    output_update(
+                        &mapped_output.output,
+                        output_geometry,
+                        &surface,
+                        mapped.location + location,
+                    );
+                }
+            }
+
+            let mut layer_map = layer_map_for_output(&mapped_output.output);
+            layer_map.refresh();
+            layer_map.update_outputs(&mapped_output.output, output_geometry);
+        }
+    }
+
+    /// Sends the frame callbacks to all windows and layer surfaces displayed on the mapped outputs
+    ///
+    /// Windows not overlapping any output do not receive frame callbacks, as they are not visible.
+    pub fn send_frames(&self, time: u32) {
+        for mapped in &self.windows {
+            if !self.outputs_for_window(&mapped.window).is_empty() {
+                mapped.window.send_frame(time);
+            }
+        }
+        for mapped_output in &self.outputs {
+            layer_map_for_output(&mapped_output.output).send_frames(time);
+        }
+    }
+}
+
+// The surfaces of a window and its popups with their location relative to the window, bottom first
+fn window_surfaces(window: &Window) -> Vec<(WlSurface, Point<i32, Logical>)> {
+    window
+        .toplevel()
+        .get_surface()
+        .map(|surface| (surface.clone(), Point::from((0, 0))))
+        .into_iter()
+        .chain(
+            window.popups().into_iter().filter_map(|(popup, location)| {
+                popup.get_surface().map(|surface| (surface.clone(), location))
+            }),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, sync::Mutex};
+
+    use wayland_client::{protocol::wl_compositor::WlCompositor, Main};
+    use wayland_protocols::xdg_shell::client::{
+        xdg_popup::XdgPopup, xdg_surface::XdgSurface, xdg_wm_base::XdgWmBase,
+    };
+    use wayland_server::protocol::wl_surface::WlSurface;
+
+    use super::Space;
+    use crate::{
+        desktop::{utils::SurfaceState, Kind, PopupKind, PopupManager, Window},
+        testing::TestHarness,
+        utils::{Logical, Point, Rectangle},
+        wayland::{
+            compositor::{compositor_init, with_states},
+            shell::xdg::{
+                xdg_shell_init, PopupSurface, ToplevelSurface, XdgPopupSurfaceRoleAttributes, XdgRequest,
+            },
+        },
+    };
+
+    #[derive(Default)]
+    struct State {
+        toplevels: Vec<ToplevelSurface>,
+        popups: Vec<PopupSurface>,
+    }
+
+    struct Client {
+        harness: TestHarness<State>,
+        compositor: Main<WlCompositor>,
+        wm_base: Main<XdgWmBase>,
+    }
+
+    impl Client {
+        fn new() -> Client {
+            let mut harness = TestHarness::new(State::default()).unwrap();
+            compositor_init(harness.display_mut(), |_, _| {}, None);
+            xdg_shell_init(
+                harness.display_mut(),
+                |request, mut ddata| {
+                    let state = ddata.get::<State>().unwrap();
+                    match request {
+                        XdgRequest::NewToplevel { surface } => state.toplevels.push(surface),
+                        XdgRequest::NewPopup { surface, .. } => state.popups.push(surface),
+                        _ => {}
+                    }
+                },
+                None,
+            );
+            harness.roundtrip().unwrap();
+            let compositor = harness.globals().instantiate_exact::<WlCompositor>(4).unwrap();
+            let wm_base = harness.globals().instantiate_exact::<XdgWmBase>(1).unwrap();
+            Client {
+                harness,
+                compositor,
+                wm_base,
+            }
+        }
+
+        // A window whose surface is mapped with the given size
+        fn window(&mut self, size: (i32, i32)) -> (Window, Main<XdgSurface>) {
+            let surface = self.compositor.create_surface();
+            let xdg_surface = self.wm_base.get_xdg_surface(&surface);
+            xdg_surface.get_toplevel();
+            self.harness.roundtrip().unwrap();
+
+            let toplevel = self.harness.state_mut().toplevels.pop().unwrap();
+            set_size(toplevel.get_surface().unwrap(), size);
+            (Window::new(Kind::Xdg(toplevel)), xdg_surface)
+        }
+
+        // A mapped popup of the given parent, placed at `geometry` relative to it
+        fn popup(
+            &mut self,
+            parent: &XdgSurface,
+            geometry: Rectangle<i32, Logical>,
+        ) -> (PopupKind, Main<XdgSurface>, Main<XdgPopup>) {
+            let positioner = self.wm_base.create_positioner();
+            positioner.set_size(geometry.size.w, geometry.size.h);
+            positioner.set_anchor_rect(0, 0, 1, 1);
+            let surface = self.compositor.create_surface();
+            let xdg_surface = self.wm_base.get_xdg_surface(&surface);
+            let xdg_popup = xdg_surface.get_popup(Some(parent), &positioner);
+            self.harness.roundtrip().unwrap();
+
+            let popup = self.harness.state_mut().popups.pop().unwrap();
+            let wl_surface = popup.get_surface().unwrap();
+            // pretend the client acked and committed the configure
+            with_states(wl_surface, |states| {
+                states
+                    .data_map
+                    .get::<Mutex<XdgPopupSurfaceRoleAttributes>>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .current
+                    .geometry = geometry;
+            })
+            .unwrap();
+            set_size(wl_surface, geometry.size.into());
+            (PopupKind::Xdg(popup), xdg_surface, xdg_popup)
+        }
+    }
+
+    fn set_size(surface: &WlSurface, size: (i32, i32)) {
+        with_states(surface, |states| {
+            states
+                .data_map
+                .insert_if_missing(|| RefCell::new(SurfaceState::default()));
+            *states
+                .data_map
+                .get::<RefCell<SurfaceState>>()
+                .unwrap()
+                .borrow_mut() = SurfaceState::mapped(size.into());
+        })
+        .unwrap();
+    }
+
+    fn stacking(space: &Space) -> Vec<Window> {
+        space.windows().cloned().collect()
+    }
+
+    #[test]
+    fn map_and_raise_order() {
+        let mut client = Client::new();
+        let (w1, _) = client.window((100, 100));
+        let (w2, _) = client.window((100, 100));
+        let (w3, _) = client.window((100, 100));
+
+        let mut space = Space::new(None);
+        space.map_window(&w1, (0, 0));
+        space.map_window(&w2, (50, 50));
+        space.map_window(&w3, (500, 500));
+        assert_eq!(stacking(&space), vec![w3.clone(), w2.clone(), w1.clone()]);
+
+        // mapping again moves the window and raises it
+        space.map_window(&w1, (10, 10));
+        assert_eq!(stacking(&space), vec![w1.clone(), w3.clone(), w2.clone()]);
+        assert_eq!(space.window_location(&w1), Some((10, 10).into()));
+
+        space.raise_window(&w2, false);
+        assert_eq!(stacking(&space), vec![w2.clone(), w1.clone(), w3.clone()]);
+
+        space.unmap_window(&w3);
+        assert_eq!(stacking(&space), vec![w2.clone(), w1.clone()]);
+        assert_eq!(space.window_location(&w3), None);
+
+        // raising a window that is not mapped does nothing
+        space.raise_window(&w3, false);
+        assert_eq!(stacking(&space), vec![w2, w1]);
+    }
+
+    #[test]
+    fn window_under_topmost() {
+        let mut client = Client::new();
+        let (w1, _) = client.window((100, 100));
+        let (w2, _) = client.window((100, 100));
+
+        let mut space = Space::new(None);
+        space.map_window(&w1, (0, 0));
+        space.map_window(&w2, (50, 50));
+
+        assert_eq!(space.window_under((75.0, 75.0)), Some(&w2));
+        assert_eq!(space.window_under((25.0, 25.0)), Some(&w1));
+        assert_eq!(space.window_under((125.0, 125.0)), Some(&w2));
+        assert_eq!(space.window_under((300.0, 300.0)), None);
+
+        space.raise_window(&w1, false);
+        assert_eq!(space.window_under((75.0, 75.0)), Some(&w1));
+        assert_eq!(
+            space.surface_under((125.0, 125.0)),
+            Some((w2.toplevel().get_surface().unwrap().clone(), (50, 50).into()))
+        );
+    }
+
+    #[test]
+    fn popups_are_part_of_window() {
+        let mut client = Client::new();
+        let mut popups = PopupManager::new(None);
+        let (window, xdg_surface) = client.window((100, 100));
+        let (popup, popup_xdg_surface, xdg_popup) =
+            client.popup(&xdg_surface, Rectangle::from_loc_and_size((80, 20), (50, 50)));
+        let (nested, _, nested_xdg_popup) = client.popup(
+            &popup_xdg_surface,
+            Rectangle::from_loc_and_size((10, 40), (20, 20)),
+        );
+        popups.track_popup(popup.clone()).unwrap();
+        popups.track_popup(nested.clone()).unwrap();
+
+        let mut space = Space::new(None);
+        space.map_window(&window, (100, 100));
+        space.refresh();
+
+        assert_eq!(
+            window.popups(),
+            vec![
+                (popup.clone(), Point::from((80, 20))),
+                (nested.clone(), Point::from((90, 60)))
+            ]
+        );
+        assert_eq!(window.bbox(), Rectangle::from_loc_and_size((0, 0), (130, 100)));
+
+        // outside of the toplevel surface
+        assert_eq!(space.window_under((220.0, 130.0)), Some(&window));
+        // popups are above the toplevel surface
+        assert_eq!(
+            space.surface_under((190.0, 130.0)),
+            Some((popup.get_surface().unwrap().clone(), (180, 120).into()))
+        );
+        assert_eq!(
+            space.surface_under((195.0, 165.0)),
+            Some((nested.get_surface().unwrap().clone(), (190, 160).into()))
+        );
+        assert_eq!(
+            space.surface_under((150.0, 150.0)),
+            Some((
+                window.toplevel().get_surface().unwrap().clone(),
+                (100, 100).into()
+            ))
+        );
+
+        let popup_surface = popup.get_surface().unwrap().clone();
+        // the topmost popup has to be destroyed first
+        nested_xdg_popup.destroy();
+        xdg_popup.destroy();
+        client.harness.roundtrip().unwrap();
+        popups.cleanup();
+        space.refresh();
+        assert!(popups.find_popup(&popup_surface).is_none());
+        assert_eq!(window.bbox(), Rectangle::from_loc_and_size((0, 0), (100, 100)));
+        assert_eq!(space.window_under((220.0, 130.0)), None);
+    }
+}
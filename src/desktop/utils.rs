@@ -0,0 +1,521 @@
+//! Helper functions to ease dealing with surface trees
+
+use std::{cell::RefCell, collections::VecDeque, sync::Mutex};
+
+use wayland_server::protocol::{wl_output::Transform, wl_surface::WlSurface};
+
+use crate::{
+    backend::renderer::{
+        buffer_dimensions,
+        damage::{CommitCounter, DamageElement, ElementId},
+    },
+    utils::{Logical, Physical, Point, Rectangle, Region, Size},
+    wayland::{
+        compositor::{
+            is_sync_subsurface, with_states, with_surface_tree_downward, with_surface_tree_upward,
+            BufferAssignment, Damage, SubsurfaceCachedState, SurfaceAttributes, TraversalAction,
+        },
+        output::Output,
+    },
+};
+
+// Number of commits the damage of a surface is kept for
+const MAX_DAMAGE_HISTORY: usize = 16;
+
+/// Size and damage related state of a surface, as tracked by [`on_commit_buffer_handler`]
+#[derive(Debug)]
+pub(crate) struct SurfaceState {
+    buffer_dimensions: Option<Size<i32, Physical>>,
+    buffer_scale: i32,
+    id: ElementId,
+    commit: CommitCounter,
+    // damage of the last commits in surface-local coordinates, the most recent one first
+    damage: VecDeque<Vec<Rectangle<i32, Logical>>>,
+}
+
+impl Default for SurfaceState {
+    fn default() -> Self {
+        SurfaceState {
+            buffer_dimensions: None,
+            buffer_scale: 1,
+            id: ElementId::new(),
+            commit: CommitCounter::default(),
+            damage: VecDeque::new(),
+        }
+    }
+}
+
+impl SurfaceState {
+    fn update_buffer(&mut self, attrs: &SurfaceAttributes) {
+        let previous_size = self.size();
+        match attrs.buffer {
+            Some(BufferAssignment::NewBuffer { ref buffer, .. }) => {
+                self.buffer_dimensions = buffer_dimensions(buffer);
+                self.buffer_scale = attrs.buffer_scale;
+            }
+            Some(BufferAssignment::Removed) => {
+                self.buffer_dimensions = None;
+            }
+            None => return,
+        }
+
+        // The damage of the attributes accumulates until it is cleared by the compositor, so this may
+        // record more damage than the client actually submitted, but never less.
+        let size = self.size();
+        let full = Rectangle::from_loc_and_size((0, 0), size.or(previous_size).unwrap_or_default());
+        let damage = if size != previous_size || attrs.buffer_transform != Transform::Normal {
+            vec![full]
+        } else {
+            let scale = self.buffer_scale.max(1);
+            attrs
+                .damage
+                .iter()
+                .map(|damage| match damage {
+                    Damage::Surface(rect) => *rect,
+                    Damage::Buffer(rect) => rect.to_logical(scale),
+                })
+                .filter_map(|rect| rect.intersection(full))
+                .filter(|rect| rect.size.w > 0 && rect.size.h > 0)
+                .collect()
+        };
+        self.commit.increment();
+        self.damage.push_front(damage);
+        self.damage.truncate(MAX_DAMAGE_HISTORY);
+    }
+
+    // Damage since the given commit in surface-local coordinates, `None` if unknown
+    fn damage_since(&self, commit: Option<CommitCounter>) -> Option<Vec<Rectangle<i32, Logical>>> {
+        let distance = self.commit.distance(commit)?;
+        if distance > self.damage.len() {
+            return None;
+        }
+        Some(self.damage.iter().take(distance).flatten().copied().collect())
+    }
+
+    /// Returns the size of the surface.
+    pub(crate) fn size(&self) -> Option<Size<i32, Logical>> {
+        self.buffer_dimensions
+            .map(|dims| dims.to_logical(self.buffer_scale.max(1)))
+    }
+
+    /// Checks if the surface's input region contains the point.
+    fn contains_point(&self, attrs: &SurfaceAttributes, point: Point<f64, Logical>) -> bool {
+        let size = match self.size() {
+            None => return false, // If the surface has no size, it can't have an input region.
+            Some(size) => size,
+        };
+
+        let rect = Rectangle {
+            loc: (0, 0).into(),
+            size,
+        }
+        .to_f64();
+
+        // The input region is always within the surface itself, so if the surface itself doesn't contain the
+        // point we can return false.
+        if !rect.contains(point) {
+            return false;
+        }
+
+        // If there's no input region, we're done.
+        match attrs.input_region {
+            Some(ref region) => region.contains(point.to_i32_floor()),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+impl SurfaceState {
+    /// The state of a surface with a committed buffer of the given size
+    pub(crate) fn mapped(size: Size<i32, Logical>) -> SurfaceState {
+        SurfaceState {
+            buffer_dimensions: Some(size.to_physical(1)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Outputs a surface has been sent `wl_surface.enter` for
+#[derive(Debug, Default)]
+struct OutputTracking {
+    outputs: Vec<Output>,
+}
+
+/// Keep track of the size of the surfaces of a committed surface tree
+///
+/// This handler needs to be called in the commit callback of your compositor (see
+/// [`compositor_init`](crate::wayland::compositor::compositor_init)) for every surface that is
+/// used with the types of the [`desktop`](crate::desktop) module. It does not consume the buffers
+/// attached to the surfaces, so it can be combined with your own buffer handling, as long as it is
+/// called before you [`Option::take`] the buffer out of the
+/// [`SurfaceAttributes`](crate::wayland::compositor::SurfaceAttributes).
+pub fn on_commit_buffer_handler(surface: &WlSurface) {
+    if is_sync_subsurface(surface) {
+        return;
+    }
+    with_surface_tree_upward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |_, states, _| {
+            states
+                .data_map
+                .insert_if_missing(|| RefCell::new(SurfaceState::default()));
+            let mut data = states
+                .data_map
+                .get::<RefCell<SurfaceState>>()
+                .unwrap()
+                .borrow_mut();
+            data.update_buffer(&states.cached_state.current::<SurfaceAttributes>());
+        },
+        |_, _, _| true,
+    );
+}
+
+/// Returns the bounding box of a surface tree, including all its mapped subsurfaces
+///
+/// `location` is the location of the root surface.
+pub fn bbox_from_surface_tree<P>(surface: &WlSurface, location: P) -> Rectangle<i32, Logical>
+where
+    P: Into<Point<i32, Logical>>,
+{
+    let location = location.into();
+    let mut bounding_box = Rectangle::from_loc_and_size(location, (0, 0));
+    with_surface_tree_downward(
+        surface,
+        location,
+        |_, states, loc: &Point<i32, Logical>| {
+            let mut loc = *loc;
+            let data = states.data_map.get::<RefCell<SurfaceState>>();
+
+            if let Some(size) = data.and_then(|d| d.borrow().size()) {
+                if states.role == Some("subsurface") {
+                    let current = states.cached_state.current::<SubsurfaceCachedState>();
+                    loc += current.location;
+                }
+
+                // Update the bounding box.
+                bounding_box = bounding_box.merge(Rectangle::from_loc_and_size(loc, size));
+
+                TraversalAction::DoChildren(loc)
+            } else {
+                // If the parent surface is unmapped, then the child surfaces are hidden as
+                // well, no need to consider them here.
+                TraversalAction::SkipChildren
+            }
+        },
+        |_, _, _| {},
+        |_, _, _| true,
+    );
+    bounding_box
+}
+
+/// Finds the topmost surface of a surface tree under the given point, taking input regions into account
+///
+/// `location` is the location of the root surface. Returns the found surface together with its location.
+pub fn under_from_surface_tree<P>(
+    surface: &WlSurface,
+    point: Point<f64, Logical>,
+    location: P,
+) -> Option<(WlSurface, Point<i32, Logical>)>
+where
+    P: Into<Point<i32, Logical>>,
+{
+    let found = RefCell::new(None);
+    with_surface_tree_downward(
+        surface,
+        location.into(),
+        |wl_surface, states, location: &Point<i32, Logical>| {
+            let mut location = *location;
+            let data = states.data_map.get::<RefCell<SurfaceState>>();
+
+            if states.role == Some("subsurface") {
+                let current = states.cached_state.current::<SubsurfaceCachedState>();
+                location += current.location;
+            }
+
+            let contains_the_point = data
+                .map(|data| {
+                    data.borrow()
+                        .contains_point(&states.cached_state.current(), point - location.to_f64())
+                })
+                .unwrap_or(false);
+            if contains_the_point {
+                *found.borrow_mut() = Some((wl_surface.clone(), location));
+            }
+
+            TraversalAction::DoChildren(location)
+        },
+        |_, _, _| {},
+        |_, _, _| {
+            // only continue if the point is not found
+            found.borrow().is_none()
+        },
+    );
+    found.into_inner()
+}
+
+/// Sends frame callbacks to all surfaces of a surface tree that requested one
+pub fn send_frames_surface_tree(surface: &WlSurface, time: u32) {
+    with_surface_tree_downward(
+        surface,
+        (),
+        |_, _, &()| TraversalAction::DoChildren(()),
+        |_, states, &()| {
+            // the surface may not have any user_data if it is a subsurface and has not
+            // yet been commited
+            for callback in states
+                .cached_state
+                .current::<SurfaceAttributes>()
+                .frame_callbacks
+                .drain(..)
+            {
+                callback.done(time);
+            }
+        },
+        |_, _, &()| true,
+    );
+}
+
+/// A mapped surface of a surface tree placed on an output, whose damage can be tracked
+///
+/// The damage is provided by the state recorded by [`on_commit_buffer_handler`].
+/// See [`damage_elements_from_surface_tree`] and
+/// [`OutputDamageTracker`](crate::backend::renderer::damage::OutputDamageTracker).
+#[derive(Debug, Clone)]
+pub struct SurfaceDamageElement {
+    surface: WlSurface,
+    id: ElementId,
+    commit: CommitCounter,
+    geometry: Rectangle<i32, Physical>,
+    scale: f64,
+}
+
+impl SurfaceDamageElement {
+    /// The surface represented by this element
+    pub fn surface(&self) -> &WlSurface {
+        &self.surface
+    }
+}
+
+impl DamageElement for SurfaceDamageElement {
+    fn id(&self) -> ElementId {
+        self.id
+    }
+
+    fn geometry(&self) -> Rectangle<i32, Physical> {
+        self.geometry
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit
+    }
+
+    fn damage_since(&self, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>> {
+        let full = Rectangle::from_loc_and_size((0, 0), self.geometry.size);
+        with_states(&self.surface, |states| {
+            states
+                .data_map
+                .get::<RefCell<SurfaceState>>()
+                .and_then(|data| data.borrow().damage_since(commit))
+        })
+        .ok()
+        .flatten()
+        .map(|damage| {
+            damage
+                .into_iter()
+                .filter_map(|rect| {
+                    rect.to_f64()
+                        .to_physical(self.scale)
+                        .to_i32_up()
+                        .intersection(full)
+                })
+                .collect()
+        })
+        .unwrap_or_else(|| vec![full])
+    }
+
+    fn opaque_regions(&self) -> Vec<Rectangle<i32, Physical>> {
+        with_states(&self.surface, |states| {
+            let attrs = states.cached_state.current::<SurfaceAttributes>();
+            attrs.opaque_region.as_ref().map(|opaque_region| {
+                let mut region = Region::from(opaque_region)
+                    .to_f64()
+                    .to_physical(self.scale)
+                    .to_i32_down();
+                region.intersect_rect(Rectangle::from_loc_and_size((0, 0), self.geometry.size));
+                region.into_rects()
+            })
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+    }
+}
+
+/// Collects the damage elements of all mapped surfaces of a surface tree, bottom first
+///
+/// `location` is the location of the root surface relative to the output and `scale` the scale
+/// of the output.
+pub fn damage_elements_from_surface_tree<P>(
+    surface: &WlSurface,
+    location: P,
+    scale: f64,
+) -> Vec<SurfaceDamageElement>
+where
+    P: Into<Point<i32, Logical>>,
+{
+    let mut elements = Vec::new();
+    with_surface_tree_upward(
+        surface,
+        location.into(),
+        |_, states, location: &Point<i32, Logical>| {
+            let mut location = *location;
+            let data = states.data_map.get::<RefCell<SurfaceState>>();
+
+            if data.and_then(|d| d.borrow().size()).is_some() {
+                if states.role == Some("subsurface") {
+                    let current = states.cached_state.current::<SubsurfaceCachedState>();
+                    location += current.location;
+                }
+                TraversalAction::DoChildren(location)
+            } else {
+                // children of unmapped surfaces are not displayed
+                TraversalAction::SkipChildren
+            }
+        },
+        |wl_surface, states, location: &Point<i32, Logical>| {
+            let mut location = *location;
+            if let Some(data) = states.data_map.get::<RefCell<SurfaceState>>() {
+                let data = data.borrow();
+                if let Some(size) = data.size() {
+                    if states.role == Some("subsurface") {
+                        let current = states.cached_state.current::<SubsurfaceCachedState>();
+                        location += current.location;
+                    }
+                    elements.push(SurfaceDamageElement {
+                        surface: wl_surface.clone(),
+                        id: data.id,
+                        commit: data.commit,
+                        geometry: Rectangle::from_loc_and_size(location, size)
+                            .to_f64()
+                            .to_physical(scale)
+                            .to_i32_round(),
+                        scale,
+                    });
+                }
+            }
+        },
+        |_, _, _| true,
+    );
+    elements
+}
+
+/// Sends `wl_surface.enter`/`wl_surface.leave` events for all surfaces of a surface tree
+///
+/// Every mapped surface overlapping `output_geometry` enters `output`, all other surfaces leave it.
+/// `location` is the location of the root surface, in the same coordinate space as `output_geometry`.
+pub(crate) fn output_update(
+    output: &Output,
+    output_geometry: Rectangle<i32, Logical>,
+    surface: &WlSurface,
+    location: Point<i32, Logical>,
+) {
+    with_surface_tree_downward(
+        surface,
+        (location, true),
+        |_, states, &(location, parent_mapped)| {
+            let mut location = location;
+            let mapped = parent_mapped
+                && states
+                    .data_map
+                    .get::<RefCell<SurfaceState>>()
+                    .and_then(|d| d.borrow().size())
+                    .is_some();
+            if states.role == Some("subsurface") {
+                let current = states.cached_state.current::<SubsurfaceCachedState>();
+                location += current.location;
+            }
+            // also process unmapped children, so they leave the output
+            TraversalAction::DoChildren((location, mapped))
+        },
+        |wl_surface, states, &(loc, mapped)| {
+            states
+                .data_map
+                .insert_if_missing_threadsafe(Mutex::<OutputTracking>::default);
+            let mut tracking = states
+                .data_map
+                .get::<Mutex<OutputTracking>>()
+                .unwrap()
+                .lock()
+                .unwrap();
+            let size = states
+                .data_map
+                .get::<RefCell<SurfaceState>>()
+                .and_then(|d| d.borrow().size());
+
+            let overlaps = match size {
+                Some(size) if mapped => output_geometry.overlaps(Rectangle { loc, size }),
+                _ => false,
+            };
+            let entered = tracking.outputs.contains(output);
+            if overlaps && !entered {
+                output.enter(wl_surface);
+                tracking.outputs.push(output.clone());
+            } else if !overlaps && entered {
+                output.leave(wl_surface);
+                tracking.outputs.retain(|o| o != output);
+            }
+        },
+        |_, _, _| true,
+    );
+}
+
+/// Sends `wl_surface.leave` for `output` to all surfaces of a surface tree that entered it
+pub(crate) fn output_leave(output: &Output, surface: &WlSurface) {
+    with_surface_tree_downward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |wl_surface, states, _| {
+            if let Some(tracking) = states.data_map.get::<Mutex<OutputTracking>>() {
+                let mut tracking = tracking.lock().unwrap();
+                if tracking.outputs.contains(output) {
+                    output.leave(wl_surface);
+                    tracking.outputs.retain(|o| o != output);
+                }
+            }
+        },
+        |_, _, _| true,
+    );
+}
+
+/// Returns the outputs a surface has entered, as managed by the [`desktop`](crate::desktop) module
+pub fn surface_outputs(surface: &WlSurface) -> Vec<Output> {
+    crate::wayland::compositor::with_states(surface, |states| {
+        states
+            .data_map
+            .get::<Mutex<OutputTracking>>()
+            .map(|tracking| tracking.lock().unwrap().outputs.clone())
+            .unwrap_or_default()
+    })
+    .unwrap_or_default()
+}
+
+/// Returns the size of an output in the global logical coordinate space
+///
+/// This takes the current mode, transform and scale of the output into account.
+pub(crate) fn output_logical_size(output: &Output) -> Size<i32, Logical> {
+    let size = output
+        .current_mode()
+        .map(|mode| mode.size)
+        .unwrap_or_else(|| (0, 0).into());
+    let size = match output.current_transform() {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => {
+            (size.h, size.w).into()
+        }
+        _ => size,
+    };
+    size.to_logical(output.current_scale().max(1))
+}
@@ -0,0 +1,292 @@
+use std::{
+    cell::Cell,
+    hash::{Hash, Hasher},
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use wayland_protocols::xdg_shell::server::xdg_toplevel;
+use wayland_server::{protocol::wl_surface::WlSurface, UserDataMap};
+
+use crate::{
+    desktop::{
+        popup::{PopupKind, PopupManager},
+        utils::{bbox_from_surface_tree, send_frames_surface_tree, under_from_surface_tree},
+    },
+    utils::{Logical, Point, Rectangle, Size},
+    wayland::{
+        compositor::with_states,
+        shell::{
+            legacy::ShellSurface,
+            xdg::{SurfaceCachedState, ToplevelSurface},
+        },
+    },
+};
+
+static WINDOW_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The shell surface backing a [`Window`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    /// A xdg-shell toplevel
+    Xdg(ToplevelSurface),
+    /// A wl_shell surface
+    Wl(ShellSurface),
+    /// An X11 window managed by XWayland
+    #[cfg(feature = "xwayland")]
+    X11(X11Surface),
+}
+
+/// The `wl_surface` of an X11 window
+///
+/// XWayland pairs every mapped X11 window with a `wl_surface`, using the `WL_SURFACE_ID`
+/// client message. Once your window manager has done this pairing, the surface can be
+/// wrapped into a [`Window`].
+#[cfg(feature = "xwayland")]
+#[derive(Debug, Clone)]
+pub struct X11Surface {
+    surface: WlSurface,
+}
+
+#[cfg(feature = "xwayland")]
+impl std::cmp::PartialEq for X11Surface {
+    fn eq(&self, other: &Self) -> bool {
+        self.alive() && other.alive() && self.surface == other.surface
+    }
+}
+
+#[cfg(feature = "xwayland")]
+impl X11Surface {
+    /// Wrap the `wl_surface` of an X11 window
+    pub fn new(surface: WlSurface) -> X11Surface {
+        X11Surface { surface }
+    }
+
+    /// Checks if the surface is still alive
+    pub fn alive(&self) -> bool {
+        self.surface.as_ref().is_alive()
+    }
+
+    /// Access the underlying `wl_surface`
+    ///
+    /// Returns `None` if the surface has been destroyed.
+    pub fn get_surface(&self) -> Option<&WlSurface> {
+        if self.alive() {
+            Some(&self.surface)
+        } else {
+            None
+        }
+    }
+}
+
+impl Kind {
+    /// Checks if the shell surface is still alive
+    pub fn alive(&self) -> bool {
+        match *self {
+            Kind::Xdg(ref t) => t.alive(),
+            Kind::Wl(ref t) => t.alive(),
+            #[cfg(feature = "xwayland")]
+            Kind::X11(ref t) => t.alive(),
+        }
+    }
+
+    /// Access the underlying `wl_surface`
+    ///
+    /// Returns `None` if the shell surface has been destroyed.
+    pub fn get_surface(&self) -> Option<&WlSurface> {
+        match *self {
+            Kind::Xdg(ref t) => t.get_surface(),
+            Kind::Wl(ref t) => t.get_surface(),
+            #[cfg(feature = "xwayland")]
+            Kind::X11(ref t) => t.get_surface(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct WindowInner {
+    id: usize,
+    toplevel: Kind,
+    bbox: Cell<Rectangle<i32, Logical>>,
+    user_data: UserDataMap,
+}
+
+/// A toplevel window of the desktop
+///
+/// This is a cheaply clonable handle, all clones refer to the same window.
+#[derive(Debug, Clone)]
+pub struct Window(Rc<WindowInner>);
+
+impl PartialEq for Window {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
+}
+
+impl Eq for Window {}
+
+impl Hash for Window {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.id.hash(state);
+    }
+}
+
+impl Window {
+    /// Create a new window from a shell surface
+    pub fn new(toplevel: Kind) -> Window {
+        let id = WINDOW_ID.fetch_add(1, Ordering::SeqCst);
+        let window = Window(Rc::new(WindowInner {
+            id,
+            toplevel,
+            bbox: Cell::new(Rectangle::from_loc_and_size((0, 0), (0, 0))),
+            user_data: UserDataMap::new(),
+        }));
+        window.refresh();
+        window
+    }
+
+    /// The shell surface backing this window
+    pub fn toplevel(&self) -> &Kind {
+        &self.0.toplevel
+    }
+
+    /// Checks if the window is still alive
+    pub fn alive(&self) -> bool {
+        self.0.toplevel.alive()
+    }
+
+    /// The geometry of this window relative to its location
+    ///
+    /// This is the window geometry set by the client if any (see `xdg_surface.set_window_geometry`),
+    /// otherwise the bounding box of the window.
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        let geometry = match (&self.0.toplevel, self.0.toplevel.get_surface()) {
+            (Kind::Xdg(_), Some(surface)) => with_states(surface, |states| {
+                states.cached_state.current::<SurfaceCachedState>().geometry
+            })
+            .ok()
+            .flatten(),
+            _ => None,
+        };
+        geometry.unwrap_or_else(|| self.bbox())
+    }
+
+    /// The bounding box of this window, its subsurfaces and its popups, relative to its location
+    ///
+    /// The bounding box is updated by [`Window::refresh`].
+    pub fn bbox(&self) -> Rectangle<i32, Logical> {
+        self.0.bbox.get()
+    }
+
+    /// Recompute the bounding box of this window
+    ///
+    /// This should be called after the surfaces of this window were committed.
+    /// [`Space::refresh`](crate::desktop::Space::refresh) does this for all mapped windows.
+    pub fn refresh(&self) {
+        let mut bbox = match self.0.toplevel.get_surface() {
+            Some(surface) => bbox_from_surface_tree(surface, (0, 0)),
+            None => Rectangle::from_loc_and_size((0, 0), (0, 0)),
+        };
+        for (popup, location) in self.popups() {
+            if let Some(surface) = popup.get_surface() {
+                let popup_bbox = bbox_from_surface_tree(surface, location);
+                // unmapped popups must not extend the bounding box to their location
+                if popup_bbox.size.w > 0 && popup_bbox.size.h > 0 {
+                    bbox = bbox.merge(popup_bbox);
+                }
+            }
+        }
+        self.0.bbox.set(bbox);
+    }
+
+    /// Activate or deactivate this window
+    ///
+    /// Returns `true` if the state changed, in which case you need to call
+    /// [`Window::configure`] to notify the client.
+    pub fn set_activated(&self, active: bool) -> bool {
+        match self.0.toplevel {
+            Kind::Xdg(ref t) => t
+                .with_pending_state(|state| {
+                    if active {
+                        state.states.set(xdg_toplevel::State::Activated)
+                    } else {
+                        state.states.unset(xdg_toplevel::State::Activated)
+                    }
+                })
+                .unwrap_or(false),
+            Kind::Wl(_) => false,
+            #[cfg(feature = "xwayland")]
+            Kind::X11(_) => false,
+        }
+    }
+
+    /// Request a new size for this window
+    ///
+    /// You need to call [`Window::configure`] to notify the client.
+    pub fn request_size(&self, size: Size<i32, Logical>) {
+        if let Kind::Xdg(ref t) = self.0.toplevel {
+            let _ = t.with_pending_state(|state| state.size = Some(size));
+        }
+    }
+
+    /// Send the pending state of this window to the client
+    pub fn configure(&self) {
+        match self.0.toplevel {
+            Kind::Xdg(ref t) => t.send_configure(),
+            Kind::Wl(_) => {}
+            #[cfg(feature = "xwayland")]
+            Kind::X11(_) => {}
+        }
+    }
+
+    /// Sends the frame callback to all the surfaces of this window and its popups that requested it
+    pub fn send_frame(&self, time: u32) {
+        if let Some(surface) = self.0.toplevel.get_surface() {
+            send_frames_surface_tree(surface, time);
+        }
+        for (popup, _) in self.popups() {
+            if let Some(surface) = popup.get_surface() {
+                send_frames_surface_tree(surface, time);
+            }
+        }
+    }
+
+    /// The popups of this window tracked by a [`PopupManager`], bottom first
+    ///
+    /// Every popup is returned together with the location of its `wl_surface` relative to the
+    /// location of the window.
+    pub fn popups(&self) -> Vec<(PopupKind, Point<i32, Logical>)> {
+        match self.0.toplevel.get_surface() {
+            Some(surface) => PopupManager::popups_for_surface(surface),
+            None => Vec::new(),
+        }
+    }
+
+    /// Finds the topmost surface of this window or its popups under the given point
+    ///
+    /// The point is relative to the location of the window. Returns the found surface together
+    /// with its location relative to the location of the window.
+    pub fn surface_under(&self, point: Point<f64, Logical>) -> Option<(WlSurface, Point<i32, Logical>)> {
+        if !self.bbox().to_f64().contains(point) {
+            return None;
+        }
+        // popups are displayed above their parent
+        let popup_under = self.popups().into_iter().rev().find_map(|(popup, location)| {
+            popup
+                .get_surface()
+                .and_then(|surface| under_from_surface_tree(surface, point, location))
+        });
+        if popup_under.is_some() {
+            return popup_under;
+        }
+        self.0
+            .toplevel
+            .get_surface()
+            .and_then(|surface| under_from_surface_tree(surface, point, (0, 0)))
+    }
+
+    /// Access the `UserDataMap` associated with this `Window`
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.0.user_data
+    }
+}
@@ -15,7 +15,8 @@
 //! the operating system, such as session management, interactions with the graphic stack and input
 //! processing. On the other hand, [`wayland`] contains helpers for interacting with wayland clients
 //! according to the wayland protocol. In addition, the [`xwayland`] module contains helpers for managing
-//! an XWayland instance if you want to support it. See the documentation of these respective modules for
+//! an XWayland instance if you want to support it, and the [`desktop`] module contains optional, higher-level
+//! helpers for desktop-style window management. See the documentation of these respective modules for
 //! information about their usage.
 //!
 //! ## General principles for using Smithay
@@ -51,6 +52,8 @@
 pub extern crate nix;
 
 pub mod backend;
+#[cfg(feature = "desktop")]
+pub mod desktop;
 pub mod utils;
 #[cfg(feature = "wayland_frontend")]
 pub mod wayland;
@@ -60,6 +63,10 @@ pub mod xwayland;
 
 pub mod reexports;
 
+// also used by the tests of the protocol handlers
+#[cfg(any(feature = "testing", all(test, feature = "wayland_frontend")))]
+pub mod testing;
+
 #[cfg(feature = "slog-stdlog")]
 #[allow(dead_code)]
 fn slog_or_fallback<L>(logger: L) -> ::slog::Logger
@@ -13,6 +13,8 @@ pub use input;
 pub use nix;
 #[cfg(feature = "backend_udev")]
 pub use udev;
+#[cfg(feature = "testing")]
+pub use wayland_client;
 #[cfg(feature = "wayland_frontend")]
 pub use wayland_commons;
 #[cfg(feature = "wayland_frontend")]
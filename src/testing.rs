@@ -0,0 +1,343 @@
+//! Helpers to test protocol handlers against a real wayland client
+//!
+//! The [`TestHarness`] creates a [`Display`] and connects an in-process
+//! [`wayland-client`](wayland_client) to it over a socketpair. Both sides are driven explicitly by the
+//! test, which makes it possible to check the state of the server after every step:
+//!
+//! - [`TestHarness::dispatch_server`] sends the pending requests of the client and lets the server process them,
+//! - [`TestHarness::dispatch_client`] lets the client process the events sent by the server,
+//! - [`TestHarness::roundtrip`] does both until the server processed all requests sent so far.
+//!
+//! Events received by the client can be collected with [`record_events`], protocol errors posted by the
+//! server are returned by [`TestHarness::roundtrip`] and [`TestHarness::protocol_error`].
+//!
+//! ```rust,no_run
+//! use smithay::testing::{record_events, HarnessError, TestHarness};
+//! use smithay::reexports::wayland_protocols::xdg_shell::client::{xdg_positioner, xdg_wm_base::XdgWmBase};
+//! use smithay::wayland::shell::xdg::xdg_shell_init;
+//!
+//! let mut harness = TestHarness::new(()).unwrap();
+//! xdg_shell_init(harness.display_mut(), |_, _| {}, None);
+//! harness.roundtrip().unwrap();
+//!
+//! let wm_base = harness.globals().instantiate_exact::<XdgWmBase>(1).unwrap();
+//! let events = record_events(&wm_base);
+//! let positioner = wm_base.create_positioner();
+//! positioner.set_size(0, 0);
+//!
+//! match harness.roundtrip() {
+//!     Err(HarnessError::Protocol(err)) => {
+//!         assert_eq!(err.code, xdg_positioner::Error::InvalidInput as u32);
+//!     }
+//!     _ => panic!("Expected a protocol error"),
+//! }
+//! assert!(events.is_empty());
+//! ```
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt, io,
+    os::unix::{io::IntoRawFd, net::UnixStream},
+    rc::Rc,
+    time::Duration,
+};
+
+use wayland_client::{
+    protocol::wl_display::WlDisplay, Attached, ConnectError, EventQueue, GlobalManager, Interface, Main,
+    MessageGroup, Proxy, ProxyMap,
+};
+use wayland_server::{Client, Display};
+
+pub use wayland_client::ProtocolError;
+
+// number of dispatch cycles before a roundtrip is considered stuck
+const MAX_ROUNDTRIP_ITERATIONS: usize = 100;
+
+/// Error returned by the [`TestHarness`]
+#[derive(Debug, thiserror::Error)]
+pub enum HarnessError {
+    /// The server posted a protocol error to the client
+    #[error("{0}")]
+    Protocol(ProtocolError),
+    /// The connection failed
+    #[error("The connection failed")]
+    Io(#[from] io::Error),
+    /// The client failed to connect
+    #[error("The client failed to connect")]
+    Connect(#[from] ConnectError),
+    /// The server did not answer a roundtrip
+    #[error("The server did not answer the roundtrip")]
+    RoundtripTimeout,
+}
+
+/// A wayland server and an in-process client connected to it
+///
+/// `D` is the dispatch data passed to the server when it processes requests of the client, it is
+/// available to the handlers of the server as [`DispatchData`](wayland_server::DispatchData).
+pub struct TestHarness<D: 'static> {
+    display: Display,
+    state: D,
+    client: Client,
+    client_display: wayland_client::Display,
+    attached_display: Attached<WlDisplay>,
+    event_queue: EventQueue,
+    globals: GlobalManager,
+}
+
+impl<D> fmt::Debug for TestHarness<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestHarness")
+            .field("display", &self.display)
+            .field("client", &self.client)
+            .field("client_display", &self.client_display)
+            .field("event_queue", &self.event_queue)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: 'static> TestHarness<D> {
+    /// Create a new server and connect a client to it
+    ///
+    /// No globals are created, use [`TestHarness::display_mut`] to initialize the protocol handlers
+    /// you want to test.
+    pub fn new(mut state: D) -> Result<TestHarness<D>, HarnessError> {
+        let (server_socket, client_socket) = UnixStream::pair()?;
+        // the client is driven by the test, reading from it must never block
+        client_socket.set_nonblocking(true)?;
+
+        let mut display = Display::new();
+        // Safety: the file descriptor is a freshly created and connected socket, its ownership is
+        // transferred to libwayland
+        let client = unsafe { display.create_client(server_socket.into_raw_fd(), &mut state) };
+        // Safety: same for the client side
+        let client_display = unsafe { wayland_client::Display::from_fd(client_socket.into_raw_fd())? };
+        let event_queue = client_display.create_event_queue();
+        let attached_display = (*client_display).clone().attach(event_queue.token());
+        let globals = GlobalManager::new(&attached_display);
+
+        Ok(TestHarness {
+            display,
+            state,
+            client,
+            client_display,
+            attached_display,
+            event_queue,
+            globals,
+        })
+    }
+
+    /// The server side [`Display`]
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// The server side [`Display`], e.g. to create new globals
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    /// The dispatch data of the server
+    pub fn state(&self) -> &D {
+        &self.state
+    }
+
+    /// The dispatch data of the server
+    pub fn state_mut(&mut self) -> &mut D {
+        &mut self.state
+    }
+
+    /// The client, as seen by the server
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// The `wl_display` of the client
+    pub fn client_display(&self) -> &Attached<WlDisplay> {
+        &self.attached_display
+    }
+
+    /// The globals advertised to the client
+    ///
+    /// The list of globals is updated when the client processes the events of its registry, so globals
+    /// created on the server are only available after a [`TestHarness::roundtrip`].
+    pub fn globals(&self) -> &GlobalManager {
+        &self.globals
+    }
+
+    /// The protocol error posted by the server to the client, if any
+    pub fn protocol_error(&self) -> Option<ProtocolError> {
+        self.client_display.protocol_error()
+    }
+
+    /// Send the pending requests of the client to the server and process them
+    ///
+    /// The events generated by the server are sent to the client, but not yet processed by it.
+    pub fn dispatch_server(&mut self) -> Result<(), HarnessError> {
+        match self.client_display.flush() {
+            Err(err) if err.kind() != io::ErrorKind::WouldBlock => return Err(self.client_error(err)),
+            _ => {}
+        }
+        self.display.dispatch(Duration::ZERO, &mut self.state)?;
+        self.display.flush_clients(&mut self.state);
+        Ok(())
+    }
+
+    /// Process the events received by the client
+    ///
+    /// Returns the number of dispatched events.
+    pub fn dispatch_client(&mut self) -> Result<u32, HarnessError> {
+        if let Some(guard) = self.event_queue.prepare_read() {
+            match guard.read_events() {
+                Err(err) if err.kind() != io::ErrorKind::WouldBlock => return Err(self.client_error(err)),
+                _ => {}
+            }
+        }
+        self.event_queue
+            .dispatch_pending(&mut (), |_, _, _| {})
+            .map_err(|err| self.client_error(err))
+    }
+
+    /// Dispatch the server and the client until the server processed all requests sent so far
+    /// and the client processed the resulting events
+    pub fn roundtrip(&mut self) -> Result<(), HarnessError> {
+        let done = Rc::new(Cell::new(false));
+        let done_clone = done.clone();
+        self.attached_display
+            .sync()
+            .quick_assign(move |_, _, _| done_clone.set(true));
+
+        for _ in 0..MAX_ROUNDTRIP_ITERATIONS {
+            self.dispatch_server()?;
+            self.dispatch_client()?;
+            if done.get() {
+                return Ok(());
+            }
+        }
+        Err(HarnessError::RoundtripTimeout)
+    }
+
+    fn client_error(&self, err: io::Error) -> HarnessError {
+        match self.protocol_error() {
+            Some(err) => HarnessError::Protocol(err),
+            None => HarnessError::Io(err),
+        }
+    }
+}
+
+/// Events received by a client object, see [`record_events`]
+#[derive(Debug)]
+pub struct RecordedEvents<E> {
+    events: Rc<RefCell<Vec<E>>>,
+}
+
+impl<E> RecordedEvents<E> {
+    /// Take the events received so far
+    pub fn take(&self) -> Vec<E> {
+        std::mem::take(&mut *self.events.borrow_mut())
+    }
+
+    /// Number of events received and not yet taken
+    pub fn len(&self) -> usize {
+        self.events.borrow().len()
+    }
+
+    /// Returns `true` if no events were received since they were last taken
+    pub fn is_empty(&self) -> bool {
+        self.events.borrow().is_empty()
+    }
+}
+
+/// Record the events received by a client object
+///
+/// This replaces the current implementation of the object.
+pub fn record_events<I>(proxy: &Main<I>) -> RecordedEvents<I::Event>
+where
+    I: Interface + AsRef<Proxy<I>> + From<Proxy<I>> + Sync,
+    I::Event: MessageGroup<Map = ProxyMap> + 'static,
+{
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let events_clone = events.clone();
+    proxy.quick_assign(move |_, event, _| events_clone.borrow_mut().push(event));
+    RecordedEvents { events }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::{
+        compositor::compositor_init,
+        shell::xdg::{xdg_shell_init, ShellClient, XdgRequest},
+        SERIAL_COUNTER,
+    };
+    use wayland_client::protocol::wl_compositor::WlCompositor;
+    use wayland_protocols::xdg_shell::client::{
+        xdg_positioner,
+        xdg_wm_base::{self, XdgWmBase},
+    };
+
+    #[test]
+    fn globals() {
+        let mut harness = TestHarness::new(()).unwrap();
+        compositor_init(harness.display_mut(), |_, _| {}, None);
+        xdg_shell_init(harness.display_mut(), |_, _| {}, None);
+        harness.roundtrip().unwrap();
+
+        let globals = harness.globals().list();
+        assert!(globals
+            .iter()
+            .any(|(_, interface, _)| interface == "wl_compositor"));
+        assert!(globals.iter().any(|(_, interface, _)| interface == "xdg_wm_base"));
+    }
+
+    #[test]
+    fn events() {
+        let mut harness = TestHarness::new(Vec::<ShellClient>::new()).unwrap();
+        xdg_shell_init(
+            harness.display_mut(),
+            |request, mut data| {
+                if let XdgRequest::NewClient { client } = request {
+                    data.get::<Vec<ShellClient>>().unwrap().push(client);
+                }
+            },
+            None,
+        );
+        harness.roundtrip().unwrap();
+
+        let wm_base = harness.globals().instantiate_exact::<XdgWmBase>(1).unwrap();
+        let events = record_events(&wm_base);
+        harness.roundtrip().unwrap();
+        assert_eq!(harness.state().len(), 1);
+
+        let serial = SERIAL_COUNTER.next_serial();
+        harness.state()[0].send_ping(serial).unwrap();
+        harness.dispatch_server().unwrap();
+        assert!(events.is_empty());
+        harness.dispatch_client().unwrap();
+        match &events.take()[..] {
+            [xdg_wm_base::Event::Ping { serial: received }] => assert_eq!(*received, u32::from(serial)),
+            events => panic!("Unexpected events: {:?}", events),
+        }
+    }
+
+    #[test]
+    fn protocol_error() {
+        let mut harness = TestHarness::new(()).unwrap();
+        compositor_init(harness.display_mut(), |_, _| {}, None);
+        xdg_shell_init(harness.display_mut(), |_, _| {}, None);
+        harness.roundtrip().unwrap();
+
+        let _compositor = harness.globals().instantiate_exact::<WlCompositor>(4).unwrap();
+        let wm_base = harness.globals().instantiate_exact::<XdgWmBase>(1).unwrap();
+        let positioner = wm_base.create_positioner();
+        positioner.set_size(0, 0);
+
+        match harness.roundtrip() {
+            Err(HarnessError::Protocol(err)) => {
+                assert_eq!(err.code, xdg_positioner::Error::InvalidInput as u32);
+                assert_eq!(err.object_interface, "xdg_positioner");
+            }
+            result => panic!("Expected a protocol error, got {:?}", result),
+        }
+        assert!(harness.protocol_error().is_some());
+    }
+}
@@ -1,6 +1,9 @@
 use std::fmt;
+use std::iter::FromIterator;
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 
+use crate::backend::renderer::Transform;
+
 /// Type-level marker for the logical coordinate space
 #[derive(Debug)]
 pub struct Logical;
@@ -1040,3 +1043,410 @@ impl<N: Default, Kind> Default for Rectangle<N, Kind> {
         }
     }
 }
+
+/// A region defined by a set of non-overlapping rectangles
+///
+/// All operations keep the rectangles disjoint and drop empty ones, so the area of a region is the
+/// sum of the areas of its rectangles. Use [`Region::simplify`] to reduce the number of rectangles
+/// after many operations.
+pub struct Region<N, Kind> {
+    rects: Vec<Rectangle<N, Kind>>,
+}
+
+impl<N: Coordinate, Kind> Region<N, Kind> {
+    /// Create a new empty region
+    #[inline]
+    pub fn new() -> Self {
+        Region { rects: Vec::new() }
+    }
+
+    /// Create a new region covering the given rectangle
+    pub fn from_rect(rect: impl Into<Rectangle<N, Kind>>) -> Self {
+        let mut region = Region::new();
+        region.add_rect(rect);
+        region
+    }
+
+    /// The non-overlapping rectangles making up this region
+    #[inline]
+    pub fn rects(&self) -> &[Rectangle<N, Kind>] {
+        &self.rects
+    }
+
+    /// Convert this region into its non-overlapping rectangles
+    #[inline]
+    pub fn into_rects(self) -> Vec<Rectangle<N, Kind>> {
+        self.rects
+    }
+
+    /// Checks whether this region is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// The smallest rectangle containing the whole region, `None` if the region is empty
+    pub fn bbox(&self) -> Option<Rectangle<N, Kind>> {
+        let first = *self.rects.first()?;
+        Some(
+            self.rects
+                .iter()
+                .skip(1)
+                .fold(first, |bbox, rect| bbox.merge(*rect)),
+        )
+    }
+
+    /// Checks whether given [`Point`] is inside the region
+    pub fn contains<P: Into<Point<N, Kind>>>(&self, point: P) -> bool {
+        let point = point.into();
+        self.rects.iter().any(|rect| rect.contains(point))
+    }
+
+    /// Checks whether a given [`Rectangle`] is completely covered by this region
+    pub fn contains_rect(&self, rect: impl Into<Rectangle<N, Kind>>) -> bool {
+        let mut remaining = Region::from_rect(rect);
+        for rect in &self.rects {
+            remaining.subtract_rect(*rect);
+        }
+        remaining.is_empty()
+    }
+
+    /// Checks whether a given [`Rectangle`] overlaps with this region
+    ///
+    /// Unlike [`Rectangle::overlaps`], rectangles only touching the region do not overlap it.
+    pub fn overlaps(&self, rect: impl Into<Rectangle<N, Kind>>) -> bool {
+        let rect = rect.into();
+        self.rects
+            .iter()
+            .any(|r| r.intersection(rect).map(|r| !is_empty(&r)).unwrap_or(false))
+    }
+
+    /// Add a rectangle to this region
+    pub fn add_rect(&mut self, rect: impl Into<Rectangle<N, Kind>>) {
+        let rect = rect.into();
+        if is_empty(&rect) {
+            return;
+        }
+        let mut pieces = vec![rect];
+        for existing in &self.rects {
+            pieces = pieces
+                .into_iter()
+                .flat_map(|piece| subtract_rect(piece, *existing))
+                .collect();
+            if pieces.is_empty() {
+                return;
+            }
+        }
+        self.rects.extend(pieces);
+    }
+
+    /// Remove a rectangle from this region
+    pub fn subtract_rect(&mut self, rect: impl Into<Rectangle<N, Kind>>) {
+        let rect = rect.into();
+        self.rects = self
+            .rects
+            .drain(..)
+            .flat_map(|existing| subtract_rect(existing, rect))
+            .collect();
+    }
+
+    /// Restrict this region to the given rectangle
+    pub fn intersect_rect(&mut self, rect: impl Into<Rectangle<N, Kind>>) {
+        let rect = rect.into();
+        self.rects = self
+            .rects
+            .drain(..)
+            .filter_map(|existing| existing.intersection(rect))
+            .filter(|rect| !is_empty(rect))
+            .collect();
+    }
+
+    /// The union of this region and another one
+    pub fn union(&self, other: &Region<N, Kind>) -> Region<N, Kind> {
+        let mut region = self.clone();
+        for rect in &other.rects {
+            region.add_rect(*rect);
+        }
+        region
+    }
+
+    /// The intersection of this region and another one
+    pub fn intersection(&self, other: &Region<N, Kind>) -> Region<N, Kind> {
+        // the rectangles of both regions are disjoint, so are their pairwise intersections
+        let rects = self
+            .rects
+            .iter()
+            .flat_map(|rect| {
+                other
+                    .rects
+                    .iter()
+                    .filter_map(move |other| rect.intersection(*other))
+            })
+            .filter(|rect| !is_empty(rect))
+            .collect();
+        Region { rects }
+    }
+
+    /// This region without the parts covered by another one
+    pub fn subtract(&self, other: &Region<N, Kind>) -> Region<N, Kind> {
+        let mut region = self.clone();
+        for rect in &other.rects {
+            region.subtract_rect(*rect);
+        }
+        region
+    }
+
+    /// Move this region by the given offset
+    pub fn translate(&mut self, offset: impl Into<Point<N, Kind>>) {
+        let offset = offset.into();
+        for rect in &mut self.rects {
+            rect.loc += offset;
+        }
+    }
+
+    /// Transform this region inside an area of the given size
+    ///
+    /// The transformed region is relative to the transformed area, see [`Transform::transform_rect_in`].
+    pub fn transform(&self, transform: Transform, area: &Size<N, Kind>) -> Region<N, Kind> {
+        Region {
+            rects: self
+                .rects
+                .iter()
+                .map(|rect| transform.transform_rect_in(*rect, area))
+                .collect(),
+        }
+    }
+
+    /// Merge neighbouring rectangles, that together form a rectangle
+    ///
+    /// This does not change the area covered by the region.
+    pub fn simplify(&mut self) {
+        'merge: loop {
+            for i in 0..self.rects.len() {
+                for j in (i + 1)..self.rects.len() {
+                    if let Some(merged) = merge_neighbours(self.rects[i], self.rects[j]) {
+                        self.rects[i] = merged;
+                        self.rects.swap_remove(j);
+                        continue 'merge;
+                    }
+                }
+            }
+            break;
+        }
+    }
+
+    /// Convert the underlying numerical type to f64 for floating point manipulations
+    pub fn to_f64(&self) -> Region<f64, Kind> {
+        Region {
+            rects: self.rects.iter().map(|rect| rect.to_f64()).collect(),
+        }
+    }
+}
+
+impl<Kind> Region<f64, Kind> {
+    /// Convert to i32 by returning the largest integer-space region fitting into the float-based region
+    pub fn to_i32_down<N: Coordinate>(&self) -> Region<N, Kind> {
+        self.rects.iter().map(|rect| rect.to_i32_down()).collect()
+    }
+
+    /// Convert to i32 by returning the smallest integer-space region encapsulating the float-based region
+    pub fn to_i32_up<N: Coordinate>(&self) -> Region<N, Kind> {
+        self.rects.iter().map(|rect| rect.to_i32_up()).collect()
+    }
+}
+
+impl<N: Coordinate> Region<N, Logical> {
+    /// Convert this logical region to physical coordinate space according to given scale factor
+    pub fn to_physical(&self, scale: N) -> Region<N, Physical> {
+        self.rects.iter().map(|rect| rect.to_physical(scale)).collect()
+    }
+
+    /// Convert this logical region to buffer coordinate space according to given scale factor
+    pub fn to_buffer(&self, scale: N) -> Region<N, Buffer> {
+        self.rects.iter().map(|rect| rect.to_buffer(scale)).collect()
+    }
+}
+
+impl<N: Coordinate> Region<N, Physical> {
+    /// Convert this physical region to logical coordinate space according to given scale factor
+    pub fn to_logical(&self, scale: N) -> Region<N, Logical> {
+        self.rects.iter().map(|rect| rect.to_logical(scale)).collect()
+    }
+}
+
+impl<N: Coordinate> Region<N, Buffer> {
+    /// Convert this buffer region to logical coordinate space according to given scale factor
+    pub fn to_logical(&self, scale: N) -> Region<N, Logical> {
+        self.rects.iter().map(|rect| rect.to_logical(scale)).collect()
+    }
+}
+
+impl<N: Coordinate, Kind> FromIterator<Rectangle<N, Kind>> for Region<N, Kind> {
+    fn from_iter<T: IntoIterator<Item = Rectangle<N, Kind>>>(iter: T) -> Self {
+        let mut region = Region::new();
+        for rect in iter {
+            region.add_rect(rect);
+        }
+        region
+    }
+}
+
+impl<N: Coordinate, Kind> From<Rectangle<N, Kind>> for Region<N, Kind> {
+    fn from(rect: Rectangle<N, Kind>) -> Self {
+        Region::from_rect(rect)
+    }
+}
+
+impl<N: Clone, Kind> Clone for Region<N, Kind> {
+    fn clone(&self) -> Self {
+        Region {
+            rects: self.rects.clone(),
+        }
+    }
+}
+
+impl<N, Kind> Default for Region<N, Kind> {
+    fn default() -> Self {
+        Region { rects: Vec::new() }
+    }
+}
+
+impl<N, Kind> fmt::Debug for Region<N, Kind>
+where
+    Rectangle<N, Kind>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.rects.iter()).finish()
+    }
+}
+
+fn is_empty<N: Coordinate, Kind>(rect: &Rectangle<N, Kind>) -> bool {
+    rect.size.w <= N::default() || rect.size.h <= N::default()
+}
+
+// Splits `rect` into up to four rectangles covering the parts not covered by `other`
+fn subtract_rect<N: Coordinate, Kind>(
+    rect: Rectangle<N, Kind>,
+    other: Rectangle<N, Kind>,
+) -> Vec<Rectangle<N, Kind>> {
+    let inter = match rect.intersection(other) {
+        Some(inter) if !is_empty(&inter) => inter,
+        _ => return vec![rect],
+    };
+
+    let (left, top) = (rect.loc.x, rect.loc.y);
+    let (right, bottom) = (
+        rect.loc.x.saturating_add(rect.size.w),
+        rect.loc.y.saturating_add(rect.size.h),
+    );
+    let (inter_right, inter_bottom) = (
+        inter.loc.x.saturating_add(inter.size.w),
+        inter.loc.y.saturating_add(inter.size.h),
+    );
+    [
+        // above and below the intersection, spanning the whole width
+        Rectangle::from_extemities((left, top), (right, inter.loc.y)),
+        Rectangle::from_extemities((left, inter_bottom), (right, bottom)),
+        // left and right of the intersection
+        Rectangle::from_extemities((left, inter.loc.y), (inter.loc.x, inter_bottom)),
+        Rectangle::from_extemities((inter_right, inter.loc.y), (right, inter_bottom)),
+    ]
+    .iter()
+    .copied()
+    .filter(|rect| !is_empty(rect))
+    .collect()
+}
+
+// Merges two rectangles sharing a whole edge
+fn merge_neighbours<N: Coordinate, Kind>(
+    a: Rectangle<N, Kind>,
+    b: Rectangle<N, Kind>,
+) -> Option<Rectangle<N, Kind>> {
+    let horizontal = a.loc.y == b.loc.y
+        && a.size.h == b.size.h
+        && (a.loc.x.saturating_add(a.size.w) == b.loc.x || b.loc.x.saturating_add(b.size.w) == a.loc.x);
+    let vertical = a.loc.x == b.loc.x
+        && a.size.w == b.size.w
+        && (a.loc.y.saturating_add(a.size.h) == b.loc.y || b.loc.y.saturating_add(b.size.h) == a.loc.y);
+    if horizontal || vertical {
+        Some(a.merge(b))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(region: &Region<i32, Logical>) -> i32 {
+        region.rects().iter().map(|rect| rect.size.w * rect.size.h).sum()
+    }
+
+    #[test]
+    fn region_ops() {
+        let a = Region::from_rect(Rectangle::<i32, Logical>::from_loc_and_size((0, 0), (10, 10)));
+        let b = Region::from_rect(Rectangle::from_loc_and_size((5, 5), (10, 10)));
+
+        let union = a.union(&b);
+        assert_eq!(area(&union), 175);
+        assert!(union.contains((12, 12)) && union.contains((2, 2)) && !union.contains((12, 2)));
+
+        let intersection = a.intersection(&b);
+        assert_eq!(
+            intersection.rects(),
+            &[Rectangle::from_loc_and_size((5, 5), (5, 5))]
+        );
+
+        let subtract = a.subtract(&b);
+        assert_eq!(area(&subtract), 75);
+        assert!(!subtract.contains((7, 7)));
+        assert!(subtract.contains_rect(Rectangle::from_loc_and_size((0, 0), (10, 5))));
+        assert!(!subtract.overlaps(Rectangle::from_loc_and_size((5, 5), (5, 5))));
+
+        let mut split = Region::from_rect(Rectangle::<i32, Logical>::from_loc_and_size((0, 0), (10, 10)));
+        split.subtract_rect(Rectangle::from_loc_and_size((4, 4), (2, 2)));
+        split.add_rect(Rectangle::from_loc_and_size((4, 4), (2, 2)));
+        assert_eq!(area(&split), 100);
+        split.simplify();
+        assert_eq!(split.rects(), &[Rectangle::from_loc_and_size((0, 0), (10, 10))]);
+    }
+
+    #[test]
+    fn region_disjoint() {
+        let region = [
+            Rectangle::<i32, Logical>::from_loc_and_size((0, 0), (10, 10)),
+            Rectangle::from_loc_and_size((5, 5), (10, 10)),
+            Rectangle::from_loc_and_size((2, 2), (3, 3)),
+        ]
+        .iter()
+        .copied()
+        .collect::<Region<_, _>>();
+        assert_eq!(area(&region), 100 + 100 - 25);
+        for (i, a) in region.rects().iter().enumerate() {
+            for b in region.rects().iter().skip(i + 1) {
+                assert!(a
+                    .intersection(*b)
+                    .map(|i| i.size.w * i.size.h == 0)
+                    .unwrap_or(true));
+            }
+        }
+    }
+
+    #[test]
+    fn region_transform() {
+        let mut region = Region::from_rect(Rectangle::<i32, Logical>::from_loc_and_size((0, 0), (2, 1)));
+        region.translate((1, 0));
+        let area = Size::from((4, 3));
+
+        let rotated = region.transform(Transform::_90, &area);
+        assert_eq!(rotated.rects(), &[Rectangle::from_loc_and_size((0, 1), (1, 2))]);
+        let flipped = region.transform(Transform::Flipped, &area);
+        assert_eq!(flipped.rects(), &[Rectangle::from_loc_and_size((1, 0), (2, 1))]);
+        let back = rotated.transform(Transform::_270, &Size::from((3, 4)));
+        assert_eq!(back.rects(), region.rects());
+
+        let physical = region.to_physical(2);
+        assert_eq!(physical.rects(), &[Rectangle::from_loc_and_size((2, 0), (4, 2))]);
+    }
+}
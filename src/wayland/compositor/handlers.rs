@@ -17,6 +17,7 @@ use crate::utils::{Logical, Point};
 
 use super::{
     cache::Cacheable,
+    transaction::queue_transaction,
     tree::{Location, PrivateSurfaceData},
     AlreadyHasRole, BufferAssignment, Damage, Rectangle, RectangleKind, RegionAttributes, SurfaceAttributes,
 };
@@ -53,7 +54,7 @@ where
  * wl_surface
  */
 
-type SurfaceImplemFn = dyn for<'a> FnMut(wl_surface::WlSurface, DispatchData<'a>);
+pub(crate) type SurfaceImplemFn = dyn for<'a> FnMut(wl_surface::WlSurface, DispatchData<'a>);
 
 // Internal implementation data of surfaces
 pub(crate) struct SurfaceImplem {
@@ -75,7 +76,7 @@ impl SurfaceImplem {
         &mut self,
         req: wl_surface::Request,
         surface: wl_surface::WlSurface,
-        ddata: DispatchData<'_>,
+        mut ddata: DispatchData<'_>,
     ) {
         match req {
             wl_surface::Request::Attach { buffer, x, y } => {
@@ -129,15 +130,31 @@ impl SurfaceImplem {
                 });
             }
             wl_surface::Request::Commit => {
-                let mut user_impl = self.implem.borrow_mut();
                 PrivateSurfaceData::invoke_commit_hooks(&surface);
                 if !surface.as_ref().is_alive() {
                     // the client was killed by a hook, abort
                     return;
                 }
-                PrivateSurfaceData::commit(&surface);
-                trace!(self.log, "Calling user implementation for wl_surface.commit");
-                (&mut *user_impl)(surface, ddata);
+                let transaction = PrivateSurfaceData::commit(&surface);
+                let mut user_impl = self.implem.borrow_mut();
+                match transaction {
+                    Some(transaction) => {
+                        // Only the commits whose state got applied are reported, including previously
+                        // blocked ones applied along with this one. If this commit is blocked, it is
+                        // reported once its blockers are cleared.
+                        for applied in queue_transaction(transaction, &self.implem) {
+                            if applied.as_ref().is_alive() {
+                                trace!(self.log, "Calling user implementation for wl_surface.commit");
+                                user_impl(applied, ddata.reborrow());
+                            }
+                        }
+                    }
+                    None => {
+                        // synchronized subsurface, its state will be applied along with its parent
+                        trace!(self.log, "Calling user implementation for wl_surface.commit");
+                        user_impl(surface, ddata);
+                    }
+                }
             }
             wl_surface::Request::SetBufferTransform { transform } => {
                 PrivateSurfaceData::with_states(&surface, |states| {
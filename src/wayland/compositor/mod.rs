@@ -66,7 +66,8 @@
 //!    illegal state before it is applied on commit.
 //! 2. The pending state is either applied and made current, or cached for later application
 //!    is the surface is a synchronize subsurface. If the current state is applied, state
-//!    of the synchronized children subsurface are applied as well at this point.
+//!    of the synchronized children subsurface are applied as well at this point. If the commit
+//!    is held back by a [`Blocker`], the state is cached until it is released (see below).
 //! 3. Your user callback provided to [`compositor_init`] is invoked, so that you can access
 //!    the new current state of the surface. The state of sync children subsurfaces of your
 //!    surface may have changed as well, so this is the place to check it, using functions
@@ -74,6 +75,19 @@
 //!    if the surface is a sync subsurface, its current state will note have changed as
 //!    the result of that commit. You can check if it is using [`is_sync_subsurface`].
 //!
+//! ### Blockers
+//!
+//! The application of a commit can be delayed by attaching a [`Blocker`] to it using
+//! [`add_blocker`], typically from a commit hook. This is for example used by the
+//! [`explicit_synchronization`](crate::wayland::explicit_synchronization) module to
+//! wait for the acquire fence of a buffer to be signaled before using it.
+//!
+//! Blocked commits are queued per client, and applied in order once all their blockers are
+//! released. Commits of synchronized subsurfaces are applied along with their parent, ensuring
+//! they stay atomic. Once a blocker might have been released, you need to call [`blocker_cleared`],
+//! which will apply all the now unblocked commits of that client and invoke your callback
+//! provided to [`compositor_init`] for each of the committed surfaces.
+//!
 //! ### Surface roles
 //!
 //! The wayland protocol specifies that a surface needs to be assigned a role before it can
@@ -91,14 +105,15 @@ mod tree;
 
 pub use self::cache::{Cacheable, MultiCache};
 pub use self::handlers::SubsurfaceCachedState;
+pub use self::transaction::{Blocker, BlockerState};
 use self::tree::PrivateSurfaceData;
 pub use self::tree::{AlreadyHasRole, TraversalAction};
-use crate::utils::{Buffer, DeadResource, Logical, Point, Rectangle};
+use crate::utils::{Buffer, DeadResource, Logical, Point, Rectangle, Region};
 use wayland_server::{
     protocol::{
         wl_buffer, wl_callback, wl_compositor, wl_output, wl_region, wl_subcompositor, wl_surface::WlSurface,
     },
-    DispatchData, Display, Filter, Global, UserDataMap,
+    Client, DispatchData, Display, Filter, Global, UserDataMap,
 };
 
 /// Description of a part of a surface that
@@ -267,6 +282,20 @@ impl RegionAttributes {
     }
 }
 
+impl From<&RegionAttributes> for Region<i32, Logical> {
+    fn from(attrs: &RegionAttributes) -> Self {
+        let mut region = Region::new();
+        for (kind, rect) in &attrs.rects {
+            match kind {
+                RectangleKind::Add => region.add_rect(*rect),
+                RectangleKind::Subtract => region.subtract_rect(*rect),
+            }
+        }
+        region.simplify();
+        region
+    }
+}
+
 /// Access the data of a surface tree from bottom to top
 ///
 /// You provide three closures, a "filter", a "processor" and a "post filter".
@@ -405,6 +434,30 @@ pub fn add_commit_hook(surface: &WlSurface, hook: fn(&WlSurface)) {
     PrivateSurfaceData::add_commit_hook(surface, hook)
 }
 
+/// Attach a [`Blocker`] to the next commit of this surface
+///
+/// The state of this commit will not be applied until the blocker is released. This should be
+/// called from a commit hook (see [`add_commit_hook`]) to block the commit being processed.
+///
+/// Once the blocker might have been released, you need to call [`blocker_cleared`].
+pub fn add_blocker<B: Blocker + Send + 'static>(surface: &WlSurface, blocker: B) {
+    if !surface.as_ref().is_alive() {
+        return;
+    }
+    PrivateSurfaceData::add_blocker(surface, blocker)
+}
+
+/// Re-evaluate the blocked commits of a client
+///
+/// Call this once a [`Blocker`] attached to commits of this client might have been released.
+/// All commits of this client, that are no longer blocked, will be applied and the callback
+/// provided to [`compositor_init`] will be invoked for each of the committed surfaces.
+///
+/// This function must not be called from within that callback.
+pub fn blocker_cleared(client: &Client, ddata: DispatchData<'_>) {
+    self::transaction::blocker_cleared(client, ddata)
+}
+
 /// Create new [`wl_compositor`](wayland_server::protocol::wl_compositor)
 /// and [`wl_subcompositor`](wayland_server::protocol::wl_subcompositor) globals.
 ///
@@ -421,22 +474,47 @@ pub fn compositor_init<Impl, L>(
 where
     L: Into<Option<::slog::Logger>>,
     Impl: for<'a> FnMut(WlSurface, DispatchData<'a>) + 'static,
+{
+    compositor_init_with_filter(display, implem, |_| true, logger)
+}
+
+/// Create new [`wl_compositor`](wayland_server::protocol::wl_compositor)
+/// and [`wl_subcompositor`](wayland_server::protocol::wl_subcompositor) globals with a client filter.
+///
+/// Both globals are only advertised to clients for which `filter` returns `true`.
+pub fn compositor_init_with_filter<Impl, F, L>(
+    display: &mut Display,
+    implem: Impl,
+    filter: F,
+    logger: L,
+) -> (
+    Global<wl_compositor::WlCompositor>,
+    Global<wl_subcompositor::WlSubcompositor>,
+)
+where
+    L: Into<Option<::slog::Logger>>,
+    Impl: for<'a> FnMut(WlSurface, DispatchData<'a>) + 'static,
+    F: FnMut(Client) -> bool + 'static,
 {
     let log = crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "compositor_handler"));
     let implem = Rc::new(RefCell::new(implem));
+    let filter = Rc::new(RefCell::new(filter));
 
-    let compositor = display.create_global(
+    let compositor_filter = filter.clone();
+    let compositor = display.create_global_with_filter(
         4,
         Filter::new(move |(new_compositor, _version), _, _| {
             self::handlers::implement_compositor::<Impl>(new_compositor, log.clone(), implem.clone());
         }),
+        move |client| (*compositor_filter.borrow_mut())(client),
     );
 
-    let subcompositor = display.create_global(
+    let subcompositor = display.create_global_with_filter(
         1,
         Filter::new(move |(new_subcompositor, _version), _, _| {
             self::handlers::implement_subcompositor(new_subcompositor);
         }),
+        move |client| (*filter.borrow_mut())(client),
     );
 
     (compositor, subcompositor)
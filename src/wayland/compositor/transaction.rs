@@ -23,41 +23,56 @@
 // - Then, still on commit, if the surface is not a synchronized subsurface, its pending transaction is
 //   directly applied
 //
-// This last step will change once we have support for explicit synchronization (and further in the future,
-// of the wp_transaction protocol). Explicit synchronization introduces a notion of blockers: the transaction
-// cannot be applied before all blockers are released, and thus must wait for it to be the case.
+// - Then, still on commit, if the surface is not a synchronized subsurface, its pending transaction is
+//   finalized and pushed into the transaction queue of its client
+//
+// Explicit synchronization introduces a notion of blockers: the transaction cannot be applied before all
+// blockers are released, and thus must wait for it to be the case. Blockers can be attached to the pending
+// transaction of a surface using `add_blocker`, usually from a commit hook.
 //
-// For thoses situations, the (currently unused) `TransactionQueue` will come into play. It is a per-client
-// queue of transactions, that stores and applies them by both respecting their topological order
-// (ensuring that for each surface, states are applied in the correct order) and that all transactions
-// wait befor all their blockers are resolved to be merged. If a blocker is cancelled, the whole transaction
-// it blocks is cancelled as well, and simply dropped. Thanks to the logic of `Cache::apply_state`, the
-// associated state will be applied automatically when the next valid transaction is applied, ensuring
-// global coherence.
-
-// A significant part of the logic of this module is not yet used,
-// but will be once proper transaction & blockers support is
-// added to smithay
-#![allow(dead_code)]
+// This is handled by the `TransactionQueue`. It is a per-client queue of transactions, that stores and
+// applies them by both respecting their topological order (ensuring that for each surface, states are
+// applied in the correct order) and that all transactions wait befor all their blockers are resolved to be
+// merged. If a blocker is cancelled, the whole transaction it blocks is cancelled as well, and simply
+// dropped. Thanks to the logic of `Cache::apply_state`, the associated state will be applied automatically
+// when the next valid transaction is applied, ensuring global coherence.
+//
+// Transactions without blockers that do not depend on a blocked transaction are applied immediately on
+// commit. Blocked transactions are re-evaluated on every commit of the client, and whenever the compositor
+// signals that a blocker may have been released using `blocker_cleared`.
 
 use std::{
+    cell::RefCell,
     collections::HashSet,
+    rc::Rc,
     sync::{Arc, Mutex},
 };
 
-use wayland_server::protocol::wl_surface::WlSurface;
+use wayland_server::{protocol::wl_surface::WlSurface, Client, DispatchData};
 
 use crate::wayland::Serial;
 
-use super::tree::PrivateSurfaceData;
+use super::{handlers::SurfaceImplemFn, tree::PrivateSurfaceData};
 
+/// Types potentially blocking state changes
+///
+/// A blocker can be attached to the next commit of a surface using
+/// [`add_blocker`](super::add_blocker). The state of this commit, and of all
+/// synchronized subsurfaces committed along with it, is then held back until
+/// the blocker is released.
 pub trait Blocker {
+    /// Retrieve the current state of the blocker
     fn state(&self) -> BlockerState;
 }
 
+/// States of a [`Blocker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BlockerState {
+    /// The blocker is still holding back the transaction
     Pending,
+    /// The blocker has been released, and the transaction can be applied
     Released,
+    /// The blocker has been cancelled, and the whole transaction should be discarded
     Cancelled,
 }
 
@@ -155,7 +170,7 @@ impl PendingTransaction {
         });
     }
 
-    pub(crate) fn finalize(mut self) -> Transaction {
+    pub(crate) fn finalize(mut self, root: &WlSurface) -> Transaction {
         // When finalizing a transaction, this *must* be the last handle to this transaction
         loop {
             let inner = match Arc::try_unwrap(self.inner) {
@@ -165,13 +180,21 @@ impl PendingTransaction {
             match inner {
                 TransactionInner::Data(TransactionState {
                     surfaces, blockers, ..
-                }) => return Transaction { surfaces, blockers },
+                }) => {
+                    return Transaction {
+                        root: root.clone(),
+                        surfaces,
+                        blockers,
+                    }
+                }
                 TransactionInner::Fused(into) => self.inner = into,
             }
         }
     }
 }
 pub(crate) struct Transaction {
+    // the surface whose commit finalized this transaction
+    root: WlSurface,
     surfaces: Vec<(WlSurface, Serial)>,
     blockers: Vec<Box<dyn Blocker + Send>>,
 }
@@ -197,6 +220,9 @@ impl Transaction {
 
     pub(crate) fn apply(self) {
         for (surface, id) in self.surfaces {
+            if !surface.as_ref().is_alive() {
+                continue;
+            }
             PrivateSurfaceData::with_states(&surface, |states| {
                 states.cached_state.apply_state(id);
             })
@@ -204,7 +230,7 @@ impl Transaction {
     }
 }
 
-// This queue should be per-client
+// This queue is per-client, see `ClientTransactions`
 #[derive(Default)]
 pub(crate) struct TransactionQueue {
     transactions: Vec<Transaction>,
@@ -217,7 +243,13 @@ impl TransactionQueue {
         self.transactions.push(t);
     }
 
-    pub(crate) fn apply_ready(&mut self) {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Applies all transactions that are ready, returning the surfaces whose commit created them
+    pub(crate) fn apply_ready(&mut self) -> Vec<WlSurface> {
+        let mut applied = Vec::new();
         // this is a very non-optimized implementation
         // we just iterate over the queue of transactions, keeping track of which
         // surface we have seen as they encode transaction dependencies
@@ -226,7 +258,7 @@ impl TransactionQueue {
         let mut i = 0;
         // the loop will terminate, as at every iteration either i is incremented by 1
         // or the lenght of self.transactions is reduced by 1.
-        while i <= self.transactions.len() {
+        while i < self.transactions.len() {
             let mut skip = false;
             // does the transaction have any active blocker?
             match self.transactions[i].state() {
@@ -265,8 +297,189 @@ impl TransactionQueue {
                 i += 1;
             } else {
                 // this transaction is to be applied, yay!
-                self.transactions.remove(i).apply();
+                // its root is reported once per transaction, so that every commit is seen by
+                // the compositor even if several commits of a surface become ready together
+                let transaction = self.transactions.remove(i);
+                applied.push(transaction.root.clone());
+                transaction.apply();
             }
         }
+        applied
+    }
+}
+
+// Per-client transaction state, stored in the `data_map` of the client
+struct ClientTransactions {
+    queue: RefCell<TransactionQueue>,
+    implem: Rc<RefCell<SurfaceImplemFn>>,
+}
+
+/// Queues a finalized transaction, applying all the transactions of this client that are ready
+///
+/// Returns the surfaces whose commits have been applied as a result.
+pub(crate) fn queue_transaction(
+    transaction: Transaction,
+    implem: &Rc<RefCell<SurfaceImplemFn>>,
+) -> Vec<WlSurface> {
+    let client = match transaction.root.as_ref().client() {
+        Some(client) => client,
+        None => {
+            // the client is gone, there is nobody to wait for
+            transaction.apply();
+            return Vec::new();
+        }
+    };
+    client.data_map().insert_if_missing(|| ClientTransactions {
+        queue: RefCell::new(TransactionQueue::default()),
+        implem: implem.clone(),
+    });
+    let data = client.data_map().get::<ClientTransactions>().unwrap();
+    let mut queue = data.queue.borrow_mut();
+
+    if queue.is_empty() && transaction.blockers.is_empty() {
+        // fast path, nothing to wait for
+        let root = transaction.root.clone();
+        transaction.apply();
+        return vec![root];
+    }
+
+    queue.append(transaction);
+    queue.apply_ready()
+}
+
+/// Re-evaluates the blocked transactions of a client, applying those that are ready
+///
+/// The compositor callback is invoked for every surface whose commit got applied.
+pub(crate) fn blocker_cleared(client: &Client, mut ddata: DispatchData<'_>) {
+    let data = match client.data_map().get::<ClientTransactions>() {
+        Some(data) => data,
+        None => return,
+    };
+    let applied = data.queue.borrow_mut().apply_ready();
+    if applied.is_empty() {
+        return;
+    }
+    let mut implem = data.implem.borrow_mut();
+    for surface in applied {
+        if surface.as_ref().is_alive() {
+            implem(surface, ddata.reborrow());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use wayland_client::{
+        protocol::{wl_compositor::WlCompositor, wl_surface::WlSurface as ClientSurface},
+        Main,
+    };
+    use wayland_server::{protocol::wl_surface::WlSurface, DispatchData};
+
+    use super::{Blocker, BlockerState};
+    use crate::{
+        testing::TestHarness,
+        wayland::compositor::{
+            add_blocker, blocker_cleared, compositor_init, with_states, SurfaceAttributes,
+        },
+    };
+
+    #[derive(Clone)]
+    struct TestBlocker(Arc<Mutex<BlockerState>>);
+
+    impl TestBlocker {
+        fn new() -> TestBlocker {
+            TestBlocker(Arc::new(Mutex::new(BlockerState::Pending)))
+        }
+
+        fn release(&self) {
+            *self.0.lock().unwrap() = BlockerState::Released;
+        }
+    }
+
+    impl Blocker for TestBlocker {
+        fn state(&self) -> BlockerState {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    // The state of the harness records the surfaces reported to the compositor callback
+    fn setup() -> (TestHarness<Vec<WlSurface>>, Main<ClientSurface>, WlSurface) {
+        let mut harness = TestHarness::new(Vec::new()).unwrap();
+        compositor_init(
+            harness.display_mut(),
+            |surface, mut ddata| ddata.get::<Vec<WlSurface>>().unwrap().push(surface),
+            None,
+        );
+        harness.roundtrip().unwrap();
+
+        let compositor = harness.globals().instantiate_exact::<WlCompositor>(4).unwrap();
+        let surface = compositor.create_surface();
+        surface.commit();
+        harness.roundtrip().unwrap();
+
+        // the first commit is not blocked and gives us the server side of the surface
+        let server_surface = harness.state_mut().pop().unwrap();
+        (harness, surface, server_surface)
+    }
+
+    fn current_scale(surface: &WlSurface) -> i32 {
+        with_states(surface, |states| {
+            states.cached_state.current::<SurfaceAttributes>().buffer_scale
+        })
+        .unwrap()
+    }
+
+    fn clear_blockers(harness: &mut TestHarness<Vec<WlSurface>>) {
+        let client = harness.client().clone();
+        blocker_cleared(&client, DispatchData::wrap(harness.state_mut()));
+    }
+
+    #[test]
+    fn blocked_transaction_defers_state() {
+        let (mut harness, surface, server_surface) = setup();
+
+        let blocker = TestBlocker::new();
+        add_blocker(&server_surface, blocker.clone());
+        surface.set_buffer_scale(2);
+        surface.commit();
+        harness.roundtrip().unwrap();
+
+        assert_eq!(current_scale(&server_surface), 1);
+        assert!(harness.state().is_empty());
+
+        // nothing changes until the blocker is released
+        clear_blockers(&mut harness);
+        assert_eq!(current_scale(&server_surface), 1);
+        assert!(harness.state().is_empty());
+
+        blocker.release();
+        clear_blockers(&mut harness);
+        assert_eq!(current_scale(&server_surface), 2);
+        assert_eq!(harness.state().len(), 1);
+    }
+
+    #[test]
+    fn later_transactions_wait_behind_blocked() {
+        let (mut harness, surface, server_surface) = setup();
+
+        let blocker = TestBlocker::new();
+        add_blocker(&server_surface, blocker.clone());
+        surface.set_buffer_scale(2);
+        surface.commit();
+        surface.set_buffer_scale(3);
+        surface.commit();
+        harness.roundtrip().unwrap();
+
+        // the second commit has no blocker, but must not overtake the first one
+        assert_eq!(current_scale(&server_surface), 1);
+        assert!(harness.state().is_empty());
+
+        blocker.release();
+        clear_blockers(&mut harness);
+        assert_eq!(current_scale(&server_surface), 3);
+        // both commits are reported once
+        assert_eq!(harness.state().len(), 2);
     }
 }
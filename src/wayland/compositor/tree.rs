@@ -1,7 +1,10 @@
 use crate::wayland::Serial;
 
 use super::{
-    cache::MultiCache, get_children, handlers::is_effectively_sync, transaction::PendingTransaction,
+    cache::MultiCache,
+    get_children,
+    handlers::is_effectively_sync,
+    transaction::{Blocker, PendingTransaction, Transaction},
     SurfaceData,
 };
 use std::sync::{atomic::Ordering, Mutex};
@@ -188,7 +191,21 @@ impl PrivateSurfaceData {
         }
     }
 
-    pub fn commit(surface: &WlSurface) {
+    pub fn add_blocker<B: Blocker + Send + 'static>(surface: &WlSurface, blocker: B) {
+        let my_data_mutex = surface
+            .as_ref()
+            .user_data()
+            .get::<Mutex<PrivateSurfaceData>>()
+            .unwrap();
+        let my_data = my_data_mutex.lock().unwrap();
+        my_data.pending_transaction.add_blocker(blocker);
+    }
+
+    /// Commits the pending state of the surface
+    ///
+    /// If the surface is not effectively sync, returns the finalized transaction, that
+    /// now needs to be queued.
+    pub fn commit(surface: &WlSurface) -> Option<Transaction> {
         let is_sync = is_effectively_sync(surface);
         let children = get_children(surface);
         let my_data_mutex = surface
@@ -228,12 +245,13 @@ impl PrivateSurfaceData {
             .pending_transaction
             .insert_state(surface.clone(), my_data.current_txid);
         if !is_sync {
-            // if we are not sync, apply the transaction immediately
+            // if we are not sync, the transaction is complete
             let tx = std::mem::take(&mut my_data.pending_transaction);
             // release the mutex, as applying the transaction will try to lock it
             std::mem::drop(my_data);
-            // apply the transaction
-            tx.finalize().apply();
+            Some(tx.finalize(surface))
+        } else {
+            None
         }
     }
 
@@ -277,15 +277,36 @@ where
     F: FnMut(DndAction, DndAction) -> DndAction + 'static,
     C: FnMut(DataDeviceEvent) + 'static,
     L: Into<Option<::slog::Logger>>,
+{
+    init_data_device_with_filter(display, callback, action_choice, |_| true, logger)
+}
+
+/// Initialize the data device global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`init_data_device`] for details.
+pub fn init_data_device_with_filter<F, C, G, L>(
+    display: &mut Display,
+    callback: C,
+    action_choice: F,
+    filter: G,
+    logger: L,
+) -> Global<wl_data_device_manager::WlDataDeviceManager>
+where
+    F: FnMut(DndAction, DndAction) -> DndAction + 'static,
+    C: FnMut(DataDeviceEvent) + 'static,
+    G: FnMut(Client) -> bool + 'static,
+    L: Into<Option<::slog::Logger>>,
 {
     let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "data_device_mgr"));
     let action_choice = Rc::new(RefCell::new(action_choice));
     let callback = Rc::new(RefCell::new(callback));
-    display.create_global(
+    display.create_global_with_filter(
         3,
         Filter::new(move |(ddm, _version), _, _| {
             implement_ddm(ddm, callback.clone(), action_choice.clone(), log.clone());
         }),
+        filter,
     )
 }
 
@@ -0,0 +1,546 @@
+//! Explicit synchronization using DRM timeline synchronization objects
+//!
+//! This module implements the `wp_linux_drm_syncobj_v1` protocol, which supersedes the
+//! [`explicit_synchronization`](super::explicit_synchronization) protocol. Instead of passing
+//! `dma_fence`s around, clients share timeline syncobjs with the compositor and attach two points
+//! on those timelines to each commit of a new buffer:
+//!
+//! - an acquire point, that will be signaled once the client is done rendering into the buffer
+//! - a release point, that the compositor has to signal once it is done using the buffer
+//!
+//! The timelines are imported on the [`DrmNode`] given at initialization, usually the render node
+//! the compositor is using, see [`supports_syncobj_eventfd`] to figure out if a node can be used.
+//!
+//! Commits carrying an acquire point are held back using a [`Blocker`](crate::wayland::compositor::Blocker)
+//! until the point is signaled, so the compositor never samples a buffer the GPU is still writing into.
+//! The points are polled through the provided `calloop` event loop, once a point is signaled the commit is
+//! applied and the callback provided to [`compositor_init`](crate::wayland::compositor::compositor_init) is
+//! invoked with the data of the event loop as [`DispatchData`](wayland_server::DispatchData).
+//! The type of the event loop data thus needs to match the data you use to dispatch the
+//! [`Display`](wayland_server::Display).
+//!
+//! ## Usage
+//!
+//! First, you need to initialize the global:
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::backend::drm::{syncobj::supports_syncobj_eventfd, DrmNode};
+//! use smithay::wayland::drm_syncobj::init_drm_syncobj_global;
+//!
+//! # fn init(display: &mut wayland_server::Display, render_node: DrmNode) {
+//! # let event_loop = smithay::reexports::calloop::EventLoop::<()>::try_new().unwrap();
+//! if supports_syncobj_eventfd(&render_node) {
+//!     init_drm_syncobj_global(
+//!         display,
+//!         render_node,
+//!         event_loop.handle(),
+//!         None /* You can insert a logger here */
+//!     );
+//! }
+//! # }
+//! ```
+//!
+//! Then when handling a surface commit, the acquire point is guaranteed to be signaled and the release
+//! point has to be signaled once you are done using the buffer. When you start using the buffer of the
+//! current state of a surface, take its release point using [`take_release_point`] and keep it along
+//! with the buffer. The point is signaled once the returned [`DrmSyncReleasePoint`] is dropped, for example
+//! when you release the buffer:
+//!
+//! ```
+//! # extern crate wayland_server;
+//! # use wayland_server::protocol::wl_surface::WlSurface;
+//! use smithay::wayland::compositor::with_states;
+//! use smithay::wayland::drm_syncobj::take_release_point;
+//!
+//! # fn dummy_function(surface: &WlSurface) {
+//! let release_point = with_states(surface, take_release_point).unwrap();
+//! /* use the buffer */
+//! // done with the buffer, signal the release point
+//! drop(release_point);
+//! # }
+//! ```
+//!
+//! Release points that are never taken are signaled once the next commit of a new buffer replaces them.
+//! If you keep track of the buffers using
+//! [`renderer::utils::on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler),
+//! the release points are taken and signaled for you once the buffers are released.
+
+use std::{
+    cell::RefCell,
+    ops::Deref as _,
+    os::unix::io::{AsRawFd, RawFd},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction};
+use slog::{debug, warn};
+use wayland_server::{protocol::wl_surface::WlSurface, Client, DispatchData, Display, Filter, Global, Main};
+
+use super::compositor::{
+    add_blocker, add_commit_hook, blocker_cleared, with_states, Blocker, BlockerState, BufferAssignment,
+    Cacheable, SurfaceAttributes, SurfaceData,
+};
+use super::protocols::linux_drm_syncobj::v1::server::{
+    wp_linux_drm_syncobj_manager_v1::{self, WpLinuxDrmSyncobjManagerV1},
+    wp_linux_drm_syncobj_surface_v1::{self, WpLinuxDrmSyncobjSurfaceV1},
+    wp_linux_drm_syncobj_timeline_v1::WpLinuxDrmSyncobjTimelineV1,
+};
+use crate::backend::allocator::dmabuf::Dmabuf;
+pub use crate::backend::drm::syncobj::{supports_syncobj_eventfd, DrmSyncPoint, DrmTimeline};
+use crate::backend::drm::DrmNode;
+
+/// Synchronization points of a surface commit
+///
+/// Both points are set if and only if the commit attached a new buffer.
+///
+/// When processing the current state, take the release point from it using [`take_release_point`]
+/// and drop it once you are done using the buffer. Otherwise it will be treated as unused and signaled
+/// when overwritten by the next client commit.
+#[derive(Debug, Default)]
+pub struct DrmSyncobjCachedState {
+    /// The point the client signals once the buffer is ready to be accessed
+    pub acquire_point: Option<DrmSyncPoint>,
+    /// The point you should signal once you are done accessing the buffer
+    pub release_point: Option<DrmSyncPoint>,
+}
+
+impl Cacheable for DrmSyncobjCachedState {
+    fn commit(&mut self) -> Self {
+        std::mem::take(self)
+    }
+    fn merge_into(self, into: &mut Self) {
+        if self.acquire_point.is_none() && self.release_point.is_none() {
+            return;
+        }
+        into.acquire_point = self.acquire_point;
+        if let Some(release_point) = std::mem::replace(&mut into.release_point, self.release_point) {
+            // the overriden buffer was never used
+            let _ = release_point.signal();
+        }
+    }
+}
+
+/// The release point of a commit, signaled when dropped
+///
+/// See [`take_release_point`].
+#[derive(Debug)]
+pub struct DrmSyncReleasePoint(Option<DrmSyncPoint>);
+
+impl DrmSyncReleasePoint {
+    /// The point that will be signaled
+    pub fn point(&self) -> &DrmSyncPoint {
+        self.0.as_ref().unwrap()
+    }
+
+    /// Take over signaling the point
+    ///
+    /// The point is no longer signaled when this is dropped, this is useful to signal it using
+    /// [`DrmSyncPoint::signal_with_sync_file`] once the rendering operations using the buffer finish.
+    pub fn into_point(mut self) -> DrmSyncPoint {
+        self.0.take().unwrap()
+    }
+}
+
+impl Drop for DrmSyncReleasePoint {
+    fn drop(&mut self) {
+        if let Some(point) = self.0.take() {
+            // the client is gone if the timeline cannot be signaled anymore
+            let _ = point.signal();
+        }
+    }
+}
+
+/// Take the release point of the current state of a surface
+///
+/// Call this when you start using the buffer of the current state, the returned point is signaled once
+/// dropped. Returns `None` if the commit did not use explicit synchronization, or if the release point
+/// was already taken.
+pub fn take_release_point(states: &SurfaceData) -> Option<DrmSyncReleasePoint> {
+    if !states.cached_state.has::<DrmSyncobjCachedState>() {
+        return None;
+    }
+    states
+        .cached_state
+        .current::<DrmSyncobjCachedState>()
+        .release_point
+        .take()
+        .map(|point| DrmSyncReleasePoint(Some(point)))
+}
+
+type PointWatcher = dyn Fn(&WlSurface, &DrmSyncPoint);
+
+struct DrmSyncobjSurfaceData {
+    state: RefCell<Option<WpLinuxDrmSyncobjSurfaceV1>>,
+    watcher: Rc<PointWatcher>,
+}
+
+/// A [`Blocker`] waiting for an acquire point to be signaled
+#[derive(Debug)]
+struct AcquirePointBlocker {
+    signaled: Arc<AtomicBool>,
+}
+
+impl Blocker for AcquirePointBlocker {
+    fn state(&self) -> BlockerState {
+        if self.signaled.load(Ordering::Acquire) {
+            BlockerState::Released
+        } else {
+            BlockerState::Pending
+        }
+    }
+}
+
+// Eventfd owned by the event source
+#[derive(Debug)]
+struct EventFd(RawFd);
+
+impl AsRawFd for EventFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl Drop for EventFd {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.0);
+    }
+}
+
+fn commit_hook(surface: &WlSurface) {
+    let acquire_point = with_states(surface, |states| {
+        let data = states.data_map.get::<DrmSyncobjSurfaceData>()?;
+        let state = data.state.borrow();
+        let syncobj_surface = state.as_ref()?;
+
+        let has_buffer = matches!(
+            states.cached_state.pending::<SurfaceAttributes>().buffer,
+            Some(BufferAssignment::NewBuffer { .. })
+        );
+        let is_dmabuf = match states.cached_state.pending::<SurfaceAttributes>().buffer {
+            Some(BufferAssignment::NewBuffer { ref buffer, .. }) => {
+                buffer.as_ref().user_data().get::<Dmabuf>().is_some()
+            }
+            _ => false,
+        };
+        let pending = states.cached_state.pending::<DrmSyncobjCachedState>();
+
+        let error = check_points(
+            pending.acquire_point.as_ref().map(|p| (&p.timeline, p.point)),
+            pending.release_point.as_ref().map(|p| (&p.timeline, p.point)),
+            has_buffer,
+            is_dmabuf,
+        );
+        if let Some((error, msg)) = error {
+            syncobj_surface.as_ref().post_error(error as u32, msg.into());
+            return None;
+        }
+
+        let acquire_point = pending.acquire_point.clone()?;
+        Some((acquire_point, data.watcher.clone()))
+    })
+    .ok()
+    .flatten();
+
+    // add the blocker without holding the surface lock, as it requires it
+    if let Some((acquire_point, watcher)) = acquire_point {
+        watcher(surface, &acquire_point);
+    }
+}
+
+/// Returns the protocol error to post, if the points of a commit are invalid
+///
+/// The points are given as their timeline and value.
+fn check_points<T: PartialEq>(
+    acquire_point: Option<(T, u64)>,
+    release_point: Option<(T, u64)>,
+    has_buffer: bool,
+    is_dmabuf: bool,
+) -> Option<(wp_linux_drm_syncobj_surface_v1::Error, &'static str)> {
+    if has_buffer {
+        if !is_dmabuf {
+            Some((
+                wp_linux_drm_syncobj_surface_v1::Error::UnsupportedBuffer,
+                "The buffer does not support explicit synchronization.",
+            ))
+        } else if acquire_point.is_none() {
+            Some((
+                wp_linux_drm_syncobj_surface_v1::Error::NoAcquirePoint,
+                "No acquire timeline point was set.",
+            ))
+        } else if release_point.is_none() {
+            Some((
+                wp_linux_drm_syncobj_surface_v1::Error::NoReleasePoint,
+                "No release timeline point was set.",
+            ))
+        } else {
+            match (acquire_point, release_point) {
+                (Some((acquire_timeline, acquire)), Some((release_timeline, release)))
+                    if acquire_timeline == release_timeline && acquire >= release =>
+                {
+                    Some((
+                        wp_linux_drm_syncobj_surface_v1::Error::ConflictingPoints,
+                        "The release point needs to be greater than the acquire point.",
+                    ))
+                }
+                _ => None,
+            }
+        }
+    } else if acquire_point.is_some() || release_point.is_some() {
+        Some((
+            wp_linux_drm_syncobj_surface_v1::Error::NoBuffer,
+            "No buffer was attached.",
+        ))
+    } else {
+        None
+    }
+}
+
+/// Initialize the linux-drm-syncobj global
+///
+/// Client timelines are imported on the provided `import_device`.
+/// See module-level documentation for its use.
+pub fn init_drm_syncobj_global<L, Data>(
+    display: &mut Display,
+    import_device: DrmNode,
+    loop_handle: LoopHandle<'static, Data>,
+    logger: L,
+) -> Global<WpLinuxDrmSyncobjManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+    Data: 'static,
+{
+    init_drm_syncobj_global_with_filter(display, import_device, loop_handle, |_| true, logger)
+}
+
+/// Initialize the linux-drm-syncobj global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`init_drm_syncobj_global`] for details.
+pub fn init_drm_syncobj_global_with_filter<L, Data, F>(
+    display: &mut Display,
+    import_device: DrmNode,
+    loop_handle: LoopHandle<'static, Data>,
+    filter: F,
+    logger: L,
+) -> Global<WpLinuxDrmSyncobjManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+    Data: 'static,
+    F: FnMut(Client) -> bool + 'static,
+{
+    let log = crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "wayland_drm_syncobj"));
+    let import_device = Arc::new(import_device);
+
+    let watcher_log = log.clone();
+    let watcher: Rc<PointWatcher> = Rc::new(move |surface: &WlSurface, point: &DrmSyncPoint| {
+        if let Ok(true) = point.is_signaled() {
+            return;
+        }
+        let eventfd = match point.eventfd() {
+            Ok(fd) => EventFd(fd),
+            Err(err) => {
+                warn!(watcher_log, "Failed to wait for acquire point: {}", err);
+                return;
+            }
+        };
+        let signaled = Arc::new(AtomicBool::new(false));
+        let client = surface.as_ref().client();
+        let source_signaled = signaled.clone();
+        let result = loop_handle.insert_source(
+            Generic::new(eventfd, Interest::READ, Mode::OneShot),
+            move |_, _, data: &mut Data| {
+                source_signaled.store(true, Ordering::Release);
+                if let Some(client) = client.as_ref().filter(|client| client.alive()) {
+                    blocker_cleared(client, DispatchData::wrap(data));
+                }
+                Ok(PostAction::Remove)
+            },
+        );
+        match result {
+            Ok(_) => add_blocker(surface, AcquirePointBlocker { signaled }),
+            Err(err) => warn!(watcher_log, "Failed to watch acquire point: {}", err.error),
+        }
+    });
+
+    display.create_global_with_filter::<WpLinuxDrmSyncobjManagerV1, _, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<WpLinuxDrmSyncobjManagerV1>, _), _, _| {
+                let watcher = watcher.clone();
+                let import_device = import_device.clone();
+                let log = log.clone();
+                manager.quick_assign(move |manager, req, _| match req {
+                    wp_linux_drm_syncobj_manager_v1::Request::GetSurface { id, surface } => {
+                        let (exists, initialized) = with_states(&surface, |states| {
+                            let initialized = states.data_map.insert_if_missing(|| DrmSyncobjSurfaceData {
+                                state: RefCell::new(None),
+                                watcher: watcher.clone(),
+                            });
+                            let exists = states
+                                .data_map
+                                .get::<DrmSyncobjSurfaceData>()
+                                .map(|data| data.state.borrow().is_some())
+                                .unwrap();
+                            (exists, initialized)
+                        })
+                        .unwrap_or((false, false));
+                        if initialized {
+                            add_commit_hook(&surface, commit_hook);
+                        }
+                        if exists {
+                            manager.as_ref().post_error(
+                                wp_linux_drm_syncobj_manager_v1::Error::SurfaceExists as u32,
+                                "The surface already has a syncobj surface object associated.".into(),
+                            );
+                            return;
+                        }
+                        let syncobj_surface = implement_syncobj_surface(id, surface.clone());
+                        with_states(&surface, |states| {
+                            let data = states.data_map.get::<DrmSyncobjSurfaceData>().unwrap();
+                            *data.state.borrow_mut() = Some(syncobj_surface);
+                        })
+                        .unwrap();
+                    }
+                    wp_linux_drm_syncobj_manager_v1::Request::ImportTimeline { id, fd } => {
+                        let result = DrmTimeline::import(import_device.clone(), fd);
+                        let _ = nix::unistd::close(fd);
+                        match result {
+                            Ok(timeline) => {
+                                id.quick_assign(|_, _, _| {});
+                                id.as_ref().user_data().set_threadsafe(|| timeline);
+                            }
+                            Err(err) => {
+                                debug!(log, "Failed to import syncobj timeline: {}", err);
+                                manager.as_ref().post_error(
+                                    wp_linux_drm_syncobj_manager_v1::Error::InvalidTimeline as u32,
+                                    "The timeline could not be imported.".into(),
+                                );
+                            }
+                        }
+                    }
+                    wp_linux_drm_syncobj_manager_v1::Request::Destroy => {}
+                });
+            },
+        ),
+        filter,
+    )
+}
+
+fn sync_point(timeline: &WpLinuxDrmSyncobjTimelineV1, point_hi: u32, point_lo: u32) -> Option<DrmSyncPoint> {
+    let timeline = timeline.as_ref().user_data().get::<DrmTimeline>()?.clone();
+    Some(DrmSyncPoint {
+        timeline,
+        point: ((point_hi as u64) << 32) | point_lo as u64,
+    })
+}
+
+fn implement_syncobj_surface(
+    id: Main<WpLinuxDrmSyncobjSurfaceV1>,
+    surface: WlSurface,
+) -> WpLinuxDrmSyncobjSurfaceV1 {
+    id.quick_assign(move |syncobj_surface, req, _| {
+        let point = match req {
+            wp_linux_drm_syncobj_surface_v1::Request::SetAcquirePoint {
+                ref timeline,
+                point_hi,
+                point_lo,
+            }
+            | wp_linux_drm_syncobj_surface_v1::Request::SetReleasePoint {
+                ref timeline,
+                point_hi,
+                point_lo,
+            } => sync_point(timeline, point_hi, point_lo),
+            wp_linux_drm_syncobj_surface_v1::Request::Destroy => {
+                // pending points set since the last commit are discarded
+                with_states(&surface, |states| {
+                    *states.cached_state.pending::<DrmSyncobjCachedState>() = Default::default();
+                    if let Some(data) = states.data_map.get::<DrmSyncobjSurfaceData>() {
+                        *data.state.borrow_mut() = None;
+                    }
+                })
+                .ok();
+                return;
+            }
+        };
+
+        if !surface.as_ref().is_alive() {
+            syncobj_surface.as_ref().post_error(
+                wp_linux_drm_syncobj_surface_v1::Error::NoSurface as u32,
+                "The associated wl_surface was destroyed.".into(),
+            );
+            return;
+        }
+
+        with_states(&surface, |states| {
+            let mut pending = states.cached_state.pending::<DrmSyncobjCachedState>();
+            match req {
+                wp_linux_drm_syncobj_surface_v1::Request::SetAcquirePoint { .. } => {
+                    pending.acquire_point = point;
+                }
+                wp_linux_drm_syncobj_surface_v1::Request::SetReleasePoint { .. } => {
+                    pending.release_point = point;
+                }
+                wp_linux_drm_syncobj_surface_v1::Request::Destroy => unreachable!(),
+            }
+        })
+        .unwrap();
+    });
+    id.deref().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_points;
+    use super::wp_linux_drm_syncobj_surface_v1::Error;
+
+    // timelines are identified by a number, as comparing them is all the validation does
+    fn error(
+        acquire: Option<(u32, u64)>,
+        release: Option<(u32, u64)>,
+        has_buffer: bool,
+        is_dmabuf: bool,
+    ) -> Option<Error> {
+        check_points(acquire, release, has_buffer, is_dmabuf).map(|(error, _)| error)
+    }
+
+    #[test]
+    fn valid_points() {
+        assert_eq!(error(None, None, false, false), None);
+        assert_eq!(error(Some((0, 1)), Some((0, 2)), true, true), None);
+        // points on different timelines are not ordered
+        assert_eq!(error(Some((0, 2)), Some((1, 1)), true, true), None);
+    }
+
+    #[test]
+    fn no_buffer() {
+        assert_eq!(error(Some((0, 1)), None, false, false), Some(Error::NoBuffer));
+        assert_eq!(error(None, Some((0, 1)), false, false), Some(Error::NoBuffer));
+    }
+
+    #[test]
+    fn missing_points() {
+        assert_eq!(error(None, Some((0, 1)), true, true), Some(Error::NoAcquirePoint));
+        assert_eq!(error(Some((0, 1)), None, true, true), Some(Error::NoReleasePoint));
+        assert_eq!(
+            error(Some((0, 1)), Some((0, 2)), true, false),
+            Some(Error::UnsupportedBuffer)
+        );
+    }
+
+    #[test]
+    fn conflicting_points() {
+        assert_eq!(
+            error(Some((0, 2)), Some((0, 2)), true, true),
+            Some(Error::ConflictingPoints)
+        );
+        assert_eq!(
+            error(Some((0, 3)), Some((0, 2)), true, true),
+            Some(Error::ConflictingPoints)
+        );
+    }
+}
@@ -15,6 +15,14 @@
 //! The use of these `dma_fence`s in conjunction with the graphics stack allows for efficient synchronization
 //! between the clients and the compositor.
 //!
+//! Commits carrying an acquire fence are held back using a [`Blocker`](crate::wayland::compositor::Blocker)
+//! until the fence is signaled, so the compositor never samples a buffer the GPU is still writing into.
+//! The fences are polled through the provided `calloop` event loop, once a fence is signaled the commit is
+//! applied and the callback provided to [`compositor_init`](crate::wayland::compositor::compositor_init) is
+//! invoked with the data of the event loop as [`DispatchData`](wayland_server::DispatchData).
+//! The type of the event loop data thus needs to match the data you use to dispatch the
+//! [`Display`](wayland_server::Display).
+//!
 //! ## Usage
 //!
 //! First, you need to initialize the global:
@@ -23,13 +31,16 @@
 //! # extern crate wayland_server;
 //! use smithay::wayland::explicit_synchronization::*;
 //! # let mut display = wayland_server::Display::new();
+//! # let event_loop = smithay::reexports::calloop::EventLoop::<()>::try_new().unwrap();
 //! init_explicit_synchronization_global(
 //!     &mut display,
+//!     event_loop.handle(),
 //!     None /* You can insert a logger here */
 //! );
 //! ```
 //!
-//! Then when handling a surface commit, you can retrieve the synchronization information for the surface states:
+//! Then when handling a surface commit, you can retrieve the synchronization information for the surface states.
+//! The acquire fence is guaranteed to be signaled at this point:
 //! ```
 //! # extern crate wayland_server;
 //! # #[macro_use] extern crate smithay;
@@ -46,16 +57,30 @@
 //! # }
 //! ```
 
-use std::{cell::RefCell, ops::Deref as _, os::unix::io::RawFd};
+use std::{
+    cell::RefCell,
+    ops::Deref as _,
+    os::unix::io::{AsRawFd, RawFd},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction};
+use slog::warn;
 
 use wayland_protocols::unstable::linux_explicit_synchronization::v1::server::{
     zwp_linux_buffer_release_v1::ZwpLinuxBufferReleaseV1,
     zwp_linux_explicit_synchronization_v1::{self, ZwpLinuxExplicitSynchronizationV1},
     zwp_linux_surface_synchronization_v1::{self, ZwpLinuxSurfaceSynchronizationV1},
 };
-use wayland_server::{protocol::wl_surface::WlSurface, Display, Filter, Global, Main};
+use wayland_server::{protocol::wl_surface::WlSurface, Client, DispatchData, Display, Filter, Global, Main};
 
-use super::compositor::{with_states, Cacheable, SurfaceData};
+use super::compositor::{
+    add_blocker, add_commit_hook, blocker_cleared, with_states, Blocker, BlockerState, Cacheable, SurfaceData,
+};
 
 /// An object to signal end of use of a buffer
 #[derive(Debug)]
@@ -117,8 +142,59 @@ impl Cacheable for ExplicitSyncState {
     }
 }
 
+type FenceWatcher = dyn Fn(&WlSurface, RawFd);
+
 struct ESUserData {
     state: RefCell<Option<ZwpLinuxSurfaceSynchronizationV1>>,
+    watcher: Rc<FenceWatcher>,
+}
+
+/// A [`Blocker`] waiting for an acquire fence to be signaled
+#[derive(Debug)]
+struct FenceBlocker {
+    signaled: Arc<AtomicBool>,
+}
+
+impl Blocker for FenceBlocker {
+    fn state(&self) -> BlockerState {
+        if self.signaled.load(Ordering::Acquire) {
+            BlockerState::Released
+        } else {
+            BlockerState::Pending
+        }
+    }
+}
+
+// Duplicated fence fd owned by the event source, so the client state can be freely processed
+#[derive(Debug)]
+struct SyncFile(RawFd);
+
+impl AsRawFd for SyncFile {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl Drop for SyncFile {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.0);
+    }
+}
+
+fn commit_hook(surface: &WlSurface) {
+    // retrieve the fence without holding the surface lock, as adding a blocker requires it
+    let fence = with_states(surface, |states| {
+        let data = states.data_map.get::<ESUserData>()?;
+        data.state.borrow().as_ref()?;
+        let fd = states.cached_state.pending::<ExplicitSyncState>().acquire?;
+        Some((fd, data.watcher.clone()))
+    })
+    .ok()
+    .flatten();
+
+    if let Some((fd, watcher)) = fence {
+        watcher(surface, fd);
+    }
 }
 
 /// Possible errors you can send to an ill-behaving clients
@@ -173,37 +249,90 @@ pub fn send_explicit_synchronization_error(attrs: &SurfaceData, error: ExplicitS
 /// Initialize the explicit synchronization global
 ///
 /// See module-level documentation for its use.
-pub fn init_explicit_synchronization_global<L>(
+pub fn init_explicit_synchronization_global<L, Data>(
+    display: &mut Display,
+    loop_handle: LoopHandle<'static, Data>,
+    logger: L,
+) -> Global<ZwpLinuxExplicitSynchronizationV1>
+where
+    L: Into<Option<::slog::Logger>>,
+    Data: 'static,
+{
+    init_explicit_synchronization_global_with_filter(display, loop_handle, |_| true, logger)
+}
+
+/// Initialize the explicit synchronization global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`init_explicit_synchronization_global`] for details.
+pub fn init_explicit_synchronization_global_with_filter<L, Data, F>(
     display: &mut Display,
+    loop_handle: LoopHandle<'static, Data>,
+    filter: F,
     logger: L,
 ) -> Global<ZwpLinuxExplicitSynchronizationV1>
 where
     L: Into<Option<::slog::Logger>>,
+    Data: 'static,
+    F: FnMut(Client) -> bool + 'static,
 {
-    let _log =
+    let log =
         crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "wayland_explicit_synchronization"));
 
-    display.create_global::<ZwpLinuxExplicitSynchronizationV1, _>(
+    let watcher: Rc<FenceWatcher> = Rc::new(move |surface: &WlSurface, fd: RawFd| {
+        let fence = match nix::unistd::dup(fd) {
+            Ok(fence) => SyncFile(fence),
+            Err(err) => {
+                warn!(log, "Failed to duplicate acquire fence: {}", err);
+                return;
+            }
+        };
+        let signaled = Arc::new(AtomicBool::new(false));
+        let client = surface.as_ref().client();
+        let source_signaled = signaled.clone();
+        let result = loop_handle.insert_source(
+            Generic::new(fence, Interest::READ, Mode::OneShot),
+            move |_, _, data: &mut Data| {
+                source_signaled.store(true, Ordering::Release);
+                if let Some(client) = client.as_ref().filter(|client| client.alive()) {
+                    blocker_cleared(client, DispatchData::wrap(data));
+                }
+                Ok(PostAction::Remove)
+            },
+        );
+        match result {
+            Ok(_) => add_blocker(surface, FenceBlocker { signaled }),
+            Err(err) => warn!(log, "Failed to watch acquire fence: {}", err.error),
+        }
+    });
+
+    display.create_global_with_filter::<ZwpLinuxExplicitSynchronizationV1, _, _>(
         2,
         Filter::new(
             move |(sync, _version): (Main<ZwpLinuxExplicitSynchronizationV1>, _), _, _| {
+                let watcher = watcher.clone();
                 sync.quick_assign(move |explicit_sync, req, _| {
                     if let zwp_linux_explicit_synchronization_v1::Request::GetSynchronization {
                         id,
                         surface,
                     } = req
                     {
-                        let exists = with_states(&surface, |states| {
-                            states.data_map.insert_if_missing(|| ESUserData {
+                        let (exists, initialized) = with_states(&surface, |states| {
+                            let initialized = states.data_map.insert_if_missing(|| ESUserData {
                                 state: RefCell::new(None),
+                                watcher: watcher.clone(),
                             });
-                            states
+                            let exists = states
                                 .data_map
                                 .get::<ESUserData>()
                                 .map(|ud| ud.state.borrow().is_some())
-                                .unwrap()
+                                .unwrap();
+                            (exists, initialized)
                         })
-                        .unwrap_or(false);
+                        .unwrap_or((false, false));
+                        if initialized {
+                            add_commit_hook(&surface, commit_hook);
+                        }
                         if exists {
                             explicit_sync.as_ref().post_error(
                                 zwp_linux_explicit_synchronization_v1::Error::SynchronizationExists as u32,
@@ -221,6 +350,7 @@ where
                 });
             },
         ),
+        filter,
     )
 }
 
@@ -50,19 +50,25 @@
 //!
 //! The [`explicit_synchronization`] module provides helpers to give clients fine-grained control
 //! over the synchronization for accessing graphics buffer with the compositor, for low-latency
-//! rendering. It is however still experimental, and largely untested.
+//! rendering. It is however still experimental, and largely untested. Its successor, the
+//! [`drm_syncobj`] module, uses DRM timeline synchronization objects instead.
 
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub mod compositor;
 pub mod data_device;
 pub mod dmabuf;
+#[cfg(feature = "backend_drm")]
+pub mod drm_syncobj;
 pub mod explicit_synchronization;
 pub mod output;
+pub mod protocols;
 pub mod seat;
+pub mod security_context;
 pub mod shell;
 pub mod shm;
 pub mod tablet_manager;
+pub mod tearing_control;
 pub mod xdg_activation;
 pub mod xdg_foreign;
 
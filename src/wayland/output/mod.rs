@@ -61,7 +61,7 @@ use wayland_server::protocol::{
 };
 use wayland_server::{
     protocol::wl_output::{Mode as WMode, WlOutput},
-    Client, Display, Filter, Global, Main,
+    Client, Display, Filter, Global, Main, UserDataMap,
 };
 
 use slog::{info, o, trace, warn};
@@ -113,6 +113,7 @@ struct Inner {
     preferred_mode: Option<Mode>,
 
     xdg_output: Option<XdgOutput>,
+    user_data: Arc<UserDataMap>,
 }
 
 impl Inner {
@@ -166,11 +167,22 @@ impl Inner {
 ///
 /// This handle is stored in the event loop, and allows you to notify clients
 /// about any change in the properties of this output.
-#[derive(Debug)]
+///
+/// This handle can be cloned, all clones refer to the same output.
+#[derive(Debug, Clone)]
 pub struct Output {
     inner: Arc<Mutex<Inner>>,
+    user_data: Arc<UserDataMap>,
+}
+
+impl PartialEq for Output {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
 }
 
+impl Eq for Output {}
+
 impl Output {
     /// Create a new output global with given name and physical properties
     ///
@@ -185,6 +197,24 @@ impl Output {
     ) -> (Output, Global<WlOutput>)
     where
         L: Into<Option<::slog::Logger>>,
+    {
+        Output::new_with_filter(display, name, physical, |_| true, logger)
+    }
+
+    /// Create a new output global with given name and physical properties and a client filter
+    ///
+    /// The global is only advertised to clients for which `filter` returns `true`,
+    /// see [`Output::new`] for details.
+    pub fn new_with_filter<F, L>(
+        display: &mut Display,
+        name: String,
+        physical: PhysicalProperties,
+        filter: F,
+        logger: L,
+    ) -> (Output, Global<WlOutput>)
+    where
+        F: FnMut(Client) -> bool + 'static,
+        L: Into<Option<::slog::Logger>>,
     {
         let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "output_handler"));
 
@@ -202,11 +232,16 @@ impl Output {
             current_mode: None,
             preferred_mode: None,
             xdg_output: None,
+            user_data: Arc::new(UserDataMap::new()),
         }));
 
-        let output = Output { inner: inner.clone() };
+        let user_data = inner.lock().unwrap().user_data.clone();
+        let output = Output {
+            inner: inner.clone(),
+            user_data,
+        };
 
-        let global = display.create_global(
+        let global = display.create_global_with_filter(
             3,
             Filter::new(move |(output, _version): (Main<WlOutput>, _), _, _| {
                 output.assign_destructor(Filter::new(|output: WlOutput, _, _| {
@@ -223,6 +258,7 @@ impl Output {
                 });
                 inner.lock().unwrap().new_global(output.deref().clone());
             }),
+            filter,
         );
 
         (output, global)
@@ -235,7 +271,45 @@ impl Output {
             .user_data()
             .get::<Arc<Mutex<Inner>>>()
             .cloned()
-            .map(|inner| Output { inner })
+            .map(|inner| {
+                let user_data = inner.lock().unwrap().user_data.clone();
+                Output { inner, user_data }
+            })
+    }
+
+    /// The name of this output
+    pub fn name(&self) -> String {
+        self.inner.lock().unwrap().name.clone()
+    }
+
+    /// The current mode of this output, if any
+    pub fn current_mode(&self) -> Option<Mode> {
+        self.inner.lock().unwrap().current_mode
+    }
+
+    /// The preferred mode of this output, if any
+    pub fn preferred_mode(&self) -> Option<Mode> {
+        self.inner.lock().unwrap().preferred_mode
+    }
+
+    /// The current transform of this output
+    pub fn current_transform(&self) -> Transform {
+        self.inner.lock().unwrap().transform
+    }
+
+    /// The current scale factor of this output
+    pub fn current_scale(&self) -> i32 {
+        self.inner.lock().unwrap().scale
+    }
+
+    /// The location of this output in the global compositor space, as advertised to clients
+    pub fn current_location(&self) -> Point<i32, Logical> {
+        self.inner.lock().unwrap().location
+    }
+
+    /// Access the `UserDataMap` associated with this `Output`
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.user_data
     }
 
     /// Sets the preferred mode of this output
@@ -13,7 +13,7 @@ use wayland_protocols::unstable::xdg_output::v1::server::{
     zxdg_output_manager_v1::{self, ZxdgOutputManagerV1},
     zxdg_output_v1::ZxdgOutputV1,
 };
-use wayland_server::{protocol::wl_output::WlOutput, Display, Filter, Global, Main};
+use wayland_server::{protocol::wl_output::WlOutput, Client, Display, Filter, Global, Main};
 
 use crate::utils::{Logical, Physical, Point, Size};
 
@@ -146,10 +146,25 @@ impl XdgOutput {
 pub fn init_xdg_output_manager<L>(display: &mut Display, logger: L) -> Global<ZxdgOutputManagerV1>
 where
     L: Into<Option<::slog::Logger>>,
+{
+    init_xdg_output_manager_with_filter(display, |_| true, logger)
+}
+
+/// Initialize a xdg output manager global with a client filter.
+///
+/// The global is only advertised to clients for which `filter` returns `true`.
+pub fn init_xdg_output_manager_with_filter<F, L>(
+    display: &mut Display,
+    filter: F,
+    logger: L,
+) -> Global<ZxdgOutputManagerV1>
+where
+    F: FnMut(Client) -> bool + 'static,
+    L: Into<Option<::slog::Logger>>,
 {
     let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "xdg_output_handler"));
 
-    display.create_global(
+    display.create_global_with_filter(
         3,
         Filter::new(move |(manager, _version): (Main<ZxdgOutputManagerV1>, _), _, _| {
             let log = log.clone();
@@ -173,5 +188,6 @@ where
                 _ => {}
             });
         }),
+        filter,
     )
 }
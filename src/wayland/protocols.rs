@@ -0,0 +1,57 @@
+//! Server-side bindings for protocols not yet provided by `wayland-protocols`
+//!
+//! The bindings are generated at build time from the XML files shipped in the `protocols`
+//! directory of this crate and follow the layout of the `wayland-protocols` crate, so they
+//! can be used in exactly the same way.
+
+macro_rules! wayland_protocol(
+    ($name: expr, [$(($import: ident, $interface: ident)),*]) => {
+        pub use self::generated::server;
+
+        mod generated {
+            #![allow(dead_code,non_camel_case_types,unused_unsafe,unused_variables)]
+            #![allow(non_upper_case_globals,non_snake_case,unused_imports)]
+            #![allow(missing_docs, missing_debug_implementations, rust_2018_idioms, clippy::all)]
+            #![allow(unknown_lints, static_mut_refs)]
+
+            pub mod server {
+                //! Server-side API of this protocol
+                pub(crate) use wayland_server::{Main, AnonymousObject, Resource, ResourceMap};
+                pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+                pub(crate) use wayland_commons::{Interface, MessageGroup};
+                pub(crate) use wayland_commons::wire::{Argument, MessageDesc, ArgumentType, Message};
+                pub(crate) use wayland_commons::smallvec;
+                pub(crate) use wayland_server::protocol::{$($import),*};
+                pub(crate) use wayland_server::sys;
+                include!(concat!(env!("OUT_DIR"), "/", $name, "_server_api.rs"));
+            }
+        }
+    }
+);
+
+pub mod linux_drm_syncobj {
+    //! Linux DRM timeline synchronization object protocol
+
+    /// Version 1 of the protocol
+    pub mod v1 {
+        wayland_protocol!("linux-drm-syncobj-v1", [(wl_surface, wl_surface_interface)]);
+    }
+}
+
+pub mod security_context {
+    //! Security context protocol
+
+    /// Version 1 of the protocol
+    pub mod v1 {
+        wayland_protocol!("security-context-v1", []);
+    }
+}
+
+pub mod tearing_control {
+    //! Tearing control protocol
+
+    /// Version 1 of the protocol
+    pub mod v1 {
+        wayland_protocol!("tearing-control-v1", [(wl_surface, wl_surface_interface)]);
+    }
+}
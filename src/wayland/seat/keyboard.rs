@@ -1,4 +1,4 @@
-use crate::backend::input::KeyState;
+use crate::backend::input::{KeyState, Led, LedHandle};
 use crate::wayland::Serial;
 use slog::{debug, info, o, trace, warn};
 use std::{
@@ -91,6 +91,7 @@ struct KbdInternal {
     focus: Option<WlSurface>,
     pressed_keys: Vec<u32>,
     mods_state: ModifiersState,
+    led_state: Led,
     keymap: xkb::Keymap,
     state: xkb::State,
     repeat_rate: i32,
@@ -106,6 +107,7 @@ impl fmt::Debug for KbdInternal {
             .field("focus", &self.focus)
             .field("pressed_keys", &self.pressed_keys)
             .field("mods_state", &self.mods_state)
+            .field("led_state", &self.led_state)
             .field("keymap", &self.keymap.get_raw_ptr())
             .field("state", &self.state.get_raw_ptr())
             .field("repeat_rate", &self.repeat_rate)
@@ -149,6 +151,7 @@ impl KbdInternal {
             focus: None,
             pressed_keys: Vec::new(),
             mods_state: ModifiersState::default(),
+            led_state: Led::empty(),
             keymap,
             state,
             repeat_rate,
@@ -178,12 +181,26 @@ impl KbdInternal {
 
         if state_components != 0 {
             self.mods_state.update_with(&self.state);
+            self.update_leds();
             true
         } else {
             false
         }
     }
 
+    // update the led state from the xkb state
+    fn update_leds(&mut self) {
+        let mut led_state = Led::empty();
+        led_state.set(Led::NUMLOCK, self.state.led_name_is_active(&xkb::LED_NAME_NUM));
+        led_state.set(Led::CAPSLOCK, self.state.led_name_is_active(&xkb::LED_NAME_CAPS));
+        led_state.set(
+            Led::SCROLLLOCK,
+            self.state.led_name_is_active(&xkb::LED_NAME_SCROLL),
+        );
+
+        self.led_state = led_state;
+    }
+
     fn serialize_modifiers(&self) -> (u32, u32, u32, u32) {
         let mods_depressed = self.state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
         let mods_latched = self.state.serialize_mods(xkb::STATE_MODS_LATCHED);
@@ -257,6 +274,7 @@ where
     Ok(KeyboardHandle {
         arc: Rc::new(KbdRc {
             internal: RefCell::new(internal),
+            led_handles: RefCell::new(Vec::new()),
             keymap,
             logger: log,
         }),
@@ -266,6 +284,7 @@ where
 #[derive(Debug)]
 struct KbdRc {
     internal: RefCell<KbdInternal>,
+    led_handles: RefCell<Vec<Box<dyn LedHandle>>>,
     keymap: String,
     logger: ::slog::Logger,
 }
@@ -363,6 +382,7 @@ impl KeyboardHandle {
     {
         trace!(self.arc.logger, "Handling keystroke"; "keycode" => keycode, "state" => format_args!("{:?}", state));
         let mut guard = self.arc.internal.borrow_mut();
+        let led_state = guard.led_state;
         let mods_changed = guard.key_input(keycode, state);
         let handle = KeysymHandle {
             // Offset the keycode by 8, as the evdev XKB rules reflect X's
@@ -376,37 +396,48 @@ impl KeyboardHandle {
             "mods_state" => format_args!("{:?}", guard.mods_state), "sym" => xkb::keysym_get_name(handle.modified_sym())
         );
 
-        if let FilterResult::Intercept(val) = filter(&guard.mods_state, handle) {
+        let result = if let FilterResult::Intercept(val) = filter(&guard.mods_state, handle) {
             // the filter returned false, we do not forward to client
             trace!(self.arc.logger, "Input was intercepted by filter");
-            return Some(val);
-        }
-
-        // forward to client if no keybinding is triggered
-        let modifiers = if mods_changed {
-            Some(guard.serialize_modifiers())
+            Some(val)
         } else {
+            // forward to client if no keybinding is triggered
+            let modifiers = if mods_changed {
+                Some(guard.serialize_modifiers())
+            } else {
+                None
+            };
+            let wl_state = match state {
+                KeyState::Pressed => WlKeyState::Pressed,
+                KeyState::Released => WlKeyState::Released,
+            };
+            guard.with_focused_kbds(|kbd, _| {
+                // key event must be sent before modifers event for libxkbcommon
+                // to process them correctly
+                kbd.key(serial.into(), time, keycode, wl_state);
+                if let Some((dep, la, lo, gr)) = modifiers {
+                    kbd.modifiers(serial.into(), dep, la, lo, gr);
+                }
+            });
+            if guard.focus.is_some() {
+                trace!(self.arc.logger, "Input forwarded to client");
+            } else {
+                trace!(self.arc.logger, "No client currently focused");
+            }
             None
         };
-        let wl_state = match state {
-            KeyState::Pressed => WlKeyState::Pressed,
-            KeyState::Released => WlKeyState::Released,
-        };
-        guard.with_focused_kbds(|kbd, _| {
-            // key event must be sent before modifers event for libxkbcommon
-            // to process them correctly
-            kbd.key(serial.into(), time, keycode, wl_state);
-            if let Some((dep, la, lo, gr)) = modifiers {
-                kbd.modifiers(serial.into(), dep, la, lo, gr);
+
+        // the led handles are updated once the keyboard is released, so they can access it
+        let new_led_state = guard.led_state;
+        drop(guard);
+        if new_led_state != led_state {
+            trace!(self.arc.logger, "Updating keyboard LEDs"; "leds" => format_args!("{:?}", new_led_state));
+            for led_handle in self.arc.led_handles.borrow().iter() {
+                led_handle.update_leds(new_led_state);
             }
-        });
-        if guard.focus.is_some() {
-            trace!(self.arc.logger, "Input forwarded to client");
-        } else {
-            trace!(self.arc.logger, "No client currently focused");
         }
 
-        None
+        result
     }
 
     /// Set the current focus of this keyboard
@@ -505,6 +536,25 @@ impl KeyboardHandle {
         guard.known_kbds.push(kbd);
     }
 
+    /// The current state of the keyboard LEDs
+    pub fn led_state(&self) -> Led {
+        self.arc.internal.borrow().led_state
+    }
+
+    /// Add a handle the state of the keyboard LEDs is pushed to
+    ///
+    /// The current state is applied to the handle right away. Afterwards, the handle is updated by
+    /// [`KeyboardHandle::input`] whenever a keystroke changes the LEDs, once the keystroke was processed.
+    /// This keeps the LEDs of the physical keyboards in sync with this keyboard, whatever the backend
+    /// providing the handle.
+    pub fn add_led_handle<H>(&self, handle: H)
+    where
+        H: LedHandle + 'static,
+    {
+        handle.update_leds(self.led_state());
+        self.arc.led_handles.borrow_mut().push(Box::new(handle));
+    }
+
     /// Change the repeat info configured for this keyboard
     pub fn change_repeat_info(&self, rate: i32, delay: i32) {
         let mut guard = self.arc.internal.borrow_mut();
@@ -538,3 +588,65 @@ pub(crate) fn implement_keyboard(keyboard: Main<WlKeyboard>, handle: Option<&Key
 
     keyboard.deref().clone()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::{create_keyboard_handler, FilterResult, KeyboardHandle, XkbConfig};
+    use crate::backend::input::{KeyState, Led, LedHandle};
+
+    // evdev keycodes
+    const KEY_CAPSLOCK: u32 = 58;
+    const KEY_NUMLOCK: u32 = 69;
+
+    #[derive(Debug)]
+    struct RecordLeds {
+        keyboard: KeyboardHandle,
+        updates: Rc<RefCell<Vec<Led>>>,
+    }
+
+    impl LedHandle for RecordLeds {
+        fn update_leds(&self, leds: Led) {
+            // the keyboard must not be borrowed anymore
+            assert_eq!(self.keyboard.led_state(), leds);
+            self.updates.borrow_mut().push(leds);
+        }
+    }
+
+    fn tap(keyboard: &KeyboardHandle, keycode: u32) {
+        for state in [KeyState::Pressed, KeyState::Released] {
+            keyboard.input(keycode, state, 0.into(), 0, |_, _| FilterResult::Forward::<()>);
+        }
+    }
+
+    #[test]
+    fn leds_follow_lock_keys() {
+        let logger = slog::Logger::root(slog::Discard, slog::o!());
+        let keyboard = create_keyboard_handler(XkbConfig::default(), 200, 25, &logger, |_| {}).unwrap();
+        let updates = Rc::new(RefCell::new(Vec::new()));
+        keyboard.add_led_handle(RecordLeds {
+            keyboard: keyboard.clone(),
+            updates: updates.clone(),
+        });
+        assert_eq!(*updates.borrow(), vec![Led::empty()]);
+
+        tap(&keyboard, KEY_CAPSLOCK);
+        assert_eq!(keyboard.led_state(), Led::CAPSLOCK);
+        tap(&keyboard, KEY_NUMLOCK);
+        assert_eq!(keyboard.led_state(), Led::CAPSLOCK | Led::NUMLOCK);
+        tap(&keyboard, KEY_CAPSLOCK);
+        assert_eq!(keyboard.led_state(), Led::NUMLOCK);
+
+        // handles are only updated when the LEDs change
+        assert_eq!(
+            *updates.borrow(),
+            vec![
+                Led::empty(),
+                Led::CAPSLOCK,
+                Led::CAPSLOCK | Led::NUMLOCK,
+                Led::NUMLOCK
+            ]
+        );
+    }
+}
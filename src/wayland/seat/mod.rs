@@ -51,7 +51,7 @@ pub use self::{
 
 use wayland_server::{
     protocol::{wl_seat, wl_surface},
-    Display, Filter, Global, Main, UserDataMap,
+    Client, Display, Filter, Global, Main, UserDataMap,
 };
 
 #[derive(Debug)]
@@ -127,6 +127,23 @@ impl Seat {
     pub fn new<L>(display: &mut Display, name: String, logger: L) -> (Seat, Global<wl_seat::WlSeat>)
     where
         L: Into<Option<::slog::Logger>>,
+    {
+        Seat::new_with_filter(display, name, |_| true, logger)
+    }
+
+    /// Create a new seat global with a client filter
+    ///
+    /// The global is only advertised to clients for which `filter` returns `true`,
+    /// see [`Seat::new`] for details.
+    pub fn new_with_filter<F, L>(
+        display: &mut Display,
+        name: String,
+        filter: F,
+        logger: L,
+    ) -> (Seat, Global<wl_seat::WlSeat>)
+    where
+        F: FnMut(Client) -> bool + 'static,
+        L: Into<Option<::slog::Logger>>,
     {
         let log = crate::slog_or_fallback(logger);
         let arc = Rc::new(SeatRc {
@@ -140,7 +157,7 @@ impl Seat {
             user_data: UserDataMap::new(),
         });
         let seat = Seat { arc: arc.clone() };
-        let global = display.create_global(
+        let global = display.create_global_with_filter(
             5,
             Filter::new(move |(new_seat, _version), _, _| {
                 let seat = implement_seat(new_seat, arc.clone());
@@ -151,6 +168,7 @@ impl Seat {
                 seat.capabilities(inner.compute_caps());
                 inner.known_seats.push(seat);
             }),
+            filter,
         );
         (seat, global)
     }
@@ -0,0 +1,403 @@
+//! Utilities for handling the `wp_security_context_v1` protocol
+//!
+//! This protocol allows sandbox engines (like Flatpak) to create a new listening socket for
+//! the sandboxed application and to attach metadata describing the sandbox to it. Every client
+//! connecting through that socket is tagged with this [`SecurityContext`].
+//!
+//! Smithay does not restrict anything on its own. Instead, the security context of a client can
+//! be retrieved using [`client_security_context`], for example in the filter given to the
+//! `*_with_filter` variants of the global constructors, to hide privileged globals from sandboxed
+//! clients.
+//!
+//! ## Usage
+//!
+//! First, you need to initialize the global. Every time a sandbox engine commits a new security
+//! context, your callback is provided with a [`SecurityContextListenerSource`], which you need to
+//! insert into your event loop. It yields new client connections, that you need to insert into your
+//! [`Display`](wayland_server::Display), before tagging them with the security context:
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use std::{cell::RefCell, os::unix::io::IntoRawFd, rc::Rc};
+//! use smithay::wayland::security_context::{
+//!     client_security_context, init_security_context_global_with_filter,
+//!     set_client_security_context,
+//! };
+//!
+//! # let display = Rc::new(RefCell::new(wayland_server::Display::new()));
+//! # let event_loop = smithay::reexports::calloop::EventLoop::<()>::try_new().unwrap();
+//! let handle = event_loop.handle();
+//! let client_display = display.clone();
+//! init_security_context_global_with_filter(
+//!     &mut *display.borrow_mut(),
+//!     move |source, context, _dispatch_data| {
+//!         let display = client_display.clone();
+//!         handle
+//!             .insert_source(source, move |client_stream, _, data| {
+//!                 let client = unsafe {
+//!                     display
+//!                         .borrow_mut()
+//!                         .create_client(client_stream.into_raw_fd(), data)
+//!                 };
+//!                 set_client_security_context(&client, context.clone());
+//!             })
+//!             .unwrap();
+//!     },
+//!     // sandboxed clients may not create nested security contexts
+//!     |client| client_security_context(&client).is_none(),
+//!     None /* You can insert a logger here */
+//! );
+//! ```
+//!
+//! Then, for example, only expose a privileged global to unsandboxed clients:
+//!
+//! ```
+//! # extern crate wayland_server;
+//! use smithay::wayland::output::xdg::init_xdg_output_manager_with_filter;
+//! use smithay::wayland::security_context::client_security_context;
+//!
+//! # let mut display = wayland_server::Display::new();
+//! init_xdg_output_manager_with_filter(
+//!     &mut display,
+//!     |client| client_security_context(&client).is_none(),
+//!     None /* You can insert a logger here */
+//! );
+//! ```
+
+use std::{
+    cell::RefCell,
+    fs::File,
+    io,
+    os::unix::{
+        io::{AsRawFd, FromRawFd},
+        net::{UnixListener, UnixStream},
+    },
+    rc::Rc,
+};
+
+use calloop::{
+    generic::Generic, EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use nix::sys::socket::{getsockopt, sockopt::AcceptConn};
+use slog::{debug, warn};
+use wayland_server::{Client, DispatchData, Display, Filter, Global, Main};
+
+use super::protocols::security_context::v1::server::{
+    wp_security_context_manager_v1::{self, WpSecurityContextManagerV1},
+    wp_security_context_v1::{self, WpSecurityContextV1},
+};
+
+/// Metadata attached to clients connecting through a security context
+///
+/// All fields are optional, their meaning depends on the sandbox engine.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SecurityContext {
+    /// Reverse-DNS name of the sandbox engine, e.g. `org.flatpak`
+    pub sandbox_engine: Option<String>,
+    /// Opaque identifier of the sandboxed application
+    pub app_id: Option<String>,
+    /// Opaque identifier of the running instance of the sandboxed application
+    pub instance_id: Option<String>,
+}
+
+/// Retrieve the security context of a client
+///
+/// Returns `None` if the client did not connect through a security context listener.
+pub fn client_security_context(client: &Client) -> Option<SecurityContext> {
+    client.data_map().get::<SecurityContext>().cloned()
+}
+
+/// Tag a client with a security context
+///
+/// This needs to be called for every client accepted from a [`SecurityContextListenerSource`],
+/// before it gets a chance to bind any global. The security context of a client can only
+/// be set once, following calls are ignored.
+pub fn set_client_security_context(client: &Client, context: SecurityContext) {
+    client.data_map().insert_if_missing_threadsafe(move || context);
+}
+
+/// Event source accepting client connections for a security context
+///
+/// Each event is the stream of a newly connected client. The source removes itself from the
+/// event loop once the sandbox engine asks to stop accepting new connections.
+#[derive(Debug)]
+pub struct SecurityContextListenerSource {
+    listen_fd: Generic<UnixListener>,
+    close_fd: Generic<File>,
+    log: ::slog::Logger,
+}
+
+impl EventSource for SecurityContextListenerSource {
+    type Event = UnixStream;
+    type Metadata = ();
+    type Ret = ();
+
+    fn process_events<C>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: C,
+    ) -> io::Result<PostAction>
+    where
+        C: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let log = &self.log;
+        let closed = self
+            .close_fd
+            .process_events(readiness, token, |_, _| Ok(PostAction::Remove))?;
+        if let PostAction::Remove = closed {
+            debug!(log, "Security context listener closed");
+            return Ok(PostAction::Remove);
+        }
+
+        self.listen_fd.process_events(readiness, token, |_, listener| {
+            loop {
+                match listener.accept() {
+                    Ok((stream, _)) => callback(stream, &mut ()),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(err) => {
+                        warn!(log, "Failed to accept security context client: {}", err);
+                        return Ok(PostAction::Remove);
+                    }
+                }
+            }
+            Ok(PostAction::Continue)
+        })
+    }
+
+    fn register(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> io::Result<()> {
+        self.listen_fd.register(poll, factory)?;
+        self.close_fd.register(poll, factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> io::Result<()> {
+        self.listen_fd.reregister(poll, factory)?;
+        self.close_fd.reregister(poll, factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+        self.listen_fd.unregister(poll)?;
+        self.close_fd.unregister(poll)
+    }
+}
+
+/// Initialize a security context manager global
+///
+/// The callback is invoked every time a new security context is committed, see the module-level
+/// documentation for its use. As nested security contexts are forbidden, clients with a security
+/// context trying to create a new one are killed. You should additionally hide the global from them
+/// using [`init_security_context_global_with_filter`].
+pub fn init_security_context_global<Impl, L>(
+    display: &mut Display,
+    implementation: Impl,
+    logger: L,
+) -> Global<WpSecurityContextManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+    Impl: FnMut(SecurityContextListenerSource, SecurityContext, DispatchData<'_>) + 'static,
+{
+    init_security_context_global_with_filter(display, implementation, |_| true, logger)
+}
+
+/// Initialize a security context manager global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`init_security_context_global`] for details.
+pub fn init_security_context_global_with_filter<Impl, F, L>(
+    display: &mut Display,
+    implementation: Impl,
+    filter: F,
+    logger: L,
+) -> Global<WpSecurityContextManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+    Impl: FnMut(SecurityContextListenerSource, SecurityContext, DispatchData<'_>) + 'static,
+    F: FnMut(Client) -> bool + 'static,
+{
+    let log = crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "wayland_security_context"));
+    let implementation = Rc::new(RefCell::new(implementation));
+
+    display.create_global_with_filter::<WpSecurityContextManagerV1, _, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<WpSecurityContextManagerV1>, _), _, _| {
+                let implementation = implementation.clone();
+                let log = log.clone();
+                manager.quick_assign(move |manager, req, _| match req {
+                    wp_security_context_manager_v1::Request::CreateListener {
+                        id,
+                        listen_fd,
+                        close_fd,
+                    } => {
+                        // take ownership of the fds right away, so they are closed on error
+                        let listen_fd = unsafe { UnixListener::from_raw_fd(listen_fd) };
+                        let close_fd = unsafe { File::from_raw_fd(close_fd) };
+
+                        let nested = manager
+                            .as_ref()
+                            .client()
+                            .map(|client| client_security_context(&client).is_some())
+                            .unwrap_or(false);
+                        if nested {
+                            manager.as_ref().post_error(
+                                wp_security_context_manager_v1::Error::Nested as u32,
+                                "Nested security contexts are forbidden.".into(),
+                            );
+                            return;
+                        }
+
+                        if !is_listening_socket(&listen_fd) || listen_fd.set_nonblocking(true).is_err() {
+                            manager.as_ref().post_error(
+                                wp_security_context_manager_v1::Error::InvalidListenFd as u32,
+                                "The listening socket is invalid.".into(),
+                            );
+                            return;
+                        }
+
+                        implement_security_context(
+                            id,
+                            listen_fd,
+                            close_fd,
+                            implementation.clone(),
+                            log.clone(),
+                        );
+                    }
+                    wp_security_context_manager_v1::Request::Destroy => {}
+                });
+            },
+        ),
+        filter,
+    )
+}
+
+fn is_listening_socket(listener: &UnixListener) -> bool {
+    getsockopt(listener.as_raw_fd(), AcceptConn).unwrap_or(false)
+}
+
+fn implement_security_context<Impl>(
+    id: Main<WpSecurityContextV1>,
+    listen_fd: UnixListener,
+    close_fd: File,
+    implementation: Rc<RefCell<Impl>>,
+    log: ::slog::Logger,
+) where
+    Impl: FnMut(SecurityContextListenerSource, SecurityContext, DispatchData<'_>) + 'static,
+{
+    // `None` once committed
+    let mut fds = Some((listen_fd, close_fd));
+    let mut context = SecurityContext::default();
+
+    id.quick_assign(move |security_context, req, ddata| {
+        if fds.is_none() {
+            if let wp_security_context_v1::Request::Destroy = req {
+                return;
+            }
+            security_context.as_ref().post_error(
+                wp_security_context_v1::Error::AlreadyUsed as u32,
+                "The security context has already been committed.".into(),
+            );
+            return;
+        }
+
+        let (field, value) = match req {
+            wp_security_context_v1::Request::SetSandboxEngine { name } => (&mut context.sandbox_engine, name),
+            wp_security_context_v1::Request::SetAppId { app_id } => (&mut context.app_id, app_id),
+            wp_security_context_v1::Request::SetInstanceId { instance_id } => {
+                (&mut context.instance_id, instance_id)
+            }
+            wp_security_context_v1::Request::Commit => {
+                let (listen_fd, close_fd) = fds.take().unwrap();
+                let source = SecurityContextListenerSource {
+                    listen_fd: Generic::new(listen_fd, Interest::READ, Mode::Level),
+                    close_fd: Generic::new(close_fd, Interest::READ, Mode::Level),
+                    log: log.clone(),
+                };
+                debug!(log, "New security context"; "context" => ?context);
+                (*implementation.borrow_mut())(source, context.clone(), ddata);
+                return;
+            }
+            wp_security_context_v1::Request::Destroy => {
+                // the fds of an uncommitted context are closed once the closure is dropped
+                return;
+            }
+        };
+
+        if field.is_some() {
+            security_context.as_ref().post_error(
+                wp_security_context_v1::Error::AlreadySet as u32,
+                "The metadata has already been set.".into(),
+            );
+            return;
+        }
+        *field = Some(value);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing::TestHarness, wayland::output::xdg::init_xdg_output_manager_with_filter};
+
+    // a client of the harness, optionally connected through a security context, next to a global
+    // that is only exposed to unsandboxed clients
+    fn setup(context: Option<SecurityContext>) -> TestHarness<()> {
+        let mut harness = TestHarness::new(()).unwrap();
+        // the context is set before the client gets its registry dispatched, as for
+        // clients accepted from a listener
+        if let Some(context) = context {
+            set_client_security_context(harness.client(), context);
+        }
+        init_xdg_output_manager_with_filter(
+            harness.display_mut(),
+            |client| client_security_context(&client).is_none(),
+            None,
+        );
+        harness.roundtrip().unwrap();
+        harness
+    }
+
+    fn sees_privileged_global(harness: &TestHarness<()>) -> bool {
+        harness
+            .globals()
+            .list()
+            .iter()
+            .any(|(_, interface, _)| interface == "zxdg_output_manager_v1")
+    }
+
+    #[test]
+    fn unsandboxed_client_sees_privileged_global() {
+        let harness = setup(None);
+        assert_eq!(client_security_context(harness.client()), None);
+        assert!(sees_privileged_global(&harness));
+    }
+
+    #[test]
+    fn sandboxed_client_cannot_see_privileged_global() {
+        let context = SecurityContext {
+            sandbox_engine: Some("org.flatpak".into()),
+            app_id: Some("org.example.App".into()),
+            instance_id: None,
+        };
+        let harness = setup(Some(context.clone()));
+        assert_eq!(client_security_context(harness.client()), Some(context));
+        assert!(!sees_privileged_global(&harness));
+    }
+
+    #[test]
+    fn security_context_is_set_once() {
+        let harness = TestHarness::new(()).unwrap();
+        let first = SecurityContext {
+            app_id: Some("first".into()),
+            ..Default::default()
+        };
+        set_client_security_context(harness.client(), first.clone());
+        set_client_security_context(
+            harness.client(),
+            SecurityContext {
+                app_id: Some("second".into()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(client_security_context(harness.client()), Some(first));
+    }
+}
@@ -56,7 +56,7 @@ use crate::{
 
 use wayland_server::{
     protocol::{wl_output, wl_seat, wl_shell, wl_shell_surface, wl_surface},
-    DispatchData, Display, Filter, Global,
+    Client, DispatchData, Display, Filter, Global,
 };
 
 use super::PingError;
@@ -283,6 +283,24 @@ pub fn wl_shell_init<L, Impl>(
 where
     L: Into<Option<::slog::Logger>>,
     Impl: FnMut(ShellRequest, DispatchData<'_>) + 'static,
+{
+    wl_shell_init_with_filter(display, implementation, |_| true, logger)
+}
+
+/// Create a new `wl_shell` global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`wl_shell_init`] for details.
+pub fn wl_shell_init_with_filter<L, Impl, F>(
+    display: &mut Display,
+    implementation: Impl,
+    filter: F,
+    logger: L,
+) -> (Arc<Mutex<ShellState>>, Global<wl_shell::WlShell>)
+where
+    L: Into<Option<::slog::Logger>>,
+    Impl: FnMut(ShellRequest, DispatchData<'_>) + 'static,
+    F: FnMut(Client) -> bool + 'static,
 {
     let _log = crate::slog_or_fallback(logger);
 
@@ -293,11 +311,12 @@ where
     }));
     let state2 = state.clone();
 
-    let global = display.create_global(
+    let global = display.create_global_with_filter(
         1,
         Filter::new(move |(shell, _version), _, _data| {
             self::wl_handlers::implement_shell(shell, implementation.clone(), state2.clone());
         }),
+        filter,
     );
 
     (state, global)
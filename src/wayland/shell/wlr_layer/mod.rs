@@ -34,7 +34,7 @@ use std::{
 use wayland_protocols::wlr::unstable::layer_shell::v1::server::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
 use wayland_server::{
     protocol::{wl_output::WlOutput, wl_surface},
-    DispatchData, Display, Filter, Global, Main,
+    Client, DispatchData, Display, Filter, Global, Main,
 };
 
 use crate::{
@@ -182,6 +182,27 @@ pub fn wlr_layer_shell_init<L, Impl>(
 where
     L: Into<Option<::slog::Logger>>,
     Impl: FnMut(LayerShellRequest, DispatchData<'_>) + 'static,
+{
+    wlr_layer_shell_init_with_filter(display, implementation, |_| true, logger)
+}
+
+/// Create a new `wlr_layer_shell` global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`wlr_layer_shell_init`] for details.
+pub fn wlr_layer_shell_init_with_filter<L, Impl, F>(
+    display: &mut Display,
+    implementation: Impl,
+    filter: F,
+    logger: L,
+) -> (
+    Arc<Mutex<LayerShellState>>,
+    Global<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+)
+where
+    L: Into<Option<::slog::Logger>>,
+    Impl: FnMut(LayerShellRequest, DispatchData<'_>) + 'static,
+    F: FnMut(Client) -> bool + 'static,
 {
     let log = crate::slog_or_fallback(logger);
     let shell_state = Arc::new(Mutex::new(LayerShellState {
@@ -194,7 +215,7 @@ where
         shell_state: shell_state.clone(),
     };
 
-    let layer_shell_global = display.create_global(
+    let layer_shell_global = display.create_global_with_filter(
         4,
         Filter::new(
             move |(shell, _version): (Main<zwlr_layer_shell_v1::ZwlrLayerShellV1>, _), _, _ddata| {
@@ -205,6 +226,7 @@ where
                 });
             },
         ),
+        filter,
     );
 
     (shell_state, layer_shell_global)
@@ -38,7 +38,7 @@ use wayland_protocols::unstable::xdg_decoration::v1::server::{
     zxdg_decoration_manager_v1::{self, ZxdgDecorationManagerV1},
     zxdg_toplevel_decoration_v1::{self, Mode, ZxdgToplevelDecorationV1},
 };
-use wayland_server::{DispatchData, Display, Filter, Global, Main};
+use wayland_server::{Client, DispatchData, Display, Filter, Global, Main};
 
 use super::ToplevelSurface;
 use crate::wayland::shell::xdg::xdg_handlers::ShellSurfaceUserData;
@@ -74,9 +74,27 @@ pub fn init_xdg_decoration_manager<L, Impl>(
 where
     L: Into<Option<::slog::Logger>>,
     Impl: FnMut(XdgDecorationRequest, DispatchData<'_>) + 'static,
+{
+    init_xdg_decoration_manager_with_filter(display, implementation, |_| true, _logger)
+}
+
+/// Create a new XDG Decoration Manager global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`init_xdg_decoration_manager`] for details.
+pub fn init_xdg_decoration_manager_with_filter<L, Impl, F>(
+    display: &mut Display,
+    implementation: Impl,
+    filter: F,
+    _logger: L,
+) -> Global<ZxdgDecorationManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+    Impl: FnMut(XdgDecorationRequest, DispatchData<'_>) + 'static,
+    F: FnMut(Client) -> bool + 'static,
 {
     let cb = Rc::new(RefCell::new(implementation));
-    display.create_global(
+    display.create_global_with_filter(
         1,
         Filter::new(
             move |(manager, _version): (Main<ZxdgDecorationManagerV1>, _), _, _| {
@@ -145,6 +163,7 @@ where
                 });
             },
         ),
+        filter,
     )
 }
 
@@ -81,7 +81,7 @@ use wayland_protocols::xdg_shell::server::{xdg_popup, xdg_positioner, xdg_toplev
 use wayland_server::DispatchData;
 use wayland_server::{
     protocol::{wl_output, wl_seat, wl_surface},
-    Display, Filter, Global, UserDataMap,
+    Client, Display, Filter, Global, UserDataMap,
 };
 
 use self::xdg_handlers::ShellSurfaceUserData;
@@ -762,6 +762,24 @@ pub fn xdg_shell_init<L, Impl>(
 where
     L: Into<Option<::slog::Logger>>,
     Impl: FnMut(XdgRequest, DispatchData<'_>) + 'static,
+{
+    xdg_shell_init_with_filter(display, implementation, |_| true, logger)
+}
+
+/// Create a new `xdg_shell` global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`xdg_shell_init`] for details.
+pub fn xdg_shell_init_with_filter<L, Impl, F>(
+    display: &mut Display,
+    implementation: Impl,
+    filter: F,
+    logger: L,
+) -> (Arc<Mutex<ShellState>>, Global<xdg_wm_base::XdgWmBase>)
+where
+    L: Into<Option<::slog::Logger>>,
+    Impl: FnMut(XdgRequest, DispatchData<'_>) + 'static,
+    F: FnMut(Client) -> bool + 'static,
 {
     let log = crate::slog_or_fallback(logger);
     let shell_state = Arc::new(Mutex::new(ShellState {
@@ -775,11 +793,12 @@ where
         shell_state: shell_state.clone(),
     };
 
-    let xdg_shell_global = display.create_global(
+    let xdg_shell_global = display.create_global_with_filter(
         3,
         Filter::new(move |(shell, _version), _, dispatch_data| {
             self::xdg_handlers::implement_wm_base(shell, &shell_data, dispatch_data);
         }),
+        filter,
     );
 
     (shell_state, xdg_shell_global)
@@ -77,7 +77,7 @@ use self::pool::{Pool, ResizeError};
 use std::{ops::Deref as _, rc::Rc, sync::Arc};
 use wayland_server::{
     protocol::{wl_buffer, wl_shm, wl_shm_pool},
-    Display, Filter, Global, Main,
+    Client, Display, Filter, Global, Main,
 };
 
 mod pool;
@@ -98,11 +98,28 @@ struct ShmGlobalData {
 /// and this function returns the global handle, in case you wish to remove this global in
 /// the future.
 pub fn init_shm_global<L>(
+    display: &mut Display,
+    formats: Vec<wl_shm::Format>,
+    logger: L,
+) -> Global<wl_shm::WlShm>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    init_shm_global_with_filter(display, formats, |_| true, logger)
+}
+
+/// Create a new SHM global advertizing given supported formats with a client filter.
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`init_shm_global`] for details.
+pub fn init_shm_global_with_filter<F, L>(
     display: &mut Display,
     mut formats: Vec<wl_shm::Format>,
+    filter: F,
     logger: L,
 ) -> Global<wl_shm::WlShm>
 where
+    F: FnMut(Client) -> bool + 'static,
     L: Into<Option<::slog::Logger>>,
 {
     let log = crate::slog_or_fallback(logger);
@@ -115,7 +132,7 @@ where
         log: log.new(slog::o!("smithay_module" => "shm_handler")),
     };
 
-    display.create_global::<wl_shm::WlShm, _>(
+    display.create_global_with_filter::<wl_shm::WlShm, _, _>(
         1,
         Filter::new(move |(shm, _version): (Main<wl_shm::WlShm>, _), _, _| {
             shm.quick_assign({
@@ -128,6 +145,7 @@ where
                 shm.format(f);
             }
         }),
+        filter,
     )
 }
 
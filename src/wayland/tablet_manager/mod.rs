@@ -34,7 +34,7 @@
 
 use crate::wayland::seat::Seat;
 use wayland_protocols::unstable::tablet::v2::server::zwp_tablet_manager_v2::{self, ZwpTabletManagerV2};
-use wayland_server::{Display, Filter, Global, Main};
+use wayland_server::{Client, Display, Filter, Global, Main};
 
 const MANAGER_VERSION: u32 = 1;
 
@@ -62,7 +62,20 @@ impl TabletSeatTrait for Seat {
 
 /// Initialize a tablet manager global.
 pub fn init_tablet_manager_global(display: &mut Display) -> Global<ZwpTabletManagerV2> {
-    display.create_global::<ZwpTabletManagerV2, _>(
+    init_tablet_manager_global_with_filter(display, |_| true)
+}
+
+/// Initialize a tablet manager global with a client filter.
+///
+/// The global is only advertised to clients for which `filter` returns `true`.
+pub fn init_tablet_manager_global_with_filter<F>(
+    display: &mut Display,
+    filter: F,
+) -> Global<ZwpTabletManagerV2>
+where
+    F: FnMut(Client) -> bool + 'static,
+{
+    display.create_global_with_filter::<ZwpTabletManagerV2, _, _>(
         MANAGER_VERSION,
         Filter::new(
             move |(manager, _version): (Main<ZwpTabletManagerV2>, u32), _, _| {
@@ -85,5 +98,6 @@ pub fn init_tablet_manager_global(display: &mut Display) -> Global<ZwpTabletMana
                 });
             },
         ),
+        filter,
     )
 }
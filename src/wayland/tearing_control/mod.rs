@@ -0,0 +1,250 @@
+//! Utilities for handling the `wp_tearing_control_v1` protocol
+//!
+//! This protocol allows clients to hint to the compositor whether the contents of their surfaces
+//! are suitable for presentation with tearing, trading visual correctness for lower latency.
+//! This is typically used by games.
+//!
+//! The hint is stored as double-buffered state of the surface, and can be queried at any time
+//! from the current [`TearingControlSurfaceCachedState`] of the surface. The compositor is free
+//! to ignore it, and would usually only honor it for fullscreen surfaces, if the output backend
+//! supports asynchronous page flips (see for example
+//! [`DrmDevice::supports_async_page_flip`](crate::backend::drm::DrmDevice::supports_async_page_flip)).
+//!
+//! ## Usage
+//!
+//! First, you need to initialize the global:
+//!
+//! ```
+//! # extern crate wayland_server;
+//! use smithay::wayland::tearing_control::init_tearing_control_manager;
+//! # let mut display = wayland_server::Display::new();
+//! init_tearing_control_manager(
+//!     &mut display,
+//!     None /* You can insert a logger here */
+//! );
+//! ```
+//!
+//! Then, when deciding how to present a frame, you can retrieve the hint of the surface:
+//!
+//! ```
+//! # extern crate wayland_server;
+//! # use wayland_server::protocol::wl_surface::WlSurface;
+//! use smithay::wayland::compositor::with_states;
+//! use smithay::wayland::tearing_control::{PresentationHint, TearingControlSurfaceCachedState};
+//!
+//! # fn dummy_function(surface: &WlSurface) {
+//! let allow_tearing = with_states(surface, |states| {
+//!     states
+//!         .cached_state
+//!         .current::<TearingControlSurfaceCachedState>()
+//!         .presentation_hint
+//!         == PresentationHint::Async
+//! })
+//! .unwrap_or(false);
+//! # }
+//! ```
+
+use std::{
+    ops::Deref as _,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use wayland_server::{protocol::wl_surface::WlSurface, Client, Display, Filter, Global, Main};
+
+use super::compositor::{with_states, Cacheable, SurfaceData};
+use super::protocols::tearing_control::v1::server::{
+    wp_tearing_control_manager_v1::{self, WpTearingControlManagerV1},
+    wp_tearing_control_v1::{self, WpTearingControlV1},
+};
+
+/// Presentation hint of a surface
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PresentationHint {
+    /// The content of this surface is meant to be synchronized to the vertical blanking period
+    #[default]
+    Vsync,
+    /// The content of this surface is meant to be presented with minimal latency
+    /// and tearing is acceptable
+    Async,
+}
+
+/// Double-buffered tearing control state of a surface
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TearingControlSurfaceCachedState {
+    /// The presentation hint requested by the client
+    pub presentation_hint: PresentationHint,
+}
+
+impl Cacheable for TearingControlSurfaceCachedState {
+    fn commit(&mut self) -> Self {
+        *self
+    }
+    fn merge_into(self, into: &mut Self) {
+        *into = self;
+    }
+}
+
+/// Tracks whether a surface already has a `wp_tearing_control_v1` object
+struct TearingControlSurfaceData {
+    is_resource_attached: AtomicBool,
+}
+
+/// Initialize the tearing control global
+///
+/// See module-level documentation for its use.
+pub fn init_tearing_control_manager<L>(display: &mut Display, logger: L) -> Global<WpTearingControlManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    init_tearing_control_manager_with_filter(display, |_| true, logger)
+}
+
+/// Initialize the tearing control global with a client filter
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`init_tearing_control_manager`] for details.
+pub fn init_tearing_control_manager_with_filter<L, F>(
+    display: &mut Display,
+    filter: F,
+    logger: L,
+) -> Global<WpTearingControlManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+    F: FnMut(Client) -> bool + 'static,
+{
+    let _log = crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "wayland_tearing_control"));
+
+    display.create_global_with_filter::<WpTearingControlManagerV1, _, _>(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<WpTearingControlManagerV1>, _), _, _| {
+                manager.quick_assign(move |manager, req, _| {
+                    if let wp_tearing_control_manager_v1::Request::GetTearingControl { id, surface } = req {
+                        let exists = with_states(&surface, attach_tearing_control).unwrap_or(false);
+
+                        if exists {
+                            manager.as_ref().post_error(
+                                wp_tearing_control_manager_v1::Error::TearingControlExists as u32,
+                                "The surface already has a tearing control object associated.".into(),
+                            );
+                            return;
+                        }
+
+                        implement_tearing_control(id, surface);
+                    }
+                });
+            },
+        ),
+        filter,
+    )
+}
+
+fn implement_tearing_control(id: Main<WpTearingControlV1>, surface: WlSurface) -> WpTearingControlV1 {
+    id.quick_assign(move |_, req, _| {
+        // the object is inert once the surface is gone
+        if !surface.as_ref().is_alive() {
+            return;
+        }
+
+        match req {
+            wp_tearing_control_v1::Request::SetPresentationHint { hint } => {
+                let presentation_hint = match hint {
+                    wp_tearing_control_v1::PresentationHint::Async => PresentationHint::Async,
+                    _ => PresentationHint::Vsync,
+                };
+                with_states(&surface, |states| {
+                    set_presentation_hint(states, presentation_hint)
+                })
+                .unwrap();
+            }
+            wp_tearing_control_v1::Request::Destroy => {
+                with_states(&surface, detach_tearing_control).unwrap();
+            }
+        }
+    });
+    id.deref().clone()
+}
+
+/// Marks the surface as having a tearing control object
+///
+/// Returns `true` if the surface already had one.
+fn attach_tearing_control(states: &SurfaceData) -> bool {
+    states
+        .data_map
+        .insert_if_missing_threadsafe(|| TearingControlSurfaceData {
+            is_resource_attached: AtomicBool::new(false),
+        });
+    states
+        .data_map
+        .get::<TearingControlSurfaceData>()
+        .unwrap()
+        .is_resource_attached
+        .swap(true, Ordering::SeqCst)
+}
+
+fn set_presentation_hint(states: &SurfaceData, presentation_hint: PresentationHint) {
+    states
+        .cached_state
+        .pending::<TearingControlSurfaceCachedState>()
+        .presentation_hint = presentation_hint;
+}
+
+fn detach_tearing_control(states: &SurfaceData) {
+    // revert to vsync on the next commit and allow a new object to be created
+    set_presentation_hint(states, PresentationHint::Vsync);
+    if let Some(data) = states.data_map.get::<TearingControlSurfaceData>() {
+        data.is_resource_attached.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::compositor::MultiCache;
+
+    fn surface_data() -> SurfaceData {
+        SurfaceData {
+            role: None,
+            data_map: Default::default(),
+            cached_state: MultiCache::new(),
+        }
+    }
+
+    fn current_hint(states: &SurfaceData) -> PresentationHint {
+        states
+            .cached_state
+            .current::<TearingControlSurfaceCachedState>()
+            .presentation_hint
+    }
+
+    #[test]
+    fn hint_cached_until_commit() {
+        let mut states = surface_data();
+        assert!(!attach_tearing_control(&states));
+
+        set_presentation_hint(&states, PresentationHint::Async);
+        assert_eq!(current_hint(&states), PresentationHint::Vsync);
+
+        states.cached_state.commit(None);
+        assert_eq!(current_hint(&states), PresentationHint::Async);
+    }
+
+    #[test]
+    fn hint_reset_on_destroy() {
+        let mut states = surface_data();
+        assert!(!attach_tearing_control(&states));
+        set_presentation_hint(&states, PresentationHint::Async);
+        states.cached_state.commit(None);
+
+        // a second object is a protocol error
+        assert!(attach_tearing_control(&states));
+
+        detach_tearing_control(&states);
+        assert_eq!(current_hint(&states), PresentationHint::Async);
+        states.cached_state.commit(None);
+        assert_eq!(current_hint(&states), PresentationHint::Vsync);
+
+        // a new object may be created once the old one is destroyed
+        assert!(!attach_tearing_control(&states));
+    }
+}
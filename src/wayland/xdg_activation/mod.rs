@@ -42,7 +42,7 @@ use std::{
 use wayland_protocols::staging::xdg_activation::v1::server::xdg_activation_v1;
 use wayland_server::{
     protocol::{wl_seat::WlSeat, wl_surface::WlSurface},
-    DispatchData, Display, Filter, Global, Main, UserDataMap,
+    Client, DispatchData, Display, Filter, Global, Main, UserDataMap,
 };
 
 use rand::distributions::{Alphanumeric, DistString};
@@ -197,6 +197,27 @@ pub fn init_xdg_activation_global<L, Impl>(
 where
     L: Into<Option<::slog::Logger>>,
     Impl: FnMut(&Mutex<XdgActivationState>, XdgActivationEvent, DispatchData<'_>) + 'static,
+{
+    init_xdg_activation_global_with_filter(display, implementation, |_| true, logger)
+}
+
+/// Creates new `xdg-activation` global with a client filter.
+///
+/// The global is only advertised to clients for which `filter` returns `true`,
+/// see [`init_xdg_activation_global`] for details.
+pub fn init_xdg_activation_global_with_filter<L, Impl, F>(
+    display: &mut Display,
+    implementation: Impl,
+    filter: F,
+    logger: L,
+) -> (
+    Arc<Mutex<XdgActivationState>>,
+    Global<xdg_activation_v1::XdgActivationV1>,
+)
+where
+    L: Into<Option<::slog::Logger>>,
+    Impl: FnMut(&Mutex<XdgActivationState>, XdgActivationEvent, DispatchData<'_>) + 'static,
+    F: FnMut(Client) -> bool + 'static,
 {
     let log = crate::slog_or_fallback(logger);
 
@@ -210,13 +231,14 @@ where
     }));
 
     let state = activation_state.clone();
-    let global = display.create_global(
+    let global = display.create_global_with_filter(
         1,
         Filter::new(
             move |(global, _version): (Main<xdg_activation_v1::XdgActivationV1>, _), _, _| {
                 handlers::implement_activation_global(global, state.clone(), implementation.clone());
             },
         ),
+        filter,
     );
 
     (activation_state, global)
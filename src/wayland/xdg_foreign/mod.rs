@@ -36,13 +36,15 @@ use crate::wayland::shell::is_toplevel_equivalent;
 use crate::wayland::shell::legacy::WL_SHELL_SURFACE_ROLE;
 use crate::wayland::shell::xdg::ShellState;
 use rand::distributions::{Alphanumeric, DistString};
+use std::cell::RefCell;
 use std::ops::Deref;
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use wayland_protocols::unstable::xdg_foreign::v2::server::{
     zxdg_exported_v2, zxdg_exporter_v2, zxdg_imported_v2, zxdg_importer_v2,
 };
 use wayland_server::protocol::wl_surface::WlSurface;
-use wayland_server::{Display, Filter, Global, Main};
+use wayland_server::{Client, Display, Filter, Global, Main};
 
 /// Manages all exported and imported surfaces.
 #[derive(Debug)]
@@ -80,8 +82,30 @@ pub fn xdg_foreign_init<L>(
 )
 where
     L: Into<Option<::slog::Logger>>,
+{
+    xdg_foreign_init_with_filter(display, xdg_shell_state, |_| true, logger)
+}
+
+/// Creates new `xdg-foreign` globals with a client filter.
+///
+/// Both globals are only advertised to clients for which `filter` returns `true`,
+/// see [`xdg_foreign_init`] for details.
+pub fn xdg_foreign_init_with_filter<F, L>(
+    display: &mut Display,
+    xdg_shell_state: Arc<Mutex<ShellState>>,
+    filter: F,
+    logger: L,
+) -> (
+    Arc<Mutex<XdgForeignState>>,
+    Global<zxdg_exporter_v2::ZxdgExporterV2>,
+    Global<zxdg_importer_v2::ZxdgImporterV2>,
+)
+where
+    F: FnMut(Client) -> bool + 'static,
+    L: Into<Option<::slog::Logger>>,
 {
     let log = crate::slog_or_fallback(logger);
+    let filter = Rc::new(RefCell::new(filter));
 
     let state = Arc::new(Mutex::new(XdgForeignState {
         _log: log.new(slog::o!("smithay_module" => "xdg_foreign_handler")),
@@ -94,18 +118,22 @@ where
     let import_state = state.clone();
     let export_shell = xdg_shell_state.clone();
 
-    let zxdg_exporter_v2_global = display.create_global(
+    let export_filter = filter.clone();
+
+    let zxdg_exporter_v2_global = display.create_global_with_filter(
         1,
         Filter::new(move |(exporter, _version), _, _| {
             implement_exporter(exporter, export_state.clone(), export_shell.clone());
         }),
+        move |client| (*export_filter.borrow_mut())(client),
     );
 
-    let zxdg_importer_v2_global = display.create_global(
+    let zxdg_importer_v2_global = display.create_global_with_filter(
         1,
         Filter::new(move |(importer, _version), _, _| {
             implement_importer(importer, import_state.clone(), xdg_shell_state.clone());
         }),
+        move |client| (*filter.borrow_mut())(client),
     );
 
     (state, zxdg_exporter_v2_global, zxdg_importer_v2_global)
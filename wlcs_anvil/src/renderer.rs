@@ -107,10 +107,20 @@ impl Frame for DummyFrame {
         _src: Rectangle<i32, Buffer>,
         _dst: Rectangle<f64, Physical>,
         _src_transform: Transform,
+        _damage: &[Rectangle<i32, Physical>],
         _alpha: f32,
     ) -> Result<(), Self::Error> {
         Ok(())
     }
+
+    fn draw_solid(
+        &mut self,
+        _dst: Rectangle<i32, Physical>,
+        _damage: &[Rectangle<i32, Physical>],
+        _color: [f32; 4],
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
 pub struct DummyTexture {